@@ -36,15 +36,12 @@ describe("SqliteStorageAdapter", () => {
       expect(mockInvoke).not.toHaveBeenCalled()
     })
 
-    it("should handle initialization errors gracefully", async () => {
-      // When invoke fails, the adapter catches it and returns empty rows
-      // This causes the CREATE TABLE to succeed with no rows
+    it("should not depend on invoke", async () => {
+      // Tables are created by the backend, so initialize runs no query
       mockInvoke.mockRejectedValue(new Error("Database error"))
 
       const result = await adapter.initialize()
 
-      // The adapter catches errors in the invoke callback and returns { rows: [] }
-      // So the result will still be success: true
       expect(result.success).toBe(true)
     })
   })
@@ -144,17 +141,37 @@ describe("SqliteStorageAdapter", () => {
       }
     })
 
-    it("should return empty array when invoke fails", async () => {
-      // When invoke fails, the adapter catches it and returns { rows: [] }
+    it("should return an error when invoke fails", async () => {
       const consoleSpy = vi.spyOn(console, "error").mockImplementation(() => {})
       mockInvoke.mockRejectedValue(new Error("Query failed"))
 
       const result = await adapter.getWorkspaces()
 
-      // The adapter catches errors and returns empty results
-      expect(result.success).toBe(true)
-      if (result.success) {
-        expect(result.data).toEqual([])
+      expect(result.success).toBe(false)
+      if (!result.success) {
+        expect(result.error).toBe("Failed to get workspaces: Query failed")
+      }
+
+      consoleSpy.mockRestore()
+    })
+
+    it("should pass on the message of a command error", async () => {
+      const consoleSpy = vi.spyOn(console, "error").mockImplementation(() => {})
+      // The commands reject with a SqlError object rather than an Error
+      mockInvoke.mockRejectedValue({
+        code: "too_many_rows",
+        message: "Query returned too many rows (db.limits.max_rows is 10000)",
+        setting: "db.limits.max_rows",
+        max: 10000,
+      })
+
+      const result = await adapter.getWorkspaces()
+
+      expect(result.success).toBe(false)
+      if (!result.success) {
+        expect(result.error).toBe(
+          "Failed to get workspaces: Query returned too many rows (db.limits.max_rows is 10000)"
+        )
       }
 
       consoleSpy.mockRestore()
@@ -255,16 +272,12 @@ describe("SqliteStorageAdapter", () => {
       }
     })
 
-    it("should return null when invoke fails", async () => {
-      // When invoke fails, the adapter catches it and returns { rows: [] }
+    it("should return an error when invoke fails", async () => {
       mockInvoke.mockRejectedValue(new Error("Query failed"))
 
       const result = await adapter.getPage("ws-1", "2024-01-28")
 
-      expect(result.success).toBe(true)
-      if (result.success) {
-        expect(result.data).toBeNull()
-      }
+      expect(result.success).toBe(false)
     })
   })
 
@@ -343,16 +356,12 @@ describe("SqliteStorageAdapter", () => {
       }
     })
 
-    it("should return empty array when invoke fails", async () => {
-      // When invoke fails, the adapter catches it and returns { rows: [] }
+    it("should return an error when invoke fails", async () => {
       mockInvoke.mockRejectedValue(new Error("Query failed"))
 
       const result = await adapter.getTodos("ws-1", "2024-01-28")
 
-      expect(result.success).toBe(true)
-      if (result.success) {
-        expect(result.data).toEqual([])
-      }
+      expect(result.success).toBe(false)
     })
   })
 
@@ -377,8 +386,7 @@ describe("SqliteStorageAdapter", () => {
       }
     })
 
-    it("should return success even when invoke fails", async () => {
-      // When invoke fails, the adapter catches it and returns { rows: [] }
+    it("should return an error when invoke fails", async () => {
       mockInvoke.mockRejectedValue(new Error("Insert failed"))
 
       const workspace: Workspace = {
@@ -392,8 +400,10 @@ describe("SqliteStorageAdapter", () => {
 
       const result = await adapter.createWorkspace(workspace)
 
-      // The adapter catches errors and still returns the workspace
-      expect(result.success).toBe(true)
+      expect(result.success).toBe(false)
+      if (!result.success) {
+        expect(result.error).toBe("Failed to create workspace: Insert failed")
+      }
     })
   })
 
@@ -420,8 +430,7 @@ describe("SqliteStorageAdapter", () => {
       }
     })
 
-    it("should return success even when invoke fails", async () => {
-      // When invoke fails, the adapter catches it and returns { rows: [] }
+    it("should return an error when invoke fails", async () => {
       mockInvoke.mockRejectedValue(new Error("Insert failed"))
 
       const todo: TodoItem = {
@@ -437,8 +446,7 @@ describe("SqliteStorageAdapter", () => {
 
       const result = await adapter.createTodo("ws-1", "2024-01-28", todo)
 
-      // The adapter catches errors and still returns the todo
-      expect(result.success).toBe(true)
+      expect(result.success).toBe(false)
     })
   })
 
@@ -500,9 +508,10 @@ describe("SqliteStorageAdapter", () => {
 
       const result = await adapter.updateTodo("todo-1", { text: "Updated" })
 
-      // When the select query fails, adapter returns empty rows
-      // This causes the update to fail with "Todo not found after update"
       expect(result.success).toBe(false)
+      if (!result.success) {
+        expect(result.error).toBe("Failed to update todo: Update failed")
+      }
     })
   })
 
@@ -515,14 +524,12 @@ describe("SqliteStorageAdapter", () => {
       expect(result.success).toBe(true)
     })
 
-    it("should return success even when invoke fails", async () => {
-      // When invoke fails, the adapter catches it and returns { rows: [] }
+    it("should return an error when invoke fails", async () => {
       mockInvoke.mockRejectedValue(new Error("Delete failed"))
 
       const result = await adapter.deleteTodo("todo-1")
 
-      // The adapter catches errors and still returns success
-      expect(result.success).toBe(true)
+      expect(result.success).toBe(false)
     })
   })
 
@@ -658,10 +665,10 @@ describe("SqliteStorageAdapter", () => {
 
       const result = await adapter.savePage("ws-1", page)
 
-      // When getPage fails, it returns { rows: [] } which means data: null
-      // savePage then tries to create a new page, which also fails
-      // But the adapter catches the error and returns success: true
-      expect(result.success).toBe(true)
+      expect(result.success).toBe(false)
+      if (!result.success) {
+        expect(result.error).toBe("Failed to save page: Save failed")
+      }
     })
   })
 })
@@ -18,13 +18,23 @@ interface SqlRequest {
 }
 
 // Row format from Rust - columns and values in order
+// columns is omitted for the 'values' method
 interface SqlRow {
-  columns: string[]
+  columns?: string[]
   rows: unknown[]
 }
 
+// Declared type and affinity of a result column; declaredType is null for expressions
+interface ColumnType {
+  name: string
+  declaredType: string | null
+  affinity: string | null
+}
+
 interface SqlResponse {
   rows: SqlRow[]
+  // Omitted when no rows came back
+  columns?: ColumnType[]
 }
 
 interface BatchSqlRequest {
@@ -35,6 +45,37 @@ interface BatchSqlResponse {
   results: SqlResponse[]
 }
 
+// Error thrown by both commands; code is "sql" for database errors, otherwise
+// the db.limits.* limit that was hit (e.g. "too_many_rows", "rate_limited")
+export interface SqlError {
+  code: string
+  message: string
+  setting?: string
+  max?: number
+}
+
+// invoke rejects with the command's SqlError as a plain object; rethrow it as an
+// Error that keeps the code, so callers can tell a limit that was hit from a bad query
+function toSqlError(e: unknown): Error & SqlError {
+  const { code = "sql", message = String(e), ...rest } = (
+    typeof e === "object" && e !== null ? e : {}
+  ) as Partial<SqlError>
+  return Object.assign(new Error(message), { ...rest, code })
+}
+
+function errorMessage(error: unknown): string {
+  return error instanceof Error ? error.message : String(error)
+}
+
+// Compressed entries have their notes in notes_zstd, so read them through pageNotes
+const pageColumns = {
+  workspaceId: schema.pages.workspaceId,
+  date: schema.pages.date,
+  notes: schema.pageNotes,
+  createdAt: schema.pages.createdAt,
+  updatedAt: schema.pages.updatedAt,
+}
+
 /**
  * SQLite storage adapter using Drizzle ORM with sqlite-proxy
  * Communicates with Tauri backend via invoke commands
@@ -57,7 +98,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
           return { rows }
         } catch (e) {
           console.error("SQL Error:", e)
-          return { rows: [] }
+          throw toSqlError(e)
         }
       },
       // Batch query callback
@@ -84,7 +125,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
           }))
         } catch (e) {
           console.error("Batch SQL Error:", e)
-          throw e
+          throw toSqlError(e)
         }
       },
       { schema }
@@ -156,7 +197,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
       for (const ws of workspaceRows) {
         // Get all pages for this workspace
         const pageRows = await this.db
-          .select()
+          .select(pageColumns)
           .from(schema.pages)
           .where(eq(schema.pages.workspaceId, ws.id))
           .all()
@@ -209,7 +250,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: workspaces }
     } catch (error) {
-      return { success: false, error: `Failed to get workspaces: ${error}` }
+      return { success: false, error: `Failed to get workspaces: ${errorMessage(error)}` }
     }
   }
 
@@ -229,7 +270,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       // Get all pages for this workspace
       const pageRows = await this.db
-        .select()
+        .select(pageColumns)
         .from(schema.pages)
         .where(eq(schema.pages.workspaceId, ws.id))
         .all()
@@ -281,7 +322,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: workspace }
     } catch (error) {
-      return { success: false, error: `Failed to get workspace: ${error}` }
+      return { success: false, error: `Failed to get workspace: ${errorMessage(error)}` }
     }
   }
 
@@ -297,7 +338,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: workspace }
     } catch (error) {
-      return { success: false, error: `Failed to create workspace: ${error}` }
+      return { success: false, error: `Failed to create workspace: ${errorMessage(error)}` }
     }
   }
 
@@ -325,7 +366,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: result.data }
     } catch (error) {
-      return { success: false, error: `Failed to update workspace: ${error}` }
+      return { success: false, error: `Failed to update workspace: ${errorMessage(error)}` }
     }
   }
 
@@ -348,7 +389,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: undefined }
     } catch (error) {
-      return { success: false, error: `Failed to delete workspace: ${error}` }
+      return { success: false, error: `Failed to delete workspace: ${errorMessage(error)}` }
     }
   }
 
@@ -358,7 +399,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
   ): Promise<Result<JournalPage | null>> {
     try {
       const pageRows = await this.db
-        .select()
+        .select(pageColumns)
         .from(schema.pages)
         .where(
           and(
@@ -408,7 +449,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: journalPage }
     } catch (error) {
-      return { success: false, error: `Failed to get page: ${error}` }
+      return { success: false, error: `Failed to get page: ${errorMessage(error)}` }
     }
   }
 
@@ -427,7 +468,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: page }
     } catch (error) {
-      return { success: false, error: `Failed to create page: ${error}` }
+      return { success: false, error: `Failed to create page: ${errorMessage(error)}` }
     }
   }
 
@@ -459,7 +500,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: result.data }
     } catch (error) {
-      return { success: false, error: `Failed to update page: ${error}` }
+      return { success: false, error: `Failed to update page: ${errorMessage(error)}` }
     }
   }
 
@@ -492,7 +533,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: todos }
     } catch (error) {
-      return { success: false, error: `Failed to get todos: ${error}` }
+      return { success: false, error: `Failed to get todos: ${errorMessage(error)}` }
     }
   }
 
@@ -518,7 +559,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: todo }
     } catch (error) {
-      return { success: false, error: `Failed to create todo: ${error}` }
+      return { success: false, error: `Failed to create todo: ${errorMessage(error)}` }
     }
   }
 
@@ -568,7 +609,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: updatedTodo }
     } catch (error) {
-      return { success: false, error: `Failed to update todo: ${error}` }
+      return { success: false, error: `Failed to update todo: ${errorMessage(error)}` }
     }
   }
 
@@ -577,7 +618,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
       await this.db.delete(schema.todos).where(eq(schema.todos.id, id))
       return { success: true, data: undefined }
     } catch (error) {
-      return { success: false, error: `Failed to delete todo: ${error}` }
+      return { success: false, error: `Failed to delete todo: ${errorMessage(error)}` }
     }
   }
 
@@ -674,7 +715,7 @@ export class SqliteStorageAdapter implements StorageAdapter {
 
       return { success: true, data: undefined }
     } catch (error) {
-      return { success: false, error: `Failed to save page: ${error}` }
+      return { success: false, error: `Failed to save page: ${errorMessage(error)}` }
     }
   }
 }
@@ -1,12 +1,31 @@
-import { sqliteTable, text, integer, primaryKey } from "drizzle-orm/sqlite-core"
+import { sql } from "drizzle-orm"
+import { sqliteTable, text, integer, customType, primaryKey } from "drizzle-orm/sqlite-core"
 import { workspaces } from "./workspace"
 
+// Only the backend reads the compressed bytes; the proxy hands BLOBs over as text or null,
+// so the column is never useful as data here. Read notes through `pageNotes` instead.
+const compressedNotes = customType<{ data: null }>({
+  dataType() {
+    return "blob"
+  },
+})
+
 export const pages = sqliteTable("pages", {
   workspaceId: text("workspace_id").notNull().references(() => workspaces.id),
   date: text("date").notNull(), // YYYY-MM-DD format
   notes: text("notes"),
   createdAt: integer("created_at", { mode: "timestamp" }).notNull(),
   updatedAt: integer("updated_at", { mode: "timestamp" }).notNull(),
+  // Written by the backend when it compresses old entries: `notes` is then NULL and the
+  // zstd-compressed text is here. Writing `notes` drops the compressed copy.
+  notesZstd: compressedNotes("notes_zstd"),
+  notesCompressed: integer("notes_compressed", { mode: "boolean" }).notNull().default(false),
+  // Hash of the notes, kept by backend triggers for the integrity check
+  contentHash: text("content_hash"),
 }, (table) => ({
   pk: primaryKey({ columns: [table.workspaceId, table.date] }),
 }))
+
+// A page's notes, compressed or not. Filter and search on this rather than on `pages.notes`,
+// which is NULL for compressed entries. `zstd_decompress` is registered by the backend.
+export const pageNotes = sql<string | null>`coalesce(${pages.notes}, zstd_decompress(${pages.notesZstd}))`
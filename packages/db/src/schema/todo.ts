@@ -13,6 +13,8 @@ export const todos = sqliteTable("todos", {
   parentId: text("parent_id"),
   createdAt: integer("created_at", { mode: "timestamp" }).notNull(),
   updatedAt: integer("updated_at", { mode: "timestamp" }).notNull(),
+  // Hash of the todo's content, kept by backend triggers for the integrity check
+  contentHash: text("content_hash"),
 }, (table) => ({
   pageFk: foreignKey({
     columns: [table.workspaceId, table.pageDate],
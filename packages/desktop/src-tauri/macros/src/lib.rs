@@ -0,0 +1,62 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, ItemFn, ReturnType, Type};
+
+/// `#[tauri::command]` that also records the call in `crate::metrics`.
+/// Arguments are passed through to `tauri::command` unchanged.
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = proc_macro2::TokenStream::from(attr);
+    let mut func = parse_macro_input!(item as ItemFn);
+
+    let name = func.sig.ident.to_string();
+    let output = match &func.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    // Only `Result` returns can fail; anything else counts as a success
+    let track = match (returns_result(&func.sig.output), func.sig.asyncness.is_some()) {
+        (true, true) => quote!(track_result),
+        (false, true) => quote!(track),
+        (true, false) => quote!(track_result_sync),
+        (false, false) => quote!(track_sync),
+    };
+
+    let block = &func.block;
+    func.block = if func.sig.asyncness.is_some() {
+        parse_quote!({
+            crate::metrics::#track(concat!(module_path!(), "::", #name), async move {
+                let output: #output = #block;
+                output
+            })
+            .await
+        })
+    } else {
+        parse_quote!({
+            crate::metrics::#track(concat!(module_path!(), "::", #name), move || {
+                let output: #output = #block;
+                output
+            })
+        })
+    };
+
+    quote!(
+        #[tauri::command(#attr)]
+        #func
+    )
+    .into()
+}
+
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Result"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
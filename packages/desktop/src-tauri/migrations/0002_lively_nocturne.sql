@@ -0,0 +1,9 @@
+-- no-parse
+ALTER TABLE `pages` ADD `notes_zstd` blob;--> statement-breakpoint
+ALTER TABLE `pages` ADD `notes_compressed` integer DEFAULT false NOT NULL;--> statement-breakpoint
+-- Writing `notes` drops the compressed copy, so the frontend never has to know about it
+CREATE TRIGGER `pages_notes_written` AFTER UPDATE OF `notes` ON `pages`
+WHEN OLD.`notes_compressed` = 1 AND NEW.`notes_compressed` = 1
+BEGIN
+	UPDATE `pages` SET `notes_zstd` = NULL, `notes_compressed` = 0 WHERE rowid = NEW.rowid;
+END;
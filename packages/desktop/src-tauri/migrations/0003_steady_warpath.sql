@@ -0,0 +1,2 @@
+ALTER TABLE `pages` ADD `content_hash` text;--> statement-breakpoint
+ALTER TABLE `todos` ADD `content_hash` text;
\ No newline at end of file
@@ -0,0 +1,226 @@
+-- no-parse
+-- Tables the backend owns; the frontend reaches them only through backend commands
+CREATE TABLE `settings` (
+	`key` text PRIMARY KEY NOT NULL,
+	`value` text NOT NULL,
+	`updated_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `todo_reminders` (
+	`todo_id` text PRIMARY KEY NOT NULL,
+	`due_at` integer NOT NULL,
+	`snoozed_until` integer,
+	`notified_at` integer,
+	`created_at` integer NOT NULL,
+	`updated_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX `todo_reminders_due_at_idx` ON `todo_reminders` (`due_at`);--> statement-breakpoint
+CREATE TABLE `link_previews` (
+	`url` text PRIMARY KEY NOT NULL,
+	`title` text,
+	`description` text,
+	`image_url` text,
+	`site_name` text,
+	`fetched_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `attachments` (
+	`id` text PRIMARY KEY NOT NULL,
+	`workspace_id` text NOT NULL,
+	`page_date` text,
+	`todo_id` text,
+	`file_name` text NOT NULL,
+	`stored_name` text NOT NULL,
+	`mime_type` text,
+	`size` integer NOT NULL,
+	`ocr_text` text,
+	`created_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX `attachments_page_idx` ON `attachments` (`workspace_id`,`page_date`);--> statement-breakpoint
+CREATE VIRTUAL TABLE `attachments_fts` USING fts5(attachment_id UNINDEXED, file_name, ocr_text);--> statement-breakpoint
+CREATE TABLE `voice_memos` (
+	`attachment_id` text PRIMARY KEY NOT NULL,
+	`duration_ms` integer NOT NULL,
+	`sample_rate` integer NOT NULL,
+	`waveform` text NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `ai_results` (
+	`id` text PRIMARY KEY NOT NULL,
+	`kind` text NOT NULL,
+	`target` text NOT NULL,
+	`model` text NOT NULL,
+	`content` text NOT NULL,
+	`created_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX `ai_results_target_idx` ON `ai_results` (`kind`,`target`);--> statement-breakpoint
+CREATE TABLE `page_locations` (
+	`workspace_id` text NOT NULL,
+	`page_date` text NOT NULL,
+	`latitude` real NOT NULL,
+	`longitude` real NOT NULL,
+	`accuracy` real,
+	`place_name` text,
+	`recorded_at` integer NOT NULL,
+	PRIMARY KEY(`workspace_id`, `page_date`)
+);
+--> statement-breakpoint
+CREATE INDEX `page_locations_coords_idx` ON `page_locations` (`latitude`,`longitude`);--> statement-breakpoint
+CREATE TABLE `daily_weather` (
+	`date` text PRIMARY KEY NOT NULL,
+	`provider` text NOT NULL,
+	`latitude` real NOT NULL,
+	`longitude` real NOT NULL,
+	`summary` text,
+	`weather_code` integer,
+	`temp_min` real,
+	`temp_max` real,
+	`precipitation` real,
+	`fetched_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `page_moods` (
+	`workspace_id` text NOT NULL,
+	`page_date` text NOT NULL,
+	`mood` integer NOT NULL CHECK (`mood` BETWEEN 1 AND 5),
+	`updated_at` integer NOT NULL,
+	PRIMARY KEY(`workspace_id`, `page_date`)
+);
+--> statement-breakpoint
+CREATE TABLE `time_sessions` (
+	`id` text PRIMARY KEY NOT NULL,
+	`todo_id` text,
+	`kind` text NOT NULL,
+	`started_at` integer NOT NULL,
+	`ended_at` integer,
+	`paused_at` integer,
+	`paused_ms` integer DEFAULT 0 NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX `time_sessions_todo_idx` ON `time_sessions` (`todo_id`);--> statement-breakpoint
+CREATE TABLE `saved_filters` (
+	`id` text PRIMARY KEY NOT NULL,
+	`name` text NOT NULL,
+	`definition` text NOT NULL,
+	`created_at` integer NOT NULL,
+	`updated_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `telemetry_events` (
+	`kind` text NOT NULL,
+	`name` text NOT NULL,
+	`day` text NOT NULL,
+	`count` integer NOT NULL,
+	PRIMARY KEY(`kind`, `name`, `day`)
+);
+--> statement-breakpoint
+CREATE TABLE `private_pages` (
+	`workspace_id` text NOT NULL,
+	`page_date` text NOT NULL,
+	`created_at` integer NOT NULL,
+	PRIMARY KEY(`workspace_id`, `page_date`)
+);
+--> statement-breakpoint
+CREATE TABLE `locked_pages` (
+	`workspace_id` text NOT NULL,
+	`page_date` text NOT NULL,
+	`salt` text NOT NULL,
+	`key_check` text NOT NULL,
+	`created_at` integer NOT NULL,
+	PRIMARY KEY(`workspace_id`, `page_date`)
+);
+--> statement-breakpoint
+-- Keeps writes through the proxy from replacing a locked entry's notes with plaintext
+CREATE TRIGGER `locked_pages_keep_notes_encrypted` BEFORE UPDATE OF `notes` ON `pages`
+WHEN EXISTS (SELECT 1 FROM `locked_pages` WHERE `workspace_id` = OLD.`workspace_id` AND `page_date` = OLD.`date`)
+	AND (NEW.`notes` IS NULL OR NEW.`notes` NOT LIKE 'enc:v1:%')
+BEGIN
+	SELECT RAISE(ABORT, 'Entry is locked');
+END;
+--> statement-breakpoint
+CREATE TABLE `job_runs` (
+	`name` text PRIMARY KEY NOT NULL,
+	`last_run_at` integer,
+	`last_success_at` integer,
+	`last_error` text,
+	`failures` integer DEFAULT 0 NOT NULL,
+	`next_run_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `todo_rollovers` (
+	`id` text PRIMARY KEY NOT NULL,
+	`mode` text NOT NULL,
+	`from_date` text NOT NULL,
+	`to_date` text NOT NULL,
+	`todo_ids` text NOT NULL,
+	`created_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `email_imports` (
+	`message_id` text PRIMARY KEY NOT NULL,
+	`workspace_id` text NOT NULL,
+	`page_date` text NOT NULL,
+	`todo_id` text,
+	`imported_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `rules` (
+	`id` text PRIMARY KEY NOT NULL,
+	`name` text NOT NULL,
+	`enabled` integer DEFAULT 1 NOT NULL,
+	`definition` text NOT NULL,
+	`created_at` integer NOT NULL,
+	`updated_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `rule_runs` (
+	`id` text PRIMARY KEY NOT NULL,
+	`rule_id` text NOT NULL,
+	`todo_id` text,
+	`status` text NOT NULL,
+	`message` text,
+	`created_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX `rule_runs_rule_idx` ON `rule_runs` (`rule_id`,`created_at`);--> statement-breakpoint
+CREATE TABLE `paired_devices` (
+	`id` text PRIMARY KEY NOT NULL,
+	`name` text NOT NULL,
+	`endpoint` text,
+	`paired_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `goals` (
+	`id` text PRIMARY KEY NOT NULL,
+	`workspace_id` text NOT NULL,
+	`name` text NOT NULL,
+	`target_date` text,
+	`target_minutes` integer,
+	`archived` integer DEFAULT 0 NOT NULL,
+	`created_at` integer NOT NULL,
+	`updated_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE TABLE `goal_links` (
+	`goal_id` text NOT NULL,
+	`kind` text NOT NULL,
+	`target` text NOT NULL,
+	`weight` real DEFAULT 1 NOT NULL,
+	PRIMARY KEY(`goal_id`, `kind`, `target`)
+);
+--> statement-breakpoint
+CREATE INDEX `goals_workspace_idx` ON `goals` (`workspace_id`);--> statement-breakpoint
+CREATE TABLE `time_blocks` (
+	`id` text PRIMARY KEY NOT NULL,
+	`workspace_id` text NOT NULL,
+	`page_date` text NOT NULL,
+	`todo_id` text NOT NULL,
+	`start_time` text NOT NULL,
+	`end_time` text NOT NULL,
+	`created_at` integer NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX `time_blocks_page_idx` ON `time_blocks` (`workspace_id`,`page_date`);
@@ -0,0 +1,350 @@
+use chrono::Utc;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, secrets, settings};
+
+/// Base URL of an OpenAI-compatible API, e.g. "https://api.openai.com/v1"
+const BASE_URL_SETTING: &str = "ai.base_url";
+const MODEL_SETTING: &str = "ai.model";
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+/// Keychain entry holding the API key
+const API_KEY_SECRET: &str = "ai.api_key";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Emitted for every streamed chunk of a response
+pub const AI_TOKEN_EVENT: &str = "ai://token";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DateRange {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AiResult {
+    pub id: String,
+    /// "summary", "breakdown" or "review"
+    pub kind: String,
+    /// The summarized range ("from..to") or the todo id
+    pub target: String,
+    pub model: String,
+    pub content: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AiToken {
+    request_id: String,
+    token: String,
+}
+
+struct AiConfig {
+    base_url: String,
+    model: String,
+    api_key: String,
+}
+
+async fn load_config(state: &DatabaseState) -> Result<AiConfig, String> {
+    let (base_url, model) = {
+        let pool = state.pool.lock().await;
+        (
+            settings::get_or(&pool, BASE_URL_SETTING, DEFAULT_BASE_URL.to_string()).await,
+            settings::get_or(&pool, MODEL_SETTING, DEFAULT_MODEL.to_string()).await,
+        )
+    };
+    let api_key = secrets::get(API_KEY_SECRET)?
+        .ok_or("No AI API key configured")?;
+    Ok(AiConfig {
+        base_url: base_url.trim_end_matches('/').to_string(),
+        model,
+        api_key,
+    })
+}
+
+/// Pull the content delta out of one server-sent event line
+fn parse_stream_line(line: &str) -> Option<String> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    let chunk: serde_json::Value = serde_json::from_str(data).ok()?;
+    chunk["choices"][0]["delta"]["content"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Run a chat completion, emitting tokens as they arrive, and return the full text
+async fn complete(
+    app: &AppHandle,
+    config: &AiConfig,
+    request_id: &str,
+    system: &str,
+    prompt: &str,
+) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let response = client
+        .post(format!("{}/chat/completions", config.base_url))
+        .bearer_auth(&config.api_key)
+        .json(&json!({
+            "model": config.model,
+            "stream": true,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": prompt },
+            ],
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("AI request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("AI request failed ({}): {}", status, body.trim()));
+    }
+
+    let mut content = String::new();
+    let mut buffer = String::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(newline) = buffer.find('\n') {
+            let line: String = buffer.drain(..=newline).collect();
+            if let Some(token) = parse_stream_line(line.trim()) {
+                content.push_str(&token);
+                let _ = app.emit(
+                    AI_TOKEN_EVENT,
+                    AiToken {
+                        request_id: request_id.to_string(),
+                        token,
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(content.trim().to_string())
+}
+
+async fn store_result(
+    app: &AppHandle,
+    state: &DatabaseState,
+    result: &AiResult,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        sqlx::query(
+            "INSERT INTO ai_results (id, kind, target, model, content, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&result.id)
+        .bind(&result.kind)
+        .bind(&result.target)
+        .bind(&result.model)
+        .bind(&result.content)
+        .bind(result.created_at)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    changes::notify(app, vec!["ai_results".to_string()]);
+    Ok(())
+}
+
+/// Run a prompt and store the answer; `request_id` tags the streamed tokens
+async fn run(
+    app: &AppHandle,
+    state: &DatabaseState,
+    request_id: Option<String>,
+    kind: &str,
+    target: String,
+    system: &str,
+    prompt: String,
+) -> Result<AiResult, String> {
+    let config = load_config(state).await?;
+    let id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let content = complete(app, &config, &id, system, &prompt).await.map_err(|e| {
+        logger::error(&format!("AI {} failed: {}", kind, e));
+        e
+    })?;
+
+    let result = AiResult {
+        id,
+        kind: kind.to_string(),
+        target,
+        model: config.model,
+        content,
+        created_at: Utc::now().timestamp_millis(),
+    };
+    store_result(app, state, &result).await?;
+    Ok(result)
+}
+
+/// Whether an API key is set, so optional AI steps can be skipped without an error
+pub(crate) fn is_configured() -> bool {
+    secrets::get(API_KEY_SECRET).ok().flatten().is_some()
+}
+
+/// Summarize a generated week or month review for the top of the review
+pub(crate) async fn summarize_review(
+    app: &AppHandle,
+    state: &DatabaseState,
+    request_id: Option<String>,
+    target: String,
+    review: &str,
+) -> Result<AiResult, String> {
+    run(
+        app,
+        state,
+        request_id,
+        "review",
+        target,
+        "You write the opening of a personal weekly or monthly review. From the statistics and \
+         completed todos given, write two to four sentences on what went well, what took the most \
+         time and what to keep in mind for the next period. Answer in the language of the todos.",
+        review.to_string(),
+    )
+    .await
+}
+
+/// Summarize the notes and todos of a date range
+#[crate::metrics::command]
+pub async fn summarize_entries(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    range: DateRange,
+    request_id: Option<String>,
+) -> Result<AiResult, String> {
+    let (pages, todos) = {
+        let pool = state.pool.lock().await;
+        let pages: Vec<(String, Option<String>)> = sqlx::query_as(
+            "SELECT date, COALESCE(notes, zstd_decompress(notes_zstd)) FROM pages
+             WHERE workspace_id = ? AND date BETWEEN ? AND ? ORDER BY date",
+        )
+        .bind(&workspace_id)
+        .bind(&range.from)
+        .bind(&range.to)
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        let todos: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT page_date, text, status FROM todos WHERE workspace_id = ? AND page_date BETWEEN ? AND ? ORDER BY page_date, `order`",
+        )
+        .bind(&workspace_id)
+        .bind(&range.from)
+        .bind(&range.to)
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        (pages, todos)
+    };
+
+    let mut prompt = String::new();
+    for (date, notes) in &pages {
+        prompt.push_str(&format!("## {}\n", date));
+        if let Some(notes) = notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            prompt.push_str(notes.trim());
+            prompt.push('\n');
+        }
+        for (_, text, status) in todos.iter().filter(|(d, text, _)| d == date && !text.trim().is_empty()) {
+            let mark = if status == "done" { "x" } else { " " };
+            prompt.push_str(&format!("- [{}] {}\n", mark, text.trim()));
+        }
+        prompt.push('\n');
+    }
+    if prompt.trim().is_empty() {
+        return Err("Nothing to summarize in this range".to_string());
+    }
+
+    run(
+        &app,
+        &state,
+        request_id,
+        "summary",
+        format!("{}..{}", range.from, range.to),
+        "You summarize personal journal entries. Write a short summary of what happened, \
+         what was accomplished and what is still open. Answer in the language of the entries.",
+        prompt,
+    )
+    .await
+}
+
+/// Suggest smaller steps for a todo
+#[crate::metrics::command]
+pub async fn suggest_todo_breakdown(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    todo_id: String,
+    request_id: Option<String>,
+) -> Result<AiResult, String> {
+    let (text, subtasks): (String, Vec<(String,)>) = {
+        let pool = state.pool.lock().await;
+        let (text,): (String,) = sqlx::query_as("SELECT text FROM todos WHERE id = ?")
+            .bind(&todo_id)
+            .fetch_optional(&*pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Todo not found: {}", todo_id))?;
+        let subtasks = sqlx::query_as("SELECT text FROM todos WHERE parent_id = ? ORDER BY `order`")
+            .bind(&todo_id)
+            .fetch_all(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        (text, subtasks)
+    };
+
+    let mut prompt = format!("Task: {}\n", text.trim());
+    if !subtasks.is_empty() {
+        prompt.push_str("Existing subtasks:\n");
+        for (subtask,) in &subtasks {
+            prompt.push_str(&format!("- {}\n", subtask.trim()));
+        }
+    }
+
+    run(
+        &app,
+        &state,
+        request_id,
+        "breakdown",
+        todo_id,
+        "You break tasks down into small, concrete next steps. Reply with a markdown list of 3 to 7 \
+         subtasks, one line each, without any other text. Answer in the language of the task.",
+        prompt,
+    )
+    .await
+}
+
+/// Previously stored results for a summary range or todo
+#[crate::metrics::command]
+pub async fn get_ai_results(
+    state: State<'_, DatabaseState>,
+    kind: String,
+    target: String,
+) -> Result<Vec<AiResult>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as(
+        "SELECT id, kind, target, model, content, created_at FROM ai_results WHERE kind = ? AND target = ? ORDER BY created_at DESC",
+    )
+    .bind(kind)
+    .bind(target)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,124 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{logger, workspaces};
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Machine-level configuration, persisted as `config.json` in the app data directory.
+///
+/// The journal database travels with imports, sync and merges, and the webview can write
+/// anything in it through the SQL proxy, so whatever decides what the app runs or is
+/// allowed to do on this machine lives here instead.
+pub struct AppConfig {
+    path: PathBuf,
+    values: Mutex<serde_json::Map<String, serde_json::Value>>,
+    /// False when an unreadable file couldn't be set aside, so it must not be overwritten
+    writable: bool,
+}
+
+impl AppConfig {
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(CONFIG_FILE_NAME);
+        let mut writable = true;
+        let values = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                logger::error(&format!("Failed to parse app config: {}", e));
+                writable = workspaces::set_aside_corrupt(&path);
+                serde_json::Map::new()
+            }),
+            Err(_) => serde_json::Map::new(),
+        };
+        Self {
+            path,
+            values: Mutex::new(values),
+            writable,
+        }
+    }
+
+    /// The value under `key`, None when it's missing or doesn't deserialize as `T`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.values.lock().unwrap().get(key).cloned()?;
+        serde_json::from_value(value).ok()
+    }
+
+    pub fn get_or<T: DeserializeOwned>(&self, key: &str, default: T) -> T {
+        self.get(key).unwrap_or(default)
+    }
+
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<(), String> {
+        let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        let mut values = self.values.lock().unwrap();
+        values.insert(key.to_string(), value);
+        self.save(&values)
+    }
+
+    pub fn remove(&self, key: &str) -> Result<(), String> {
+        let mut values = self.values.lock().unwrap();
+        values.remove(key);
+        self.save(&values)
+    }
+
+    /// Change the value under `key` in place, starting from `T::default()` when it's missing,
+    /// without another writer slipping in between
+    pub fn update<T, R>(&self, key: &str, change: impl FnOnce(&mut T) -> Result<R, String>) -> Result<R, String>
+    where
+        T: DeserializeOwned + Serialize + Default,
+    {
+        let mut values = self.values.lock().unwrap();
+        let mut current = values
+            .get(key)
+            .cloned()
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default();
+        let result = change(&mut current)?;
+        let value = serde_json::to_value(&current).map_err(|e| e.to_string())?;
+        values.insert(key.to_string(), value);
+        self.save(&values)?;
+        Ok(result)
+    }
+
+    fn save(&self, values: &serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+        if !self.writable {
+            return Err(format!("{} is unreadable and was left untouched", self.path.display()));
+        }
+        let content = serde_json::to_string_pretty(values).map_err(|e| e.to_string())?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, &self.path).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_survive_a_reload() {
+        let dir = std::env::temp_dir().join(format!("journal-todo-config-{}", uuid::Uuid::new_v4()));
+        let config = AppConfig::load(&dir);
+        assert_eq!(config.get_or("answer", 0), 0);
+        config
+            .update("answer", |answer: &mut i64| {
+                *answer = 42;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(AppConfig::load(&dir).get::<i64>("answer"), Some(42));
+        let refused = config.update("answer", |_: &mut i64| Err::<(), _>("no".to_string()));
+        assert!(refused.is_err());
+        assert_eq!(AppConfig::load(&dir).get::<i64>("answer"), Some(42), "a failed change isn't saved");
+
+        std::fs::write(dir.join(CONFIG_FILE_NAME), "{broken").unwrap();
+        let config = AppConfig::load(&dir);
+        assert_eq!(config.get::<i64>("answer"), None);
+        assert!(dir.join("config.json.corrupt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
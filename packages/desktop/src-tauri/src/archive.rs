@@ -0,0 +1,280 @@
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+use crate::db::{attach, changes, DatabaseState};
+use crate::search::{like_pattern, snippet, EntryHit, TodoHit};
+
+/// Archive file, kept next to the main database
+const ARCHIVE_FILE_NAME: &str = "journal-archive.db";
+/// Schema name the archive is attached under, also for proxy queries after `attach_database`
+const ARCHIVE_ALIAS: &str = "archive";
+const DEFAULT_LIMIT: i64 = 20;
+
+/// Same columns as the main tables, so rows move across with a plain INSERT ... SELECT
+const ARCHIVE_SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS archive.pages (
+        workspace_id TEXT NOT NULL,
+        date TEXT NOT NULL,
+        notes TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (workspace_id, date)
+    )",
+    "CREATE TABLE IF NOT EXISTS archive.todos (
+        id TEXT PRIMARY KEY NOT NULL,
+        workspace_id TEXT NOT NULL,
+        page_date TEXT NOT NULL,
+        text TEXT NOT NULL,
+        status TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        `order` TEXT NOT NULL,
+        level INTEGER NOT NULL,
+        parent_id TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS archive.todos_page ON todos (workspace_id, page_date)",
+];
+
+const PAGE_COLUMNS: &str = "workspace_id, date, notes, created_at, updated_at";
+/// `PAGE_COLUMNS` read from the main database; the archive keeps notes uncompressed
+const PAGE_VALUES: &str =
+    "workspace_id, date, COALESCE(notes, zstd_decompress(notes_zstd)), created_at, updated_at";
+const TODO_COLUMNS: &str =
+    "id, workspace_id, page_date, text, status, tags, `order`, level, parent_id, created_at, updated_at";
+
+/// Pages before the cutoff that can move; private and locked entries stay with
+/// the flags and keys that open them
+const ARCHIVABLE_PAGES: &str = "date < ?1
+    AND NOT EXISTS (SELECT 1 FROM main.private_pages pp WHERE pp.workspace_id = pages.workspace_id AND pp.page_date = pages.date)
+    AND NOT EXISTS (SELECT 1 FROM main.locked_pages lp WHERE lp.workspace_id = pages.workspace_id AND lp.page_date = pages.date)";
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSummary {
+    pub pages: u64,
+    pub todos: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveResults {
+    pub todos: Vec<TodoHit>,
+    pub entries: Vec<EntryHit>,
+}
+
+async fn archive_path(state: &DatabaseState) -> PathBuf {
+    let db_path = state.db_path.lock().await;
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(ARCHIVE_FILE_NAME)
+}
+
+/// Attach the archive as `archive` unless `attach_database` already did;
+/// true if it needs detaching afterwards
+async fn attach(conn: &mut SqliteConnection, path: &Path) -> Result<bool, String> {
+    attach::attach_to(conn, ARCHIVE_ALIAS, path)
+        .await
+        .map_err(|e| format!("Failed to open archive: {}", e))
+}
+
+async fn detach(conn: &mut SqliteConnection, attached: bool) {
+    if !attached {
+        return;
+    }
+    if let Err(e) = attach::detach_from(conn, ARCHIVE_ALIAS).await {
+        crate::logger::error(&format!("Failed to detach archive: {}", e));
+    }
+}
+
+/// Copy entries before `before_date` into the archive and delete them, all or nothing
+async fn move_entries(conn: &mut SqliteConnection, before_date: &str) -> Result<ArchiveSummary, String> {
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    for statement in ARCHIVE_SCHEMA {
+        sqlx::query(statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let pages_query = format!(
+        "INSERT OR REPLACE INTO archive.pages ({0}) SELECT {1} FROM main.pages WHERE {2}",
+        PAGE_COLUMNS, PAGE_VALUES, ARCHIVABLE_PAGES
+    );
+    let pages = sqlx::query(&pages_query)
+        .bind(before_date)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+    let todos_query = format!(
+        "INSERT OR REPLACE INTO archive.todos ({0}) SELECT {0} FROM main.todos
+         WHERE EXISTS (SELECT 1 FROM archive.pages p WHERE p.workspace_id = todos.workspace_id AND p.date = todos.page_date)
+           AND page_date < ?1",
+        TODO_COLUMNS
+    );
+    let todos = sqlx::query(&todos_query)
+        .bind(before_date)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+    // Todos first, they reference their page
+    sqlx::query(
+        "DELETE FROM main.todos WHERE page_date < ?1
+         AND EXISTS (SELECT 1 FROM archive.todos a WHERE a.id = todos.id)",
+    )
+    .bind(before_date)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query(&format!("DELETE FROM main.pages WHERE {}", ARCHIVABLE_PAGES))
+        .bind(before_date)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(ArchiveSummary { pages, todos })
+}
+
+/// Move journal entries and their todos dated before `before_date` (YYYY-MM-DD)
+/// into `journal-archive.db`
+#[crate::metrics::command]
+pub async fn archive_entries(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    before_date: String,
+) -> Result<ArchiveSummary, String> {
+    if chrono::NaiveDate::parse_from_str(&before_date, "%Y-%m-%d").is_err() {
+        return Err(format!("Invalid date: {}", before_date));
+    }
+    let path = archive_path(&state).await;
+    let summary = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        let attached = attach(&mut conn, &path).await?;
+        let result = move_entries(&mut conn, &before_date).await;
+        detach(&mut conn, attached).await;
+        result?
+    };
+
+    crate::logger::info(&format!(
+        "Archived {} pages and {} todos from before {}",
+        summary.pages, summary.todos, before_date
+    ));
+    changes::notify(&app, vec!["pages".to_string(), "todos".to_string()]);
+    Ok(summary)
+}
+
+async fn search_attached(conn: &mut SqliteConnection, query: &str, limit: i64) -> Result<ArchiveResults, String> {
+    let pattern = like_pattern(query);
+    let todos: Vec<TodoHit> = sqlx::query_as(
+        "SELECT id, workspace_id, page_date, text, status FROM archive.todos
+         WHERE text LIKE ?1 ESCAPE '\\'
+         ORDER BY page_date DESC
+         LIMIT ?2",
+    )
+    .bind(&pattern)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let pages: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT workspace_id, date, notes FROM archive.pages
+         WHERE notes LIKE ?1 ESCAPE '\\'
+         ORDER BY date DESC
+         LIMIT ?2",
+    )
+    .bind(&pattern)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    let entries = pages
+        .into_iter()
+        .map(|(workspace_id, date, notes)| EntryHit {
+            workspace_id,
+            date,
+            snippet: snippet(&notes, query),
+        })
+        .collect();
+
+    Ok(ArchiveResults { todos, entries })
+}
+
+/// Search archived todos and journal notes, newest first
+#[crate::metrics::command]
+pub async fn search_archive(
+    state: State<'_, DatabaseState>,
+    query: String,
+    limit: Option<i64>,
+) -> Result<ArchiveResults, String> {
+    let query = query.trim();
+    let path = archive_path(&state).await;
+    if query.is_empty() || !path.exists() {
+        return Ok(ArchiveResults {
+            todos: Vec::new(),
+            entries: Vec::new(),
+        });
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, 200);
+
+    let pool = state.pool.lock().await;
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let attached = attach(&mut conn, &path).await?;
+    let result = search_attached(&mut conn, query, limit).await;
+    detach(&mut conn, attached).await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_archive_moves_old_entries() {
+        let dir = std::env::temp_dir().join(format!("journal-todo-archive-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        crate::db::functions::register(&mut conn).await.unwrap();
+        for statement in [
+            "CREATE TABLE pages (workspace_id TEXT, date TEXT, notes TEXT, created_at INTEGER, updated_at INTEGER,
+                notes_zstd BLOB)",
+            "CREATE TABLE todos (id TEXT, workspace_id TEXT, page_date TEXT, text TEXT, status TEXT, tags TEXT,
+                `order` TEXT, level INTEGER, parent_id TEXT, created_at INTEGER, updated_at INTEGER)",
+            "CREATE TABLE private_pages (workspace_id TEXT, page_date TEXT)",
+            "CREATE TABLE locked_pages (workspace_id TEXT, page_date TEXT)",
+            "INSERT INTO pages (workspace_id, date, notes, created_at, updated_at) VALUES
+                ('w', '2023-01-01', 'old notes', 0, 0), ('w', '2023-01-02', 'secret', 0, 0),
+                ('w', '2024-06-01', 'new notes', 0, 0)",
+            "INSERT INTO private_pages VALUES ('w', '2023-01-02')",
+            "INSERT INTO todos VALUES ('t1', 'w', '2023-01-01', 'old todo', 'done', '[]', 'a0', 0, NULL, 0, 0),
+                ('t2', 'w', '2024-06-01', 'new todo', 'todo', '[]', 'a0', 0, NULL, 0, 0)",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+
+        assert!(attach(&mut conn, &dir.join(ARCHIVE_FILE_NAME)).await.unwrap());
+        let summary = move_entries(&mut conn, "2024-01-01").await.unwrap();
+        assert_eq!((summary.pages, summary.todos), (1, 1));
+
+        let left: Vec<(String,)> = sqlx::query_as("SELECT date FROM main.pages ORDER BY date")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(left, vec![("2023-01-02".to_string(),), ("2024-06-01".to_string(),)]);
+
+        let results = search_attached(&mut conn, "old", 10).await.unwrap();
+        assert_eq!(results.todos.len(), 1);
+        assert_eq!(results.entries[0].date, "2023-01-01");
+        detach(&mut conn, true).await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,362 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, settings};
+
+const ATTACHMENTS_DIR_NAME: &str = "attachments";
+/// When on, the background cleanup deletes what it finds instead of only logging it
+const AUTO_CLEANUP_SETTING: &str = "attachments.auto_cleanup";
+/// Files younger than this may belong to an attachment still being stored
+const ORPHAN_GRACE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub workspace_id: String,
+    pub page_date: Option<String>,
+    pub todo_id: Option<String>,
+    pub file_name: String,
+    pub stored_name: String,
+    pub mime_type: Option<String>,
+    pub size: i64,
+    pub ocr_text: Option<String>,
+    pub created_at: i64,
+}
+
+pub const ATTACHMENT_COLUMNS: &str =
+    "id, workspace_id, page_date, todo_id, file_name, stored_name, mime_type, size, ocr_text, created_at";
+
+/// Attachment files live next to the database so each workspace keeps its own
+pub async fn attachments_dir(state: &DatabaseState) -> PathBuf {
+    let db_path = state.db_path.lock().await;
+    db_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(ATTACHMENTS_DIR_NAME)
+}
+
+/// Location of an attachment's file on disk
+pub async fn file_path(state: &DatabaseState, attachment: &Attachment) -> PathBuf {
+    attachments_dir(state).await.join(&attachment.stored_name)
+}
+
+/// Guess a MIME type from the file extension
+pub fn guess_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" => "audio/ogg",
+        _ => return None,
+    })
+}
+
+pub async fn get(state: &DatabaseState, id: &str) -> Result<Attachment, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as(&format!("SELECT {} FROM attachments WHERE id = ?", ATTACHMENT_COLUMNS))
+        .bind(id)
+        .fetch_optional(&*pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Attachment not found: {}", id))
+}
+
+/// Copy a file into the attachment store and record it
+pub async fn store_file(
+    state: &DatabaseState,
+    source: &Path,
+    workspace_id: &str,
+    page_date: Option<&str>,
+    todo_id: Option<&str>,
+) -> Result<Attachment, String> {
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Not a file: {}", source.display()))?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let stored_name = match source.extension() {
+        Some(ext) => format!("{}.{}", id, ext.to_string_lossy()),
+        None => id.clone(),
+    };
+
+    let dir = attachments_dir(state).await;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let size = std::fs::copy(source, dir.join(&stored_name))
+        .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+
+    let attachment = Attachment {
+        id,
+        workspace_id: workspace_id.to_string(),
+        page_date: page_date.map(str::to_string),
+        todo_id: todo_id.map(str::to_string),
+        file_name,
+        stored_name,
+        mime_type: guess_mime_type(source).map(str::to_string),
+        size: size as i64,
+        ocr_text: None,
+        created_at: Utc::now().timestamp_millis(),
+    };
+
+    let pool = state.pool.lock().await;
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query(&format!(
+        "INSERT INTO attachments ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(&attachment.id)
+    .bind(&attachment.workspace_id)
+    .bind(&attachment.page_date)
+    .bind(&attachment.todo_id)
+    .bind(&attachment.file_name)
+    .bind(&attachment.stored_name)
+    .bind(&attachment.mime_type)
+    .bind(attachment.size)
+    .bind(&attachment.ocr_text)
+    .bind(attachment.created_at)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query("INSERT INTO attachments_fts (attachment_id, file_name, ocr_text) VALUES (?, ?, NULL)")
+        .bind(&attachment.id)
+        .bind(&attachment.file_name)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(attachment)
+}
+
+/// Copy a file into the attachment store, bound to a page and optionally a todo
+#[crate::metrics::command]
+pub async fn add_attachment(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    source_path: String,
+    workspace_id: String,
+    page_date: Option<String>,
+    todo_id: Option<String>,
+) -> Result<Attachment, String> {
+    let attachment = store_file(
+        &state,
+        Path::new(&source_path),
+        &workspace_id,
+        page_date.as_deref(),
+        todo_id.as_deref(),
+    )
+    .await?;
+    changes::notify(&app, vec!["attachments".to_string()]);
+    Ok(attachment)
+}
+
+#[crate::metrics::command]
+pub async fn list_attachments(
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    page_date: String,
+) -> Result<Vec<Attachment>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as(&format!(
+        "SELECT {} FROM attachments WHERE workspace_id = ? AND page_date = ? ORDER BY created_at",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(workspace_id)
+    .bind(page_date)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Absolute path of an attachment's file, for display in the webview
+#[crate::metrics::command]
+pub async fn get_attachment_path(state: State<'_, DatabaseState>, id: String) -> Result<String, String> {
+    let attachment = get(&state, &id).await?;
+    Ok(file_path(&state, &attachment).await.to_string_lossy().to_string())
+}
+
+/// Remove an attachment's rows, leaving its file alone
+async fn delete_record(pool: &SqlitePool, id: &str) -> Result<(), String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    for statement in [
+        "DELETE FROM attachments WHERE id = ?",
+        "DELETE FROM attachments_fts WHERE attachment_id = ?",
+        "DELETE FROM voice_memos WHERE attachment_id = ?",
+    ] {
+        sqlx::query(statement)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+#[crate::metrics::command]
+pub async fn delete_attachment(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: String,
+) -> Result<(), String> {
+    let attachment = get(&state, &id).await?;
+    let path = file_path(&state, &attachment).await;
+
+    {
+        let pool = state.pool.lock().await;
+        delete_record(&pool, &id).await?;
+    }
+
+    if let Err(e) = std::fs::remove_file(&path) {
+        logger::error(&format!("Failed to delete {}: {}", path.display(), e));
+    }
+    changes::notify(&app, vec!["attachments".to_string()]);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupReport {
+    /// Files in the attachments directory no attachment refers to
+    pub orphaned_files: Vec<String>,
+    /// Ids of attachments whose file is gone
+    pub missing_files: Vec<String>,
+    /// Whether both were deleted
+    pub fixed: bool,
+}
+
+/// Stored names no row refers to, and ids of rows whose stored name isn't on disk
+fn find_orphans(rows: &[(String, String)], files: &[String]) -> (Vec<String>, Vec<String>) {
+    let referenced: HashSet<&str> = rows.iter().map(|(_, stored_name)| stored_name.as_str()).collect();
+    let present: HashSet<&str> = files.iter().map(String::as_str).collect();
+    let orphaned = files.iter().filter(|f| !referenced.contains(f.as_str())).cloned().collect();
+    let missing = rows
+        .iter()
+        .filter(|(_, stored_name)| !present.contains(stored_name.as_str()))
+        .map(|(id, _)| id.clone())
+        .collect();
+    (orphaned, missing)
+}
+
+/// Files in `dir`, left out if modified within `ORPHAN_GRACE` of now when `settled` is set
+fn stored_files(dir: &Path, settled: bool) -> Result<Vec<String>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let now = SystemTime::now();
+    Ok(entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter(|entry| {
+            !settled
+                || entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() >= ORPHAN_GRACE)
+        })
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect())
+}
+
+/// Find attachment files without a row and rows without a file, deleting both if `fix`
+pub async fn cleanup(state: &DatabaseState, fix: bool) -> Result<CleanupReport, String> {
+    let dir = attachments_dir(state).await;
+    let pool = state.pool.lock().await;
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT id, stored_name FROM attachments")
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Every file counts when looking for missing ones; only settled files can be orphans
+    let (_, missing_files) = find_orphans(&rows, &stored_files(&dir, false)?);
+    let (orphaned_files, _) = find_orphans(&rows, &stored_files(&dir, true)?);
+
+    if fix {
+        for id in &missing_files {
+            delete_record(&pool, id).await?;
+        }
+        for name in &orphaned_files {
+            let path = dir.join(name);
+            if let Err(e) = std::fs::remove_file(&path) {
+                logger::error(&format!("Failed to delete {}: {}", path.display(), e));
+            }
+        }
+    }
+    Ok(CleanupReport {
+        orphaned_files,
+        missing_files,
+        fixed: fix,
+    })
+}
+
+/// Report, and with `fix` delete, orphaned attachment files and rows
+#[crate::metrics::command]
+pub async fn cleanup_attachments(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    fix: Option<bool>,
+) -> Result<CleanupReport, String> {
+    let report = cleanup(&state, fix.unwrap_or(false)).await?;
+    if report.fixed && !report.missing_files.is_empty() {
+        changes::notify(&app, vec!["attachments".to_string()]);
+    }
+    Ok(report)
+}
+
+/// Scheduled cleanup; only deletes when `attachments.auto_cleanup` is on
+pub async fn cleanup_job(app: AppHandle) -> Result<(), String> {
+    let state = app.state::<DatabaseState>();
+    let fix = {
+        let pool = state.pool.lock().await;
+        settings::get_or(&pool, AUTO_CLEANUP_SETTING, false).await
+    };
+    let report = cleanup(&state, fix).await?;
+    if report.orphaned_files.is_empty() && report.missing_files.is_empty() {
+        return Ok(());
+    }
+    logger::info(&format!(
+        "Attachment cleanup: {} orphaned files, {} rows missing their file{}",
+        report.orphaned_files.len(),
+        report.missing_files.len(),
+        if report.fixed { ", deleted" } else { "" }
+    ));
+    if report.fixed && !report.missing_files.is_empty() {
+        changes::notify(&app, vec!["attachments".to_string()]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_orphans() {
+        let rows = vec![
+            ("a".to_string(), "a.png".to_string()),
+            ("b".to_string(), "b.pdf".to_string()),
+        ];
+        let files = vec!["a.png".to_string(), "stray.txt".to_string()];
+        let (orphaned, missing) = find_orphans(&rows, &files);
+        assert_eq!(orphaned, vec!["stray.txt"]);
+        assert_eq!(missing, vec!["b"]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Command line verbs for automation tools (Keyboard Maestro, AutoHotkey, shell scripts).
+//! While the app runs, a second launch forwards its arguments over the single-instance pipe,
+//! so `journal-todo add "Call Sam"` adds a todo without opening another window:
+//!
+//! - `add <text> [--date=YYYY-MM-DD]` adds a todo
+//! - `note <text> [--date=YYYY-MM-DD]` appends to the notes
+//! - `open [--date=YYYY-MM-DD]` and `new-todo [--date=YYYY-MM-DD]` bring up the app
+//! - `today --output=<file>` writes today's todos to the file as JSON, replacing it whole once
+//!   ready, so callers can wait for it to appear
+
+use chrono::Local;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::db::DatabaseState;
+use crate::dispatch::{self, Action};
+use crate::logger;
+
+#[derive(Debug, Clone)]
+pub enum Verb {
+    Run(Action),
+    Today { output: PathBuf },
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TodayTodo {
+    pub id: String,
+    pub text: String,
+    pub status: String,
+    pub level: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TodayList {
+    pub date: String,
+    pub todos: Vec<TodayTodo>,
+}
+
+/// The verb in a launch's arguments (after the executable), if the launch is one
+pub fn parse(args: &[String]) -> Option<Result<Verb, String>> {
+    let verb = args.get(1)?.as_str();
+    if !matches!(verb, "add" | "note" | "open" | "new-todo" | "today") {
+        return None;
+    }
+    let option = |name: &str| {
+        args[2..]
+            .iter()
+            .find_map(|arg| arg.strip_prefix(&format!("--{}=", name)).map(str::to_string))
+    };
+    let text = args[2..]
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let date = option("date");
+
+    Some(match verb {
+        "add" => Ok(Verb::Run(Action::CreateTodo { text, date })),
+        "note" => Ok(Verb::Run(Action::AppendNote { text, date })),
+        "open" => Ok(Verb::Run(Action::OpenEntry { date })),
+        "new-todo" => Ok(Verb::Run(Action::NewTodo { date })),
+        _ => option("output")
+            .map(|output| Verb::Today { output: PathBuf::from(output) })
+            .ok_or_else(|| "today needs --output=<file> to write the list to".to_string()),
+    })
+}
+
+/// Today's todos in the current workspace, in page order
+pub(crate) async fn today_list(app: &AppHandle) -> Result<TodayList, String> {
+    let date = Local::now().format("%Y-%m-%d").to_string();
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let workspace_id = dispatch::current_workspace_id(&mut conn).await?;
+    // Locked entries stay hidden from scripts as they do in the app
+    let todos = sqlx::query_as(
+        "SELECT t.id, t.text, t.status, t.level FROM todos t
+         WHERE t.workspace_id = ?1 AND t.page_date = ?2
+           AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = ?1 AND lp.page_date = ?2)
+         ORDER BY t.`order`",
+    )
+    .bind(&workspace_id)
+    .bind(&date)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(TodayList { date, todos })
+}
+
+/// Write the whole file at once, so a caller waiting for it never reads half of it
+fn write_output(path: &Path, contents: &str) -> Result<(), String> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, contents).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+}
+
+async fn run(app: &AppHandle, verb: Verb) -> Result<(), String> {
+    match verb {
+        Verb::Run(action) => dispatch::dispatch(app, action).await.map(|_| ()),
+        Verb::Today { output } => {
+            let list = today_list(app).await?;
+            let json = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
+            write_output(&output, &json)
+        }
+    }
+}
+
+/// Run the verb in a launch's arguments. Returns false when they hold none, e.g. a plain
+/// second launch that should just bring the window up.
+pub fn handle(app: &AppHandle, args: &[String]) -> bool {
+    let verb = match parse(args) {
+        None => return false,
+        Some(Ok(verb)) => verb,
+        Some(Err(e)) => {
+            logger::error(&format!("Automation command failed: {}", e));
+            return true;
+        }
+    };
+    logger::info(&format!("Automation command: {}", args[1]));
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = run(&app, verb).await {
+            logger::error(&format!("Automation command failed: {}", e));
+        }
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("journal-todo").chain(args.iter().copied()).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_verbs() {
+        let verb = parse(&args(&["add", "Call", "Sam", "--date=2024-03-04"])).unwrap().unwrap();
+        assert!(matches!(
+            verb,
+            Verb::Run(Action::CreateTodo { text, date }) if text == "Call Sam" && date.as_deref() == Some("2024-03-04")
+        ));
+        let verb = parse(&args(&["today", "--output=/tmp/today.json"])).unwrap().unwrap();
+        assert!(matches!(verb, Verb::Today { output } if output == Path::new("/tmp/today.json")));
+        assert!(parse(&args(&["today"])).unwrap().is_err());
+        assert!(parse(&args(&["--profile=Alice"])).is_none());
+        assert!(parse(&args(&[])).is_none());
+    }
+}
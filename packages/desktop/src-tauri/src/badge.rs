@@ -0,0 +1,81 @@
+use chrono::Local;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::logger;
+
+/// Also refresh periodically so the count follows the date rolling over
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Keep the dock / taskbar badge in sync with today's remaining todos
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    let listener_app = app.clone();
+    changes::listen(&app, &["todos"], move |_| {
+        let app = listener_app.clone();
+        tauri::async_runtime::spawn(async move { refresh(&app).await });
+    });
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            refresh(&app).await;
+        }
+    })
+}
+
+async fn refresh(app: &AppHandle) {
+    match remaining_today(app).await {
+        Ok(count) => apply(app, count),
+        Err(e) => logger::error(&format!("Failed to count remaining todos: {}", e)),
+    }
+}
+
+/// Incomplete todos on today's page
+pub async fn remaining_today(app: &AppHandle) -> Result<i64, String> {
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let (count,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM todos WHERE page_date = ? AND status != 'done' AND trim(text) != ''",
+    )
+    .bind(today)
+    .fetch_one(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+fn apply(app: &AppHandle, count: i64) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let result = window.set_badge_count(if count > 0 { Some(count) } else { None });
+
+    // Windows has no badge count; show a dot overlay on the taskbar icon instead
+    #[cfg(target_os = "windows")]
+    let result = window.set_overlay_icon(if count > 0 { Some(overlay_dot()) } else { None });
+
+    if let Err(e) = result {
+        logger::error(&format!("Failed to update badge: {}", e));
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn overlay_dot() -> tauri::image::Image<'static> {
+    const SIZE: u32 = 16;
+    let center = (SIZE as f32 - 1.0) / 2.0;
+    let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+            let alpha = if distance <= center { 255 } else { 0 };
+            rgba.extend_from_slice(&[220, 38, 38, alpha]);
+        }
+    }
+    tauri::image::Image::new_owned(rgba, SIZE, SIZE)
+}
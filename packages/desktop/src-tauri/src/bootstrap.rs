@@ -0,0 +1,150 @@
+use chrono::Local;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{changes, DatabaseState};
+
+/// Tables the bootstrap data is read from
+const WATCHED_TABLES: &[&str] = &["workspaces", "pages", "todos"];
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceRow {
+    pub id: String,
+    pub name: String,
+    pub current_date_key: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRow {
+    pub workspace_id: String,
+    pub date: String,
+    pub notes: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoRow {
+    pub id: String,
+    pub workspace_id: String,
+    pub page_date: String,
+    pub text: String,
+    pub status: String,
+    /// JSON array, as stored
+    pub tags: String,
+    pub order: String,
+    pub level: i64,
+    pub parent_id: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Everything the first screen needs, in one call
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootstrapData {
+    pub today: String,
+    pub workspaces: Vec<WorkspaceRow>,
+    /// Today's page in every workspace that has one
+    pub pages: Vec<PageRow>,
+    /// Today's todos plus open todos from earlier days
+    pub todos: Vec<TodoRow>,
+}
+
+/// Bootstrap data prefetched after startup, dropped when its tables change
+#[derive(Default)]
+pub struct BootstrapCache(Mutex<Option<BootstrapData>>);
+
+impl BootstrapCache {
+    fn get(&self, today: &str) -> Option<BootstrapData> {
+        self.0
+            .lock()
+            .ok()?
+            .as_ref()
+            .filter(|data| data.today == today)
+            .cloned()
+    }
+
+    fn set(&self, data: Option<BootstrapData>) {
+        if let Ok(mut cached) = self.0.lock() {
+            *cached = data;
+        }
+    }
+}
+
+async fn load(pool: &SqlitePool, today: &str) -> Result<BootstrapData, String> {
+    let workspaces: Vec<WorkspaceRow> =
+        sqlx::query_as("SELECT id, name, current_date_key, created_at, updated_at FROM workspaces ORDER BY created_at")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+    let pages: Vec<PageRow> =
+        sqlx::query_as("SELECT workspace_id, date, notes, created_at, updated_at FROM pages WHERE date = ?")
+            .bind(today)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+    let todos: Vec<TodoRow> = sqlx::query_as(
+        r#"SELECT id, workspace_id, page_date, text, status, tags, "order", level, parent_id, created_at, updated_at
+           FROM todos
+           WHERE page_date = ?1 OR (page_date < ?1 AND status != 'done')
+           ORDER BY page_date, "order""#,
+    )
+    .bind(today)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(BootstrapData {
+        today: today.to_string(),
+        workspaces,
+        pages,
+        todos,
+    })
+}
+
+/// Drop the cache whenever its tables change
+pub fn start(app: AppHandle) {
+    let listener_app = app.clone();
+    changes::listen(&app, WATCHED_TABLES, move |_| {
+        listener_app.state::<BootstrapCache>().set(None);
+    });
+}
+
+/// Load today's data into the cache ahead of the first `get_bootstrap_data` call
+pub async fn preload(app: &AppHandle) -> Result<(), String> {
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let data = load(&pool, &today).await?;
+    app.state::<BootstrapCache>().set(Some(data));
+    Ok(())
+}
+
+/// Workspaces, today's pages and open todos for rendering the first screen
+#[crate::metrics::command]
+pub async fn get_bootstrap_data(
+    state: State<'_, DatabaseState>,
+    cache: State<'_, BootstrapCache>,
+) -> Result<BootstrapData, String> {
+    state.ensure_ready()?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    if let Some(data) = cache.get(&today) {
+        return Ok(data);
+    }
+
+    let pool = state.pool.lock().await;
+    let data = load(&pool, &today).await?;
+    // Set while holding the pool so a concurrent write can't leave stale data behind
+    cache.set(Some(data.clone()));
+    Ok(data)
+}
@@ -0,0 +1,281 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, SqliteConnection};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::rollover::{self, RolloverTodo};
+
+/// One change applied to every selected todo
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum BulkOperation {
+    Complete,
+    Uncomplete,
+    /// To the end of the day's page, sub-tasks coming along
+    #[serde(rename_all = "camelCase")]
+    Move { date: String },
+    #[serde(rename_all = "camelCase")]
+    Retag {
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    /// Sub-tasks are deleted too
+    Delete,
+}
+
+impl BulkOperation {
+    /// `kind` plus the fields of `patch`, e.g. "move" and `{"date": "2024-03-04"}`
+    fn parse(kind: &str, patch: Option<serde_json::Value>) -> Result<BulkOperation, String> {
+        let mut fields = match patch {
+            Some(serde_json::Value::Object(fields)) => fields,
+            None | Some(serde_json::Value::Null) => serde_json::Map::new(),
+            Some(_) => return Err("The patch must be an object".to_string()),
+        };
+        fields.insert("kind".to_string(), serde_json::Value::String(kind.to_string()));
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|e| format!("Invalid {} operation: {}", kind, e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkResult {
+    /// Todos changed, including sub-tasks moved or deleted along with the selected ones
+    pub affected: u64,
+}
+
+/// `tags` (a JSON array) with `remove` taken out and `add` appended, skipping ones already there
+fn retag(tags: &str, add: &[String], remove: &[String]) -> String {
+    let mut tags: Vec<String> = serde_json::from_str(tags).unwrap_or_default();
+    tags.retain(|tag| !remove.contains(tag));
+    for tag in add {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    serde_json::to_string(&tags).unwrap_or_else(|_| "[]".to_string())
+}
+
+async fn set_status(conn: &mut SqliteConnection, ids_json: &str, status: &str) -> Result<u64, String> {
+    sqlx::query(
+        "UPDATE todos SET status = ?1, updated_at = ?2
+         WHERE id IN (SELECT value FROM json_each(?3)) AND status != ?1",
+    )
+    .bind(status)
+    .bind(Utc::now().timestamp())
+    .bind(ids_json)
+    .execute(&mut *conn)
+    .await
+    .map(|result| result.rows_affected())
+    .map_err(|e| e.to_string())
+}
+
+async fn move_todos(conn: &mut SqliteConnection, ids: &[String], ids_json: &str, date: &str) -> Result<u64, String> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))?;
+    // Every todo on the pages the selected ones are on, so their subtrees can be found
+    let todos: Vec<RolloverTodo> = sqlx::query_as(
+        "SELECT t.id, t.workspace_id, t.status, t.level, t.parent_id FROM todos t
+         WHERE EXISTS (
+             SELECT 1 FROM todos s
+             WHERE s.id IN (SELECT value FROM json_each(?))
+               AND s.workspace_id = t.workspace_id AND s.page_date = t.page_date
+         )
+         ORDER BY t.workspace_id, t.page_date, t.`order`",
+    )
+    .bind(ids_json)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut workspaces: Vec<&str> = todos.iter().map(|t| t.workspace_id.as_str()).collect();
+    workspaces.dedup();
+    let mut affected = 0;
+    for workspace_id in workspaces {
+        let in_workspace: Vec<RolloverTodo> =
+            todos.iter().filter(|t| t.workspace_id == workspace_id).cloned().collect();
+        let moves = rollover::plan_subtree_moves(&in_workspace, |todo| ids.contains(&todo.id));
+        rollover::move_to(conn, workspace_id, date, &moves).await?;
+        affected += moves.len() as u64;
+    }
+    Ok(affected)
+}
+
+async fn retag_todos(
+    conn: &mut SqliteConnection,
+    ids_json: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<u64, String> {
+    let todos: Vec<(String, String)> =
+        sqlx::query_as("SELECT id, tags FROM todos WHERE id IN (SELECT value FROM json_each(?))")
+            .bind(ids_json)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    let now = Utc::now().timestamp();
+    let mut affected = 0;
+    for (id, tags) in todos {
+        let retagged = retag(&tags, add, remove);
+        if retagged == tags {
+            continue;
+        }
+        sqlx::query("UPDATE todos SET tags = ?, updated_at = ? WHERE id = ?")
+            .bind(retagged)
+            .bind(now)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        affected += 1;
+    }
+    Ok(affected)
+}
+
+/// Delete the todos and their subtrees. Their attachments and time sessions stay on the page.
+async fn delete_todos(conn: &mut SqliteConnection, ids_json: &str) -> Result<u64, String> {
+    let subtree: Vec<(String,)> = sqlx::query_as(
+        "WITH RECURSIVE subtree(id) AS (
+             SELECT value FROM json_each(?)
+             UNION
+             SELECT t.id FROM subtree s JOIN todos t ON t.parent_id = s.id
+         )
+         SELECT id FROM subtree",
+    )
+    .bind(ids_json)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    let subtree_json =
+        serde_json::to_string(&subtree.into_iter().map(|(id,)| id).collect::<Vec<_>>()).map_err(|e| e.to_string())?;
+
+    for statement in [
+        "UPDATE attachments SET todo_id = NULL WHERE todo_id IN (SELECT value FROM json_each(?))",
+        "UPDATE time_sessions SET todo_id = NULL WHERE todo_id IN (SELECT value FROM json_each(?))",
+        "DELETE FROM todo_reminders WHERE todo_id IN (SELECT value FROM json_each(?))",
+    ] {
+        sqlx::query(statement)
+            .bind(&subtree_json)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    sqlx::query("DELETE FROM todos WHERE id IN (SELECT value FROM json_each(?))")
+        .bind(&subtree_json)
+        .execute(&mut *conn)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| e.to_string())
+}
+
+/// Apply the operation to every todo in one transaction, all or nothing
+async fn apply(conn: &mut SqliteConnection, operation: &BulkOperation, ids: &[String]) -> Result<u64, String> {
+    let ids_json = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let (found,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM todos WHERE id IN (SELECT value FROM json_each(?))")
+        .bind(&ids_json)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut unique = ids.to_vec();
+    unique.sort();
+    unique.dedup();
+    if found as usize != unique.len() {
+        return Err(format!("{} of the todos weren't found", unique.len() - found as usize));
+    }
+
+    let affected = match operation {
+        BulkOperation::Complete => set_status(&mut tx, &ids_json, "done").await?,
+        BulkOperation::Uncomplete => set_status(&mut tx, &ids_json, "todo").await?,
+        BulkOperation::Move { date } => move_todos(&mut tx, ids, &ids_json, date).await?,
+        BulkOperation::Retag { add, remove } => retag_todos(&mut tx, &ids_json, add, remove).await?,
+        BulkOperation::Delete => delete_todos(&mut tx, &ids_json).await?,
+    };
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(affected)
+}
+
+/// Complete, uncomplete, move, retag or delete many todos at once, with one change event.
+/// `patch` carries the operation's fields: `{date}` for "move", `{add, remove}` for "retag".
+#[crate::metrics::command]
+pub async fn bulk_update(
+    app: AppHandle,
+    kind: String,
+    ids: Vec<String>,
+    patch: Option<serde_json::Value>,
+) -> Result<BulkResult, String> {
+    let operation = BulkOperation::parse(&kind, patch)?;
+    if ids.is_empty() {
+        return Ok(BulkResult { affected: 0 });
+    }
+    let affected = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        apply(&mut conn, &operation, &ids).await?
+    };
+
+    let mut tables = vec!["todos".to_string()];
+    match operation {
+        BulkOperation::Move { .. } => tables.push("pages".to_string()),
+        BulkOperation::Delete => tables.extend(["todo_reminders", "attachments", "time_sessions"].map(String::from)),
+        _ => {}
+    }
+    if affected > 0 {
+        changes::notify(&app, tables);
+    }
+    Ok(BulkResult { affected })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_operation_and_retag() {
+        assert_eq!(BulkOperation::parse("complete", None).unwrap(), BulkOperation::Complete);
+        assert_eq!(
+            BulkOperation::parse("move", Some(serde_json::json!({ "date": "2024-03-04" }))).unwrap(),
+            BulkOperation::Move {
+                date: "2024-03-04".to_string()
+            }
+        );
+        assert!(BulkOperation::parse("move", None).is_err());
+        assert!(BulkOperation::parse("archive", None).is_err());
+
+        let tags = ["work".to_string()];
+        assert_eq!(retag(r#"["home","work"]"#, &["urgent".to_string()], &tags), r#"["home","urgent"]"#);
+        assert_eq!(retag(r#"["work"]"#, &tags, &[]), r#"["work"]"#);
+    }
+
+    #[tokio::test]
+    async fn test_delete_is_all_or_nothing() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for statement in [
+            "CREATE TABLE todos (id TEXT, workspace_id TEXT, page_date TEXT, status TEXT, level INTEGER,
+                parent_id TEXT)",
+            "CREATE TABLE attachments (todo_id TEXT)",
+            "CREATE TABLE time_sessions (todo_id TEXT)",
+            "CREATE TABLE todo_reminders (todo_id TEXT)",
+            "INSERT INTO todos VALUES ('a', 'w', 'd', 'todo', 0, NULL), ('a1', 'w', 'd', 'todo', 1, 'a'),
+                ('b', 'w', 'd', 'todo', 0, NULL)",
+            "INSERT INTO todo_reminders VALUES ('a1')",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+
+        let ids = vec!["a".to_string(), "missing".to_string()];
+        assert!(apply(&mut conn, &BulkOperation::Delete, &ids).await.is_err());
+        assert_eq!(apply(&mut conn, &BulkOperation::Delete, &ids[..1]).await.unwrap(), 2);
+
+        let left: Vec<(String,)> = sqlx::query_as("SELECT id FROM todos").fetch_all(&mut conn).await.unwrap();
+        assert_eq!(left, vec![("b".to_string(),)]);
+        let reminders: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM todo_reminders")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(reminders.0, 0);
+    }
+}
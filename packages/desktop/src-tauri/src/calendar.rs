@@ -0,0 +1,412 @@
+//! Events from the OS calendar, read-only: Calendar.app on macOS, the appointment store on
+//! Windows, and on every platform the .ics files of Evolution's local calendars (Linux) and any
+//! listed in the `calendar.ics_paths` setting.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::db::DatabaseState;
+use crate::filters::{date_range, DateSpec};
+use crate::{logger, settings};
+
+/// Extra .ics files to read, e.g. exported or synced calendars
+const ICS_PATHS_SETTING: &str = "calendar.ics_paths";
+/// Occurrences a recurring event may expand to, so a runaway rule can't stall a read
+const MAX_OCCURRENCES: usize = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarEvent {
+    /// Stable per occurrence of a recurring event
+    pub id: String,
+    pub title: String,
+    pub start: i64,
+    pub end: i64,
+    pub all_day: bool,
+    pub location: Option<String>,
+    pub calendar: Option<String>,
+}
+
+/// Undo iCalendar text escaping
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// A DATE or DATE-TIME value as local time, and whether it was a date
+fn parse_ics_time(value: &str) -> Option<(NaiveDateTime, bool)> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Some((date.and_hms_opt(0, 0, 0)?, true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&time).with_timezone(&Local).naive_local(), false));
+    }
+    // Floating times and those with a TZID are taken as local time
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok().map(|time| (time, false))
+}
+
+/// A DURATION value such as `PT1H30M` or `P1D`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let spec = value.strip_prefix('+').unwrap_or(value).strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in spec.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match unit {
+                    'W' => Duration::weeks(n),
+                    'D' => Duration::days(n),
+                    'H' => Duration::hours(n),
+                    'M' => Duration::minutes(n),
+                    'S' => Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total)
+}
+
+fn to_millis(time: NaiveDateTime) -> i64 {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .unwrap_or_else(|| Utc.from_utc_datetime(&time).with_timezone(&Local))
+        .timestamp_millis()
+}
+
+fn weekday(code: &str) -> Option<Weekday> {
+    // BYDAY entries may carry an ordinal, e.g. 1MO; only the day is used
+    let day = code.trim_start_matches(|c: char| c == '-' || c == '+' || c.is_ascii_digit());
+    match day {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn add_months(time: NaiveDateTime, months: u32) -> Option<NaiveDateTime> {
+    time.date()
+        .checked_add_months(chrono::Months::new(months))
+        .filter(|date| date.day() == time.day())
+        .map(|date| date.and_time(time.time()))
+}
+
+/// Start times of an event's occurrences up to `until`, following the common RRULE parts:
+/// FREQ, INTERVAL, COUNT, UNTIL and BYDAY for weekly rules
+fn occurrences(start: NaiveDateTime, rrule: Option<&str>, until: NaiveDateTime) -> Vec<NaiveDateTime> {
+    let Some(rrule) = rrule else {
+        return vec![start];
+    };
+    let parts: Vec<(&str, &str)> = rrule.split(';').filter_map(|part| part.split_once('=')).collect();
+    let part = |name: &str| parts.iter().find(|(key, _)| *key == name).map(|(_, value)| *value);
+    let interval: u32 = part("INTERVAL").and_then(|n| n.parse().ok()).filter(|n| *n > 0).unwrap_or(1);
+    let count: Option<usize> = part("COUNT").and_then(|n| n.parse().ok());
+    let rule_until = part("UNTIL").and_then(parse_ics_time).map(|(time, is_date)| {
+        if is_date { time + Duration::days(1) - Duration::seconds(1) } else { time }
+    });
+    let until = rule_until.map_or(until, |rule_until| rule_until.min(until));
+    let mut by_day: Vec<Weekday> =
+        part("BYDAY").map(|days| days.split(',').filter_map(weekday).collect()).unwrap_or_default();
+    by_day.sort_by_key(|day| day.num_days_from_monday());
+
+    let limit = count.unwrap_or(MAX_OCCURRENCES).min(MAX_OCCURRENCES);
+    let mut out = Vec::new();
+    for step in 0..MAX_OCCURRENCES as u32 {
+        let n = step * interval;
+        let candidates: Vec<NaiveDateTime> = match part("FREQ") {
+            Some("DAILY") => vec![start + Duration::days(n as i64)],
+            Some("WEEKLY") if !by_day.is_empty() => {
+                let week = start - Duration::days(start.weekday().num_days_from_monday() as i64)
+                    + Duration::weeks(n as i64);
+                by_day
+                    .iter()
+                    .map(|day| week + Duration::days(day.num_days_from_monday() as i64))
+                    .filter(|time| *time >= start)
+                    .collect()
+            }
+            Some("WEEKLY") => vec![start + Duration::weeks(n as i64)],
+            Some("MONTHLY") => add_months(start, n).into_iter().collect(),
+            Some("YEARLY") => add_months(start, n * 12).into_iter().collect(),
+            _ => return vec![start],
+        };
+        for time in candidates {
+            if time > until || out.len() >= limit {
+                return out;
+            }
+            out.push(time);
+        }
+    }
+    out
+}
+
+/// Events of an iCalendar file overlapping `from`..`to` (milliseconds)
+fn parse_ics(ics: &str, calendar: Option<&str>, from: i64, to: i64) -> Vec<CalendarEvent> {
+    // Lines starting with a space or tab continue the previous one
+    let unfolded = ics.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let until = DateTime::from_timestamp_millis(to)
+        .map_or(NaiveDateTime::MAX, |to| to.with_timezone(&Local).naive_local());
+    let mut events = Vec::new();
+    let mut props: Option<Vec<(String, String)>> = None;
+    for line in unfolded.lines() {
+        match line.trim_end() {
+            "BEGIN:VEVENT" => props = Some(Vec::new()),
+            "END:VEVENT" => {
+                let Some(props) = props.take() else { continue };
+                let get = |name: &str| props.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+                let Some((start, all_day)) = get("DTSTART").and_then(parse_ics_time) else { continue };
+                if get("STATUS") == Some("CANCELLED") {
+                    continue;
+                }
+                let length = match (get("DTEND").and_then(parse_ics_time), get("DURATION").and_then(parse_duration)) {
+                    (Some((end, _)), _) => end - start,
+                    (None, Some(duration)) => duration,
+                    (None, None) if all_day => Duration::days(1),
+                    (None, None) => Duration::zero(),
+                };
+                let excluded: Vec<NaiveDateTime> = props
+                    .iter()
+                    .filter(|(key, _)| key == "EXDATE")
+                    .flat_map(|(_, value)| value.split(','))
+                    .filter_map(|value| parse_ics_time(value).map(|(time, _)| time))
+                    .collect();
+                let uid = get("UID").unwrap_or_default();
+                let recurring = get("RRULE").is_some();
+                for occurrence in occurrences(start, get("RRULE"), until) {
+                    let (start_ms, end_ms) = (to_millis(occurrence), to_millis(occurrence + length));
+                    if excluded.contains(&occurrence) || end_ms <= from || start_ms >= to {
+                        continue;
+                    }
+                    events.push(CalendarEvent {
+                        id: if recurring { format!("{}@{}", uid, start_ms) } else { uid.to_string() },
+                        title: get("SUMMARY").map(unescape).unwrap_or_default(),
+                        start: start_ms,
+                        end: end_ms,
+                        all_day,
+                        location: get("LOCATION").map(unescape).filter(|location| !location.is_empty()),
+                        calendar: calendar.map(str::to_string),
+                    });
+                }
+            }
+            line => {
+                if let (Some(props), Some((name, value))) = (props.as_mut(), line.split_once(':')) {
+                    // Parameters such as TZID or VALUE=DATE come after the name
+                    let name = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+                    props.push((name, value.to_string()));
+                }
+            }
+        }
+    }
+    events
+}
+
+/// Evolution keeps each local calendar as `<uid>/calendar.ics`
+fn evolution_calendars() -> Vec<PathBuf> {
+    let Some(dir) = dirs::data_dir().map(|dir| dir.join("evolution").join("calendar")) else {
+        return Vec::new();
+    };
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path().join("calendar.ics"))
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+fn os_events(from: i64, to: i64) -> Result<Vec<CalendarEvent>, String> {
+    // Calendar.app scripting reads every account the user added, asking for access once
+    let script = format!(
+        "const from = new Date({from}), to = new Date({to});
+         const out = [];
+         for (const cal of Application('Calendar').calendars()) {{
+           const events = cal.events.whose({{_and: [
+             {{startDate: {{_lessThan: to}}}}, {{endDate: {{_greaterThan: from}}}}]}});
+           const ids = events.uid(), titles = events.summary(), starts = events.startDate(),
+             ends = events.endDate(), allDay = events.alldayEvent(), locations = events.location();
+           for (let i = 0; i < ids.length; i++) {{
+             out.push({{id: ids[i], title: titles[i] || '', start: starts[i].getTime(), end: ends[i].getTime(),
+               allDay: allDay[i], location: locations[i] || null, calendar: cal.name()}});
+           }}
+         }}
+         JSON.stringify(out);",
+        from = from,
+        to = to
+    );
+    let output = std::process::Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", &script])
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Calendar access failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())
+}
+
+#[cfg(windows)]
+fn os_events(from: i64, to: i64) -> Result<Vec<CalendarEvent>, String> {
+    use windows::ApplicationModel::Appointments::{AppointmentManager, AppointmentStoreAccessType};
+    use windows::Foundation::{DateTime as WinDateTime, TimeSpan};
+
+    /// 100ns ticks between 1601-01-01, where WinRT times start, and the Unix epoch
+    const UNIX_EPOCH_TICKS: i64 = 116_444_736_000_000_000;
+    let store = AppointmentManager::RequestStoreAsync(AppointmentStoreAccessType::AllCalendarsReadOnly)
+        .and_then(|request| request.get())
+        .map_err(|e| format!("Calendar access failed: {}", e))?;
+    let appointments = store
+        .FindAppointmentsAsync(
+            WinDateTime { UniversalTime: from * 10_000 + UNIX_EPOCH_TICKS },
+            TimeSpan { Duration: (to - from) * 10_000 },
+        )
+        .and_then(|find| find.get())
+        .map_err(|e| e.to_string())?;
+    let mut events = Vec::new();
+    for appointment in appointments {
+        let start = (appointment.StartTime().map_err(|e| e.to_string())?.UniversalTime - UNIX_EPOCH_TICKS) / 10_000;
+        let length = appointment.Duration().map_err(|e| e.to_string())?.Duration / 10_000;
+        let location = appointment.Location().map_err(|e| e.to_string())?.to_string();
+        events.push(CalendarEvent {
+            id: format!("{}@{}", appointment.LocalId().map_err(|e| e.to_string())?, start),
+            title: appointment.Subject().map_err(|e| e.to_string())?.to_string(),
+            start,
+            end: start + length,
+            all_day: appointment.AllDay().map_err(|e| e.to_string())?,
+            location: Some(location).filter(|location| !location.is_empty()),
+            calendar: None,
+        });
+    }
+    Ok(events)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn os_events(_from: i64, _to: i64) -> Result<Vec<CalendarEvent>, String> {
+    // Evolution's calendars are read from their .ics files
+    Ok(Vec::new())
+}
+
+/// Events overlapping `from`..`to` (milliseconds) from every calendar source, by start time.
+/// A source that fails is logged and skipped so the others still show.
+pub(crate) async fn events_between(app: &AppHandle, from: i64, to: i64) -> Result<Vec<CalendarEvent>, String> {
+    let mut paths: Vec<PathBuf> = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        settings::get_or::<Vec<String>>(&pool, ICS_PATHS_SETTING, Vec::new())
+            .await
+            .into_iter()
+            .map(PathBuf::from)
+            .collect()
+    };
+    if cfg!(target_os = "linux") {
+        paths.extend(evolution_calendars());
+    }
+
+    let read = move || {
+        let mut events = os_events(from, to).unwrap_or_else(|e| {
+            logger::error(&format!("Failed to read the OS calendar: {}", e));
+            Vec::new()
+        });
+        for path in paths {
+            match std::fs::read_to_string(&path) {
+                Ok(ics) => {
+                    let name = path
+                        .parent()
+                        .filter(|_| path.file_name().is_some_and(|name| name == "calendar.ics"))
+                        .unwrap_or(&path)
+                        .file_stem()
+                        .map(|name| name.to_string_lossy().into_owned());
+                    events.extend(parse_ics(&ics, name.as_deref(), from, to));
+                }
+                Err(e) => logger::error(&format!("Failed to read calendar {}: {}", path.display(), e)),
+            }
+        }
+        events.sort_by_key(|event| (event.start, event.end));
+        events
+    };
+    tauri::async_runtime::spawn_blocking(read).await.map_err(|e| e.to_string())
+}
+
+/// Calendar events on the days in `range`, for showing commitments next to todos
+#[crate::metrics::command]
+pub async fn get_calendar_events(app: AppHandle, range: DateSpec) -> Result<Vec<CalendarEvent>, String> {
+    let (from, to) = date_range(&range, Local::now().date_naive());
+    let day_start = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date: {}", date))
+            .and_then(|day| day.and_hms_opt(0, 0, 0).ok_or_else(|| format!("Invalid date: {}", date)))
+            .map(to_millis)
+    };
+    let from = day_start(&from)?;
+    let to = day_start(&to)? + Duration::days(1).num_milliseconds();
+    events_between(&app, from, to).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_ms(date: &str, time: &str) -> i64 {
+        to_millis(NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    #[test]
+    fn test_parse_ics_expands_recurring_events() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:standup\r\nSUMMARY:Stand\r\n up\r\n\
+                   DTSTART;TZID=Europe/Berlin:20240304T093000\r\nDURATION:PT15M\r\n\
+                   RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=4\r\nEXDATE:20240306T093000\r\nEND:VEVENT\r\n\
+                   BEGIN:VEVENT\r\nUID:trip\r\nSUMMARY:Trip\\, Berlin\r\nLOCATION:Berlin\r\n\
+                   DTSTART;VALUE=DATE:20240305\r\nEND:VEVENT\r\n\
+                   BEGIN:VEVENT\r\nUID:gone\r\nSTATUS:CANCELLED\r\nDTSTART:20240305T100000\r\nEND:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let events = parse_ics(ics, Some("work"), local_ms("2024-03-04", "00:00"), local_ms("2024-03-12", "00:00"));
+        let summary: Vec<(&str, i64)> = events.iter().map(|event| (event.title.as_str(), event.start)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("Standup", local_ms("2024-03-04", "09:30")),
+                ("Standup", local_ms("2024-03-11", "09:30")),
+                ("Trip, Berlin", local_ms("2024-03-05", "00:00")),
+            ]
+        );
+        assert_eq!(events[0].end - events[0].start, 15 * 60_000);
+        assert!(events[2].all_day && events[2].location.as_deref() == Some("Berlin"));
+        assert_ne!(events[0].id, events[1].id);
+    }
+
+    #[test]
+    fn test_occurrence_rules() {
+        let start = NaiveDateTime::parse_from_str("2024-01-31 10:00", "%Y-%m-%d %H:%M").unwrap();
+        let until = start + Duration::days(400);
+        let monthly = occurrences(start, Some("FREQ=MONTHLY;COUNT=3"), until);
+        assert_eq!(monthly.iter().map(|time| time.month()).collect::<Vec<_>>(), [1, 3, 5]);
+        let daily = occurrences(start, Some("FREQ=DAILY;INTERVAL=2;UNTIL=20240205"), until);
+        assert_eq!(daily.len(), 3);
+        assert_eq!(parse_duration("P1DT2H"), Some(Duration::hours(26)));
+    }
+}
@@ -0,0 +1,165 @@
+//! zstd compression of old journal notes. A compressed page has `notes` NULL, the compressed
+//! text in `notes_zstd` and `notes_compressed` set. Backend queries read notes as
+//! `COALESCE(notes, zstd_decompress(notes_zstd))`, and so does the frontend, through the
+//! `pageNotes` expression of the Drizzle schema. Writing `notes` drops the compressed copy
+//! (a trigger does it).
+
+use chrono::{Duration, Local};
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, settings};
+
+/// Setting: entries older than this many days get compressed
+const MIN_AGE_SETTING: &str = "compression.min_age_days";
+const DEFAULT_MIN_AGE_DAYS: i64 = 90;
+/// Shorter notes barely shrink, so they stay as they are
+const MIN_NOTES_BYTES: i64 = 1024;
+const LEVEL: i32 = 9;
+
+/// A page's notes, compressed or not; binds the workspace id and the date
+pub const PAGE_NOTES_SQL: &str =
+    "SELECT COALESCE(notes, zstd_decompress(notes_zstd)) FROM pages WHERE workspace_id = ? AND date = ?";
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionReport {
+    pub pages: usize,
+    pub original_bytes: usize,
+    pub compressed_bytes: usize,
+}
+
+pub fn compress(text: &str) -> Result<Vec<u8>, String> {
+    zstd::encode_all(text.as_bytes(), LEVEL).map_err(|e| format!("Failed to compress notes: {}", e))
+}
+
+pub fn decompress(bytes: &[u8]) -> Result<String, String> {
+    let data = zstd::decode_all(bytes).map_err(|e| format!("Failed to decompress notes: {}", e))?;
+    String::from_utf8(data).map_err(|e| format!("Decompressed notes aren't text: {}", e))
+}
+
+/// Compress the notes of public entries older than `before_date`, in one transaction
+async fn compress_before(conn: &mut SqliteConnection, before_date: &str) -> Result<CompressionReport, String> {
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let pages: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT p.workspace_id, p.date, p.notes FROM pages p
+         WHERE p.notes_compressed = 0 AND length(p.notes) >= ?1 AND p.date < ?2
+           AND NOT EXISTS (SELECT 1 FROM private_pages pp WHERE pp.workspace_id = p.workspace_id AND pp.page_date = p.date)
+           AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = p.workspace_id AND lp.page_date = p.date)",
+    )
+    .bind(MIN_NOTES_BYTES)
+    .bind(before_date)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut report = CompressionReport::default();
+    for (workspace_id, date, notes) in pages {
+        let compressed = compress(&notes)?;
+        if compressed.len() >= notes.len() {
+            continue;
+        }
+        // Content is unchanged, so `updated_at` stays as it was
+        sqlx::query(
+            "UPDATE pages SET notes = NULL, notes_zstd = ?, notes_compressed = 1 WHERE workspace_id = ? AND date = ?",
+        )
+        .bind(&compressed)
+        .bind(&workspace_id)
+        .bind(&date)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        report.pages += 1;
+        report.original_bytes += notes.len();
+        report.compressed_bytes += compressed.len();
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// Compress long entries older than `older_than_days` (the `compression.min_age_days` setting
+/// by default). Later edits to them are stored uncompressed until this runs again.
+#[crate::metrics::command]
+pub async fn compress_old_entries(app: AppHandle, older_than_days: Option<i64>) -> Result<CompressionReport, String> {
+    let report = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let days = match older_than_days {
+            Some(days) => days,
+            None => settings::get_or(&pool, MIN_AGE_SETTING, DEFAULT_MIN_AGE_DAYS).await,
+        };
+        if days < 1 {
+            return Err("Entries must be at least a day old to be compressed".to_string());
+        }
+        let before_date = (Local::now().date_naive() - Duration::days(days)).format("%Y-%m-%d").to_string();
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        compress_before(&mut conn, &before_date).await?
+    };
+    if report.pages > 0 {
+        logger::info(&format!(
+            "Compressed {} entries from {} to {} bytes",
+            report.pages, report.original_bytes, report.compressed_bytes
+        ));
+        changes::notify(&app, vec!["pages".to_string()]);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migration::apply_bundled;
+
+    #[tokio::test]
+    async fn test_compress_and_read_back() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        crate::db::functions::register(&mut conn).await.unwrap();
+        let long = "Dear diary, today was long. ".repeat(100);
+        for statement in [
+            "CREATE TABLE pages (workspace_id TEXT, date TEXT, notes TEXT, updated_at INTEGER)",
+            "CREATE TABLE private_pages (workspace_id TEXT, page_date TEXT)",
+            "CREATE TABLE locked_pages (workspace_id TEXT, page_date TEXT)",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+        apply_bundled(&mut conn, "0002_lively_nocturne.sql").await.unwrap();
+        sqlx::query(
+            "INSERT INTO pages (workspace_id, date, notes, updated_at)
+             VALUES ('w', '2020-01-01', ?1, 0), ('w', '2020-01-02', ?1, 0), ('w', '2030-01-01', ?1, 0)",
+        )
+        .bind(&long)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO private_pages VALUES ('w', '2020-01-02')")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let report = compress_before(&mut conn, "2025-01-01").await.unwrap();
+        assert_eq!(report.pages, 1);
+        assert!(report.compressed_bytes < report.original_bytes / 10);
+
+        let (notes, read): (Option<String>, String) = sqlx::query_as(
+            "SELECT notes, COALESCE(notes, zstd_decompress(notes_zstd)) FROM pages WHERE date = '2020-01-01'",
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!((notes, read), (None, long.clone()));
+
+        // Writing the notes replaces the compressed copy
+        sqlx::query("UPDATE pages SET notes = 'edited' WHERE date = '2020-01-01'")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let (compressed, zstd): (bool, Option<Vec<u8>>) =
+            sqlx::query_as("SELECT notes_compressed, notes_zstd FROM pages WHERE date = '2020-01-01'")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!((compressed, zstd), (false, None));
+    }
+}
@@ -0,0 +1,53 @@
+use std::process::Command;
+
+use crate::logger;
+
+/// Ask the user in a native dialog, outside the webview, whether to go ahead.
+/// Declining, closing the dialog or failing to show one all count as no.
+pub(crate) async fn ask(title: &str, message: &str) -> bool {
+    let (title, message) = (title.to_string(), message.to_string());
+    let asked = tauri::async_runtime::spawn_blocking(move || {
+        // The text goes through the environment so it is never parsed as script
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("osascript");
+            command.args([
+                "-e",
+                "display dialog (system attribute \"JOURNAL_TODO_MESSAGE\") with title \
+                 (system attribute \"JOURNAL_TODO_TITLE\") buttons {\"Cancel\", \"Allow\"} \
+                 default button \"Cancel\" cancel button \"Cancel\" with icon caution",
+            ]);
+            command
+        } else if cfg!(windows) {
+            let mut command = Command::new("powershell");
+            command.args([
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName PresentationFramework; \
+                 if ([System.Windows.MessageBox]::Show($env:JOURNAL_TODO_MESSAGE, $env:JOURNAL_TODO_TITLE, \
+                 'OKCancel', 'Warning', 'Cancel') -ne 'OK') { exit 1 }",
+            ]);
+            command
+        } else {
+            let mut command = Command::new("zenity");
+            command.args(["--question", "--no-markup", "--default-cancel", "--title", &title, "--text", &message]);
+            command
+        };
+        command.env("JOURNAL_TODO_TITLE", &title).env("JOURNAL_TODO_MESSAGE", &message);
+        match command.output() {
+            Ok(output) => output.status.success(),
+            Err(e) if cfg!(all(unix, not(target_os = "macos"))) => {
+                logger::error(&format!("Failed to ask through zenity, trying kdialog: {}", e));
+                Command::new("kdialog")
+                    .args(["--title", &title, "--warningcontinuecancel", &message])
+                    .output()
+                    .is_ok_and(|output| output.status.success())
+            }
+            Err(e) => {
+                logger::error(&format!("Failed to ask for confirmation: {}", e));
+                false
+            }
+        }
+    })
+    .await;
+    asked.unwrap_or(false)
+}
@@ -0,0 +1,310 @@
+//! The end-to-end key that encrypts change-log payloads before they leave the device. It lives
+//! in the OS keychain and is backed up as a 24-word recovery phrase; rotating it keeps the
+//! older keys around so payloads sealed with them still open.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bip39::Mnemonic;
+use chrono::Utc;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::{logger, secrets};
+
+/// Keychain entry holding the key ring as JSON
+const KEYRING_SECRET: &str = "sync.e2e_keys";
+/// Keys kept after rotation besides the current one
+const MAX_PREVIOUS_KEYS: usize = 5;
+const PAYLOAD_VERSION: u8 = 1;
+const KEY_ID_LEN: usize = 4;
+
+type Key = [u8; 32];
+
+/// The key ring as last read from or written to the keychain
+static KEYRING: Mutex<Option<KeyRing>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredKey {
+    id: String,
+    key: String,
+    created_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyRing {
+    /// Oldest first; the last one encrypts
+    keys: Vec<StoredKey>,
+    /// Id of the key whose recovery phrase the user has confirmed writing down
+    #[serde(default)]
+    backed_up: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct E2eKeyStatus {
+    pub has_key: bool,
+    pub key_id: Option<String>,
+    pub created_at: Option<i64>,
+    /// Whether the current key's recovery phrase has been verified
+    pub backed_up: bool,
+    /// Older keys kept to open payloads sealed before a rotation
+    pub previous_keys: usize,
+}
+
+/// Short id of a key, stored in every payload it seals; hex of its hash's first bytes
+fn key_id(key: &Key) -> String {
+    digest(&SHA256, key).as_ref()[..KEY_ID_LEN]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn new_key() -> Result<Key, String> {
+    let mut key = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| "No secure random source available".to_string())?;
+    Ok(key)
+}
+
+fn decode_key(stored: &StoredKey) -> Result<Key, String> {
+    STANDARD
+        .decode(&stored.key)
+        .ok()
+        .and_then(|bytes| Key::try_from(bytes).ok())
+        .ok_or_else(|| format!("Stored key {} is damaged", stored.id))
+}
+
+fn aead_key(key: &Key) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).expect("AES-256 keys are 32 bytes"))
+}
+
+fn phrase(key: &Key) -> Result<String, String> {
+    Mnemonic::from_entropy(key)
+        .map(|mnemonic| mnemonic.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// The key a recovery phrase stands for; case and spacing don't matter
+fn key_from_phrase(phrase: &str) -> Result<Key, String> {
+    let normalized = phrase.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let mnemonic = Mnemonic::parse(normalized).map_err(|e| format!("Invalid recovery phrase: {}", e))?;
+    Key::try_from(mnemonic.to_entropy()).map_err(|_| "Recovery phrase must be 24 words".to_string())
+}
+
+impl KeyRing {
+    fn current(&self) -> Option<&StoredKey> {
+        self.keys.last()
+    }
+
+    /// Make `key` the current key, keeping the previous ones for decryption
+    fn push(&mut self, key: &Key) {
+        let id = key_id(key);
+        self.keys.retain(|stored| stored.id != id);
+        self.keys.push(StoredKey {
+            id,
+            key: STANDARD.encode(key),
+            created_at: Utc::now().timestamp_millis(),
+        });
+        let excess = self.keys.len().saturating_sub(MAX_PREVIOUS_KEYS + 1);
+        self.keys.drain(..excess);
+    }
+
+    fn status(&self) -> E2eKeyStatus {
+        let current = self.current();
+        E2eKeyStatus {
+            has_key: current.is_some(),
+            key_id: current.map(|key| key.id.clone()),
+            created_at: current.map(|key| key.created_at),
+            backed_up: current.is_some_and(|key| self.backed_up.as_ref() == Some(&key.id)),
+            previous_keys: self.keys.len().saturating_sub(1),
+        }
+    }
+
+    /// `version | key id | nonce | ciphertext + tag`, with the header authenticated
+    fn seal(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        let stored = self.current().ok_or_else(|| "No end-to-end key has been set up".to_string())?;
+        let key = decode_key(stored)?;
+        let mut header = vec![PAYLOAD_VERSION];
+        header.extend(&digest(&SHA256, &key).as_ref()[..KEY_ID_LEN]);
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| "No secure random source available".to_string())?;
+        let mut data = payload.to_vec();
+        aead_key(&key)
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(&header), &mut data)
+            .map_err(|_| "Encryption failed".to_string())?;
+        let mut sealed = header;
+        sealed.extend(nonce);
+        sealed.extend(data);
+        Ok(sealed)
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        let header_len = 1 + KEY_ID_LEN;
+        if sealed.len() < header_len + NONCE_LEN || sealed[0] != PAYLOAD_VERSION {
+            return Err("Not an encrypted payload".to_string());
+        }
+        let (header, rest) = sealed.split_at(header_len);
+        let id: String = header[1..].iter().map(|b| format!("{:02x}", b)).collect();
+        let stored = self
+            .keys
+            .iter()
+            .find(|stored| stored.id == id)
+            .ok_or_else(|| format!("Payload is sealed with unknown key {}", id))?;
+        let (nonce, data) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce".to_string())?;
+        let mut data = data.to_vec();
+        let plaintext = aead_key(&decode_key(stored)?)
+            .open_in_place(nonce, Aad::from(header), &mut data)
+            .map_err(|_| "Wrong key or corrupted payload".to_string())?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+fn load() -> Result<KeyRing, String> {
+    let mut cached = KEYRING.lock().unwrap();
+    if let Some(ring) = cached.as_ref() {
+        return Ok(ring.clone());
+    }
+    let ring: KeyRing = match secrets::get(KEYRING_SECRET)? {
+        Some(json) => serde_json::from_str(&json).map_err(|e| format!("End-to-end key ring is damaged: {}", e))?,
+        None => KeyRing::default(),
+    };
+    *cached = Some(ring.clone());
+    Ok(ring)
+}
+
+fn save(ring: KeyRing) -> Result<E2eKeyStatus, String> {
+    let json = serde_json::to_string(&ring).map_err(|e| e.to_string())?;
+    secrets::set(KEYRING_SECRET, &json)?;
+    let status = ring.status();
+    *KEYRING.lock().unwrap() = Some(ring);
+    Ok(status)
+}
+
+/// Encrypt a change-log payload with the current key
+#[allow(dead_code)] // no sync transport sends payloads yet
+pub(crate) fn encrypt_payload(payload: &[u8]) -> Result<Vec<u8>, String> {
+    load()?.seal(payload)
+}
+
+/// Decrypt a change-log payload sealed with the current key or one rotated out
+#[allow(dead_code)] // no sync transport receives payloads yet
+pub(crate) fn decrypt_payload(sealed: &[u8]) -> Result<Vec<u8>, String> {
+    load()?.open(sealed)
+}
+
+#[crate::metrics::command]
+pub async fn get_e2e_key_status() -> Result<E2eKeyStatus, String> {
+    Ok(load()?.status())
+}
+
+/// Generate the end-to-end key; its recovery phrase should be exported and verified next
+#[crate::metrics::command]
+pub async fn create_e2e_key() -> Result<E2eKeyStatus, String> {
+    let mut ring = load()?;
+    if ring.current().is_some() {
+        return Err("An end-to-end key already exists; rotate it instead".to_string());
+    }
+    ring.push(&new_key()?);
+    let status = save(ring)?;
+    logger::info(&format!("Created end-to-end key {}", status.key_id.as_deref().unwrap_or_default()));
+    Ok(status)
+}
+
+/// The current key as a 24-word recovery phrase, to write down
+#[crate::metrics::command]
+pub async fn export_recovery_phrase() -> Result<String, String> {
+    let ring = load()?;
+    let stored = ring.current().ok_or_else(|| "No end-to-end key has been set up".to_string())?;
+    logger::info(&format!("Exported recovery phrase of key {}", stored.id));
+    phrase(&decode_key(stored)?)
+}
+
+/// Check a phrase typed back against the current key, marking the key backed up if it matches
+#[crate::metrics::command]
+pub async fn verify_recovery_phrase(phrase: String) -> Result<bool, String> {
+    let mut ring = load()?;
+    let Some(current) = ring.current().map(|key| key.id.clone()) else {
+        return Err("No end-to-end key has been set up".to_string());
+    };
+    let matches = key_from_phrase(&phrase).is_ok_and(|key| key_id(&key) == current);
+    if matches && ring.backed_up.as_ref() != Some(&current) {
+        ring.backed_up = Some(current);
+        save(ring)?;
+    }
+    Ok(matches)
+}
+
+/// Make the key behind a recovery phrase current, e.g. on a new device or after a reinstall
+#[crate::metrics::command]
+pub async fn restore_e2e_key(phrase: String) -> Result<E2eKeyStatus, String> {
+    let key = key_from_phrase(&phrase)?;
+    let mut ring = load()?;
+    ring.push(&key);
+    ring.backed_up = Some(key_id(&key));
+    let status = save(ring)?;
+    logger::info(&format!("Restored end-to-end key {}", key_id(&key)));
+    Ok(status)
+}
+
+/// Replace the current key with a new one. Older keys still open what they sealed; the new
+/// key's recovery phrase needs backing up again.
+#[crate::metrics::command]
+pub async fn rotate_e2e_key() -> Result<E2eKeyStatus, String> {
+    let mut ring = load()?;
+    let previous = ring.current().map(|key| key.id.clone());
+    if previous.is_none() {
+        return Err("No end-to-end key has been set up".to_string());
+    }
+    ring.push(&new_key()?);
+    let status = save(ring)?;
+    logger::info(&format!(
+        "Rotated end-to-end key {} to {}",
+        previous.unwrap_or_default(),
+        status.key_id.as_deref().unwrap_or_default()
+    ));
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_phrase_round_trips() {
+        let key = new_key().unwrap();
+        let words = phrase(&key).unwrap();
+        assert_eq!(words.split(' ').count(), 24);
+        let retyped = format!("  {}\n", words.to_uppercase().replace(' ', "   "));
+        assert_eq!(key_from_phrase(&retyped).unwrap(), key);
+        assert!(key_from_phrase("abandon abandon abandon").is_err());
+    }
+
+    #[test]
+    fn test_payloads_open_after_rotation() {
+        let mut ring = KeyRing::default();
+        assert!(ring.seal(b"change").is_err());
+        ring.push(&new_key().unwrap());
+        let old = ring.seal(b"before rotation").unwrap();
+        ring.push(&new_key().unwrap());
+        let new = ring.seal(b"after rotation").unwrap();
+        assert_eq!(ring.open(&old).unwrap(), b"before rotation");
+        assert_eq!(ring.open(&new).unwrap(), b"after rotation");
+        assert_eq!(ring.status().previous_keys, 1);
+
+        let mut tampered = new.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(ring.open(&tampered).is_err());
+        for _ in 0..MAX_PREVIOUS_KEYS {
+            ring.push(&new_key().unwrap());
+        }
+        assert!(ring.open(&old).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::hooks::{self, HookEvent};
+use crate::{dispatch, idle, logger, settings};
+
+/// "HH:MM" local time to create today's entry, or "unlock" for the first activity of the day; unset disables
+const AUTO_CREATE_SETTING: &str = "journal.auto_create_at";
+/// `{ "notes": "...", "todos": ["..."] }`; `{{date}}` and `{{weekday}}` are filled in
+const TEMPLATE_SETTING: &str = "journal.default_template";
+/// Last day an entry was auto-created, so a deleted entry isn't brought back
+const LAST_CREATED_SETTING: &str = "journal.auto_created_date";
+
+const ON_UNLOCK: &str = "unlock";
+/// Input within this long counts as the user being at an unlocked machine
+const ACTIVE_WITHIN: Duration = Duration::from_secs(60);
+
+/// Emitted after today's entry was created, so an open window can navigate to it
+pub const ENTRY_CREATED_EVENT: &str = "journal://entry-created";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct EntryTemplate {
+    notes: Option<String>,
+    todos: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EntryCreated {
+    workspace_id: String,
+    date: String,
+}
+
+fn fill(text: &str, now: &DateTime<Local>) -> String {
+    text.replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{weekday}}", &now.format("%A").to_string())
+}
+
+/// Whether the configured trigger has been reached at `now`
+fn is_due(trigger: &str, now: &DateTime<Local>, idle: Option<Duration>) -> Result<bool, String> {
+    if trigger == ON_UNLOCK {
+        return Ok(idle.is_some_and(|idle| idle < ACTIVE_WITHIN));
+    }
+    let at = NaiveTime::parse_from_str(trigger, "%H:%M")
+        .map_err(|_| format!("Invalid {}: {}", AUTO_CREATE_SETTING, trigger))?;
+    Ok(now.time() >= at)
+}
+
+/// Scheduled check that creates today's entry from the default template once the trigger is reached
+pub async fn create_job(app: AppHandle) -> Result<(), String> {
+    let now = Local::now();
+    let today = now.format("%Y-%m-%d").to_string();
+    let (trigger, last_created, template) = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        (
+            settings::get::<String>(&pool, AUTO_CREATE_SETTING).await?,
+            settings::get::<String>(&pool, LAST_CREATED_SETTING).await?,
+            settings::get_or::<EntryTemplate>(&pool, TEMPLATE_SETTING, EntryTemplate::default()).await,
+        )
+    };
+    let Some(trigger) = trigger else {
+        return Ok(());
+    };
+    if last_created.as_deref() == Some(today.as_str()) {
+        return Ok(());
+    }
+    let idle = if trigger == ON_UNLOCK {
+        match tauri::async_runtime::spawn_blocking(idle::idle_time).await {
+            Ok(idle) => idle.ok(),
+            Err(e) => return Err(e.to_string()),
+        }
+    } else {
+        None
+    };
+    if !is_due(&trigger, &now, idle)? {
+        return Ok(());
+    }
+
+    let created = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let workspace_id = dispatch::current_workspace_id(&mut tx).await?;
+        let exists: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM pages WHERE workspace_id = ? AND date = ?")
+            .bind(&workspace_id)
+            .bind(&today)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        // An entry the user already started is left as it is
+        if exists.is_none() {
+            dispatch::ensure_page(&mut tx, &workspace_id, &today).await?;
+            if let Some(notes) = template.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+                dispatch::append_note(&mut tx, &workspace_id, &today, &fill(notes, &now)).await?;
+            }
+            for todo in template.todos.iter().filter(|t| !t.trim().is_empty()) {
+                dispatch::insert_todo(&mut tx, &workspace_id, &today, fill(todo, &now).trim()).await?;
+            }
+        }
+        settings::write_value(&mut *tx, LAST_CREATED_SETTING, &serde_json::json!(today)).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        exists.is_none().then_some(workspace_id)
+    };
+
+    if let Some(workspace_id) = created {
+        logger::info(&format!("Created journal entry for {}", today));
+        changes::notify(&app, vec!["pages".to_string(), "todos".to_string()]);
+        let created = EntryCreated { workspace_id, date: today };
+        hooks::fire(&app, HookEvent::EntryCreated, &created);
+        let _ = app.emit(ENTRY_CREATED_EVENT, created);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_trigger_and_placeholders() {
+        let morning = Local.with_ymd_and_hms(2024, 3, 4, 8, 30, 0).unwrap();
+        assert!(is_due("08:00", &morning, None).unwrap());
+        assert!(!is_due("09:15", &morning, None).unwrap());
+        assert!(is_due("08:30", &morning, None).unwrap());
+        assert!(is_due("9am", &morning, None).is_err());
+        assert!(is_due(ON_UNLOCK, &morning, Some(Duration::from_secs(5))).unwrap());
+        assert!(!is_due(ON_UNLOCK, &morning, Some(Duration::from_secs(600))).unwrap());
+        assert!(!is_due(ON_UNLOCK, &morning, None).unwrap());
+
+        assert_eq!(fill("# {{weekday}} {{date}}", &morning), "# Monday 2024-03-04");
+    }
+}
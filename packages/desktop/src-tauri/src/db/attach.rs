@@ -0,0 +1,224 @@
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tauri::State;
+
+use super::DatabaseState;
+use crate::settings;
+
+/// Directories (besides the main database's own) files may be attached from
+const ALLOWLIST_SETTING: &str = "db.attach_allowlist";
+/// Schema names SQLite reserves
+const RESERVED_ALIASES: &[&str] = &["main", "temp"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachedDatabase {
+    pub alias: String,
+    pub path: String,
+}
+
+struct Registry {
+    attached: Vec<AttachedDatabase>,
+    /// Aliases detached since, which connections still holding them drop
+    retired: Vec<String>,
+}
+
+static REGISTRY: RwLock<Registry> = RwLock::new(Registry {
+    attached: Vec::new(),
+    retired: Vec::new(),
+});
+/// Bumped on every change, so connections skip syncing until the first attach
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn is_valid_alias(alias: &str) -> bool {
+    let mut chars = alias.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED_ALIASES.iter().any(|reserved| reserved.eq_ignore_ascii_case(alias))
+}
+
+/// Whether `path` sits inside one of `allowed` directories, after resolving symlinks and `..`
+fn is_allowed(path: &Path, allowed: &[PathBuf]) -> bool {
+    allowed
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| path.starts_with(dir))
+}
+
+/// Whether a file SQLite reports for a schema is `path`
+fn same_file(file: &str, path: &Path) -> bool {
+    Path::new(file).canonicalize().ok().as_deref() == path.canonicalize().ok().as_deref()
+}
+
+/// Attach `path` as `alias` on one connection; false if it is attached there already
+pub async fn attach_to(conn: &mut SqliteConnection, alias: &str, path: &Path) -> Result<bool, String> {
+    let schemas: Vec<(i64, String, String)> = sqlx::query_as("PRAGMA database_list")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some((_, _, file)) = schemas.iter().find(|(_, name, _)| name.eq_ignore_ascii_case(alias)) {
+        return if same_file(file, path) {
+            Ok(false)
+        } else {
+            Err(format!("{} is already attached to {}", alias, file))
+        };
+    }
+    // The alias is validated, the path is bound
+    sqlx::query(&format!("ATTACH DATABASE ? AS \"{}\"", alias))
+        .bind(path.to_string_lossy().into_owned())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to attach {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+pub async fn detach_from(conn: &mut SqliteConnection, alias: &str) -> Result<(), String> {
+    sqlx::query(&format!("DETACH DATABASE \"{}\"", alias))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to detach {}: {}", alias, e))?;
+    Ok(())
+}
+
+/// Bring a pooled connection's attached databases in line with the registry.
+/// Runs whenever a connection is opened or handed out; failures are logged.
+pub async fn sync(conn: &mut SqliteConnection) {
+    if GENERATION.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let (attached, retired) = match REGISTRY.read() {
+        Ok(registry) => (registry.attached.clone(), registry.retired.clone()),
+        Err(_) => return,
+    };
+    let schemas: Vec<(i64, String, String)> = match sqlx::query_as("PRAGMA database_list").fetch_all(&mut *conn).await {
+        Ok(schemas) => schemas,
+        Err(e) => {
+            crate::logger::error(&format!("Failed to list attached databases: {}", e));
+            return;
+        }
+    };
+
+    for (_, name, file) in &schemas {
+        let current = attached.iter().find(|db| db.alias.eq_ignore_ascii_case(name));
+        let stale = match current {
+            Some(db) => !same_file(file, Path::new(&db.path)),
+            None => retired.iter().any(|alias| alias.eq_ignore_ascii_case(name)),
+        };
+        if stale {
+            if let Err(e) = detach_from(conn, name).await {
+                crate::logger::error(&e);
+            }
+        }
+    }
+    for db in &attached {
+        if let Err(e) = attach_to(conn, &db.alias, Path::new(&db.path)).await {
+            crate::logger::error(&e);
+        }
+    }
+}
+
+/// Attach a SQLite file under `alias` on every connection, so proxy queries can
+/// use `alias.table`. The file must exist and sit next to the main database or
+/// in a directory listed in `db.attach_allowlist`.
+#[crate::metrics::command]
+pub async fn attach_database(
+    state: State<'_, DatabaseState>,
+    path: String,
+    alias: String,
+) -> Result<AttachedDatabase, String> {
+    if !is_valid_alias(&alias) {
+        return Err(format!("Invalid database alias: {}", alias));
+    }
+    let path = PathBuf::from(&path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot attach {}: {}", path, e))?;
+
+    let pool = state.pool.lock().await;
+    let mut allowed: Vec<PathBuf> = settings::get_or::<Vec<String>>(&pool, ALLOWLIST_SETTING, Vec::new())
+        .await
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    if let Some(dir) = state.db_path.lock().await.parent() {
+        allowed.push(dir.to_path_buf());
+    }
+    if !is_allowed(&path, &allowed) {
+        return Err(format!("{} is not in an allowed directory ({})", path.display(), ALLOWLIST_SETTING));
+    }
+
+    // Fail here on files that aren't databases, rather than on every connection
+    let options = SqliteConnectOptions::new().filename(&path).read_only(true);
+    let mut check = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+        .execute(&mut check)
+        .await
+        .map_err(|e| format!("{} is not a SQLite database: {}", path.display(), e))?;
+    check.close().await.ok();
+
+    let db = AttachedDatabase {
+        alias,
+        path: path.to_string_lossy().into_owned(),
+    };
+    {
+        let mut registry = REGISTRY.write().map_err(|e| e.to_string())?;
+        if registry.attached.iter().any(|a| a.alias.eq_ignore_ascii_case(&db.alias)) {
+            return Err(format!("{} is already attached", db.alias));
+        }
+        registry.retired.retain(|a| !a.eq_ignore_ascii_case(&db.alias));
+        registry.attached.push(db.clone());
+    }
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    state.query_cache.clear();
+    crate::logger::info(&format!("Attached {} as {}", db.path, db.alias));
+    Ok(db)
+}
+
+#[crate::metrics::command]
+pub async fn detach_database(state: State<'_, DatabaseState>, alias: String) -> Result<(), String> {
+    {
+        let mut registry = REGISTRY.write().map_err(|e| e.to_string())?;
+        let before = registry.attached.len();
+        registry.attached.retain(|a| !a.alias.eq_ignore_ascii_case(&alias));
+        if registry.attached.len() == before {
+            return Err(format!("{} is not attached", alias));
+        }
+        registry.retired.push(alias.clone());
+    }
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    state.query_cache.clear();
+    crate::logger::info(&format!("Detached {}", alias));
+    Ok(())
+}
+
+#[crate::metrics::command]
+pub fn list_attached_databases() -> Vec<AttachedDatabase> {
+    REGISTRY
+        .read()
+        .map(|registry| registry.attached.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_and_path_checks() {
+        assert!(is_valid_alias("archive"));
+        assert!(is_valid_alias("_import2"));
+        assert!(!is_valid_alias("main"));
+        assert!(!is_valid_alias("2024"));
+        assert!(!is_valid_alias("a\"; DROP"));
+
+        let allowed = [std::env::temp_dir()];
+        let inside = allowed[0].canonicalize().unwrap().join("other.db");
+        assert!(is_allowed(&inside, &allowed));
+        assert!(!is_allowed(Path::new("/etc/passwd"), &allowed));
+    }
+}
@@ -0,0 +1,269 @@
+use futures_util::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, Executor, SqlitePool};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::time::Instant;
+use tauri::State;
+
+use super::commands::{execute_sql_internal, SqlRequest};
+use super::pragmas::{configured, elapsed_ms};
+use super::{database, DatabaseState};
+
+/// Tasks reading at once, so the pool has to hand out several connections
+const CONCURRENT_READERS: u64 = 4;
+/// Writes per transaction
+const WRITE_BATCH: u64 = 100;
+const PAGE_SQL: &str = "SELECT id, page_date, text, status, updated_at FROM bench WHERE page_date = ?";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BenchmarkProfile {
+    Quick,
+    Standard,
+    Thorough,
+}
+
+impl BenchmarkProfile {
+    /// Rows inserted, and the number of selects and updates run over them
+    fn size(self) -> (u64, u64) {
+        match self {
+            BenchmarkProfile::Quick => (2_000, 500),
+            BenchmarkProfile::Standard => (20_000, 5_000),
+            BenchmarkProfile::Thorough => (100_000, 20_000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Throughput {
+    pub operations: u64,
+    pub ms: f64,
+    pub per_second: f64,
+}
+
+impl Throughput {
+    fn since(start: Instant, operations: u64) -> Self {
+        let ms = elapsed_ms(start);
+        Throughput {
+            operations,
+            ms,
+            per_second: if ms > 0.0 { operations as f64 * 1000.0 / ms } else { 0.0 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbBenchmarkReport {
+    pub profile: BenchmarkProfile,
+    /// The PRAGMA settings the scratch database ran with, from the current database
+    pub pragmas: HashMap<String, serde_json::Value>,
+    pub inserts: Throughput,
+    /// A page's todos at a time, read straight through sqlx
+    pub selects: Throughput,
+    pub updates: Throughput,
+    /// The same selects through the SQL proxy, converted to JSON and serialized as for IPC
+    pub proxy_selects: Throughput,
+    /// Time the proxy adds to each select
+    pub proxy_overhead_ms: f64,
+}
+
+fn page_date(i: u64) -> String {
+    format!("2024-{:02}-{:02}", i % 12 + 1, i % 28 + 1)
+}
+
+/// Create the scratch database with the PRAGMA settings stored like the real ones, so the
+/// pool's `after_connect` applies them
+async fn prepare(path: &Path, pragmas: &HashMap<String, serde_json::Value>) -> Result<(), String> {
+    let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+         CREATE TABLE bench (id INTEGER PRIMARY KEY, page_date TEXT NOT NULL, text TEXT NOT NULL,
+             status TEXT NOT NULL, updated_at INTEGER NOT NULL);
+         CREATE INDEX bench_date_idx ON bench (page_date);",
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    for (key, value) in pragmas {
+        sqlx::query("INSERT INTO settings (key, value) VALUES (?, ?)")
+            .bind(key)
+            .bind(value.to_string())
+            .execute(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    conn.close().await.map_err(|e| e.to_string())
+}
+
+/// Run `queries` reads spread over `CONCURRENT_READERS` tasks
+async fn read_concurrently<F, Fut>(queries: u64, read: F) -> Result<(), String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    try_join_all((0..CONCURRENT_READERS).map(|reader| {
+        let read = &read;
+        async move {
+            for i in (reader..queries).step_by(CONCURRENT_READERS as usize) {
+                read(page_date(i)).await?;
+            }
+            Ok::<_, String>(())
+        }
+    }))
+    .await?;
+    Ok(())
+}
+
+async fn run_workload(
+    pool: &SqlitePool,
+    profile: BenchmarkProfile,
+    pragmas: HashMap<String, serde_json::Value>,
+) -> Result<DbBenchmarkReport, String> {
+    let (rows, queries) = profile.size();
+
+    let start = Instant::now();
+    for batch in 0..rows / WRITE_BATCH {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        for i in 0..WRITE_BATCH {
+            let id = batch * WRITE_BATCH + i;
+            sqlx::query("INSERT INTO bench (id, page_date, text, status, updated_at) VALUES (?, ?, ?, 'todo', ?)")
+                .bind(id as i64)
+                .bind(page_date(id))
+                .bind(format!("Sample todo number {} with some journal text #bench", id))
+                .bind(id as i64)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    let inserts = Throughput::since(start, rows);
+
+    let start = Instant::now();
+    read_concurrently(queries, |date| async move {
+        sqlx::query(PAGE_SQL)
+            .bind(date)
+            .fetch_all(pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+    let selects = Throughput::since(start, queries);
+
+    let start = Instant::now();
+    read_concurrently(queries, |date| async move {
+        let request = SqlRequest {
+            sql: PAGE_SQL.to_string(),
+            params: vec![serde_json::Value::String(date)],
+            method: "all".to_string(),
+        };
+        let response = execute_sql_internal(pool, request).await?;
+        serde_json::to_vec(&response).map(|_| ()).map_err(|e| e.to_string())
+    })
+    .await?;
+    let proxy_selects = Throughput::since(start, queries);
+
+    let start = Instant::now();
+    for batch in 0..queries.div_ceil(WRITE_BATCH) {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        for i in batch * WRITE_BATCH..((batch + 1) * WRITE_BATCH).min(queries) {
+            sqlx::query("UPDATE bench SET status = 'done', updated_at = ? WHERE id = ?")
+                .bind(i as i64)
+                .bind((i * 7919 % rows) as i64)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    let updates = Throughput::since(start, queries);
+
+    Ok(DbBenchmarkReport {
+        profile,
+        pragmas,
+        proxy_overhead_ms: ((proxy_selects.ms - selects.ms) / queries as f64).max(0.0),
+        inserts,
+        selects,
+        updates,
+        proxy_selects,
+    })
+}
+
+/// Set up a scratch database like the current one and run the workload against it
+async fn run(
+    path: &Path,
+    profile: BenchmarkProfile,
+    pragmas: HashMap<String, serde_json::Value>,
+) -> Result<DbBenchmarkReport, String> {
+    prepare(path, &pragmas).await?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| "Failed to convert database path to string".to_string())?;
+    let pool = database::connect(path_str, None).await.map_err(|e| e.to_string())?;
+    let report = run_workload(&pool, profile, pragmas).await;
+    pool.close().await;
+    report
+}
+
+/// Measure insert, select and update throughput and the SQL proxy's serialization overhead
+/// on this machine. Runs on a scratch database with the same pool setup and PRAGMA settings
+/// as the current one, which is left alone.
+#[crate::metrics::command]
+pub async fn run_db_benchmark(
+    state: State<'_, DatabaseState>,
+    profile: BenchmarkProfile,
+) -> Result<DbBenchmarkReport, String> {
+    let pragmas = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        configured(&mut conn).await
+    };
+
+    let path = std::env::temp_dir().join(format!("journal-todo-db-bench-{}.db", uuid::Uuid::new_v4()));
+    let report = run(&path, profile, pragmas).await;
+    if let Err(e) = database::remove_database_files(&path) {
+        crate::logger::error(&format!("Failed to remove benchmark database: {}", e));
+    }
+    let report = report?;
+    crate::logger::info(&format!(
+        "DB benchmark ({:?}): {:.0} inserts/s, {:.0} selects/s, {:.0} updates/s, {:.3}ms proxy overhead per select",
+        profile,
+        report.inserts.per_second,
+        report.selects.per_second,
+        report.updates.per_second,
+        report.proxy_overhead_ms
+    ));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_benchmark_runs_with_current_pragmas() {
+        let path = std::env::temp_dir().join(format!("journal-todo-db-bench-test-{}.db", uuid::Uuid::new_v4()));
+        let pragmas = HashMap::from([("db.cache_size".to_string(), json!(-4000))]);
+        prepare(&path, &pragmas).await.unwrap();
+        let pool = database::connect(path.to_str().unwrap(), None).await.unwrap();
+        let (cache_size,): (i64,) = sqlx::query_as("PRAGMA cache_size").fetch_one(&pool).await.unwrap();
+        assert_eq!(cache_size, -4000);
+
+        let report = run_workload(&pool, BenchmarkProfile::Quick, pragmas).await.unwrap();
+        pool.close().await;
+        database::remove_database_files(&path).unwrap();
+        assert_eq!(report.inserts.operations, 2_000);
+        assert_eq!((report.selects.operations, report.proxy_selects.operations), (500, 500));
+        assert_eq!(report.updates.operations, 500);
+        assert!(report.proxy_overhead_ms >= 0.0);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use sqlparser::dialect::SQLiteDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use tauri::{AppHandle, Emitter, Listener, Manager};
+
+use super::DatabaseState;
+
+/// Emitted (to the frontend and Rust listeners) after statements modify tables
+pub const DB_CHANGED_EVENT: &str = "db://changed";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbChange {
+    pub tables: Vec<String>,
+}
+
+/// Tables written by INSERT / REPLACE / UPDATE / DELETE statements in `sql`
+pub fn written_tables(sql: &str) -> Vec<String> {
+    let dialect = SQLiteDialect {};
+    let tokens = match Tokenizer::new(&dialect, sql).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return Vec::new(),
+    };
+    let tokens: Vec<Token> = tokens
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+
+    let keyword_at = |i: usize| match tokens.get(i) {
+        Some(Token::Word(w)) => w.keyword,
+        _ => Keyword::NoKeyword,
+    };
+
+    let mut tables = Vec::new();
+    for i in 0..tokens.len() {
+        let name_index = match keyword_at(i) {
+            Keyword::INTO => Some(i + 1),
+            Keyword::DELETE if keyword_at(i + 1) == Keyword::FROM => Some(i + 2),
+            // UPDATE OR REPLACE t / skip the ON CONFLICT DO UPDATE SET upsert clause
+            Keyword::UPDATE if keyword_at(i + 1) == Keyword::OR => Some(i + 3),
+            Keyword::UPDATE if keyword_at(i + 1) != Keyword::SET => Some(i + 1),
+            _ => None,
+        };
+
+        if let Some(name) = name_index.and_then(|index| table_name_at(&tokens, index)) {
+            if !tables.contains(&name) {
+                tables.push(name);
+            }
+        }
+    }
+    tables
+}
+
+/// Tables a SELECT reads from (FROM / JOIN targets, including comma joins)
+pub fn read_tables(sql: &str) -> Vec<String> {
+    let dialect = SQLiteDialect {};
+    let tokens = match Tokenizer::new(&dialect, sql).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return Vec::new(),
+    };
+    let tokens: Vec<Token> = tokens
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+
+    let mut tables = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_source = matches!(
+            &tokens[i],
+            Token::Word(w) if w.keyword == Keyword::FROM || w.keyword == Keyword::JOIN
+        );
+        i += 1;
+        if !is_source {
+            continue;
+        }
+
+        // FROM a [AS] x, b [AS] y ...
+        while let Some(name) = table_name_at(&tokens, i) {
+            if !tables.contains(&name) {
+                tables.push(name);
+            }
+            i += if matches!(tokens.get(i + 1), Some(Token::Period)) { 3 } else { 1 };
+            if matches!(&tokens.get(i), Some(Token::Word(w)) if w.keyword == Keyword::AS) {
+                i += 1;
+            }
+            if matches!(&tokens.get(i), Some(Token::Word(w)) if w.keyword == Keyword::NoKeyword) {
+                i += 1;
+            }
+            if !matches!(tokens.get(i), Some(Token::Comma)) {
+                break;
+            }
+            i += 1;
+        }
+    }
+    tables
+}
+
+/// Read a possibly schema-qualified table name starting at `index`
+fn table_name_at(tokens: &[Token], index: usize) -> Option<String> {
+    let Some(Token::Word(first)) = tokens.get(index) else {
+        return None;
+    };
+    match (tokens.get(index + 1), tokens.get(index + 2)) {
+        (Some(Token::Period), Some(Token::Word(second))) => Some(second.value.clone()),
+        _ => Some(first.value.clone()),
+    }
+}
+
+/// Tell every window and backend listeners that tables changed
+pub fn notify(app: &AppHandle, tables: Vec<String>) {
+    if tables.is_empty() {
+        return;
+    }
+    if let Some(state) = app.try_state::<DatabaseState>() {
+        state.query_cache.invalidate(&tables);
+    }
+    if let Err(e) = app.emit(DB_CHANGED_EVENT, DbChange { tables }) {
+        crate::logger::error(&format!("Failed to emit {}: {}", DB_CHANGED_EVENT, e));
+    }
+}
+
+/// Run `handler` whenever any of `tables` changes
+pub fn listen<F>(app: &AppHandle, tables: &'static [&'static str], handler: F)
+where
+    F: Fn(DbChange) + Send + 'static,
+{
+    app.listen_any(DB_CHANGED_EVENT, move |event| {
+        let Ok(change) = serde_json::from_str::<DbChange>(event.payload()) else {
+            return;
+        };
+        if change.tables.iter().any(|t| tables.contains(&t.as_str())) {
+            handler(change);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_written_tables_from_drizzle_statements() {
+        assert_eq!(
+            written_tables(r#"insert into "todos" ("id", "text") values (?, ?)"#),
+            vec!["todos"]
+        );
+        assert_eq!(
+            written_tables(r#"update "pages" set "notes" = ? where "date" = ?"#),
+            vec!["pages"]
+        );
+        assert_eq!(
+            written_tables(r#"delete from "todos" where "id" = ?"#),
+            vec!["todos"]
+        );
+    }
+
+    #[test]
+    fn test_written_tables_ignores_reads_and_upsert_clause() {
+        assert!(written_tables("SELECT * FROM todos").is_empty());
+        assert_eq!(
+            written_tables(
+                "INSERT INTO settings (key, value) VALUES (?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value"
+            ),
+            vec!["settings"]
+        );
+        assert_eq!(
+            written_tables("UPDATE OR REPLACE main.todos SET status = 'done'"),
+            vec!["todos"]
+        );
+    }
+
+    #[test]
+    fn test_read_tables() {
+        assert_eq!(
+            read_tables(r#"select "id", "text" from "todos" where "page_date" between ? and ?"#),
+            vec!["todos"]
+        );
+        assert_eq!(
+            read_tables("SELECT * FROM pages p JOIN todos t ON t.page_date = p.date"),
+            vec!["pages", "todos"]
+        );
+        assert_eq!(
+            read_tables("SELECT COUNT(*) FROM main.todos AS t, json_each(t.tags) WHERE 1"),
+            vec!["todos", "json_each"]
+        );
+        assert_eq!(
+            read_tables("SELECT * FROM (SELECT id FROM todos) LEFT JOIN pages"),
+            vec!["todos", "pages"]
+        );
+    }
+}
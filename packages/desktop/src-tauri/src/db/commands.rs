@@ -1,8 +1,19 @@
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
-use sqlx::{Row, Column, SqlitePool, TypeInfo};
-use tauri::State;
+use sqlparser::dialect::SQLiteDialect;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use sqlx::sqlite::SqliteConnection;
+use sqlx::{Row, Column, SqlitePool, TypeInfo, ValueRef};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Listener, Manager, State};
 
-use super::DatabaseState;
+use super::database::InitStatus;
+use super::write_queue::WriteQueue;
+use super::{changes, DatabaseState};
+use crate::settings::{self, SettingChange, SETTINGS_CHANGED_EVENT};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SqlRequest {
@@ -17,17 +28,34 @@ pub struct BatchSqlRequest {
 }
 
 /// Row format expected by Drizzle sqlite-proxy
-/// columns: column names in order
+/// columns: column names in order, left out for the "values" method
 /// rows: values in the same order as columns
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SqlRow {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub columns: Vec<String>,
     pub rows: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Type information for one result column, so values (NULLs included)
+/// can be revived without guessing from them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnType {
+    pub name: String,
+    /// Declared type as sqlx reads it, e.g. "INTEGER" or "BOOLEAN"; `None` for expressions
+    pub declared_type: Option<String>,
+    /// SQLite affinity of the declared type, or for expressions the storage
+    /// class of the first non-NULL value
+    pub affinity: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SqlResponse {
     pub rows: Vec<SqlRow>,
+    /// Empty when no rows came back
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<ColumnType>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,27 +63,364 @@ pub struct BatchSqlResponse {
     pub results: Vec<SqlResponse>,
 }
 
+/// When on, integers beyond JS's safe range are returned as strings, and
+/// string parameters holding such integers are bound as integers
+const BIGINT_AS_STRING_SETTING: &str = "db.bigint_as_string";
+/// Largest integer a JS number holds exactly (`Number.MAX_SAFE_INTEGER`)
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+static BIGINT_AS_STRING: AtomicBool = AtomicBool::new(false);
+
+/// A cap on what the SQL proxy commands accept, read from a `db.limits.*` setting.
+/// A value of 0 turns the limit off.
+struct Limit {
+    setting: &'static str,
+    /// `SqlError::code` reported when the limit is hit
+    code: &'static str,
+    default: u64,
+    value: AtomicU64,
+}
+
+impl Limit {
+    const fn new(setting: &'static str, code: &'static str, default: u64) -> Self {
+        Self {
+            setting,
+            code,
+            default,
+            value: AtomicU64::new(default),
+        }
+    }
+
+    fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, value: Option<u64>) {
+        self.value.store(value.unwrap_or(self.default), Ordering::Relaxed);
+    }
+
+    /// Fail if `actual` goes over the limit
+    fn check(&self, actual: usize, what: &str) -> Result<(), SqlError> {
+        let max = self.get();
+        if max == 0 || actual as u64 <= max {
+            return Ok(());
+        }
+        Err(SqlError {
+            code: self.code,
+            message: format!("{} {} exceeds the limit of {} ({})", actual, what, max, self.setting),
+            setting: Some(self.setting),
+            max: Some(max),
+        })
+    }
+}
+
+static MAX_BATCH_STATEMENTS: Limit = Limit::new("db.limits.max_batch_statements", "too_many_statements", 1_000);
+/// SQLite's own cap on bound parameters (SQLITE_MAX_VARIABLE_NUMBER)
+static MAX_PARAMS: Limit = Limit::new("db.limits.max_params", "too_many_params", 32_766);
+static MAX_ROWS: Limit = Limit::new("db.limits.max_rows", "too_many_rows", 100_000);
+static MAX_REQUESTS_PER_SECOND: Limit = Limit::new("db.limits.max_requests_per_second", "rate_limited", 2_000);
+
+static LIMITS: [&Limit; 4] = [&MAX_BATCH_STATEMENTS, &MAX_PARAMS, &MAX_ROWS, &MAX_REQUESTS_PER_SECOND];
+
+/// Start of the current one-second window and the requests made in it
+static REQUEST_WINDOW: Mutex<(Option<Instant>, usize)> = Mutex::new((None, 0));
+
+/// Count a proxy call against `db.limits.max_requests_per_second`
+fn admit_request() -> Result<(), SqlError> {
+    let Ok(mut window) = REQUEST_WINDOW.lock() else {
+        return Ok(());
+    };
+    let now = Instant::now();
+    match window.0 {
+        Some(start) if now.duration_since(start) < Duration::from_secs(1) => window.1 += 1,
+        _ => *window = (Some(now), 1),
+    }
+    MAX_REQUESTS_PER_SECOND.check(window.1, "requests this second")
+}
+
+/// Error of the SQL proxy commands, so the frontend can tell a tripped limit
+/// from a failing statement
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlError {
+    /// "sql" for database errors, otherwise the limit that was hit, e.g. "too_many_rows"
+    pub code: &'static str,
+    pub message: String,
+    /// Setting holding the limit that was hit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setting: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+}
+
+impl From<String> for SqlError {
+    fn from(message: String) -> Self {
+        Self {
+            code: "sql",
+            message,
+            setting: None,
+            max: None,
+        }
+    }
+}
+
+impl std::fmt::Display for SqlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Load `db.bigint_as_string` and the `db.limits.*` settings and follow changes to them
+pub async fn watch_settings(app: &AppHandle) {
+    app.listen(SETTINGS_CHANGED_EVENT, |event| {
+        if let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) {
+            if change.key == BIGINT_AS_STRING_SETTING {
+                let enabled = change.value.and_then(|v| v.as_bool()).unwrap_or(false);
+                BIGINT_AS_STRING.store(enabled, Ordering::Relaxed);
+            } else if let Some(limit) = LIMITS.iter().find(|limit| limit.setting == change.key) {
+                limit.set(change.value.and_then(|v| v.as_u64()));
+            }
+        }
+    });
+
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let enabled = settings::get_or(&pool, BIGINT_AS_STRING_SETTING, false).await;
+    BIGINT_AS_STRING.store(enabled, Ordering::Relaxed);
+    for limit in LIMITS {
+        limit.set(settings::get(&pool, limit.setting).await.ok().flatten());
+    }
+}
+
+fn is_safe_integer(i: i64) -> bool {
+    (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&i)
+}
+
+/// JSON for an INTEGER value; a string if JS can't hold it and the setting is on
+fn integer_to_json(i: i64) -> serde_json::Value {
+    if !is_safe_integer(i) && BIGINT_AS_STRING.load(Ordering::Relaxed) {
+        serde_json::Value::String(i.to_string())
+    } else {
+        serde_json::Value::from(i)
+    }
+}
+
+/// A string parameter holding an integer JS can't represent, as sent back by `integer_to_json`
+fn string_encoded_integer(s: &str) -> Option<i64> {
+    if !BIGINT_AS_STRING.load(Ordering::Relaxed) {
+        return None;
+    }
+    s.parse::<i64>().ok().filter(|i| !is_safe_integer(*i) && i.to_string() == s)
+}
+
+/// Number of distinct SELECT results kept by `QueryCache`
+const QUERY_CACHE_CAPACITY: usize = 256;
+
+/// SQL fragments whose result can change without any table being written
+const NON_DETERMINISTIC_MARKERS: &[&str] = &[
+    "random(",
+    "'now'",
+    "current_date",
+    "current_time",
+    "changes()",
+    "last_insert_rowid",
+    // The id functions from `functions`
+    "uuid_v7(",
+    "uuidv7(",
+    "ulid(",
+];
+
+struct CachedQuery {
+    tables: Vec<String>,
+    response: SqlResponse,
+}
+
+/// LRU cache of SELECT results keyed by (SQL, params), invalidated per table by SQLite's
+/// update hook on every pool connection (see `track_writes`) and by `changes::notify`
+pub struct QueryCache {
+    entries: Mutex<LruCache<(String, String), CachedQuery>>,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(QUERY_CACHE_CAPACITY).expect("capacity is non-zero"),
+            )),
+        }
+    }
+}
+
+impl QueryCache {
+    /// Cache key and read tables for a request, or `None` if it must not be cached
+    fn key(request: &SqlRequest) -> Option<((String, String), Vec<String>)> {
+        if request.method == "run" || may_hold_several_statements(&request.sql) {
+            return None;
+        }
+        let sql = request.sql.trim_start().to_lowercase();
+        if !(sql.starts_with("select") || sql.starts_with("with"))
+            || NON_DETERMINISTIC_MARKERS.iter().any(|marker| sql.contains(marker))
+        {
+            return None;
+        }
+        let tables = changes::read_tables(&request.sql);
+        if tables.is_empty() {
+            return None;
+        }
+        let params = serde_json::to_string(&request.params).ok()?;
+        Some(((request.method.clone() + "\0" + &request.sql, params), tables))
+    }
+
+    fn get(&self, key: &(String, String)) -> Option<SqlResponse> {
+        let mut entries = self.entries.lock().ok()?;
+        entries.get(key).map(|cached| cached.response.clone())
+    }
+
+    fn insert(&self, key: (String, String), tables: Vec<String>, response: SqlResponse) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.put(key, CachedQuery { tables, response });
+        }
+    }
+
+    /// Drop every cached result that read from one of `tables`
+    pub fn invalidate(&self, tables: &[String]) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        let stale: Vec<(String, String)> = entries
+            .iter()
+            .filter(|(_, cached)| {
+                cached
+                    .tables
+                    .iter()
+                    .any(|t| tables.iter().any(|w| w.eq_ignore_ascii_case(t)))
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            entries.pop(&key);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    /// Invalidate the tables `conn` writes as its transactions commit, whoever writes them.
+    /// `changes::notify` still covers what the hook misses, like SQLite's truncate
+    /// optimization for a DELETE without WHERE.
+    pub(super) async fn track_writes(self: Arc<Self>, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        let written: Arc<Mutex<Vec<String>>> = Arc::default();
+        let mut handle = conn.lock_handle().await?;
+
+        let pending = written.clone();
+        handle.set_update_hook(move |change| {
+            if let Ok(mut pending) = pending.lock() {
+                if !pending.iter().any(|table| table == change.table) {
+                    pending.push(change.table.to_string());
+                }
+            }
+        });
+        let pending = written.clone();
+        handle.set_commit_hook(move || {
+            let tables = pending.lock().map(|mut pending| std::mem::take(&mut *pending)).unwrap_or_default();
+            if !tables.is_empty() {
+                self.invalidate(&tables);
+            }
+            // Returning false would turn the commit into a rollback
+            true
+        });
+        handle.set_rollback_hook(move || {
+            if let Ok(mut pending) = written.lock() {
+                pending.clear();
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Methods drizzle's sqlite-proxy driver sends
+const METHODS: &[&str] = &["run", "all", "get", "values"];
+
 /// Convert a SQLite row to the format expected by Drizzle
 fn row_to_sql_row(row: &sqlx::sqlite::SqliteRow) -> SqlRow {
     let columns: Vec<String> = row.columns().iter().map(|c| c.name().to_string()).collect();
+    SqlRow {
+        columns,
+        ..row_to_values(row)
+    }
+}
+
+/// A row as raw values only, for the "values" method
+fn row_to_values(row: &sqlx::sqlite::SqliteRow) -> SqlRow {
     let values: Vec<serde_json::Value> = (0..row.len())
         .map(|i| sqlx_value_to_json(row, i))
         .collect();
 
     SqlRow {
-        columns,
+        columns: Vec::new(),
         rows: values,
     }
 }
 
+/// SQLite's affinity rules (https://www.sqlite.org/datatype3.html#determination_of_column_affinity)
+fn affinity(declared_type: &str) -> &'static str {
+    let declared_type = declared_type.to_ascii_uppercase();
+    if declared_type.contains("INT") {
+        "INTEGER"
+    } else if ["CHAR", "CLOB", "TEXT"].iter().any(|t| declared_type.contains(t)) {
+        "TEXT"
+    } else if declared_type.contains("BLOB") || declared_type.is_empty() {
+        "BLOB"
+    } else if ["REAL", "FLOA", "DOUB"].iter().any(|t| declared_type.contains(t)) {
+        "REAL"
+    } else {
+        "NUMERIC"
+    }
+}
+
+/// Column types of a result set, read from its first row
+fn column_types(rows: &[sqlx::sqlite::SqliteRow]) -> Vec<ColumnType> {
+    let Some(first) = rows.first() else {
+        return Vec::new();
+    };
+    first
+        .columns()
+        .iter()
+        .map(|column| {
+            let declared_type = Some(column.type_info().name())
+                .filter(|name| *name != "NULL")
+                .map(str::to_string);
+            let affinity = match &declared_type {
+                Some(declared_type) => Some(affinity(declared_type).to_string()),
+                None => rows.iter().find_map(|row| {
+                    let value = row.try_get_raw(column.ordinal()).ok()?;
+                    (!value.is_null()).then(|| value.type_info().name().to_string())
+                }),
+            };
+            ColumnType {
+                name: column.name().to_string(),
+                declared_type,
+                affinity,
+            }
+        })
+        .collect()
+}
+
 /// Convert a SQLite value to JSON, handling different types
 fn sqlx_value_to_json(row: &sqlx::sqlite::SqliteRow, index: usize) -> serde_json::Value {
     let col = row.column(index);
-    let type_name = col.type_info().name();
+    // Expressions have no declared type, so go by the value itself
+    let type_name = match row.try_get_raw(index) {
+        Ok(raw) if col.type_info().is_null() && !raw.is_null() => raw.type_info().name().to_string(),
+        _ => col.type_info().name().to_string(),
+    };
 
-    match type_name {
+    match type_name.as_str() {
         "INTEGER" => match row.try_get::<Option<i64>, _>(index) {
-            Ok(Some(i)) => serde_json::Value::from(i),
+            Ok(Some(i)) => integer_to_json(i),
             Ok(None) => serde_json::Value::Null,
             Err(_) => serde_json::Value::Null,
         },
@@ -91,16 +456,30 @@ fn sqlx_value_to_json(row: &sqlx::sqlite::SqliteRow, index: usize) -> serde_json
     }
 }
 
-/// Internal helper that executes SQL without requiring Tauri State.
-/// Used by both the Tauri command and tests.
-async fn execute_sql_internal(
-    pool: &SqlitePool,
-    request: SqlRequest,
-) -> Result<SqlResponse, String> {
-    let mut query = sqlx::query(&request.sql);
-    
-    // Bind parameters
-    for param in &request.params {
+/// Times a statement is retried when the database is busy or locked
+const MAX_BUSY_RETRIES: u32 = 5;
+/// Delay before the first retry, doubled on each attempt
+const BUSY_RETRY_BASE: Duration = Duration::from_millis(25);
+/// Statements slower than this are logged, with their literals redacted
+const SLOW_STATEMENT: Duration = Duration::from_millis(200);
+
+/// Whether an error is transient contention (SQLITE_BUSY / SQLITE_LOCKED and their extended codes)
+fn is_busy_error(error: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db_error) = error else {
+        return false;
+    };
+    db_error
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+}
+
+type SqliteQuery<'q> = sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>;
+
+/// Build a query with its JSON parameters bound
+fn bind_params<'q>(sql: &'q str, params: &'q [serde_json::Value]) -> Result<SqliteQuery<'q>, String> {
+    let mut query = sqlx::query(sql);
+    for param in params {
         query = match param {
             serde_json::Value::Null => query.bind(None::<String>),
             serde_json::Value::Bool(b) => query.bind(b),
@@ -113,7 +492,10 @@ async fn execute_sql_internal(
                     return Err("Invalid number parameter".to_string());
                 }
             }
-            serde_json::Value::String(s) => query.bind(s),
+            serde_json::Value::String(s) => match string_encoded_integer(s) {
+                Some(i) => query.bind(i),
+                None => query.bind(s),
+            },
             // Handle arrays (for JSON columns like tags)
             serde_json::Value::Array(_) => {
                 let json_str = serde_json::to_string(param).map_err(|e| e.to_string())?;
@@ -125,55 +507,281 @@ async fn execute_sql_internal(
             }
         };
     }
-    
+    Ok(query)
+}
+
+/// Cheap check for a `;` before the end of the SQL; it may sit in a string literal
+fn may_hold_several_statements(sql: &str) -> bool {
+    sql.trim_end().trim_end_matches(';').contains(';')
+}
+
+/// Split a request holding several `;`-separated statements, handing each its
+/// share of the parameters. `None` when it holds a single statement, or when
+/// numbered placeholders make the split ambiguous.
+fn split_statements(request: &SqlRequest) -> Option<Vec<SqlRequest>> {
+    if !may_hold_several_statements(&request.sql) {
+        return None;
+    }
+    // Keep literals escaped so the tokens print back to the original SQL
+    let tokens = Tokenizer::new(&SQLiteDialect {}, &request.sql)
+        .with_unescape(false)
+        .tokenize()
+        .ok()?;
+
+    let mut statements = Vec::new();
+    let mut sql = String::new();
+    let mut placeholders = 0;
+    let mut has_content = false;
+    let mut offset = 0;
+    for token in tokens.iter().chain(std::iter::once(&Token::SemiColon)) {
+        match token {
+            Token::SemiColon => {
+                if has_content {
+                    let params = request.params.get(offset..offset + placeholders)?.to_vec();
+                    offset += placeholders;
+                    statements.push(SqlRequest {
+                        sql: sql.trim().to_string(),
+                        params,
+                        method: "run".to_string(),
+                    });
+                }
+                sql.clear();
+                placeholders = 0;
+                has_content = false;
+            }
+            Token::Whitespace(_) => sql.push_str(&token.to_string()),
+            Token::Placeholder(p) if p != "?" => return None,
+            token => {
+                if matches!(token, Token::Placeholder(_)) {
+                    placeholders += 1;
+                }
+                has_content = true;
+                sql.push_str(&token.to_string());
+            }
+        }
+    }
+
+    if statements.len() < 2 || offset != request.params.len() {
+        return None;
+    }
+    // Only the last statement's result is returned
+    if let Some(last) = statements.last_mut() {
+        last.method = request.method.clone();
+    }
+    Some(statements)
+}
+
+/// Internal helper that executes SQL without requiring Tauri State.
+/// Used by both the Tauri command and tests.
+/// Statements failing because the database is busy are retried with backoff.
+/// Several `;`-separated statements run in one transaction, returning the last result.
+pub(super) async fn execute_sql_internal(
+    pool: &SqlitePool,
+    request: SqlRequest,
+) -> Result<SqlResponse, String> {
+    if !METHODS.contains(&request.method.as_str()) {
+        return Err(format!("Unsupported method: {}", request.method));
+    }
+    let statements = split_statements(&request);
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let result = match &statements {
+            Some(statements) => {
+                let queries = statements
+                    .iter()
+                    .map(|s| bind_params(&s.sql, &s.params).map(|query| (query, s.method.as_str())))
+                    .collect::<Result<Vec<_>, _>>()?;
+                run_in_transaction(pool, queries).await
+            }
+            None => {
+                let query = bind_params(&request.sql, &request.params)?;
+                run_query(pool, query, &request.method).await
+            }
+        };
+        let elapsed = started.elapsed();
+        if elapsed >= SLOW_STATEMENT {
+            crate::logger::info(&format!(
+                "Slow statement ({:.1}ms, {} params): {}",
+                elapsed.as_secs_f64() * 1000.0,
+                request.params.len(),
+                crate::logger::redact_sql(&request.sql)
+            ));
+        }
+        match result {
+            Err(e) if is_busy_error(&e) && attempt < MAX_BUSY_RETRIES => {
+                tokio::time::sleep(BUSY_RETRY_BASE * 2u32.pow(attempt)).await;
+                attempt += 1;
+            }
+            result => {
+                super::profiler::record(&request.sql, elapsed, result.as_ref().ok().map(|r| r.rows.len()));
+                if attempt > 0 {
+                    crate::logger::info(&format!("Statement retried {} time(s) while database was busy", attempt));
+                }
+                return result.map_err(|e| e.to_string());
+            }
+        }
+    }
+}
+
+/// Run queries in order inside one transaction, returning the last one's result
+async fn run_in_transaction(
+    pool: &SqlitePool,
+    queries: Vec<(SqliteQuery<'_>, &str)>,
+) -> Result<SqlResponse, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut response = SqlResponse::default();
+    for (query, method) in queries {
+        response = run_query(&mut *tx, query, method).await?;
+    }
+    tx.commit().await?;
+    Ok(response)
+}
+
+async fn run_query<'c, E>(executor: E, query: SqliteQuery<'_>, method: &str) -> Result<SqlResponse, sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Sqlite>,
+{
     // Branch on method type
-    if request.method == "run" {
+    if method == "run" {
         // For INSERT, UPDATE, DELETE - use execute instead of fetch_all
-        query
-            .execute(pool)
-            .await
-            .map_err(|e| e.to_string())?;
-        
+        query.execute(executor).await?;
+
         // Return empty rows for run method
-        return Ok(SqlResponse { rows: Vec::new() });
+        return Ok(SqlResponse::default());
     }
-    
+
     // For SELECT queries - use fetch_all
-    let rows = query
-        .fetch_all(pool)
-        .await
-        .map_err(|e| e.to_string())?;
-    
-    let result_rows: Vec<SqlRow> = rows.iter().map(row_to_sql_row).collect();
-    
-    Ok(SqlResponse { rows: result_rows })
+    let rows = query.fetch_all(executor).await?;
+
+    let result_rows: Vec<SqlRow> = match method {
+        "values" => rows.iter().map(row_to_values).collect(),
+        _ => rows.iter().map(row_to_sql_row).collect(),
+    };
+
+    Ok(SqlResponse {
+        rows: result_rows,
+        columns: column_types(&rows),
+    })
+}
+
+/// Whether the database has finished initializing; never waits on the pool
+#[crate::metrics::command]
+pub fn get_db_status(state: State<'_, DatabaseState>) -> InitStatus {
+    state.status()
 }
 
-#[tauri::command]
+#[crate::metrics::command]
 pub async fn execute_single_sql(
+    app: AppHandle,
     state: State<'_, DatabaseState>,
+    queue: State<'_, WriteQueue>,
     request: SqlRequest,
-) -> Result<SqlResponse, String> {
+) -> Result<SqlResponse, SqlError> {
+    state.ensure_ready()?;
+    admit_request()?;
+    MAX_PARAMS.check(request.params.len(), "parameters")?;
+    // Queued writes go first so statements always see them; failures are reported by the queue
+    let _ = queue.flush(&app).await;
+
+    let cache_key = QueryCache::key(&request);
+    if let Some((key, _)) = &cache_key {
+        if let Some(mut response) = state.query_cache.get(key) {
+            crate::privacy::reveal_unlocked_entries(&mut response);
+            return Ok(response);
+        }
+    }
+
+    let is_run = request.method == "run" || may_hold_several_statements(&request.sql);
+    let tables = changes::written_tables(&request.sql);
     let pool = state.pool.lock().await;
-    execute_sql_internal(&pool, request).await
+    let mut response = execute_sql_internal(&pool, request).await?;
+    MAX_ROWS.check(response.rows.len(), "rows")?;
+    // Cache while still holding the pool so no write can slip in between
+    if let Some((key, read_tables)) = cache_key {
+        state.query_cache.insert(key, read_tables, response.clone());
+    }
+    drop(pool);
+
+    // Statements we can't attribute to a table (DDL and the like) may change anything
+    if is_run && tables.is_empty() {
+        state.query_cache.clear();
+    }
+    changes::notify(&app, tables);
+    // After caching, so the cache never holds decrypted entries
+    crate::privacy::reveal_unlocked_entries(&mut response);
+    Ok(response)
 }
 
-#[tauri::command]
+#[crate::metrics::command]
 pub async fn execute_batch_sql(
+    app: AppHandle,
     state: State<'_, DatabaseState>,
+    queue: State<'_, WriteQueue>,
     request: BatchSqlRequest,
-) -> Result<BatchSqlResponse, String> {
+) -> Result<BatchSqlResponse, SqlError> {
+    state.ensure_ready()?;
+    admit_request()?;
+    MAX_BATCH_STATEMENTS.check(request.queries.len(), "statements")?;
+    for query in &request.queries {
+        MAX_PARAMS.check(query.params.len(), "parameters")?;
+    }
+    let _ = queue.flush(&app).await;
+
     let pool = state.pool.lock().await;
     let mut results = Vec::new();
+    let mut tables = Vec::new();
+    
+    let mut unknown_write = false;
+    let mut rows = 0;
     
     for query_request in request.queries {
-        let result = execute_sql_internal(&pool, query_request).await?;
+        let written = changes::written_tables(&query_request.sql);
+        unknown_write |= (query_request.method == "run" || may_hold_several_statements(&query_request.sql))
+            && written.is_empty();
+        for table in written {
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+        let mut result = execute_sql_internal(&pool, query_request).await?;
+        crate::privacy::reveal_unlocked_entries(&mut result);
+        rows += result.rows.len();
+        MAX_ROWS.check(rows, "rows")?;
         results.push(result);
     }
-    
+    drop(pool);
+
+    if unknown_write {
+        state.query_cache.clear();
+    }
+
+    changes::notify(&app, tables);
     Ok(BatchSqlResponse { results })
 }
 
+/// Delete and recreate the development database, then tell the frontend to reload.
+/// Only available in debug builds.
+#[crate::metrics::command]
+pub async fn dev_reset_database(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+) -> Result<(), String> {
+    if !cfg!(debug_assertions) {
+        return Err("dev_reset_database is only available in debug builds".to_string());
+    }
+
+    crate::logger::info("Resetting development database...");
+    state.reset().await.map_err(|e| {
+        crate::logger::error(&format!("Database reset failed: {}", e));
+        e
+    })?;
+    crate::logger::info("Development database reset complete");
+
+    app.emit("db://reload", ()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -350,4 +958,252 @@ mod tests {
         let content = &response.rows[0].rows[1];
         assert_eq!(*content, serde_json::Value::Null, "Content should be NULL");
     }
+
+    #[tokio::test]
+    async fn test_execute_single_sql_values_omit_columns() {
+        let pool = create_test_db().await.expect("Failed to create test DB");
+
+        let setup = SqlRequest {
+            sql: "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT); INSERT INTO users (name) VALUES ('Alice')"
+                .to_string(),
+            params: vec![],
+            method: "run".to_string(),
+        };
+        execute_sql_internal(&pool, setup).await.expect("Failed to set up table");
+
+        let select = SqlRequest {
+            sql: "SELECT id, name FROM users".to_string(),
+            params: vec![],
+            method: "values".to_string(),
+        };
+        let response = execute_sql_internal(&pool, select).await.expect("Failed to select values");
+        assert_eq!(
+            serde_json::to_value(&response.rows).unwrap(),
+            serde_json::json!([{ "rows": [1, "Alice"] }])
+        );
+
+        let unknown = SqlRequest {
+            sql: "SELECT 1".to_string(),
+            params: vec![],
+            method: "first".to_string(),
+        };
+        assert!(execute_sql_internal(&pool, unknown).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_single_sql_reports_column_types() {
+        let pool = create_test_db().await.expect("Failed to create test DB");
+
+        let setup = SqlRequest {
+            sql: "CREATE TABLE items (id INTEGER PRIMARY KEY, done BOOLEAN, tags TEXT); INSERT INTO items (done) VALUES (1)"
+                .to_string(),
+            params: vec![],
+            method: "run".to_string(),
+        };
+        execute_sql_internal(&pool, setup).await.expect("Failed to set up table");
+
+        let select = SqlRequest {
+            sql: "SELECT id, done, tags, id * 2 AS twice FROM items".to_string(),
+            params: vec![],
+            method: "all".to_string(),
+        };
+        let response = execute_sql_internal(&pool, select).await.expect("Failed to select");
+        let types: Vec<(Option<&str>, Option<&str>)> = response
+            .columns
+            .iter()
+            .map(|c| (c.declared_type.as_deref(), c.affinity.as_deref()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                (Some("INTEGER"), Some("INTEGER")),
+                (Some("BOOLEAN"), Some("NUMERIC")),
+                // NULL in every row, but still known to be text
+                (Some("TEXT"), Some("TEXT")),
+                (None, Some("INTEGER")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_integers_round_trip_as_strings() {
+        let big = MAX_SAFE_INTEGER + 2;
+        assert_eq!(integer_to_json(big), serde_json::json!(big));
+        assert_eq!(string_encoded_integer(&big.to_string()), None);
+
+        BIGINT_AS_STRING.store(true, Ordering::Relaxed);
+        assert_eq!(integer_to_json(big), serde_json::json!(big.to_string()));
+        assert_eq!(integer_to_json(42), serde_json::json!(42));
+        assert_eq!(string_encoded_integer(&big.to_string()), Some(big));
+        // Safe integers and anything that isn't exactly an integer stay text
+        assert_eq!(string_encoded_integer("42"), None);
+        assert_eq!(string_encoded_integer(&format!("0{}", big)), None);
+        BIGINT_AS_STRING.store(false, Ordering::Relaxed);
+    }
+
+    #[tokio::test]
+    async fn test_execute_single_sql_multiple_statements() {
+        let pool = create_test_db().await.expect("Failed to create test DB");
+
+        let request = SqlRequest {
+            sql: "CREATE TABLE tags (name TEXT); INSERT INTO tags (name) VALUES (?), ('a;b'); SELECT name FROM tags WHERE name != ?;"
+                .to_string(),
+            params: vec!["work".into(), "zzz".into()],
+            method: "all".to_string(),
+        };
+        assert_eq!(split_statements(&request).map(|s| s.len()), Some(3));
+
+        let response = execute_sql_internal(&pool, request).await.expect("Failed to run statements");
+        assert_eq!(response.rows.len(), 2, "only the last statement's rows are returned");
+        assert_eq!(response.rows[1].rows[0], "a;b");
+    }
+
+    #[test]
+    fn test_query_cache_invalidates_by_table() {
+        let cache = QueryCache::default();
+        let request = |sql: &str, method: &str| SqlRequest {
+            sql: sql.to_string(),
+            params: vec![serde_json::Value::from("2024-05")],
+            method: method.to_string(),
+        };
+
+        assert!(QueryCache::key(&request("UPDATE todos SET text = ?", "run")).is_none());
+        assert!(QueryCache::key(&request("SELECT date('now') FROM pages", "all")).is_none());
+
+        let (todos_key, tables) = QueryCache::key(&request("SELECT * FROM todos WHERE page_date LIKE ?", "all")).unwrap();
+        cache.insert(todos_key.clone(), tables, SqlResponse::default());
+        let (pages_key, tables) = QueryCache::key(&request("SELECT * FROM pages WHERE date LIKE ?", "all")).unwrap();
+        cache.insert(pages_key.clone(), tables, SqlResponse::default());
+
+        cache.invalidate(&["todos".to_string()]);
+        assert!(cache.get(&todos_key).is_none());
+        assert!(cache.get(&pages_key).is_some());
+    }
+
+    #[test]
+    fn test_limit_reports_structured_error() {
+        let limit = Limit::new("db.limits.test", "too_many_rows", 2);
+        assert!(limit.check(2, "rows").is_ok());
+
+        let error = limit.check(3, "rows").unwrap_err();
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "too_many_rows");
+        assert_eq!(json["setting"], "db.limits.test");
+        assert_eq!(json["max"], 2);
+
+        limit.set(Some(0));
+        assert!(limit.check(usize::MAX, "rows").is_ok(), "0 turns the limit off");
+        limit.set(None);
+        assert_eq!(limit.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_selects_generating_ids_are_not_cached() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .after_connect(|conn, _| Box::pin(async move { crate::db::functions::register(conn).await }))
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE pages (date TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO pages VALUES ('2024-05-01')").execute(&pool).await.unwrap();
+
+        let cache = QueryCache::default();
+        for sql in ["SELECT uuid_v7() AS id FROM pages", "SELECT ULID() AS id FROM pages"] {
+            let mut ids = Vec::new();
+            for _ in 0..2 {
+                let request = SqlRequest {
+                    sql: sql.to_string(),
+                    params: vec![],
+                    method: "all".to_string(),
+                };
+                let key = QueryCache::key(&request);
+                let cached = key.as_ref().and_then(|(key, _)| cache.get(key));
+                let response = match cached {
+                    Some(response) => response,
+                    None => execute_sql_internal(&pool, request).await.unwrap(),
+                };
+                if let Some((key, tables)) = key {
+                    cache.insert(key, tables, response.clone());
+                }
+                ids.push(serde_json::to_value(&response.rows).unwrap());
+            }
+            assert_ne!(ids[0], ids[1], "{} gave the same id twice", sql);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_selects_read_compressed_notes() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .after_connect(|conn, _| Box::pin(async move { crate::db::functions::register(conn).await }))
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("CREATE TABLE pages (workspace_id TEXT, date TEXT, notes TEXT)")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        crate::db::migration::apply_bundled(&mut conn, "0002_lively_nocturne.sql").await.unwrap();
+        drop(conn);
+        let long = "Long ago. ".repeat(200);
+        sqlx::query("INSERT INTO pages VALUES ('w', '2020-01-01', NULL, ?, 1), ('w', '2020-01-02', 'Plain', NULL, 0)")
+            .bind(crate::compression::compress(&long).unwrap())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // What Drizzle sends for `pageNotes` in a select without the key columns
+        let request = SqlRequest {
+            sql: r#"select coalesce("pages"."notes", zstd_decompress("pages"."notes_zstd")) as "text" from "pages" order by "pages"."date""#
+                .to_string(),
+            params: vec![],
+            method: "values".to_string(),
+        };
+        let response = execute_sql_internal(&pool, request).await.unwrap();
+        let notes: Vec<&serde_json::Value> = response.rows.iter().map(|row| &row.rows[0]).collect();
+        assert_eq!(notes, [&serde_json::json!(long), &serde_json::json!("Plain")]);
+    }
+
+    #[tokio::test]
+    async fn test_committed_writes_invalidate_the_cache() {
+        let cache = Arc::new(QueryCache::default());
+        let tracked = cache.clone();
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .after_connect(move |conn, _| {
+                let cache = tracked.clone();
+                Box::pin(async move { cache.track_writes(conn).await })
+            })
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE todos (text TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("CREATE TABLE pages (notes TEXT)").execute(&pool).await.unwrap();
+
+        let request = |sql: &str| SqlRequest {
+            sql: sql.to_string(),
+            params: vec![],
+            method: "all".to_string(),
+        };
+        let cache_both = || {
+            for sql in ["SELECT * FROM todos", "SELECT * FROM pages"] {
+                let (key, tables) = QueryCache::key(&request(sql)).unwrap();
+                cache.insert(key, tables, SqlResponse::default());
+            }
+        };
+        let cached = |sql: &str| cache.get(&QueryCache::key(&request(sql)).unwrap().0).is_some();
+
+        cache_both();
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query("INSERT INTO todos VALUES ('a')").execute(&mut *tx).await.unwrap();
+        tx.rollback().await.unwrap();
+        assert!(cached("SELECT * FROM todos"), "a rolled back write changes nothing");
+
+        // Written straight to the pool, without `changes::notify`
+        sqlx::query("INSERT INTO todos VALUES ('b')").execute(&pool).await.unwrap();
+        assert!(!cached("SELECT * FROM todos"));
+        assert!(cached("SELECT * FROM pages"));
+    }
 }
@@ -1,30 +1,428 @@
-use sqlx::{SqlitePool, sqlite::{SqlitePoolOptions, SqliteConnectOptions}};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sqlx::{Sqlite, SqlitePool, Transaction, sqlite::{SqlitePoolOptions, SqliteConnectOptions}};
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use super::commands::QueryCache;
+use super::{Migration, Seed};
+
+/// Used until `db.busy_timeout_ms` is configured
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Reports running at the same time, each holding one read-only connection
+const MAX_REPORTING_CONNECTIONS: u32 = 2;
+
+/// A read transaction on the reporting pool. Every query in it sees the database as it was
+/// when the snapshot was opened; dropping it ends the transaction.
+pub type ReportingSnapshot = Transaction<'static, Sqlite>;
+
+/// Where startup initialization of the database stands
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "camelCase")]
+pub enum InitStatus {
+    Initializing,
+    Ready,
+    Failed(String),
+}
 
 pub struct DatabaseState {
     pub pool: Arc<Mutex<SqlitePool>>,
+    pub db_path: Arc<Mutex<PathBuf>>,
+    pub migrations_dir: PathBuf,
+    pub query_cache: Arc<QueryCache>,
+    status: Arc<RwLock<InitStatus>>,
+    /// Read-only pool on the same file for `open_reporting_snapshot`, opened on first use
+    reporting: Arc<Mutex<Option<SqlitePool>>>,
 }
 
 impl DatabaseState {
-    pub async fn new(db_path: &str) -> Result<Self, sqlx::Error> {
-        // Create parent directory if it doesn't exist
-        if let Some(parent) = std::path::Path::new(db_path).parent() {
-            std::fs::create_dir_all(parent).ok();
+    /// Create the state without touching the disk. The returned guard holds the pool
+    /// until `initialize` swaps in the migrated one, so anything using the database waits.
+    pub async fn lazy(
+        db_path: PathBuf,
+        migrations_dir: PathBuf,
+    ) -> (Self, OwnedMutexGuard<SqlitePool>) {
+        let placeholder = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_lazy_with(SqliteConnectOptions::new().filename(&db_path));
+        let pool = Arc::new(Mutex::new(placeholder));
+        let guard = pool.clone().lock_owned().await;
+
+        let state = Self {
+            pool,
+            db_path: Arc::new(Mutex::new(db_path)),
+            migrations_dir,
+            query_cache: Arc::new(QueryCache::default()),
+            status: Arc::new(RwLock::new(InitStatus::Initializing)),
+            reporting: Arc::new(Mutex::new(None)),
+        };
+        (state, guard)
+    }
+
+    /// Connect to the database, run migrations and seed first-run data,
+    /// then release the pool to everything waiting on it
+    pub async fn initialize(&self, mut guard: OwnedMutexGuard<SqlitePool>) -> Result<(), String> {
+        let db_path = self.db_path.lock().await.clone();
+        let result = match open_pool(&db_path, &self.migrations_dir, &self.query_cache).await {
+            Ok(pool) => {
+                std::mem::replace(&mut *guard, pool).close().await;
+                Ok(())
+            }
+            Err(e) => {
+                // Leave a closed pool behind so queries fail instead of hitting an unmigrated file
+                guard.close().await;
+                Err(e)
+            }
+        };
+
+        self.set_status(match &result {
+            Ok(()) => InitStatus::Ready,
+            Err(e) => InitStatus::Failed(e.clone()),
+        });
+        result
+    }
+
+    pub fn status(&self) -> InitStatus {
+        self.status
+            .read()
+            .map(|status| status.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    fn set_status(&self, status: InitStatus) {
+        match self.status.write() {
+            Ok(mut current) => *current = status,
+            Err(e) => *e.into_inner() = status,
+        }
+    }
+
+    /// Fail fast when startup initialization failed instead of using a closed pool
+    pub fn ensure_ready(&self) -> Result<(), String> {
+        match self.status() {
+            InitStatus::Failed(e) => Err(format!("Database unavailable: {}", e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Open the database at `db_path` and swap it in for the current one.
+    /// The current database stays open if the new one fails to initialize.
+    pub async fn reopen(&self, db_path: PathBuf) -> Result<(), String> {
+        let new_pool = open_pool(&db_path, &self.migrations_dir, &self.query_cache).await?;
+
+        let mut pool = self.pool.lock().await;
+        let mut current_path = self.db_path.lock().await;
+        let old_pool = std::mem::replace(&mut *pool, new_pool);
+        *current_path = db_path;
+        self.query_cache.clear();
+        self.close_reporting().await;
+        self.set_status(InitStatus::Ready);
+        old_pool.close().await;
+        Ok(())
+    }
+
+    /// Move the database file to `new_path`: checkpoint the WAL, copy the file,
+    /// verify the copy, then reopen the pool there and delete the old file.
+    pub async fn relocate(&self, new_path: PathBuf) -> Result<(), String> {
+        if new_path.exists() {
+            return Err(format!("{} already exists", new_path.display()));
+        }
+        if let Some(parent) = new_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let mut pool = self.pool.lock().await;
+        let mut current_path = self.db_path.lock().await;
+        self.close_reporting().await;
+
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&*pool)
+            .await
+            .map_err(|e| format!("Failed to checkpoint WAL: {}", e))?;
+        let old_tables = count_schema_objects(&pool).await?;
+        pool.close().await;
+
+        let copied = match std::fs::copy(&*current_path, &new_path) {
+            Ok(_) => verify_copy(&new_path, old_tables).await,
+            Err(e) => Err(format!("Failed to copy database: {}", e)),
+        };
+
+        if let Err(e) = copied {
+            std::fs::remove_file(&new_path).ok();
+            *pool = open_pool(&current_path, &self.migrations_dir, &self.query_cache).await?;
+            return Err(e);
+        }
+
+        *pool = open_pool(&new_path, &self.migrations_dir, &self.query_cache).await?;
+        self.query_cache.clear();
+        let old_path = std::mem::replace(&mut *current_path, new_path);
+        if let Err(e) = remove_database_files(&old_path) {
+            crate::logger::error(&format!("Failed to remove old database: {}", e));
         }
+        Ok(())
+    }
+
+    /// Close the pool, delete the database file and recreate it from scratch
+    pub async fn reset(&self) -> Result<(), String> {
+        let mut pool = self.pool.lock().await;
+        let db_path = self.db_path.lock().await.clone();
+
+        pool.close().await;
+        self.query_cache.clear();
+        self.close_reporting().await;
+        remove_database_files(&db_path)?;
+
+        *pool = open_pool(&db_path, &self.migrations_dir, &self.query_cache).await?;
+        self.set_status(InitStatus::Ready);
+        Ok(())
+    }
 
-        // Use SqliteConnectOptions to avoid URL parsing issues on Windows
-        let options = SqliteConnectOptions::new()
-            .filename(db_path)
-            .create_if_missing(true);
+    /// Start a read transaction on a second, read-only pool, for stats and exports that read a
+    /// lot. It doesn't hold `pool`, and in WAL mode readers never block writers, so interactive
+    /// writes go on while the report runs, and the report sees no half-applied change.
+    pub async fn open_reporting_snapshot(&self) -> Result<ReportingSnapshot, String> {
+        self.ensure_ready()?;
+        let reporting_pool = {
+            // Taken first, as `reopen` and `relocate` do; it also waits out initialization, so
+            // the file is migrated before it's read
+            let _pool = self.pool.lock().await;
+            let mut reporting = self.reporting.lock().await;
+            match &*reporting {
+                Some(pool) => pool.clone(),
+                None => {
+                    let db_path = self.db_path.lock().await.clone();
+                    let pool = connect_read_only(&db_path)
+                        .await
+                        .map_err(|e| format!("Failed to open reporting connection: {}", e))?;
+                    *reporting = Some(pool.clone());
+                    pool
+                }
+            }
+        };
+        let mut snapshot = reporting_pool.begin().await.map_err(|e| e.to_string())?;
+        // A deferred transaction takes its snapshot at the first read
+        sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+            .execute(&mut *snapshot)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(snapshot)
+    }
 
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect_with(options)
-            .await?;
+    /// Close the reporting pool, e.g. before its file goes away; the next snapshot reopens it
+    pub async fn close_reporting(&self) {
+        if let Some(pool) = self.reporting.lock().await.take() {
+            pool.close().await;
+        }
+    }
+}
 
-        Ok(Self {
-            pool: Arc::new(Mutex::new(pool)),
+/// Create a connection pool for the given database file
+/// Connect to `db_path`; writes on every connection invalidate `cache` when there is one
+pub(super) async fn connect(db_path: &str, cache: Option<Arc<QueryCache>>) -> Result<SqlitePool, sqlx::Error> {
+    // Create parent directory if it doesn't exist
+    if let Some(parent) = std::path::Path::new(db_path).parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+
+    // Use SqliteConnectOptions to avoid URL parsing issues on Windows
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .create_if_missing(true)
+        .busy_timeout(DEFAULT_BUSY_TIMEOUT);
+
+    SqlitePoolOptions::new()
+        .max_connections(5)
+        // PRAGMA settings only affect connections opened after they change
+        .after_connect(move |conn, _| {
+            let cache = cache.clone();
+            Box::pin(async move {
+                super::functions::register(conn).await?;
+                super::pragmas::apply(conn).await?;
+                super::attach::sync(conn).await;
+                if let Some(cache) = cache {
+                    cache.track_writes(conn).await?;
+                }
+                Ok(())
+            })
+        })
+        // Databases attached or detached since the connection was last used
+        .before_acquire(|conn, _| {
+            Box::pin(async move {
+                super::attach::sync(conn).await;
+                Ok(true)
+            })
+        })
+        .connect_with(options)
+        .await
+}
+
+/// A read-only pool on a migrated database file, set up like the main one
+async fn connect_read_only(db_path: &Path) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .read_only(true)
+        .busy_timeout(DEFAULT_BUSY_TIMEOUT);
+    SqlitePoolOptions::new()
+        .max_connections(MAX_REPORTING_CONNECTIONS)
+        .after_connect(|conn, _| {
+            Box::pin(async move {
+                super::functions::register(conn).await?;
+                super::pragmas::apply(conn).await?;
+                super::attach::sync(conn).await;
+                Ok(())
+            })
+        })
+        .before_acquire(|conn, _| {
+            Box::pin(async move {
+                super::attach::sync(conn).await;
+                Ok(true)
+            })
         })
+        .connect_with(options)
+        .await
+}
+
+/// Connect, migrate and seed a database, returning the ready pool
+pub async fn open_pool(db_path: &Path, migrations_dir: &Path, cache: &Arc<QueryCache>) -> Result<SqlitePool, String> {
+    let db_path_str = db_path
+        .to_str()
+        .ok_or_else(|| "Failed to convert database path to string".to_string())?;
+
+    let pool = connect(db_path_str, Some(cache.clone()))
+        .await
+        .map_err(|e| format!("Failed to initialize database: {}", e))?;
+
+    Migration::new(pool.clone(), migrations_dir.to_path_buf())
+        .run()
+        .await
+        .map_err(|e| format!("Failed to run migrations: {}", e))?;
+
+    Seed::new(pool.clone())
+        .run()
+        .await
+        .map_err(|e| format!("Failed to seed database: {}", e))?;
+
+    Ok(pool)
+}
+
+/// Fold the WAL back into the database file and close every connection
+pub async fn checkpoint_and_close(pool: &SqlitePool) -> Result<(), String> {
+    let checkpoint = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to checkpoint WAL: {}", e));
+    pool.close().await;
+    checkpoint
+}
+
+/// Number of tables, indexes and triggers in the schema
+async fn count_schema_objects(pool: &SqlitePool) -> Result<i64, String> {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sqlite_master")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// Check a copied database file is intact and has the same schema as the original
+async fn verify_copy(path: &Path, expected_objects: i64) -> Result<(), String> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .map_err(|e| format!("Failed to open copied database: {}", e))?;
+
+    let result = async {
+        let (integrity,): (String,) = sqlx::query_as("PRAGMA integrity_check")
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if integrity != "ok" {
+            return Err(format!("Copied database failed integrity check: {}", integrity));
+        }
+
+        let objects = count_schema_objects(&pool).await?;
+        if objects != expected_objects {
+            return Err(format!(
+                "Copied database schema mismatch: expected {} objects, found {}",
+                expected_objects, objects
+            ));
+        }
+        Ok(())
+    }
+    .await;
+
+    pool.close().await;
+    result
+}
+
+/// Delete a database file along with its WAL and shared-memory files
+pub(super) fn remove_database_files(db_path: &Path) -> Result<(), String> {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = db_path.as_os_str().to_owned();
+        path.push(suffix);
+        let path = PathBuf::from(path);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::{Connection, SqliteConnection};
+
+    #[tokio::test]
+    async fn test_reporting_snapshot_reads_past_writes() {
+        let dir = std::env::temp_dir().join(format!("journal-todo-snapshot-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("journal.db");
+        let options = SqliteConnectOptions::new().filename(&path).create_if_missing(true);
+        let mut writer = SqliteConnection::connect_with(&options).await.unwrap();
+        for statement in ["PRAGMA journal_mode = WAL", "CREATE TABLE todos (id TEXT)", "INSERT INTO todos VALUES ('a')"] {
+            sqlx::query(statement).execute(&mut writer).await.unwrap();
+        }
+
+        let reporting = connect_read_only(&path).await.unwrap();
+        let mut snapshot = reporting.begin().await.unwrap();
+        let count = |rows: Vec<(String,)>| rows.len();
+        let before = sqlx::query_as("SELECT id FROM todos").fetch_all(&mut *snapshot).await.unwrap();
+        // The writer isn't blocked by the open read transaction, and the snapshot doesn't see it
+        sqlx::query("INSERT INTO todos VALUES ('b')").execute(&mut writer).await.unwrap();
+        let after = sqlx::query_as("SELECT id FROM todos").fetch_all(&mut *snapshot).await.unwrap();
+        assert_eq!((count(before), count(after)), (1, 1));
+        assert!(sqlx::query("INSERT INTO todos VALUES ('c')").execute(&mut *snapshot).await.is_err());
+
+        drop(snapshot);
+        reporting.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_bundled_migrations_set_up_a_new_database() {
+        let dir = std::env::temp_dir().join(format!("journal-todo-migrate-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let migrations_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let pool = open_pool(&dir.join("journal.db"), &migrations_dir, &Arc::new(QueryCache::default()))
+            .await
+            .unwrap();
+
+        let objects: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM sqlite_master WHERE name IN ('settings', 'attachments_fts', 'locked_pages_keep_notes_encrypted',
+                'pages_notes_written', 'todos_content_hash_updated', 'time_blocks_page_idx')",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(objects.len(), 6);
+        assert!(Migration::new(pool.clone(), migrations_dir).pending().await.unwrap().is_empty());
+
+        pool.close().await;
+        let _ = std::fs::remove_dir_all(dir);
     }
 }
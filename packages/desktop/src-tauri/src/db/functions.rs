@@ -0,0 +1,357 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, Utc};
+use libsqlite3_sys::{
+    sqlite3_context, sqlite3_create_function_v2, sqlite3_get_auxdata, sqlite3_result_error,
+    sqlite3_result_int, sqlite3_result_null, sqlite3_result_text, sqlite3_set_auxdata, sqlite3_value,
+    sqlite3_value_blob, sqlite3_value_bytes, sqlite3_value_int64, sqlite3_value_text, sqlite3_value_type,
+    SQLITE_DETERMINISTIC, SQLITE_INTEGER, SQLITE_NULL, SQLITE_OK, SQLITE_TRANSIENT, SQLITE_UTF8,
+};
+use regex::Regex;
+use sqlx::sqlite::SqliteConnection;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most ids `generate_id` hands out per call
+const MAX_GENERATED_IDS: usize = 10_000;
+
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const ULID_RANDOM_BITS: u32 = 80;
+
+/// Last ULID handed out, so ids made in the same millisecond still sort in order
+static LAST_ULID: Mutex<u128> = Mutex::new(0);
+
+/// Kinds of time-sortable ids the backend generates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    UuidV7,
+    Ulid,
+}
+
+impl IdKind {
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "uuid_v7" | "uuidv7" => Ok(Self::UuidV7),
+            "ulid" => Ok(Self::Ulid),
+            _ => Err(format!("Unknown id kind: {}", kind)),
+        }
+    }
+
+    pub fn generate(self) -> String {
+        match self {
+            Self::UuidV7 => uuid::Uuid::now_v7().to_string(),
+            Self::Ulid => ulid(),
+        }
+    }
+}
+
+/// A ULID: 48 bits of milliseconds then 80 random bits, in Crockford base32.
+/// Within one millisecond each id is the previous one plus one.
+fn ulid() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+        & ((1 << 48) - 1);
+    let random = rand::random::<u128>() & ((1 << ULID_RANDOM_BITS) - 1);
+    let mut value = (millis << ULID_RANDOM_BITS) | random;
+
+    if let Ok(mut last) = LAST_ULID.lock() {
+        if value >> ULID_RANDOM_BITS <= *last >> ULID_RANDOM_BITS {
+            value = *last + 1;
+        }
+        *last = value;
+    }
+    encode_ulid(value)
+}
+
+fn encode_ulid(value: u128) -> String {
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 31) as usize] as char)
+        .collect()
+}
+
+/// Time zone argument of the date functions: "local" (the system zone),
+/// "UTC", or a fixed offset such as "+08:00"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Zone {
+    Local,
+    Fixed(FixedOffset),
+}
+
+pub(crate) fn parse_zone(tz: &str) -> Result<Zone, String> {
+    match tz {
+        "local" => return Ok(Zone::Local),
+        "UTC" | "utc" | "Z" => return Ok(Zone::Fixed(FixedOffset::east_opt(0).expect("zero offset is valid"))),
+        _ => {}
+    }
+    let sign = match tz.as_bytes().first() {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return Err(format!("Unsupported time zone {:?}; use \"local\", \"UTC\" or an offset like +08:00", tz)),
+    };
+    let (hours, minutes) = tz[1..].split_once(':').unwrap_or((&tz[1..], "0"));
+    let (Ok(hours), Ok(minutes)) = (hours.parse::<i32>(), minutes.parse::<i32>()) else {
+        return Err(format!("Invalid time zone offset {:?}", tz));
+    };
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+        .map(Zone::Fixed)
+        .ok_or_else(|| format!("Invalid time zone offset {:?}", tz))
+}
+
+/// Calendar date of a millisecond timestamp in `zone`
+fn local_date(ts: i64, zone: Zone) -> Result<NaiveDate, String> {
+    let utc = DateTime::<Utc>::from_timestamp_millis(ts).ok_or_else(|| format!("Timestamp out of range: {}", ts))?;
+    Ok(match zone {
+        Zone::Local => utc.with_timezone(&Local).date_naive(),
+        Zone::Fixed(offset) => utc.with_timezone(&offset).date_naive(),
+    })
+}
+
+/// Local date of the first day of the week containing `ts`; `first_day` counts from Sunday = 0
+fn start_of_week(ts: i64, zone: Zone, first_day: i64) -> Result<NaiveDate, String> {
+    if !(0..7).contains(&first_day) {
+        return Err(format!("first_day must be 0 (Sunday) to 6, not {}", first_day));
+    }
+    let date = local_date(ts, zone)?;
+    let offset = (date.weekday().num_days_from_sunday() as i64 - first_day).rem_euclid(7);
+    Ok(date - chrono::Duration::days(offset))
+}
+
+unsafe fn result_text(ctx: *mut sqlite3_context, text: &str) {
+    sqlite3_result_text(ctx, text.as_ptr() as *const c_char, text.len() as c_int, SQLITE_TRANSIENT());
+}
+
+unsafe fn result_error(ctx: *mut sqlite3_context, message: &str) {
+    sqlite3_result_error(ctx, message.as_ptr() as *const c_char, message.len() as c_int);
+}
+
+/// Report a date function's outcome; `Ok(None)` is SQL NULL
+unsafe fn result_date(ctx: *mut sqlite3_context, date: Result<Option<NaiveDate>, String>) {
+    match date {
+        Ok(Some(date)) => result_text(ctx, &date.format("%Y-%m-%d").to_string()),
+        Ok(None) => sqlite3_result_null(ctx),
+        Err(e) => result_error(ctx, &e),
+    }
+}
+
+unsafe fn arg(argv: *mut *mut sqlite3_value, index: usize) -> *mut sqlite3_value {
+    *argv.add(index)
+}
+
+/// An INTEGER argument; `None` for NULL
+unsafe fn arg_i64(argv: *mut *mut sqlite3_value, index: usize, name: &str) -> Result<Option<i64>, String> {
+    let value = arg(argv, index);
+    match sqlite3_value_type(value) {
+        SQLITE_NULL => Ok(None),
+        SQLITE_INTEGER => Ok(Some(sqlite3_value_int64(value))),
+        _ => Err(format!("{} must be an integer", name)),
+    }
+}
+
+unsafe fn arg_text(argv: *mut *mut sqlite3_value, index: usize) -> Option<String> {
+    let text = sqlite3_value_text(arg(argv, index));
+    (!text.is_null()).then(|| CStr::from_ptr(text as *const c_char).to_string_lossy().into_owned())
+}
+
+/// `local_date(ts, tz)`: the date a millisecond timestamp falls on in `tz`
+unsafe extern "C" fn local_date_function(ctx: *mut sqlite3_context, _argc: c_int, argv: *mut *mut sqlite3_value) {
+    let date = (|| {
+        let Some(ts) = arg_i64(argv, 0, "ts")? else {
+            return Ok(None);
+        };
+        let zone = parse_zone(&arg_text(argv, 1).unwrap_or_else(|| "local".to_string()))?;
+        local_date(ts, zone).map(Some)
+    })();
+    result_date(ctx, date);
+}
+
+/// `start_of_week(ts, tz[, first_day])`: the date its week starts on, Monday unless `first_day` says otherwise
+unsafe extern "C" fn start_of_week_function(ctx: *mut sqlite3_context, argc: c_int, argv: *mut *mut sqlite3_value) {
+    let date = (|| {
+        let Some(ts) = arg_i64(argv, 0, "ts")? else {
+            return Ok(None);
+        };
+        let zone = parse_zone(&arg_text(argv, 1).unwrap_or_else(|| "local".to_string()))?;
+        let first_day = if argc > 2 { arg_i64(argv, 2, "first_day")?.unwrap_or(1) } else { 1 };
+        start_of_week(ts, zone, first_day).map(Some)
+    })();
+    result_date(ctx, date);
+}
+
+unsafe extern "C" fn uuid_v7_function(ctx: *mut sqlite3_context, _argc: c_int, _argv: *mut *mut sqlite3_value) {
+    result_text(ctx, &IdKind::UuidV7.generate());
+}
+
+unsafe extern "C" fn ulid_function(ctx: *mut sqlite3_context, _argc: c_int, _argv: *mut *mut sqlite3_value) {
+    result_text(ctx, &IdKind::Ulid.generate());
+}
+
+unsafe extern "C" fn drop_regex(regex: *mut c_void) {
+    drop(Box::from_raw(regex as *mut Regex));
+}
+
+/// `X REGEXP Y`, which SQLite calls as `regexp(Y, X)`. The compiled pattern is
+/// kept as auxdata, so a statement compiles it once rather than once per row.
+unsafe extern "C" fn regexp_function(ctx: *mut sqlite3_context, _argc: c_int, argv: *mut *mut sqlite3_value) {
+    let (Some(pattern), Some(text)) = (arg_text(argv, 0), arg_text(argv, 1)) else {
+        sqlite3_result_null(ctx);
+        return;
+    };
+
+    let regex = sqlite3_get_auxdata(ctx, 0) as *const Regex;
+    if !regex.is_null() {
+        sqlite3_result_int(ctx, (*regex).is_match(&text) as c_int);
+        return;
+    }
+
+    let compiled = match Regex::new(&pattern) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            result_error(ctx, &format!("Invalid regular expression: {}", e));
+            return;
+        }
+    };
+    sqlite3_result_int(ctx, compiled.is_match(&text) as c_int);
+    // SQLite owns the pattern from here on and may free it right away
+    sqlite3_set_auxdata(ctx, 0, Box::into_raw(Box::new(compiled)) as *mut c_void, Some(drop_regex));
+}
+
+/// `zstd_decompress(blob)`: compressed journal notes as text, see `compression`
+unsafe extern "C" fn zstd_decompress_function(ctx: *mut sqlite3_context, _argc: c_int, argv: *mut *mut sqlite3_value) {
+    let value = arg(argv, 0);
+    if sqlite3_value_type(value) == SQLITE_NULL {
+        sqlite3_result_null(ctx);
+        return;
+    }
+    let data = sqlite3_value_blob(value) as *const u8;
+    let bytes = if data.is_null() {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts(data, sqlite3_value_bytes(value) as usize)
+    };
+    match crate::compression::decompress(bytes) {
+        Ok(text) => result_text(ctx, &text),
+        Err(e) => result_error(ctx, &e),
+    }
+}
+
+/// `sha256(text)`: hex digest of the text, NULL hashing as empty; see `integrity`
+unsafe extern "C" fn sha256_function(ctx: *mut sqlite3_context, _argc: c_int, argv: *mut *mut sqlite3_value) {
+    result_text(ctx, &crate::integrity::hash(arg_text(argv, 0).as_deref().unwrap_or_default()));
+}
+
+type Function = unsafe extern "C" fn(*mut sqlite3_context, c_int, *mut *mut sqlite3_value);
+
+/// Functions added to every connection, with their argument count and
+/// whether the same arguments always give the same result
+const FUNCTIONS: &[(&str, c_int, bool, Function)] = &[
+    ("uuid_v7", 0, false, uuid_v7_function),
+    ("ulid", 0, false, ulid_function),
+    // "local" follows the system zone
+    ("local_date", 2, false, local_date_function),
+    ("start_of_week", 2, false, start_of_week_function),
+    ("start_of_week", 3, false, start_of_week_function),
+    ("regexp", 2, true, regexp_function),
+    ("zstd_decompress", 1, true, zstd_decompress_function),
+    ("sha256", 1, true, sha256_function),
+];
+
+/// Register the id, date, REGEXP, decompression and hash functions on a freshly opened connection
+pub async fn register(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let mut handle = conn.lock_handle().await?;
+    for &(name, args, deterministic, function) in FUNCTIONS {
+        let c_name = CString::new(name).expect("function names have no NUL");
+        let flags = if deterministic { SQLITE_UTF8 | SQLITE_DETERMINISTIC } else { SQLITE_UTF8 };
+        let code = unsafe {
+            sqlite3_create_function_v2(
+                handle.as_raw_handle().as_ptr(),
+                c_name.as_ptr(),
+                args,
+                flags,
+                std::ptr::null_mut(),
+                Some(function),
+                None,
+                None,
+                None,
+            )
+        };
+        if code != SQLITE_OK {
+            return Err(sqlx::Error::Protocol(format!(
+                "Failed to register SQL function {} (code {})",
+                name, code
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Generate `count` sortable ids of `kind` ("uuid_v7" or "ulid"), in order
+#[crate::metrics::command]
+pub fn generate_id(kind: String, count: Option<usize>) -> Result<Vec<String>, String> {
+    let kind = IdKind::parse(&kind)?;
+    let count = count.unwrap_or(1);
+    if count > MAX_GENERATED_IDS {
+        return Err(format!("At most {} ids can be generated at once", MAX_GENERATED_IDS));
+    }
+    Ok((0..count).map(|_| kind.generate()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Connection;
+
+    #[tokio::test]
+    async fn test_regexp_filters_rows() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        register(&mut conn).await.unwrap();
+        sqlx::query("CREATE TABLE notes (content TEXT); INSERT INTO notes VALUES ('todo 12'), ('note'), (NULL), ('TODO 9')")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT content FROM notes WHERE content REGEXP ? ORDER BY content")
+            .bind(r"(?i)^todo \d+$")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(rows, vec![("TODO 9".to_string(),), ("todo 12".to_string(),)]);
+
+        let invalid = sqlx::query("SELECT 'a' REGEXP '('").execute(&mut conn).await;
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_ids_sort_in_generation_order() {
+        for kind in ["uuid_v7", "ulid"] {
+            let ids = generate_id(kind.to_string(), Some(500)).unwrap();
+            let mut sorted = ids.clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(ids, sorted, "{} ids are not unique and sorted", kind);
+        }
+        assert_eq!(ulid().len(), 26);
+        assert_eq!(encode_ulid(0), "00000000000000000000000000");
+        assert_eq!(encode_ulid(u128::MAX), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+    }
+
+    #[test]
+    fn test_dates_follow_the_given_zone() {
+        // 2024-05-05T22:30:00Z: Sunday evening in UTC, Monday morning in +08:00
+        let ts = 1_714_948_200_000;
+        let utc = parse_zone("UTC").unwrap();
+        let beijing = parse_zone("+08:00").unwrap();
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert_eq!(local_date(ts, utc).unwrap(), date("2024-05-05"));
+        assert_eq!(local_date(ts, beijing).unwrap(), date("2024-05-06"));
+        assert_eq!(start_of_week(ts, utc, 1).unwrap(), date("2024-04-29"));
+        assert_eq!(start_of_week(ts, beijing, 1).unwrap(), date("2024-05-06"));
+        assert_eq!(start_of_week(ts, utc, 0).unwrap(), date("2024-05-05"));
+
+        assert_eq!(parse_zone("-05:30").unwrap(), Zone::Fixed(FixedOffset::west_opt(5 * 3600 + 1800).unwrap()));
+        assert!(parse_zone("Asia/Shanghai").is_err());
+        assert!(start_of_week(ts, utc, 7).is_err());
+    }
+}
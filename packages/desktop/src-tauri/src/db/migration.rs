@@ -1,8 +1,249 @@
+use serde::Deserialize;
+use sqlparser::ast::{ObjectName, Statement};
 use sqlparser::dialect::SQLiteDialect;
 use sqlparser::parser::Parser;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 use std::fs;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+/// drizzle-kit's `meta/_journal.json`
+#[derive(Debug, Deserialize)]
+struct DrizzleJournal {
+    entries: Vec<DrizzleJournalEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DrizzleJournalEntry {
+    idx: u32,
+    tag: String,
+}
+
+/// Why a SQL migration failed to apply
+struct ApplyError {
+    message: String,
+    /// Object a CREATE collided with, from SQLite's "<kind> <name> already exists"
+    existing: Option<(String, String)>,
+}
+
+impl From<String> for ApplyError {
+    fn from(message: String) -> Self {
+        Self { message, existing: None }
+    }
+}
+
+/// Line that makes a migration file run as written, without going through sqlparser
+const NO_PARSE_DIRECTIVE: &str = "-- no-parse";
+/// drizzle-kit's separator between statements
+const STATEMENT_BREAKPOINT: &str = "--> statement-breakpoint";
+
+/// Kind and name of the object from an SQLITE_ERROR "already exists" failure
+fn existing_object(error: &sqlx::Error) -> Option<(String, String)> {
+    let sqlx::Error::Database(db_error) = error else {
+        return None;
+    };
+    if db_error.code().as_deref() != Some("1") {
+        return None;
+    }
+    let (kind, name) = db_error.message().strip_suffix(" already exists")?.split_once(' ')?;
+    if !matches!(kind, "table" | "index" | "view" | "trigger") {
+        return None;
+    }
+    let name = name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'));
+    Some((kind.to_string(), name.to_string()))
+}
+
+/// Split a migration file into statements, skipping drizzle's statement-breakpoint comments
+fn parse_statements(sql: &str) -> Result<Vec<Statement>, String> {
+    let cleaned_sql = sql
+        .lines()
+        .filter(|line| !line.trim().starts_with("-->"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Parser::parse_sql(&SQLiteDialect {}, &cleaned_sql).map_err(|e| e.to_string())
+}
+
+/// Split a migration file on statement breakpoints only. Each chunk may hold
+/// several statements, or a trigger body whose `;` must not be split on.
+fn raw_chunks(sql: &str) -> Vec<String> {
+    sql.split(STATEMENT_BREAKPOINT)
+        .map(|chunk| {
+            chunk
+                .lines()
+                .filter(|line| line.trim() != NO_PARSE_DIRECTIVE)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string()
+        })
+        .filter(|chunk| !chunk.is_empty())
+        .collect()
+}
+
+/// SQL to execute for a migration file: the parsed statements, or the raw
+/// chunks when the file opts out of parsing or sqlparser rejects it
+fn migration_sql(name: &str, sql: &str) -> Vec<String> {
+    if sql.lines().any(|line| line.trim() == NO_PARSE_DIRECTIVE) {
+        return raw_chunks(sql);
+    }
+    match parse_statements(sql) {
+        Ok(statements) => statements.iter().map(|statement| statement.to_string()).collect(),
+        Err(e) => {
+            println!("[migration] Could not parse {} ({}); executing it as written", name, e);
+            raw_chunks(sql)
+        }
+    }
+}
+
+fn unquoted(name: &ObjectName) -> String {
+    name.0
+        .last()
+        .and_then(|part| part.as_ident())
+        .map(|ident| ident.value.clone())
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Kind, name and comparable definition of the object a CREATE statement makes.
+/// `IF NOT EXISTS`, identifier quoting and keyword case don't count as differences.
+fn create_target(statement: &Statement) -> Option<(&'static str, String, String)> {
+    let (kind, name, statement) = match statement {
+        Statement::CreateTable(create) => {
+            let mut create = create.clone();
+            create.if_not_exists = false;
+            ("table", unquoted(&create.name), Statement::CreateTable(create))
+        }
+        Statement::CreateIndex(create) => {
+            let mut create = create.clone();
+            create.if_not_exists = false;
+            ("index", unquoted(create.name.as_ref()?), Statement::CreateIndex(create))
+        }
+        _ => return None,
+    };
+    let definition = statement
+        .to_string()
+        .chars()
+        .filter(|c| !matches!(c, '"' | '`'))
+        .collect::<String>()
+        .to_ascii_lowercase();
+    Some((kind, name, definition))
+}
+
+/// Future returned by a Rust migration function
+pub type MigrationFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+/// A data migration written in Rust, for changes SQL alone can't express.
+/// It runs after the SQL migration whose index equals `version`, inside
+/// the same transaction that records it in `__migration__`.
+pub struct RustMigration {
+    pub version: u32,
+    pub name: &'static str,
+    pub up: for<'a> fn(&'a mut SqliteConnection) -> MigrationFuture<'a>,
+}
+
+impl RustMigration {
+    /// Name recorded in the migration table
+    fn tracking_name(&self) -> String {
+        format!("{:04}_{}.rs", self.version, self.name)
+    }
+}
+
+/// Registered Rust migrations, for data transforms SQL can't express; tables and indexes go
+/// in the SQL files. Add new entries at the end.
+fn rust_migrations() -> Vec<RustMigration> {
+    vec![
+        RustMigration {
+            version: 3,
+            name: "add_content_hashes",
+            up: add_content_hashes,
+        },
+        RustMigration {
+            version: 4,
+            name: "normalize_timestamps",
+            up: normalize_timestamps,
+        },
+    ]
+}
+
+/// Execute statements in order on the migration connection
+async fn execute_statements(conn: &mut SqliteConnection, statements: &[&str]) -> Result<(), String> {
+    for statement in statements {
+        sqlx::query(statement)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Hash every entry and todo as it is now and keep the hashes (the `content_hash` columns
+/// from the 0003 migration) up to date with triggers; `integrity::verify_data_integrity`
+/// checks them. The hashed content is shared with the check, so this isn't a SQL file.
+pub(crate) fn add_content_hashes(conn: &mut SqliteConnection) -> MigrationFuture<'_> {
+    use crate::integrity::{PAGE_CONTENT_SQL, TODO_CONTENT_SQL};
+    let hash_page = format!(
+        "UPDATE pages SET content_hash = sha256({}) WHERE rowid = NEW.rowid;",
+        PAGE_CONTENT_SQL
+    );
+    let hash_todo = format!(
+        "UPDATE todos SET content_hash = sha256({}) WHERE rowid = NEW.rowid;",
+        TODO_CONTENT_SQL
+    );
+    let statements = [
+        format!("UPDATE pages SET content_hash = sha256({})", PAGE_CONTENT_SQL),
+        format!("UPDATE todos SET content_hash = sha256({})", TODO_CONTENT_SQL),
+        format!(
+            "CREATE TRIGGER IF NOT EXISTS pages_content_hash_inserted AFTER INSERT ON pages BEGIN {} END",
+            hash_page
+        ),
+        format!(
+            "CREATE TRIGGER IF NOT EXISTS pages_content_hash_updated AFTER UPDATE OF notes, notes_zstd ON pages
+             BEGIN {} END",
+            hash_page
+        ),
+        format!(
+            "CREATE TRIGGER IF NOT EXISTS todos_content_hash_inserted AFTER INSERT ON todos BEGIN {} END",
+            hash_todo
+        ),
+        format!(
+            "CREATE TRIGGER IF NOT EXISTS todos_content_hash_updated
+             AFTER UPDATE OF workspace_id, page_date, text, status, tags, level, parent_id ON todos
+             BEGIN {} END",
+            hash_todo
+        ),
+    ];
+    Box::pin(async move {
+        let statements: Vec<&str> = statements.iter().map(String::as_str).collect();
+        execute_statements(conn, &statements).await
+    })
+}
+
+/// UTC milliseconds in the backend's timestamp columns, guarded by triggers; see `timestamps`
+fn normalize_timestamps(conn: &mut SqliteConnection) -> MigrationFuture<'_> {
+    Box::pin(async move { crate::timestamps::normalize(conn, false).await.map(|_| ()) })
+}
+
+/// A single pending step: either a SQL file or a Rust migration
+enum MigrationStep {
+    Sql { idx: u32, file: String },
+    Rust(RustMigration),
+}
+
+impl MigrationStep {
+    fn sort_key(&self) -> (u32, u8) {
+        match self {
+            MigrationStep::Sql { idx, .. } => (*idx, 0),
+            MigrationStep::Rust(m) => (m.version, 1),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            MigrationStep::Sql { file, .. } => file.clone(),
+            MigrationStep::Rust(m) => m.tracking_name(),
+        }
+    }
+}
 
 pub struct Migration {
     pool: SqlitePool,
@@ -24,11 +265,29 @@ impl Migration {
         println!("[migration] Running SQL migrations.");
         Self::setup_migration_table(&self.pool).await?;
 
-        let migration_files = self.get_migration_files()?;
         let mut migrations_count = 0;
 
-        for file in migration_files {
-            let file_name = file.clone();
+        for step in self.steps()? {
+            let name = step.name();
+            if self.is_migration_applied(&name).await? {
+                continue;
+            }
+
+            migrations_count += 1;
+            println!("[migration] Applying migration: {}", name);
+
+            let file = match step {
+                MigrationStep::Rust(migration) => {
+                    if let Err(err) = self.apply_rust_migration(&migration).await {
+                        println!("[migration] Migration failed: {}\nError: {}", name, err);
+                        return Err(err);
+                    }
+                    println!("[migration] Migration applied: {}", name);
+                    continue;
+                }
+                MigrationStep::Sql { file, .. } => file,
+            };
+
             let sql = fs::read_to_string(format!(
                 "{}{}{}",
                 self.migrations_dir.to_string_lossy(),
@@ -37,31 +296,31 @@ impl Migration {
             ))
             .map_err(|e| format!("Failed to read migration {}: {}", file, e))?;
 
-            if self.is_migration_applied(&file_name).await? {
-                continue;
-            }
-
-            migrations_count += 1;
-            println!("[migration] Applying migration: {}", file_name);
-            if let Err(err) = self.apply_migration(&file_name, &sql).await {
-                // If tables already exist, treat as applied and continue
-                if err.contains("already exists") {
-                    println!(
-                        "[migration] Migration {} already applied (tables exist). Marking as applied.",
-                        file_name
-                    );
-                    self.mark_migration_applied(&file_name).await?;
-                    continue;
+            if let Err(err) = self.apply_migration(&name, &sql).await {
+                // Only a file whose objects all exist as written counts as applied
+                if let Some((kind, object)) = &err.existing {
+                    match self.matches_schema(&sql).await {
+                        Ok(()) => {
+                            println!(
+                                "[migration] Migration {} already applied ({} {} exists). Marking as applied.",
+                                name, kind, object
+                            );
+                            self.mark_migration_applied(&name).await?;
+                            continue;
+                        }
+                        Err(mismatch) => {
+                            let err = format!("{} (not marking as applied: {})", err.message, mismatch);
+                            println!("[migration] Migration failed: {}\nError: {}", name, err);
+                            return Err(err);
+                        }
+                    }
                 }
 
-                println!(
-                    "[migration] Migration failed: {}\nError: {}",
-                    file_name, err
-                );
-                return Err(err);
+                println!("[migration] Migration failed: {}\nError: {}", name, err.message);
+                return Err(err.message);
             }
 
-            println!("[migration] Migration applied: {}", file_name);
+            println!("[migration] Migration applied: {}", name);
         }
 
         println!(
@@ -72,6 +331,31 @@ impl Migration {
         Ok(())
     }
 
+    /// Names of migrations that haven't been applied yet, in order
+    pub async fn pending(&self) -> Result<Vec<String>, String> {
+        Self::setup_migration_table(&self.pool).await?;
+        let mut pending = Vec::new();
+        for step in self.steps()? {
+            let name = step.name();
+            if !self.is_migration_applied(&name).await? {
+                pending.push(name);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Every SQL and Rust migration, in the order they are applied
+    fn steps(&self) -> Result<Vec<MigrationStep>, String> {
+        let mut steps: Vec<MigrationStep> = self
+            .get_migration_files()?
+            .into_iter()
+            .map(|(idx, file)| MigrationStep::Sql { idx, file })
+            .chain(rust_migrations().into_iter().map(MigrationStep::Rust))
+            .collect();
+        steps.sort_by_key(|step| step.sort_key());
+        Ok(steps)
+    }
+
     /// Create the migration tracking table if it doesn't exist
     pub async fn setup_migration_table(pool: &SqlitePool) -> Result<(), String> {
         sqlx::query(&format!(
@@ -88,8 +372,9 @@ impl Migration {
         Ok(())
     }
 
-    /// Get list of migration files sorted by name
-    fn get_migration_files(&self) -> Result<Vec<String>, String> {
+    /// Get list of migration files with their index, in the order they should be applied.
+    /// Uses drizzle's journal when present, otherwise falls back to sorting by name.
+    fn get_migration_files(&self) -> Result<Vec<(u32, String)>, String> {
         let path = Path::new(&self.migrations_dir);
 
         if !path.exists() {
@@ -113,7 +398,61 @@ impl Migration {
             .collect();
 
         files.sort();
-        Ok(files)
+
+        match self.read_journal()? {
+            Some(journal) => Self::order_by_journal(journal, &files),
+            None => Ok(files
+                .into_iter()
+                .enumerate()
+                .map(|(idx, file)| (idx as u32, file))
+                .collect()),
+        }
+    }
+
+    /// Read `meta/_journal.json` if it exists
+    fn read_journal(&self) -> Result<Option<DrizzleJournal>, String> {
+        let journal_path = self.migrations_dir.join("meta").join("_journal.json");
+        if !journal_path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&journal_path)
+            .map_err(|e| format!("Failed to read migration journal: {}", e))?;
+        let journal: DrizzleJournal = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse migration journal: {}", e))?;
+
+        Ok(Some(journal))
+    }
+
+    /// Order migration files by journal idx and cross-check them against the files on disk
+    fn order_by_journal(
+        mut journal: DrizzleJournal,
+        files: &[String],
+    ) -> Result<Vec<(u32, String)>, String> {
+        journal.entries.sort_by_key(|entry| entry.idx);
+
+        let mut ordered = Vec::with_capacity(journal.entries.len());
+        for entry in &journal.entries {
+            let file_name = format!("{}.sql", entry.tag);
+            if !files.contains(&file_name) {
+                return Err(format!(
+                    "Migration {} is listed in the journal but {} is missing",
+                    entry.tag, file_name
+                ));
+            }
+            ordered.push((entry.idx, file_name));
+        }
+
+        for file in files {
+            if !ordered.iter().any(|(_, name)| name == file) {
+                println!(
+                    "[migration] Skipping {}: not listed in meta/_journal.json",
+                    file
+                );
+            }
+        }
+
+        Ok(ordered)
     }
 
     /// Check if a migration has already been applied
@@ -144,26 +483,46 @@ impl Migration {
         Ok(())
     }
 
-    /// Apply a single migration within a transaction
-    async fn apply_migration(&self, name: &str, sql: &str) -> Result<(), String> {
-        // Parse SQL statements - handle Drizzle's statement-breakpoint comments
-        let cleaned_sql = sql
-            .lines()
-            .filter(|line| !line.trim().starts_with("-->"))
-            .collect::<Vec<_>>()
-            .join("\n");
+    /// Check that every statement of a migration is a CREATE whose object
+    /// already exists with the same definition
+    async fn matches_schema(&self, sql: &str) -> Result<(), String> {
+        for statement in parse_statements(sql)? {
+            let Some((kind, name, definition)) = create_target(&statement) else {
+                return Err(format!("cannot verify non-CREATE statement: {}", statement));
+            };
+            let existing: Option<(Option<String>,)> =
+                sqlx::query_as("SELECT sql FROM sqlite_master WHERE type = ? AND name = ?")
+                    .bind(kind)
+                    .bind(&name)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            let Some((Some(existing),)) = existing else {
+                return Err(format!("{} {} does not exist", kind, name));
+            };
+            let existing = parse_statements(&existing)?;
+            let existing = existing.first().and_then(create_target).map(|(_, _, definition)| definition);
+            if existing.as_deref() != Some(definition.as_str()) {
+                return Err(format!("{} {} exists with a different definition", kind, name));
+            }
+        }
+        Ok(())
+    }
 
-        let dialect = SQLiteDialect {};
-        let statements = Parser::parse_sql(&dialect, &cleaned_sql).map_err(|e| e.to_string())?;
+    /// Apply a single migration within a transaction
+    async fn apply_migration(&self, name: &str, sql: &str) -> Result<(), ApplyError> {
+        let statements = migration_sql(name, sql);
 
         let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
 
-        for statement in statements {
-            let sql_str = statement.to_string();
-            sqlx::query(&sql_str)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| format!("{}: {}", name, e))?;
+        for sql_str in statements {
+            // An unparsed chunk may hold several statements; sqlx's SQLite driver runs them all
+            if let Err(e) = sqlx::query(&sql_str).execute(&mut *tx).await {
+                return Err(ApplyError {
+                    message: format!("{}: {}", name, e),
+                    existing: existing_object(&e),
+                });
+            }
         }
 
         // Record the migration
@@ -180,4 +539,78 @@ impl Migration {
 
         Ok(())
     }
+
+    /// Apply a Rust migration within a transaction
+    async fn apply_rust_migration(&self, migration: &RustMigration) -> Result<(), String> {
+        let name = migration.tracking_name();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        (migration.up)(&mut tx)
+            .await
+            .map_err(|e| format!("{}: {}", name, e))?;
+
+        sqlx::query(&format!(
+            "INSERT INTO {} (name) VALUES (?)",
+            Self::MIGRATION_TABLE_NAME
+        ))
+        .bind(&name)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+/// Run one of the bundled migration files against a connection, for tests of what it sets up
+#[cfg(test)]
+pub(crate) async fn apply_bundled(conn: &mut SqliteConnection, file: &str) -> Result<(), String> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("migrations").join(file);
+    let sql = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    for statement in migration_sql(file, &sql) {
+        sqlx::query(&statement)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("{}: {}", file, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(sql: &str) -> String {
+        create_target(&parse_statements(sql).unwrap()[0]).unwrap().2
+    }
+
+    #[test]
+    fn test_create_target_ignores_quoting_and_if_not_exists() {
+        let migration = "CREATE TABLE `todos` (\n\t`id` text PRIMARY KEY NOT NULL,\n\t`text` text\n);";
+        let stored = r#"CREATE TABLE IF NOT EXISTS "todos" ("id" TEXT PRIMARY KEY NOT NULL, "text" TEXT)"#;
+        assert_eq!(definition(migration), definition(stored));
+
+        let changed = r#"CREATE TABLE "todos" ("id" TEXT PRIMARY KEY NOT NULL, "text" TEXT NOT NULL)"#;
+        assert_ne!(definition(migration), definition(changed));
+    }
+
+    #[test]
+    fn test_unparsable_files_run_as_written() {
+        let trigger = "CREATE TRIGGER todos_touch AFTER UPDATE ON todos BEGIN\n  UPDATE todos SET updated_at = 0 WHERE id = NEW.id;\nEND;";
+        let sql = format!("{}\n{}\nCREATE INDEX `a` ON `todos` (`id`);", trigger, STATEMENT_BREAKPOINT);
+        let chunks = migration_sql("0005_trigger.sql", &sql);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], trigger);
+
+        let opted_out = format!("{}\nCREATE INDEX `a` ON `todos` (`id`);", NO_PARSE_DIRECTIVE);
+        assert_eq!(migration_sql("0006_index.sql", &opted_out), vec!["CREATE INDEX `a` ON `todos` (`id`);"]);
+    }
+
+    #[test]
+    fn test_only_creates_have_a_target() {
+        let statements = parse_statements("ALTER TABLE `todos` ADD `level` integer DEFAULT 0 NOT NULL;").unwrap();
+        assert!(create_target(&statements[0]).is_none());
+    }
 }
@@ -0,0 +1,285 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+use tauri::{AppHandle, Manager};
+
+use super::{changes, DatabaseState};
+use crate::{attachments, dispatch, fractional_index, logger};
+
+const MAX_DAYS: u32 = 3650;
+const MAX_ENTRIES_PER_DAY: u32 = 20;
+const MAX_TODOS_PER_DAY: u32 = 100;
+/// One day in this many gets an attachment
+const ATTACHMENT_EVERY_DAYS: i64 = 5;
+
+const OPENINGS: &[&str] = &[
+    "Woke up early and",
+    "Spent the morning trying to",
+    "After lunch I finally managed to",
+    "Had a long call about how to",
+    "Took a walk to think about how to",
+    "Ended the day wanting to",
+];
+const MIDDLES: &[&str] = &[
+    "untangle the release plan",
+    "write down what matters this quarter",
+    "clean up the garden",
+    "sort through old photos",
+    "figure out the budget for the trip",
+    "read a few chapters of the new book",
+    "fix the bug that kept coming back",
+];
+const CLOSINGS: &[&str] = &[
+    "It went better than expected.",
+    "Still not sure it was worth it.",
+    "Need to come back to this tomorrow.",
+    "Felt good to get it out of my head.",
+    "The weather made everything slower.",
+];
+const TODO_VERBS: &[&str] = &["Call", "Email", "Review", "Plan", "Buy", "Book", "Draft", "Fix", "Read", "Clean"];
+const TODO_OBJECTS: &[&str] = &[
+    "the dentist",
+    "quarterly report",
+    "groceries",
+    "flights for June",
+    "project proposal",
+    "bike brakes",
+    "chapter 4",
+    "the garage",
+    "team retro notes",
+    "birthday present",
+];
+const TAGS: &[&str] = &["work", "home", "health", "errands", "reading", "family", "finance"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MockDataReport {
+    pub pages: u64,
+    pub todos: u64,
+    pub attachments: u64,
+}
+
+/// A file to put in the attachment store once the rows are committed
+struct MockFile {
+    stored_name: String,
+    contents: String,
+}
+
+fn paragraph(rng: &mut impl Rng) -> String {
+    format!(
+        "{} {}. {}",
+        OPENINGS.choose(rng).unwrap(),
+        MIDDLES.choose(rng).unwrap(),
+        CLOSINGS.choose(rng).unwrap()
+    )
+}
+
+/// Todo text with a `#tag` in it, and the tags as stored
+fn todo(rng: &mut impl Rng) -> (String, Vec<String>) {
+    let tag = TAGS.choose(rng).unwrap().to_string();
+    let text = format!("{} {} #{}", TODO_VERBS.choose(rng).unwrap(), TODO_OBJECTS.choose(rng).unwrap(), tag);
+    (text, vec![tag])
+}
+
+/// Fill the `days` up to `today` with notes, todos (some nested, older ones mostly done) and
+/// the odd attachment. Days that already have a page keep their notes and get todos added.
+async fn generate(
+    conn: &mut SqliteConnection,
+    rng: &mut impl Rng,
+    workspace_id: &str,
+    today: NaiveDate,
+    days: u32,
+    entries_per_day: u32,
+    todos_per_day: u32,
+) -> Result<(MockDataReport, Vec<MockFile>), String> {
+    let mut report = MockDataReport::default();
+    let mut files = Vec::new();
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    for age in 0..days as i64 {
+        let date = today - Duration::days(age);
+        let page_date = date.format("%Y-%m-%d").to_string();
+        let start = date.and_hms_opt(8, 0, 0).unwrap_or_default();
+        // Drizzle's pages and todos take seconds, the backend's attachments milliseconds
+        let at = |minutes: i64| {
+            Local.from_local_datetime(&(start + Duration::minutes(minutes))).earliest().unwrap_or_default()
+        };
+
+        let notes = (0..entries_per_day).map(|_| paragraph(rng)).collect::<Vec<_>>().join("\n\n");
+        report.pages += sqlx::query(
+            "INSERT OR IGNORE INTO pages (workspace_id, date, notes, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(workspace_id)
+        .bind(&page_date)
+        .bind(Some(notes).filter(|notes| !notes.is_empty()))
+        .bind(at(0).timestamp())
+        .bind(at(rng.gen_range(0..720)).timestamp())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        let last: Option<(String,)> = sqlx::query_as(
+            "SELECT `order` FROM todos WHERE workspace_id = ? AND page_date = ? ORDER BY `order` DESC LIMIT 1",
+        )
+        .bind(workspace_id)
+        .bind(&page_date)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        let mut order = last.map(|(order,)| order);
+        let mut parent: Option<String> = None;
+        for index in 0..todos_per_day {
+            let (text, tags) = todo(rng);
+            let id = uuid::Uuid::new_v4().to_string();
+            // Every so often a sub-task of the todo above
+            let parent_id = parent.clone().filter(|_| rng.gen_bool(0.25));
+            let done_chance = (0.3 + 0.1 * age as f64).min(0.9);
+            let status = if rng.gen_bool(done_chance) { "done" } else { "todo" };
+            let key = fractional_index::key_between(order.as_deref(), None)?;
+            let created_at = at(index as i64 * 10).timestamp();
+            sqlx::query(
+                "INSERT INTO todos (id, workspace_id, page_date, text, status, tags, `order`, level, parent_id,
+                    created_at, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(workspace_id)
+            .bind(&page_date)
+            .bind(&text)
+            .bind(status)
+            .bind(serde_json::to_string(&tags).map_err(|e| e.to_string())?)
+            .bind(&key)
+            .bind(parent_id.is_some() as i64)
+            .bind(&parent_id)
+            .bind(created_at)
+            .bind(created_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            report.todos += 1;
+            order = Some(key);
+            if parent_id.is_none() {
+                parent = Some(id);
+            }
+        }
+
+        if age % ATTACHMENT_EVERY_DAYS == 0 {
+            let id = uuid::Uuid::new_v4().to_string();
+            let file = MockFile {
+                stored_name: format!("{}.txt", id),
+                contents: format!("Notes from {}\n\n{}\n", page_date, paragraph(rng)),
+            };
+            sqlx::query(&format!(
+                "INSERT INTO attachments ({}) VALUES (?, ?, ?, NULL, ?, ?, 'text/plain', ?, NULL, ?)",
+                attachments::ATTACHMENT_COLUMNS
+            ))
+            .bind(&id)
+            .bind(workspace_id)
+            .bind(&page_date)
+            .bind(format!("Notes {}.txt", page_date))
+            .bind(&file.stored_name)
+            .bind(file.contents.len() as i64)
+            .bind(at(600).timestamp_millis())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            sqlx::query("INSERT INTO attachments_fts (attachment_id, file_name, ocr_text) VALUES (?, ?, NULL)")
+                .bind(&id)
+                .bind(format!("Notes {}.txt", page_date))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            report.attachments += 1;
+            files.push(file);
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok((report, files))
+}
+
+/// Fill the current workspace with fake entries, todos, tags and attachments for the last
+/// `days` days, for performance testing and screenshots. Only available in debug builds.
+#[crate::metrics::command]
+pub async fn generate_mock_data(
+    app: AppHandle,
+    days: u32,
+    entries_per_day: u32,
+    todos_per_day: u32,
+) -> Result<MockDataReport, String> {
+    if !cfg!(debug_assertions) {
+        return Err("generate_mock_data is only available in debug builds".to_string());
+    }
+    if days == 0 || days > MAX_DAYS || entries_per_day > MAX_ENTRIES_PER_DAY || todos_per_day > MAX_TODOS_PER_DAY {
+        return Err(format!(
+            "Use 1 to {} days, at most {} entries and {} todos a day",
+            MAX_DAYS, MAX_ENTRIES_PER_DAY, MAX_TODOS_PER_DAY
+        ));
+    }
+
+    let state = app.state::<DatabaseState>();
+    let dir = attachments::attachments_dir(&state).await;
+    let (report, files) = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        let workspace_id = dispatch::current_workspace_id(&mut conn).await?;
+        let today = Local::now().date_naive();
+        // `ThreadRng` can't be held across the awaits
+        let mut rng = StdRng::from_entropy();
+        generate(&mut conn, &mut rng, &workspace_id, today, days, entries_per_day, todos_per_day).await?
+    };
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    for file in files {
+        std::fs::write(dir.join(&file.stored_name), file.contents).map_err(|e| e.to_string())?;
+    }
+
+    logger::info(&format!(
+        "Generated mock data: {} pages, {} todos, {} attachments",
+        report.pages, report.todos, report.attachments
+    ));
+    changes::notify(&app, ["pages", "todos", "attachments"].map(String::from).to_vec());
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_generate_mock_data() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for statement in [
+            "CREATE TABLE pages (workspace_id TEXT, date TEXT, notes TEXT, created_at INTEGER, updated_at INTEGER,
+                PRIMARY KEY (workspace_id, date))",
+            "CREATE TABLE todos (id TEXT, workspace_id TEXT, page_date TEXT, text TEXT, status TEXT, tags TEXT,
+                `order` TEXT, level INTEGER, parent_id TEXT, created_at INTEGER, updated_at INTEGER)",
+            "CREATE TABLE attachments (id TEXT, workspace_id TEXT, page_date TEXT, todo_id TEXT, file_name TEXT,
+                stored_name TEXT, mime_type TEXT, size INTEGER, ocr_text TEXT, created_at INTEGER)",
+            "CREATE TABLE attachments_fts (attachment_id TEXT, file_name TEXT, ocr_text TEXT)",
+            "INSERT INTO pages VALUES ('w', '2024-03-04', 'Real notes', 0, 0)",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let today = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let (report, files) = generate(&mut conn, &mut rng, "w", today, 10, 2, 3).await.unwrap();
+        assert_eq!((report.pages, report.todos, report.attachments), (9, 30, 2));
+        assert_eq!(files.len(), 2);
+
+        let (notes,): (String,) = sqlx::query_as("SELECT notes FROM pages WHERE date = '2024-03-04'")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(notes, "Real notes");
+        let orders: Vec<(String,)> = sqlx::query_as("SELECT `order` FROM todos WHERE page_date = '2024-03-05'")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        let mut sorted = orders.clone();
+        sorted.sort();
+        assert_eq!(orders, sorted);
+    }
+}
@@ -1,7 +1,18 @@
+pub mod attach;
+pub mod benchmark;
+pub mod changes;
 pub mod database;
+pub mod functions;
 pub mod commands;
 pub mod migration;
+pub mod mock;
+pub mod pragmas;
+pub mod profiler;
+pub mod seed;
+pub mod stats;
+pub mod write_queue;
 
 pub use database::DatabaseState;
-pub use commands::{execute_single_sql, execute_batch_sql};
+pub use commands::{execute_single_sql, execute_batch_sql, dev_reset_database};
 pub use migration::Migration;
+pub use seed::Seed;
@@ -0,0 +1,204 @@
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, Executor};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+use tauri::State;
+
+use super::DatabaseState;
+
+/// Settings applied as PRAGMAs on every new connection, with the PRAGMA they set
+pub const PRAGMA_SETTINGS: &[(&str, &str)] = &[
+    ("db.busy_timeout_ms", "busy_timeout"),
+    ("db.cache_size", "cache_size"),
+    ("db.mmap_size", "mmap_size"),
+    ("db.temp_store", "temp_store"),
+    ("db.synchronous", "synchronous"),
+];
+
+/// Rows written and read by the benchmark workload
+const BENCH_ROWS: i64 = 5_000;
+const BENCH_BATCH: i64 = 100;
+const BENCH_READS: i64 = 2_000;
+const BENCH_SCANS: usize = 20;
+
+/// The PRAGMA statement for a setting value, or None if the value isn't valid for it
+pub fn pragma_statement(pragma: &str, value: &serde_json::Value) -> Option<String> {
+    let value = match pragma {
+        "busy_timeout" | "mmap_size" => value.as_u64()?.to_string(),
+        // Negative values are a size in KiB, positive ones a number of pages
+        "cache_size" => value.as_i64()?.to_string(),
+        "temp_store" => match value.as_str()? {
+            v @ ("default" | "file" | "memory") => v.to_uppercase(),
+            _ => return None,
+        },
+        "synchronous" => match value.as_str()? {
+            v @ ("off" | "normal" | "full" | "extra") => v.to_uppercase(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(format!("PRAGMA {} = {}", pragma, value))
+}
+
+/// Statements for every configured setting in `settings`, skipping invalid values
+fn statements(settings: &HashMap<String, serde_json::Value>) -> Vec<String> {
+    PRAGMA_SETTINGS
+        .iter()
+        .filter_map(|(key, pragma)| pragma_statement(pragma, settings.get(*key)?))
+        .collect()
+}
+
+pub(super) async fn configured(conn: &mut SqliteConnection) -> HashMap<String, serde_json::Value> {
+    let keys: Vec<&str> = PRAGMA_SETTINGS.iter().map(|(key, _)| *key).collect();
+    let placeholders = vec!["?"; keys.len()].join(", ");
+    let sql = format!("SELECT key, value FROM settings WHERE key IN ({})", placeholders);
+    let mut query = sqlx::query_as::<_, (String, String)>(&sql);
+    for key in keys {
+        query = query.bind(key);
+    }
+    // The settings table doesn't exist yet before the first migration
+    query
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, serde_json::from_str(&value).ok()?)))
+        .collect()
+}
+
+/// Apply the PRAGMA settings to a freshly opened connection
+pub async fn apply(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let settings = configured(conn).await;
+    for statement in statements(&settings) {
+        conn.execute(statement.as_str()).await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkResult {
+    pub pragmas: HashMap<String, serde_json::Value>,
+    pub insert_ms: f64,
+    pub read_ms: f64,
+    pub scan_ms: f64,
+    pub total_ms: f64,
+}
+
+pub(super) fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Run the sample workload against a scratch database with the given settings
+async fn run_workload(path: &Path, pragmas: HashMap<String, serde_json::Value>) -> Result<BenchmarkResult, String> {
+    let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| e.to_string())?;
+    for statement in statements(&pragmas) {
+        conn.execute(statement.as_str()).await.map_err(|e| e.to_string())?;
+    }
+    conn.execute(
+        "CREATE TABLE bench (id INTEGER PRIMARY KEY, page_date TEXT NOT NULL, text TEXT NOT NULL);
+         CREATE INDEX bench_date_idx ON bench (page_date);",
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let start = Instant::now();
+    for batch in 0..BENCH_ROWS / BENCH_BATCH {
+        let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+        for i in 0..BENCH_BATCH {
+            let id = batch * BENCH_BATCH + i;
+            sqlx::query("INSERT INTO bench (id, page_date, text) VALUES (?, ?, ?)")
+                .bind(id)
+                .bind(format!("2024-{:02}-{:02}", id % 12 + 1, id % 28 + 1))
+                .bind(format!("Sample todo number {} with some journal text", id))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    let insert_ms = elapsed_ms(start);
+
+    let start = Instant::now();
+    for i in 0..BENCH_READS {
+        sqlx::query("SELECT text FROM bench WHERE id = ?")
+            .bind(i * 7919 % BENCH_ROWS)
+            .fetch_optional(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let read_ms = elapsed_ms(start);
+
+    let start = Instant::now();
+    for i in 0..BENCH_SCANS {
+        sqlx::query("SELECT COUNT(*) FROM bench WHERE text LIKE ?")
+            .bind(format!("%{}%", i))
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let scan_ms = elapsed_ms(start);
+
+    conn.close().await.map_err(|e| e.to_string())?;
+    Ok(BenchmarkResult {
+        pragmas,
+        insert_ms,
+        read_ms,
+        scan_ms,
+        total_ms: insert_ms + read_ms + scan_ms,
+    })
+}
+
+/// Time a sample workload under each set of PRAGMA settings (keyed like the settings store).
+/// Without `configs` it compares SQLite's defaults with the current settings.
+#[crate::metrics::command]
+pub async fn benchmark_pragmas(
+    state: State<'_, DatabaseState>,
+    configs: Option<Vec<HashMap<String, serde_json::Value>>>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    let configs = match configs {
+        Some(configs) => configs,
+        None => {
+            let pool = state.pool.lock().await;
+            let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+            vec![HashMap::new(), configured(&mut conn).await]
+        }
+    };
+
+    let mut results = Vec::with_capacity(configs.len());
+    for pragmas in configs {
+        let path = std::env::temp_dir().join(format!("journal-todo-bench-{}.db", uuid::Uuid::new_v4()));
+        let result = run_workload(&path, pragmas).await;
+        if let Err(e) = super::database::remove_database_files(&path) {
+            crate::logger::error(&format!("Failed to remove benchmark database: {}", e));
+        }
+        results.push(result?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pragma_statement_validates_values() {
+        assert_eq!(
+            pragma_statement("cache_size", &json!(-20000)).as_deref(),
+            Some("PRAGMA cache_size = -20000")
+        );
+        assert_eq!(
+            pragma_statement("synchronous", &json!("normal")).as_deref(),
+            Some("PRAGMA synchronous = NORMAL")
+        );
+        assert_eq!(pragma_statement("synchronous", &json!("normal; DROP TABLE todos")), None);
+        assert_eq!(pragma_statement("mmap_size", &json!(-1)), None);
+        assert_eq!(pragma_statement("journal_mode", &json!("off")), None);
+    }
+}
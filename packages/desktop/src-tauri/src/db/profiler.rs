@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Distinct statements tracked; any further ones are counted together under `OTHER`
+const MAX_STATEMENTS: usize = 500;
+const OTHER: &str = "[other statements]";
+/// Recent durations kept per statement to estimate the p95 from
+const SAMPLES: usize = 256;
+const DEFAULT_LIMIT: usize = 20;
+
+static PROFILE: Mutex<Option<HashMap<String, Stats>>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Stats {
+    count: u64,
+    errors: u64,
+    total_ms: f64,
+    max_ms: f64,
+    rows: u64,
+    recent: VecDeque<f64>,
+}
+
+/// Timings of one normalized statement since launch or the last reset
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryProfile {
+    pub sql: String,
+    pub count: u64,
+    pub errors: u64,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    /// Over the last `SAMPLES` runs
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    /// Rows returned, over all runs
+    pub rows: u64,
+}
+
+/// The statement with its literals replaced by `?`, whitespace collapsed and `IN` lists of
+/// any length written as one placeholder, so Drizzle's queries group by shape
+fn normalize(sql: &str) -> String {
+    let mut normalized = crate::logger::redact_sql(sql).split_whitespace().collect::<Vec<_>>().join(" ");
+    while normalized.contains("?, ?") {
+        normalized = normalized.replace("?, ?", "?");
+    }
+    normalized
+}
+
+fn percentile(samples: &VecDeque<f64>, fraction: f64) -> f64 {
+    let mut sorted: Vec<f64> = samples.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    let index = ((sorted.len() as f64 * fraction).ceil() as usize).saturating_sub(1);
+    sorted.get(index).copied().unwrap_or_default()
+}
+
+/// Count one run of a statement through the SQL proxy; `rows` is `None` when it failed
+pub fn record(sql: &str, elapsed: Duration, rows: Option<usize>) {
+    let sql = normalize(sql);
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    let Ok(mut guard) = PROFILE.lock() else {
+        return;
+    };
+    let profile = guard.get_or_insert_with(HashMap::new);
+    let key = if profile.len() < MAX_STATEMENTS || profile.contains_key(&sql) { sql } else { OTHER.to_string() };
+    let stats = profile.entry(key).or_default();
+    stats.count += 1;
+    stats.total_ms += ms;
+    stats.max_ms = stats.max_ms.max(ms);
+    match rows {
+        Some(rows) => stats.rows += rows as u64,
+        None => stats.errors += 1,
+    }
+    if stats.recent.len() == SAMPLES {
+        stats.recent.pop_front();
+    }
+    stats.recent.push_back(ms);
+}
+
+/// The statements taking the most time in total, `limit` of them (20 by default)
+#[crate::metrics::command]
+pub fn get_query_profile(limit: Option<usize>) -> Vec<QueryProfile> {
+    let mut profile: Vec<QueryProfile> = PROFILE
+        .lock()
+        .ok()
+        .and_then(|guard| {
+            guard.as_ref().map(|profile| {
+                profile
+                    .iter()
+                    .map(|(sql, stats)| QueryProfile {
+                        sql: sql.clone(),
+                        count: stats.count,
+                        errors: stats.errors,
+                        total_ms: stats.total_ms,
+                        mean_ms: stats.total_ms / stats.count as f64,
+                        p95_ms: percentile(&stats.recent, 0.95),
+                        max_ms: stats.max_ms,
+                        rows: stats.rows,
+                    })
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+    profile.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    profile.truncate(limit.unwrap_or(DEFAULT_LIMIT));
+    profile
+}
+
+/// Start profiling from scratch
+#[crate::metrics::command]
+pub fn reset_query_profile() {
+    if let Ok(mut guard) = PROFILE.lock() {
+        *guard = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_groups_by_shape() {
+        assert_eq!(
+            normalize("select * from todos\n  where id in (?, ?, ?) and status = 'done'"),
+            normalize("select * from todos where id in (?) and status = 'todo'")
+        );
+        assert_eq!(normalize("SELECT 1 FROM pages LIMIT 10"), "SELECT ? FROM pages LIMIT ?");
+    }
+
+    #[test]
+    fn test_profile_aggregates_per_statement() {
+        let sql = "SELECT text FROM profiler_test WHERE id = ?";
+        for ms in 1..=20 {
+            record(sql, Duration::from_millis(ms), Some(2));
+        }
+        record(sql, Duration::from_millis(5), None);
+
+        let profile = get_query_profile(Some(MAX_STATEMENTS));
+        let stats = profile.iter().find(|p| p.sql == normalize(sql)).unwrap();
+        assert_eq!((stats.count, stats.errors, stats.rows), (21, 1, 40));
+        assert_eq!(stats.max_ms.round(), 20.0);
+        assert_eq!(stats.p95_ms.round(), 19.0);
+    }
+}
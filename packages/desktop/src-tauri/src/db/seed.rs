@@ -0,0 +1,140 @@
+use chrono::{Local, Utc};
+use sqlx::SqlitePool;
+
+/// Sample todos inserted on first run: (text, status, tags)
+const SAMPLE_TODOS: &[(&str, &str, &[&str])] = &[
+    ("Welcome to Journal Todo #getting-started", "done", &["getting-started"]),
+    ("Press Enter to add a new todo #getting-started", "todo", &["getting-started"]),
+    ("Press Tab to nest a todo under the one above #tips", "todo", &["tips"]),
+    ("Type #tags anywhere in a todo to organize it #tips", "todo", &["tips", "tags"]),
+];
+
+const WELCOME_NOTES: &str = "Welcome! Each day gets its own page. \
+Jot down notes here and keep track of what you want to get done today.";
+
+pub struct Seed {
+    pool: SqlitePool,
+}
+
+impl Seed {
+    pub const SEED_TABLE_NAME: &'static str = "__seed__";
+    const INITIAL_SEED: &'static str = "initial";
+
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert first-run data when the database is brand new
+    pub async fn run(&self) -> Result<(), String> {
+        self.setup_seed_table().await?;
+
+        if self.is_seed_applied(Self::INITIAL_SEED).await? {
+            return Ok(());
+        }
+
+        let (workspace_count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM workspaces")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        if workspace_count == 0 {
+            println!("[seed] New database detected. Inserting first-run data.");
+
+            // Seconds, as Drizzle's `mode: "timestamp"` columns hold them
+            let now = Utc::now().timestamp();
+            let today = Local::now().format("%Y-%m-%d").to_string();
+            let workspace_id = uuid::Uuid::new_v4().to_string();
+
+            sqlx::query(
+                "INSERT INTO workspaces (id, name, current_date_key, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&workspace_id)
+            .bind("Personal")
+            .bind(&today)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            sqlx::query(
+                "INSERT INTO pages (workspace_id, date, notes, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&workspace_id)
+            .bind(&today)
+            .bind(WELCOME_NOTES)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            for (i, (text, status, tags)) in SAMPLE_TODOS.iter().enumerate() {
+                let tags_json = serde_json::to_string(tags).map_err(|e| e.to_string())?;
+                sqlx::query(
+                    "INSERT INTO todos (id, workspace_id, page_date, text, status, tags, `order`, level, parent_id, created_at, updated_at) \
+                     VALUES (?, ?, ?, ?, ?, ?, ?, 0, NULL, ?, ?)",
+                )
+                .bind(uuid::Uuid::new_v4().to_string())
+                .bind(&workspace_id)
+                .bind(&today)
+                .bind(text)
+                .bind(status)
+                .bind(tags_json)
+                .bind(format!("a{}", i))
+                .bind(now)
+                .bind(now)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+        } else {
+            println!("[seed] Existing data found. Skipping first-run data.");
+        }
+
+        sqlx::query(&format!(
+            "INSERT INTO {} (name) VALUES (?)",
+            Self::SEED_TABLE_NAME
+        ))
+        .bind(Self::INITIAL_SEED)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Create the seed tracking table if it doesn't exist
+    async fn setup_seed_table(&self) -> Result<(), String> {
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );",
+            Self::SEED_TABLE_NAME
+        ))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Check if a seed has already been applied
+    async fn is_seed_applied(&self, name: &str) -> Result<bool, String> {
+        let res: Option<(i64,)> = sqlx::query_as(&format!(
+            "SELECT id FROM {} WHERE name = ? LIMIT 1;",
+            Self::SEED_TABLE_NAME
+        ))
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(res.is_some())
+    }
+}
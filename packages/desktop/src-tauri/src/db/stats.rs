@@ -0,0 +1,73 @@
+use serde::Serialize;
+use sqlx::SqliteConnection;
+use tauri::State;
+
+use super::DatabaseState;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableStats {
+    pub name: String,
+    pub rows: i64,
+}
+
+/// Size and shape of the open database
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStats {
+    pub path: String,
+    pub file_size: u64,
+    pub wal_size: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    pub tables: Vec<TableStats>,
+}
+
+async fn pragma(conn: &mut SqliteConnection, name: &str) -> Result<i64, String> {
+    let (value,): (i64,) = sqlx::query_as(&format!("PRAGMA {}", name))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(value)
+}
+
+pub async fn collect(state: &DatabaseState) -> Result<DbStats, String> {
+    let path = state.db_path.lock().await.clone();
+    // Counting every table reads the whole database, so it runs on a snapshot
+    let mut snapshot = state.open_reporting_snapshot().await?;
+
+    let names: Vec<(String,)> = sqlx::query_as(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )
+    .fetch_all(&mut *snapshot)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut tables = Vec::with_capacity(names.len());
+    for (name,) in names {
+        let (rows,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")))
+            .fetch_one(&mut *snapshot)
+            .await
+            .map_err(|e| e.to_string())?;
+        tables.push(TableStats { name, rows });
+    }
+
+    let mut wal_path = path.as_os_str().to_owned();
+    wal_path.push("-wal");
+    Ok(DbStats {
+        path: path.to_string_lossy().to_string(),
+        file_size: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+        wal_size: std::fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0),
+        page_size: pragma(&mut snapshot, "page_size").await?,
+        page_count: pragma(&mut snapshot, "page_count").await?,
+        freelist_count: pragma(&mut snapshot, "freelist_count").await?,
+        tables,
+    })
+}
+
+#[crate::metrics::command]
+pub async fn get_db_stats(state: State<'_, DatabaseState>) -> Result<DbStats, String> {
+    collect(&state).await
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use sqlparser::dialect::SQLiteDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Instant;
+
+use super::commands::{execute_sql_internal, SqlRequest};
+use super::{changes, DatabaseState};
+use crate::{logger, settings};
+
+/// Debounce window in milliseconds for queued writes
+const WINDOW_SETTING: &str = "db.write_coalesce_ms";
+const DEFAULT_WINDOW_MS: u64 = 500;
+/// Queued writes are flushed at the latest this many windows after the first one
+const MAX_DELAY_WINDOWS: u32 = 10;
+
+/// Emitted when queued writes could not be applied
+pub const WRITE_FAILED_EVENT: &str = "db://write-failed";
+
+#[derive(Debug, Clone, Serialize)]
+struct WriteFailed {
+    sql: String,
+    error: String,
+}
+
+#[derive(Default)]
+struct Pending {
+    requests: Vec<SqlRequest>,
+    first_at: Option<Instant>,
+    last_at: Option<Instant>,
+}
+
+/// Buffers autosave writes so rapid UPDATEs to the same row hit the disk once
+#[derive(Default)]
+pub struct WriteQueue {
+    pending: Mutex<Pending>,
+    wakeup: Notify,
+}
+
+/// Rows an UPDATE targets: its SQL plus the parameters bound after WHERE.
+/// Two UPDATEs with the same key only differ in the values they SET.
+fn coalesce_key(request: &SqlRequest) -> Option<String> {
+    if request.method != "run" {
+        return None;
+    }
+    let tokens = Tokenizer::new(&SQLiteDialect {}, &request.sql).tokenize().ok()?;
+    let mut words = tokens.iter().filter_map(|t| match t {
+        Token::Word(w) => Some(w.keyword),
+        _ => None,
+    });
+    if words.next() != Some(Keyword::UPDATE) {
+        return None;
+    }
+
+    let mut set_params = 0;
+    let mut depth = 0;
+    for token in &tokens {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::Placeholder(_) => set_params += 1,
+            Token::Word(w) if w.keyword == Keyword::WHERE && depth == 0 => {
+                let where_params = request.params.get(set_params..)?;
+                return Some(format!(
+                    "{}\0{}",
+                    request.sql,
+                    serde_json::to_string(where_params).ok()?
+                ));
+            }
+            // RETURNING and friends would make the result observable
+            Token::Word(w) if w.keyword == Keyword::RETURNING => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Queue a write, replacing the previous one when both update the same row back to back
+fn push(requests: &mut Vec<SqlRequest>, request: SqlRequest) {
+    if let (Some(last), Some(key)) = (requests.last_mut(), coalesce_key(&request)) {
+        if coalesce_key(last).as_deref() == Some(key.as_str()) {
+            *last = request;
+            return;
+        }
+    }
+    requests.push(request);
+}
+
+impl WriteQueue {
+    async fn enqueue(&self, request: SqlRequest) {
+        let mut pending = self.pending.lock().await;
+        let now = Instant::now();
+        pending.first_at.get_or_insert(now);
+        pending.last_at = Some(now);
+        push(&mut pending.requests, request);
+        drop(pending);
+        self.wakeup.notify_one();
+    }
+
+    /// Apply every queued write in order
+    pub async fn flush(&self, app: &AppHandle) -> Result<(), String> {
+        let failure = {
+            let mut pending = self.pending.lock().await;
+            // Keep the pending lock so writes queued meanwhile wait for this flush
+            let requests = std::mem::take(&mut *pending).requests;
+            if requests.is_empty() {
+                return Ok(());
+            }
+
+            let state = app.state::<DatabaseState>();
+            let pool = state.pool.lock().await;
+            let mut tables = Vec::new();
+            let mut failure = None;
+            for request in requests {
+                for table in changes::written_tables(&request.sql) {
+                    if !tables.contains(&table) {
+                        tables.push(table);
+                    }
+                }
+                let sql = request.sql.clone();
+                let params = request.params.clone();
+                if let Err(error) = execute_sql_internal(&pool, request).await {
+                    logger::error(&format!(
+                        "Queued write failed: {} ({})",
+                        logger::redact_params(&error, &params),
+                        logger::redact_sql(&sql)
+                    ));
+                    let _ = app.emit(WRITE_FAILED_EVENT, WriteFailed { sql, error: error.clone() });
+                    failure.get_or_insert(error);
+                }
+            }
+            drop(pool);
+            changes::notify(app, tables);
+            failure
+        };
+        failure.map_or(Ok(()), Err)
+    }
+}
+
+/// Flush queued writes once the debounce window has passed
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let queue = app.state::<WriteQueue>();
+        loop {
+            queue.wakeup.notified().await;
+
+            let window = {
+                let state = app.state::<DatabaseState>();
+                let pool = state.pool.lock().await;
+                Duration::from_millis(settings::get_or(&pool, WINDOW_SETTING, DEFAULT_WINDOW_MS).await)
+            };
+            loop {
+                let deadline = {
+                    let pending = queue.pending.lock().await;
+                    match (pending.first_at, pending.last_at) {
+                        (Some(first), Some(last)) => (last + window).min(first + window * MAX_DELAY_WINDOWS),
+                        // Already flushed by someone else
+                        _ => break,
+                    }
+                };
+                if Instant::now() >= deadline {
+                    break;
+                }
+                tokio::time::sleep_until(deadline).await;
+            }
+
+            if let Err(e) = queue.flush(&app).await {
+                logger::error(&format!("Write queue flush failed: {}", e));
+            }
+        }
+    })
+}
+
+/// Queue a write to be applied after the debounce window
+#[crate::metrics::command]
+pub async fn queue_sql(queue: State<'_, WriteQueue>, request: SqlRequest) -> Result<(), String> {
+    if request.method != "run" {
+        return Err("Only writes can be queued".to_string());
+    }
+    queue.enqueue(request).await;
+    Ok(())
+}
+
+/// Apply queued writes now, e.g. when the editor loses focus
+#[crate::metrics::command]
+pub async fn flush_write_queue(app: AppHandle, queue: State<'_, WriteQueue>) -> Result<(), String> {
+    queue.flush(&app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(notes: &str, date: &str) -> SqlRequest {
+        SqlRequest {
+            sql: r#"update "pages" set "notes" = ?, "updated_at" = ? where ("pages"."workspace_id" = ? and "pages"."date" = ?)"#
+                .to_string(),
+            params: vec![notes.into(), 1.into(), "ws".into(), date.into()],
+            method: "run".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_consecutive_updates_to_same_row_coalesce() {
+        let mut requests = Vec::new();
+        push(&mut requests, update("a", "2024-05-01"));
+        push(&mut requests, update("ab", "2024-05-01"));
+        push(&mut requests, update("x", "2024-05-02"));
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].params[0], "ab");
+
+        // An update to another row in between keeps both in order
+        push(&mut requests, update("abc", "2024-05-01"));
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].params[0], "abc");
+    }
+
+    #[test]
+    fn test_only_plain_updates_have_a_key() {
+        let insert = SqlRequest {
+            sql: "insert into todos (id) values (?)".to_string(),
+            params: vec!["1".into()],
+            method: "run".to_string(),
+        };
+        assert!(coalesce_key(&insert).is_none());
+
+        let mut whole_table = update("a", "2024-05-01");
+        whole_table.sql = r#"update "pages" set "notes" = ?"#.to_string();
+        assert!(coalesce_key(&whole_table).is_none());
+    }
+}
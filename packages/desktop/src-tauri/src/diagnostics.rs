@@ -0,0 +1,150 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::io::Write;
+use tauri::{AppHandle, Manager, State};
+use zip::write::SimpleFileOptions;
+
+use crate::db::{stats, DatabaseState, Migration};
+use crate::{logger, settings};
+
+/// Query parameters whose values are replaced in the bundled log
+const SECRET_PARAMS: &[&str] = &["token=", "key=", "password=", "secret="];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SystemInfo {
+    app_version: String,
+    tauri_version: &'static str,
+    os: &'static str,
+    os_family: &'static str,
+    arch: &'static str,
+    created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MigrationInfo {
+    pending: Vec<String>,
+    error: Option<String>,
+}
+
+/// Strip the home directory, e-mail addresses and credentials from a log
+fn scrub(text: &str, home: Option<&str>) -> String {
+    let text = match home {
+        Some(home) if home.len() > 1 => text.replace(home, "~"),
+        _ => text.to_string(),
+    };
+
+    text.lines()
+        .map(|line| {
+            let mut redact_next = false;
+            line.split(' ')
+                .map(|word| {
+                    if std::mem::take(&mut redact_next) {
+                        return "[redacted]".to_string();
+                    }
+                    if word.eq_ignore_ascii_case("bearer") {
+                        redact_next = true;
+                        return word.to_string();
+                    }
+                    let lower = word.to_ascii_lowercase();
+                    if let Some(param) = SECRET_PARAMS.iter().find_map(|p| lower.find(p).map(|i| i + p.len())) {
+                        return format!("{}[redacted]", &word[..param]);
+                    }
+                    match word.split_once('@') {
+                        Some((user, domain)) if !user.is_empty() && domain.contains('.') => "[email]".to_string(),
+                        _ => word.to_string(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| e.to_string())
+}
+
+/// Zip the scrubbed log, database stats, non-secret settings, system info
+/// and migration status into one file for bug reports
+#[crate::metrics::command]
+pub async fn create_support_bundle(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    path: String,
+) -> Result<String, String> {
+    let home = app.path().home_dir().ok().map(|p| p.to_string_lossy().to_string());
+    let log = match logger::get_log_path() {
+        Some(log_path) => std::fs::read_to_string(&log_path)
+            .map(|content| scrub(&content, home.as_deref()))
+            .unwrap_or_else(|e| format!("Failed to read log: {}", e)),
+        None => "No log file".to_string(),
+    };
+
+    let db_stats = stats::collect(&state).await?;
+    let (settings, migrations) = {
+        let pool = state.pool.lock().await;
+        let settings: std::collections::BTreeMap<_, _> = settings::get_all(&pool)
+            .await?
+            .into_iter()
+            .filter(|(key, _)| !settings::is_secret_key(key))
+            .collect();
+        let migrations = match Migration::new(pool.clone(), state.migrations_dir.clone()).pending().await {
+            Ok(pending) => MigrationInfo { pending, error: None },
+            Err(e) => MigrationInfo {
+                pending: Vec::new(),
+                error: Some(e),
+            },
+        };
+        (settings, migrations)
+    };
+    let system = SystemInfo {
+        app_version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION,
+        os: std::env::consts::OS,
+        os_family: std::env::consts::FAMILY,
+        arch: std::env::consts::ARCH,
+        created_at: Utc::now().to_rfc3339(),
+    };
+
+    let entries: [(&str, Vec<u8>); 5] = [
+        ("journal-todo.log", log.into_bytes()),
+        ("db-stats.json", to_json(&db_stats)?),
+        ("settings.json", to_json(&settings)?),
+        ("system.json", to_json(&system)?),
+        ("migrations.json", to_json(&migrations)?),
+    ];
+
+    let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, content) in entries {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(&content).map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+
+    logger::info(&format!("Support bundle written to {}", path));
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub() {
+        let log = "INFO: Database path: /home/ada/.journal-todo/journal.db\n\
+                   ERROR: sync failed for ada@example.com with Bearer abc123\n\
+                   INFO: GET /clip?token=s3cret&x=1 took 2ms";
+        let scrubbed = scrub(log, Some("/home/ada"));
+        assert_eq!(
+            scrubbed,
+            "INFO: Database path: ~/.journal-todo/journal.db\n\
+             ERROR: sync failed for [email] with Bearer [redacted]\n\
+             INFO: GET /clip?token=[redacted] took 2ms"
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::fractional_index;
+
+/// Something an external entry point (deep link, local HTTP API, ...) asks the app to do
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Action {
+    /// Add a todo to a day's page (today when `date` is omitted)
+    #[serde(rename_all = "camelCase")]
+    CreateTodo { text: String, date: Option<String> },
+    /// Append text to a day's notes (today when `date` is omitted)
+    #[serde(rename_all = "camelCase")]
+    AppendNote { text: String, date: Option<String> },
+    /// Bring the app up on a day's journal entry (today when `date` is omitted)
+    #[serde(rename_all = "camelCase")]
+    OpenEntry { date: Option<String> },
+    /// Bring the app up with a new todo started on a day's page (today when `date` is omitted)
+    #[serde(rename_all = "camelCase")]
+    NewTodo { date: Option<String> },
+    /// Mark a todo done
+    #[serde(rename_all = "camelCase")]
+    CompleteTodo { id: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ActionResult {
+    #[serde(rename_all = "camelCase")]
+    TodoCreated { todo_id: String, date: String },
+    #[serde(rename_all = "camelCase")]
+    NoteAppended { date: String },
+    #[serde(rename_all = "camelCase")]
+    TodoCompleted { todo_id: String, date: String },
+    #[serde(rename_all = "camelCase")]
+    Navigated { view: String, date: String },
+}
+
+/// Emitted to the main window to show a view: "entry" or "newTodo"
+pub const NAVIGATE_EVENT: &str = "app://navigate";
+
+const VIEW_ENTRY: &str = "entry";
+const VIEW_NEW_TODO: &str = "newTodo";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Navigate {
+    view: String,
+    date: String,
+}
+
+/// A page clipped from the browser
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clip {
+    pub title: Option<String>,
+    pub url: String,
+    pub selection: Option<String>,
+    /// "todo" or "note" (default)
+    pub kind: Option<String>,
+}
+
+impl Clip {
+    /// Turn the clip into a todo or a note containing the link
+    pub fn into_action(self) -> Action {
+        let title = self
+            .title
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| self.url.clone());
+
+        if self.kind.as_deref() == Some("todo") {
+            return Action::CreateTodo {
+                text: format!("{} {}", title, self.url),
+                date: None,
+            };
+        }
+
+        let mut text = format!("[{}]({})", title, self.url);
+        if let Some(selection) = self.selection.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()) {
+            for line in selection.lines() {
+                text.push_str("\n> ");
+                text.push_str(line);
+            }
+        }
+        Action::AppendNote { text, date: None }
+    }
+}
+
+/// Text or a link shared to the app through a `share` deep link. There's no Android or iOS
+/// project in this tree yet, so nothing forwards the native share sheet here; a mobile shell
+/// would open `share?text=&url=&subject=&kind=` with what it was given
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Share {
+    pub text: Option<String>,
+    pub url: Option<String>,
+    /// The shared item's subject or title, when the sending app gives one
+    pub subject: Option<String>,
+    /// "todo" or "note" (default)
+    pub kind: Option<String>,
+}
+
+impl Share {
+    /// A shared link becomes a clip; apps often send it inside the text, so it's looked for there too
+    pub fn into_action(self) -> Result<Action, String> {
+        let text = self.text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+        let url = self.url.filter(|u| !u.trim().is_empty()).or_else(|| {
+            text.as_deref()?
+                .split_whitespace()
+                .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+                .map(str::to_string)
+        });
+        if let Some(url) = url {
+            let rest = text
+                .map(|t| t.replace(&url, "").trim().to_string())
+                .filter(|t| !t.is_empty());
+            let (title, selection) = match self.subject {
+                Some(subject) => (Some(subject), rest),
+                None => (rest, None),
+            };
+            return Ok(Clip {
+                title,
+                url,
+                selection,
+                kind: self.kind,
+            }
+            .into_action());
+        }
+
+        let text = text.ok_or("Nothing was shared")?;
+        let text = match self.subject.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()) {
+            Some(subject) if !text.starts_with(&subject) => format!("{}\n{}", subject, text),
+            _ => text,
+        };
+        if self.kind.as_deref() == Some("todo") {
+            Ok(Action::CreateTodo { text, date: None })
+        } else {
+            Ok(Action::AppendNote { text, date: None })
+        }
+    }
+}
+
+/// Same tag rules as the frontend: `#tag` words, lowercased and de-duplicated
+pub fn extract_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in text.split(|c: char| c.is_whitespace()) {
+        for tag in word.split('#').skip(1) {
+            let tag = tag.to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// The workspace new content goes into: the most recently used one
+pub async fn current_workspace_id(conn: &mut SqliteConnection) -> Result<String, String> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT id FROM workspaces ORDER BY updated_at DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    row.map(|(id,)| id)
+        .ok_or_else(|| "No workspace exists yet".to_string())
+}
+
+/// Create the page for `date` if it doesn't exist
+pub async fn ensure_page(conn: &mut SqliteConnection, workspace_id: &str, date: &str) -> Result<(), String> {
+    let now = Utc::now().timestamp();
+    sqlx::query(
+        "INSERT OR IGNORE INTO pages (workspace_id, date, notes, created_at, updated_at) VALUES (?, ?, NULL, ?, ?)",
+    )
+    .bind(workspace_id)
+    .bind(date)
+    .bind(now)
+    .bind(now)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Insert a top-level todo at the end of a page
+pub async fn insert_todo(
+    conn: &mut SqliteConnection,
+    workspace_id: &str,
+    date: &str,
+    text: &str,
+) -> Result<String, String> {
+    ensure_page(conn, workspace_id, date).await?;
+
+    let last: Option<(String,)> = sqlx::query_as(
+        "SELECT `order` FROM todos WHERE workspace_id = ? AND page_date = ? ORDER BY `order` DESC LIMIT 1",
+    )
+    .bind(workspace_id)
+    .bind(date)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    let order = fractional_index::key_between(last.as_ref().map(|(o,)| o.as_str()), None)?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let tags = serde_json::to_string(&extract_tags(text)).map_err(|e| e.to_string())?;
+    let now = Utc::now().timestamp();
+    sqlx::query(
+        "INSERT INTO todos (id, workspace_id, page_date, text, status, tags, `order`, level, parent_id, created_at, updated_at) \
+         VALUES (?, ?, ?, ?, 'todo', ?, ?, 0, NULL, ?, ?)",
+    )
+    .bind(&id)
+    .bind(workspace_id)
+    .bind(date)
+    .bind(text)
+    .bind(tags)
+    .bind(order)
+    .bind(now)
+    .bind(now)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(id)
+}
+
+/// Append a paragraph to a page's notes
+pub async fn append_note(
+    conn: &mut SqliteConnection,
+    workspace_id: &str,
+    date: &str,
+    text: &str,
+) -> Result<(), String> {
+    ensure_page(conn, workspace_id, date).await?;
+    sqlx::query(
+        "UPDATE pages SET
+            notes = CASE WHEN notes_compressed = 0 AND (notes IS NULL OR notes = '') THEN ?1
+                ELSE COALESCE(notes, zstd_decompress(notes_zstd)) || char(10) || char(10) || ?1 END,
+            updated_at = ?2
+         WHERE workspace_id = ?3 AND date = ?4",
+    )
+    .bind(text)
+    .bind(Utc::now().timestamp())
+    .bind(workspace_id)
+    .bind(date)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// Show and focus the main window on `view` for `date`
+fn navigate(app: &AppHandle, view: &str, date: Option<String>) -> Result<ActionResult, String> {
+    let date = date.unwrap_or_else(today);
+    if chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").is_err() {
+        return Err(format!("Invalid date: {}", date));
+    }
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "Main window is not open".to_string())?;
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    window
+        .emit(
+            NAVIGATE_EVENT,
+            Navigate {
+                view: view.to_string(),
+                date: date.clone(),
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(ActionResult::Navigated {
+        view: view.to_string(),
+        date,
+    })
+}
+
+/// Run an action against the database (or the window, for navigation) and notify listeners
+pub async fn dispatch(app: &AppHandle, action: Action) -> Result<ActionResult, String> {
+    let action = match action {
+        Action::OpenEntry { date } => return navigate(app, VIEW_ENTRY, date),
+        Action::NewTodo { date } => return navigate(app, VIEW_NEW_TODO, date),
+        action => action,
+    };
+
+    let (result, tables) = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let workspace_id = current_workspace_id(&mut tx).await?;
+
+        let outcome = match action {
+            Action::CreateTodo { text, date } => {
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err("Todo text cannot be empty".to_string());
+                }
+                let date = date.unwrap_or_else(today);
+                let todo_id = insert_todo(&mut tx, &workspace_id, &date, text).await?;
+                (
+                    ActionResult::TodoCreated { todo_id, date },
+                    vec!["pages".to_string(), "todos".to_string()],
+                )
+            }
+            Action::AppendNote { text, date } => {
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err("Note text cannot be empty".to_string());
+                }
+                let date = date.unwrap_or_else(today);
+                append_note(&mut tx, &workspace_id, &date, text).await?;
+                (ActionResult::NoteAppended { date }, vec!["pages".to_string()])
+            }
+            Action::CompleteTodo { id } => {
+                let date: Option<(String,)> = sqlx::query_as("SELECT page_date FROM todos WHERE id = ?")
+                    .bind(&id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let (date,) = date.ok_or_else(|| format!("Todo not found: {}", id))?;
+                sqlx::query("UPDATE todos SET status = 'done', updated_at = ? WHERE id = ? AND status != 'done'")
+                    .bind(Utc::now().timestamp())
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                (ActionResult::TodoCompleted { todo_id: id, date }, vec!["todos".to_string()])
+            }
+            Action::OpenEntry { .. } | Action::NewTodo { .. } => unreachable!("handled before the transaction"),
+        };
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        outcome
+    };
+
+    changes::notify(app, tables);
+    Ok(result)
+}
+
+/// Run an action from the frontend, e.g. a command palette entry
+#[crate::metrics::command]
+pub async fn dispatch_action(app: AppHandle, action: Action) -> Result<ActionResult, String> {
+    dispatch(&app, action).await
+}
+
+/// Parse a `journal-todo://` deep link into an action.
+/// Supported: `clip?url=&title=&selection=&kind=`, `todo?text=&date=`, `note?text=&date=`,
+/// `open?date=`, `new-todo?date=`, and `share?text=&url=&subject=&kind=` (see [`Share`])
+pub fn from_deep_link(url: &url::Url) -> Result<Action, String> {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let target = url.host_str().unwrap_or_else(|| url.path().trim_matches('/'));
+
+    match target {
+        "clip" => Ok(Clip {
+            title: param("title"),
+            url: param("url").ok_or("Missing url parameter")?,
+            selection: param("selection"),
+            kind: param("kind"),
+        }
+        .into_action()),
+        "todo" => Ok(Action::CreateTodo {
+            text: param("text").ok_or("Missing text parameter")?,
+            date: param("date"),
+        }),
+        "note" => Ok(Action::AppendNote {
+            text: param("text").ok_or("Missing text parameter")?,
+            date: param("date"),
+        }),
+        "share" => Share {
+            text: param("text"),
+            url: param("url"),
+            subject: param("subject"),
+            kind: param("kind"),
+        }
+        .into_action(),
+        "open" => Ok(Action::OpenEntry { date: param("date") }),
+        "new-todo" => Ok(Action::NewTodo { date: param("date") }),
+        other => Err(format!("Unknown deep link: {}", other)),
+    }
+}
+
+/// Handle deep links the app was opened with
+pub fn handle_deep_links(app: &AppHandle, urls: Vec<url::Url>) {
+    for url in urls {
+        crate::logger::info(&format!("Deep link: {}", url));
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = match from_deep_link(&url) {
+                Ok(action) => dispatch(&app, action).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                crate::logger::error(&format!("Deep link failed: {}", e));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(text: Option<&str>, url: Option<&str>, subject: Option<&str>) -> Share {
+        Share {
+            text: text.map(str::to_string),
+            url: url.map(str::to_string),
+            subject: subject.map(str::to_string),
+            kind: None,
+        }
+    }
+
+    #[test]
+    fn test_shares_become_clips_or_notes() {
+        let text = |action: Action| match action {
+            Action::AppendNote { text, .. } | Action::CreateTodo { text, .. } => text,
+            other => panic!("unexpected action {:?}", other),
+        };
+        let shared = share(Some("Great read https://example.com/post"), None, None);
+        assert_eq!(text(shared.into_action().unwrap()), "[Great read](https://example.com/post)");
+        let shared = share(Some("Worth a look"), Some("https://example.com"), Some("Title"));
+        assert_eq!(text(shared.into_action().unwrap()), "[Title](https://example.com)\n> Worth a look");
+        let shared = share(Some("Buy milk"), None, None);
+        assert_eq!(text(shared.into_action().unwrap()), "Buy milk");
+        assert!(share(Some("  "), None, None).into_action().is_err());
+
+        let url = url::Url::parse("journal-todo://share?text=Call%20Sam&kind=todo").unwrap();
+        assert!(matches!(from_deep_link(&url), Ok(Action::CreateTodo { text, .. }) if text == "Call Sam"));
+    }
+}
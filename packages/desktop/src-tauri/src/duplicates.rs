@@ -0,0 +1,401 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::fractional_index;
+use crate::privacy;
+
+/// Used when `find_duplicates` gets no threshold
+const DEFAULT_THRESHOLD: f64 = 0.85;
+/// Characters of each record shown in a duplicate group
+const PREVIEW_LENGTH: usize = 80;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateRecord {
+    /// Todo id, or `<workspace id>/<date>` for a journal entry
+    pub id: String,
+    pub preview: String,
+    pub created_at: i64,
+}
+
+/// Records similar enough to be the same thing, oldest first
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub records: Vec<DuplicateRecord>,
+    /// Lowest similarity of the pairs linking the group
+    pub similarity: f64,
+}
+
+/// Lowercase words without punctuation, single-spaced
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn trigrams(normalized: &str) -> HashSet<String> {
+    let chars: Vec<char> = format!("  {} ", normalized).chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// Jaccard similarity of two trigram sets
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shared = a.intersection(b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+struct Candidate {
+    id: String,
+    workspace_id: String,
+    text: String,
+    created_at: i64,
+}
+
+/// Group candidates whose text is at least `threshold` similar, within a workspace.
+/// Only pairs sharing a trigram are compared.
+fn group_duplicates(candidates: Vec<Candidate>, threshold: f64) -> Vec<DuplicateGroup> {
+    let grams: Vec<HashSet<String>> = candidates.iter().map(|c| trigrams(&normalize(&c.text))).collect();
+    let mut index: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    // Union-find over candidates, with the weakest link seen per root
+    let mut parent: Vec<usize> = (0..candidates.len()).collect();
+    let mut weakest: Vec<f64> = vec![1.0; candidates.len()];
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for (i, candidate) in candidates.iter().enumerate() {
+        let mut seen = HashSet::new();
+        for gram in &grams[i] {
+            let key = (candidate.workspace_id.as_str(), gram.as_str());
+            for &j in index.get(&key).into_iter().flatten() {
+                if !seen.insert(j) {
+                    continue;
+                }
+                let score = similarity(&grams[i], &grams[j]);
+                if score >= threshold {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                    let link = score.min(weakest[a]).min(weakest[b]);
+                    parent[a] = b;
+                    weakest[b] = link;
+                }
+            }
+            index.entry(key).or_default().push(i);
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..candidates.len() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(r, members)| {
+            let mut records: Vec<DuplicateRecord> = members
+                .into_iter()
+                .map(|i| DuplicateRecord {
+                    id: candidates[i].id.clone(),
+                    preview: candidates[i].text.chars().take(PREVIEW_LENGTH).collect(),
+                    created_at: candidates[i].created_at,
+                })
+                .collect();
+            records.sort_by_key(|r| r.created_at);
+            DuplicateGroup {
+                records,
+                similarity: weakest[r],
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    groups
+}
+
+/// Find near-duplicate todos (`kind` "todos") or journal entries ("entries").
+/// `similarity_threshold` is the trigram similarity (0-1) two texts need.
+#[crate::metrics::command]
+pub async fn find_duplicates(
+    state: State<'_, DatabaseState>,
+    kind: String,
+    similarity_threshold: Option<f64>,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let threshold = similarity_threshold.unwrap_or(DEFAULT_THRESHOLD).clamp(0.1, 1.0);
+    let pool = state.pool.lock().await;
+    let rows: Vec<(String, String, String, i64)> = match kind.as_str() {
+        "todos" => sqlx::query_as("SELECT id, workspace_id, text, created_at FROM todos"),
+        "entries" => sqlx::query_as(
+            "SELECT workspace_id || '/' || date, workspace_id, COALESCE(notes, zstd_decompress(notes_zstd)) AS notes,
+                    created_at
+             FROM pages
+             WHERE notes_compressed = 1 OR (notes IS NOT NULL AND notes != '')",
+        ),
+        _ => return Err(format!("Unknown record kind: {}", kind)),
+    }
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    drop(pool);
+
+    let candidates = rows
+        .into_iter()
+        .filter(|(_, _, text, _)| !privacy::is_encrypted(text) && !normalize(text).is_empty())
+        .map(|(id, workspace_id, text, created_at)| Candidate {
+            id,
+            workspace_id,
+            text,
+            created_at,
+        })
+        .collect();
+    Ok(group_duplicates(candidates, threshold))
+}
+
+/// A journal entry id as listed by `find_duplicates`
+fn parse_entry_id(id: &str) -> Option<(&str, &str)> {
+    let (workspace_id, date) = id.rsplit_once('/')?;
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some((workspace_id, date))
+}
+
+/// Union of JSON tag arrays, in first-seen order
+fn merge_tags<'a>(tag_lists: impl IntoIterator<Item = &'a str>) -> String {
+    let mut tags: Vec<String> = Vec::new();
+    for list in tag_lists {
+        for tag in serde_json::from_str::<Vec<String>>(list).unwrap_or_default() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    serde_json::to_string(&tags).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Fold `remove_ids` into `keep_id`: tags are combined, and children, attachments,
+/// time sessions and a reminder move over
+async fn merge_todos(conn: &mut SqliteConnection, keep_id: &str, remove_ids: &[String]) -> Result<(), String> {
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let mut tag_lists = Vec::new();
+    for id in std::iter::once(keep_id).chain(remove_ids.iter().map(String::as_str)) {
+        let tags: Option<(String,)> = sqlx::query_as("SELECT tags FROM todos WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tag_lists.push(tags.ok_or_else(|| format!("Todo {} not found", id))?.0);
+    }
+    sqlx::query("UPDATE todos SET tags = ?, updated_at = ? WHERE id = ?")
+        .bind(merge_tags(tag_lists.iter().map(String::as_str)))
+        .bind(Utc::now().timestamp())
+        .bind(keep_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for id in remove_ids {
+        for statement in [
+            "UPDATE todos SET parent_id = ?1 WHERE parent_id = ?2",
+            "UPDATE attachments SET todo_id = ?1 WHERE todo_id = ?2",
+            "UPDATE time_sessions SET todo_id = ?1 WHERE todo_id = ?2",
+            // Keeps the surviving todo's own reminder if it has one
+            "UPDATE OR IGNORE todo_reminders SET todo_id = ?1 WHERE todo_id = ?2",
+            "DELETE FROM todo_reminders WHERE todo_id = ?2",
+            "DELETE FROM todos WHERE id = ?2",
+        ] {
+            sqlx::query(statement)
+                .bind(keep_id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+/// Fold journal entries into `keep`: notes are appended, todos and attachments
+/// move over, and mood and location move over where `keep` has none
+async fn merge_entries(conn: &mut SqliteConnection, keep: (&str, &str), remove: &[(&str, &str)]) -> Result<(), String> {
+    let (workspace_id, keep_date) = keep;
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    for (_, date) in std::iter::once(&keep).chain(remove) {
+        let (protected,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM private_pages WHERE workspace_id = ?1 AND page_date = ?2)
+                OR EXISTS (SELECT 1 FROM locked_pages WHERE workspace_id = ?1 AND page_date = ?2)",
+        )
+        .bind(workspace_id)
+        .bind(date)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if protected {
+            return Err(format!("Entry {} is private or locked", date));
+        }
+    }
+
+    let (mut notes,): (Option<String>,) = sqlx::query_as(crate::compression::PAGE_NOTES_SQL)
+        .bind(workspace_id)
+        .bind(keep_date)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Entry {} not found", keep_date))?;
+    let last: Option<(String,)> =
+        sqlx::query_as("SELECT `order` FROM todos WHERE workspace_id = ? AND page_date = ? ORDER BY `order` DESC LIMIT 1")
+            .bind(workspace_id)
+            .bind(keep_date)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    let mut last_order = last.map(|(order,)| order);
+
+    for (_, date) in remove {
+        let removed: Option<(Option<String>,)> = sqlx::query_as(crate::compression::PAGE_NOTES_SQL)
+            .bind(workspace_id)
+            .bind(date)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let (removed_notes,) = removed.ok_or_else(|| format!("Entry {} not found", date))?;
+        if let Some(removed_notes) = removed_notes.filter(|n| !n.trim().is_empty()) {
+            let known = notes.as_deref().is_some_and(|n| normalize(n).contains(&normalize(&removed_notes)));
+            if !known {
+                notes = Some(match notes {
+                    Some(n) if !n.trim().is_empty() => format!("{}\n\n{}", n, removed_notes),
+                    _ => removed_notes,
+                });
+            }
+        }
+
+        // Moved todos go after the kept entry's own, in their original order
+        let todos: Vec<(String,)> =
+            sqlx::query_as("SELECT id FROM todos WHERE workspace_id = ? AND page_date = ? ORDER BY `order`")
+                .bind(workspace_id)
+                .bind(date)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        for (id,) in todos {
+            let order = fractional_index::key_between(last_order.as_deref(), None)?;
+            sqlx::query("UPDATE todos SET page_date = ?, `order` = ? WHERE id = ?")
+                .bind(keep_date)
+                .bind(&order)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            last_order = Some(order);
+        }
+
+        for statement in [
+            "UPDATE attachments SET page_date = ?3 WHERE workspace_id = ?1 AND page_date = ?2",
+            "UPDATE OR IGNORE page_moods SET page_date = ?3 WHERE workspace_id = ?1 AND page_date = ?2",
+            "UPDATE OR IGNORE page_locations SET page_date = ?3 WHERE workspace_id = ?1 AND page_date = ?2",
+            "DELETE FROM page_moods WHERE workspace_id = ?1 AND page_date = ?2",
+            "DELETE FROM page_locations WHERE workspace_id = ?1 AND page_date = ?2",
+            "DELETE FROM pages WHERE workspace_id = ?1 AND date = ?2",
+        ] {
+            sqlx::query(statement)
+                .bind(workspace_id)
+                .bind(date)
+                .bind(keep_date)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    sqlx::query("UPDATE pages SET notes = ?, updated_at = ? WHERE workspace_id = ? AND date = ?")
+        .bind(notes)
+        .bind(Utc::now().timestamp())
+        .bind(workspace_id)
+        .bind(keep_date)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+/// Combine duplicates found by `find_duplicates` into the record to keep.
+/// Ids are todo ids, or all `<workspace id>/<date>` entry ids in one workspace.
+#[crate::metrics::command]
+pub async fn merge_records(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    keep_id: String,
+    remove_ids: Vec<String>,
+) -> Result<(), String> {
+    let remove_ids: Vec<String> = remove_ids.into_iter().filter(|id| *id != keep_id).collect();
+    if remove_ids.is_empty() {
+        return Ok(());
+    }
+    let tables = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        match parse_entry_id(&keep_id) {
+            Some(keep) => {
+                let remove = remove_ids
+                    .iter()
+                    .map(|id| parse_entry_id(id).filter(|(workspace_id, _)| *workspace_id == keep.0))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| "Entries can only be merged with entries of the same workspace".to_string())?;
+                merge_entries(&mut conn, keep, &remove).await?;
+                vec!["pages", "todos", "attachments", "page_moods", "page_locations"]
+            }
+            None => {
+                merge_todos(&mut conn, &keep_id, &remove_ids).await?;
+                vec!["todos", "attachments", "time_sessions", "todo_reminders"]
+            }
+        }
+    };
+    crate::logger::info(&format!("Merged {} records into {}", remove_ids.len(), keep_id));
+    changes::notify(&app, tables.into_iter().map(String::from).collect());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: &str, text: &str) -> Candidate {
+        Candidate {
+            id: id.to_string(),
+            workspace_id: "w".to_string(),
+            text: text.to_string(),
+            created_at: id.len() as i64,
+        }
+    }
+
+    #[test]
+    fn test_groups_near_duplicates() {
+        let groups = group_duplicates(
+            vec![
+                candidate("a", "Buy milk and eggs!"),
+                candidate("bb", "buy milk, and eggs"),
+                candidate("ccc", "Call the dentist"),
+            ],
+            0.8,
+        );
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<&str> = groups[0].records.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "bb"]);
+        assert_eq!(groups[0].similarity, 1.0);
+
+        assert_eq!(merge_tags(["[\"work\"]", "[\"home\",\"work\"]"]), "[\"work\",\"home\"]");
+        assert_eq!(parse_entry_id("ws-1/2024-05-01"), Some(("ws-1", "2024-05-01")));
+        assert_eq!(parse_entry_id("0191c1ba-todo"), None);
+    }
+}
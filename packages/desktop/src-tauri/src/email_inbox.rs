@@ -0,0 +1,370 @@
+use chrono::{Local, TimeZone, Utc};
+use mail_parser::{MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::attachments;
+use crate::hooks::{self, HookEvent};
+use crate::db::{changes, DatabaseState};
+use crate::{dispatch, logger, secrets, settings};
+
+/// `{ "host", "port", "username", "folder", "allowedSenders", "subjectPrefix" }`; unset disables the inbox
+const CONFIG_SETTING: &str = "email_inbox.config";
+const PASSWORD_SECRET: &str = "email_inbox.password";
+
+/// Keyword set on imported messages, so they're skipped on the next poll
+const PROCESSED_KEYWORD: &str = "$JournalTodoImported";
+/// Subjects starting with this become a todo instead of a note
+const TODO_PREFIX: &str = "todo:";
+/// Messages imported per poll; the rest wait for the next one
+const MAX_PER_POLL: usize = 20;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InboxConfig {
+    host: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    username: String,
+    #[serde(default = "default_folder")]
+    folder: String,
+    /// Only mail from these addresses is imported; any sender when empty
+    #[serde(default)]
+    allowed_senders: Vec<String>,
+    /// Only subjects starting with this are imported, with it removed
+    #[serde(default)]
+    subject_prefix: Option<String>,
+}
+
+fn default_port() -> u16 {
+    993
+}
+
+fn default_folder() -> String {
+    "INBOX".to_string()
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboxReport {
+    pub notes: usize,
+    pub todos: usize,
+    pub attachments: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Content {
+    Note(String),
+    Todo { text: String, details: Option<String> },
+}
+
+struct ParsedEmail {
+    message_id: String,
+    date: String,
+    content: Content,
+    attachments: Vec<(String, Vec<u8>)>,
+}
+
+/// The body without the signature and quoted replies
+fn clean_body(body: &str) -> String {
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        if line == "-- " || (line.starts_with("On ") && line.trim_end().ends_with("wrote:")) {
+            break;
+        }
+        if !line.starts_with('>') {
+            lines.push(line.trim_end());
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// What a message becomes, or None if it doesn't match the configured filters
+fn convert(config: &InboxConfig, from: Option<&str>, subject: &str, body: &str) -> Option<Content> {
+    if !config.allowed_senders.is_empty() {
+        let from = from?.to_lowercase();
+        if !config.allowed_senders.iter().any(|allowed| allowed.trim().to_lowercase() == from) {
+            return None;
+        }
+    }
+    let mut subject = subject.trim();
+    if let Some(prefix) = config.subject_prefix.as_deref().filter(|p| !p.is_empty()) {
+        subject = subject.strip_prefix(prefix)?.trim();
+    }
+    let body = clean_body(body);
+
+    let todo = subject
+        .get(..TODO_PREFIX.len())
+        .filter(|start| start.eq_ignore_ascii_case(TODO_PREFIX))
+        .map(|_| subject[TODO_PREFIX.len()..].trim());
+    match todo {
+        Some(text) if !text.is_empty() => Some(Content::Todo {
+            text: text.to_string(),
+            details: Some(body).filter(|body| !body.is_empty()),
+        }),
+        _ => {
+            let note = match (subject.is_empty(), body.is_empty()) {
+                (true, true) => return None,
+                (false, true) => format!("**{}**", subject),
+                (true, false) => body,
+                (false, false) => format!("**{}**\n\n{}", subject, body),
+            };
+            Some(Content::Note(note))
+        }
+    }
+}
+
+fn parse(config: &InboxConfig, uid: u32, raw: &[u8]) -> Option<ParsedEmail> {
+    let message = MessageParser::default().parse(raw)?;
+    let from = message.from().and_then(|from| from.first()).and_then(|addr| addr.address());
+    let body = message.body_text(0).unwrap_or_default();
+    let content = convert(config, from, message.subject().unwrap_or_default(), &body)?;
+    let date = message
+        .date()
+        .and_then(|date| Local.timestamp_opt(date.to_timestamp(), 0).single())
+        .unwrap_or_else(Local::now)
+        .format("%Y-%m-%d")
+        .to_string();
+    let attachments = message
+        .attachments()
+        .filter_map(|part| {
+            let name = part.attachment_name()?;
+            Some((name.to_string(), part.contents().to_vec()))
+        })
+        .collect();
+    Some(ParsedEmail {
+        message_id: message
+            .message_id()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}:{}", config.username, uid)),
+        date,
+        content,
+        attachments,
+    })
+}
+
+type Session = imap::Session<imap::Connection>;
+
+fn connect(config: &InboxConfig, password: &str) -> Result<Session, String> {
+    let client = imap::ClientBuilder::new(config.host.as_str(), config.port)
+        .connect()
+        .map_err(|e| format!("Failed to connect to {}: {}", config.host, e))?;
+    let mut session = client
+        .login(&config.username, password)
+        .map_err(|(e, _)| format!("Failed to log in to {}: {}", config.host, e))?;
+    session.select(&config.folder).map_err(|e| e.to_string())?;
+    Ok(session)
+}
+
+/// Unprocessed messages in the folder, oldest first
+fn fetch_new(config: &InboxConfig, password: &str) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let mut session = connect(config, password)?;
+    let mut uids: Vec<u32> = session
+        .uid_search(format!("UNKEYWORD {}", PROCESSED_KEYWORD))
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+    uids.sort_unstable();
+    uids.truncate(MAX_PER_POLL);
+    let mut messages = Vec::new();
+    if !uids.is_empty() {
+        let set: Vec<String> = uids.iter().map(u32::to_string).collect();
+        let fetches = session.uid_fetch(set.join(","), "BODY.PEEK[]").map_err(|e| e.to_string())?;
+        for fetch in fetches.iter() {
+            if let (Some(uid), Some(body)) = (fetch.uid, fetch.body()) {
+                messages.push((uid, body.to_vec()));
+            }
+        }
+    }
+    let _ = session.logout();
+    Ok(messages)
+}
+
+/// Tag messages so they aren't fetched again; imported ones are also marked read,
+/// ones that didn't match the filters are left unread for the user
+fn mark_processed(config: &InboxConfig, password: &str, imported: &[u32], ignored: &[u32]) -> Result<(), String> {
+    let mut session = connect(config, password)?;
+    for (uids, flags) in [
+        (imported, format!("+FLAGS ({} \\Seen)", PROCESSED_KEYWORD)),
+        (ignored, format!("+FLAGS ({})", PROCESSED_KEYWORD)),
+    ] {
+        if uids.is_empty() {
+            continue;
+        }
+        let set: Vec<String> = uids.iter().map(u32::to_string).collect();
+        session.uid_store(set.join(","), flags).map_err(|e| e.to_string())?;
+    }
+    let _ = session.logout();
+    Ok(())
+}
+
+/// Store the email's note or todo and its attachments; false if it was imported before
+async fn import(state: &DatabaseState, email: &ParsedEmail, report: &mut InboxReport) -> Result<bool, String> {
+    let (workspace_id, todo_id) = {
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let seen: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM email_imports WHERE message_id = ?")
+            .bind(&email.message_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if seen.is_some() {
+            return Ok(false);
+        }
+        let workspace_id = dispatch::current_workspace_id(&mut tx).await?;
+        let todo_id = match &email.content {
+            Content::Note(note) => {
+                dispatch::append_note(&mut tx, &workspace_id, &email.date, note).await?;
+                report.notes += 1;
+                None
+            }
+            Content::Todo { text, details } => {
+                let id = dispatch::insert_todo(&mut tx, &workspace_id, &email.date, text).await?;
+                if let Some(details) = details {
+                    dispatch::append_note(&mut tx, &workspace_id, &email.date, details).await?;
+                }
+                report.todos += 1;
+                Some(id)
+            }
+        };
+        sqlx::query(
+            "INSERT INTO email_imports (message_id, workspace_id, page_date, todo_id, imported_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&email.message_id)
+        .bind(&workspace_id)
+        .bind(&email.date)
+        .bind(&todo_id)
+        .bind(Utc::now().timestamp_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        (workspace_id, todo_id)
+    };
+
+    // Attachments go through a temp file like any other file added to the store
+    let temp_dir = std::env::temp_dir().join(format!("journal-todo-mail-{}", uuid::Uuid::new_v4()));
+    for (name, bytes) in &email.attachments {
+        let name = std::path::Path::new(name)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "attachment".to_string());
+        std::fs::create_dir_all(&temp_dir).map_err(|e| e.to_string())?;
+        let temp_path = temp_dir.join(&name);
+        std::fs::write(&temp_path, bytes).map_err(|e| e.to_string())?;
+        let stored = attachments::store_file(state, &temp_path, &workspace_id, Some(&email.date), todo_id.as_deref()).await;
+        let _ = std::fs::remove_file(&temp_path);
+        match stored {
+            Ok(_) => report.attachments += 1,
+            Err(e) => logger::error(&format!("Failed to store email attachment {}: {}", name, e)),
+        }
+    }
+    let _ = std::fs::remove_dir(&temp_dir);
+    Ok(true)
+}
+
+async fn poll(app: &AppHandle) -> Result<Option<InboxReport>, String> {
+    let config = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        settings::get::<InboxConfig>(&pool, CONFIG_SETTING).await?
+    };
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let password = secrets::get(PASSWORD_SECRET)?.ok_or("No email inbox password configured")?;
+
+    let fetch_config = config.clone();
+    let fetch_password = password.clone();
+    let messages = tauri::async_runtime::spawn_blocking(move || fetch_new(&fetch_config, &fetch_password))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let state = app.state::<DatabaseState>();
+    let mut report = InboxReport::default();
+    let mut imported = Vec::new();
+    let mut ignored = Vec::new();
+    for (uid, raw) in &messages {
+        match parse(&config, *uid, raw) {
+            Some(email) => {
+                if !import(&state, &email, &mut report).await? {
+                    report.skipped += 1;
+                }
+                imported.push(*uid);
+            }
+            None => {
+                report.skipped += 1;
+                ignored.push(*uid);
+            }
+        }
+    }
+    if !messages.is_empty() {
+        tauri::async_runtime::spawn_blocking(move || mark_processed(&config, &password, &imported, &ignored))
+            .await
+            .map_err(|e| e.to_string())??;
+    }
+
+    if report.notes + report.todos > 0 {
+        logger::info(&format!(
+            "Imported {} notes and {} todos from email",
+            report.notes, report.todos
+        ));
+        changes::notify(
+            app,
+            vec!["pages".to_string(), "todos".to_string(), "attachments".to_string()],
+        );
+        hooks::fire(app, HookEvent::EmailImported, &report);
+    }
+    Ok(Some(report))
+}
+
+/// Check the configured inbox now
+#[crate::metrics::command]
+pub async fn check_email_inbox(app: AppHandle) -> Result<InboxReport, String> {
+    poll(&app).await?.ok_or_else(|| format!("No email inbox configured in {}", CONFIG_SETTING))
+}
+
+/// Scheduled poll of the inbox, when one is configured
+pub async fn poll_job(app: AppHandle) -> Result<(), String> {
+    poll(&app).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(allowed_senders: &[&str], subject_prefix: Option<&str>) -> InboxConfig {
+        InboxConfig {
+            host: "imap.example.com".to_string(),
+            port: 993,
+            username: "me".to_string(),
+            folder: "INBOX".to_string(),
+            allowed_senders: allowed_senders.iter().map(|s| s.to_string()).collect(),
+            subject_prefix: subject_prefix.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_convert_email() {
+        let any = config(&[], None);
+        assert_eq!(
+            convert(&any, None, "Lunch", "Was great.\n\n-- \nSent from my phone"),
+            Some(Content::Note("**Lunch**\n\nWas great.".to_string()))
+        );
+        assert_eq!(
+            convert(&any, None, "TODO: Renew passport", "> quoted\n"),
+            Some(Content::Todo {
+                text: "Renew passport".to_string(),
+                details: None
+            })
+        );
+        assert_eq!(convert(&any, None, " ", ""), None);
+
+        let strict = config(&["Me@Example.com"], Some("[journal]"));
+        assert!(convert(&strict, Some("me@example.com"), "[journal] Idea", "").is_some());
+        assert!(convert(&strict, Some("me@example.com"), "Idea", "").is_none());
+        assert!(convert(&strict, Some("spam@example.com"), "[journal] Idea", "").is_none());
+        assert!(convert(&strict, None, "[journal] Idea", "").is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+
+use crate::logger;
+use crate::site_export::{self, escape, Entry};
+
+const STYLESHEET: &str = "\
+body { font-family: serif; line-height: 1.5; }
+h1 { font-size: 1.4em; margin-top: 2em; }
+section > h1:first-child { margin-top: 0; }
+ul { padding-left: 1.25em; }
+figure { margin: 1em 0; text-align: center; }
+figure img { max-width: 100%; }
+";
+
+/// Image types every reading system supports; others are left out of the book
+fn core_media_type(mime_type: Option<&str>) -> Option<&'static str> {
+    match mime_type? {
+        "image/jpeg" => Some("image/jpeg"),
+        "image/png" => Some("image/png"),
+        "image/gif" => Some("image/gif"),
+        "image/svg+xml" => Some("image/svg+xml"),
+        "image/webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpubReport {
+    pub entries: usize,
+    pub chapters: usize,
+    pub images: usize,
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"en\" xml:lang=\"en\">\n\
+         <head>\n<meta charset=\"UTF-8\" />\n<title>{}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\" />\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        body
+    )
+}
+
+/// Checklist boxes as characters, which e-readers show more reliably than form inputs
+fn checkboxes_as_text(html: &str) -> String {
+    html.replace("<input disabled=\"\" type=\"checkbox\" checked=\"\"/>", "☑")
+        .replace("<input disabled=\"\" type=\"checkbox\"/>", "☐")
+}
+
+/// Every file of the book besides the images, in archive order with `mimetype` first
+fn build(title: &str, entries: &[Entry], modified: &str) -> Vec<(String, String)> {
+    let mut months: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        months.entry(site_export::month_of(&entry.date)).or_default().push(entry);
+    }
+
+    let mut files = vec![
+        ("mimetype".to_string(), "application/epub+zip".to_string()),
+        (
+            "META-INF/container.xml".to_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+             <rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles>\n\
+             </container>\n"
+                .to_string(),
+        ),
+        ("OEBPS/style.css".to_string(), STYLESHEET.to_string()),
+    ];
+
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::from("<itemref idref=\"nav\"/>\n");
+    let mut toc = String::new();
+    for (month, month_entries) in &months {
+        let month_title = site_export::month_title(month);
+        let mut body = format!("<section epub:type=\"chapter\">\n<h1>{}</h1>\n", escape(&month_title));
+        for entry in month_entries {
+            body.push_str(&format!(
+                "<section id=\"d{}\">\n{}</section>\n",
+                entry.date,
+                checkboxes_as_text(&site_export::entry_html(entry, "images"))
+            ));
+        }
+        body.push_str("</section>\n");
+        files.push((format!("OEBPS/{}.xhtml", month), xhtml(&month_title, &body)));
+        manifest.push_str(&format!(
+            "<item id=\"m{0}\" href=\"{0}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+            month
+        ));
+        spine.push_str(&format!("<itemref idref=\"m{}\"/>\n", month));
+        toc.push_str(&format!("<li><a href=\"{}.xhtml\">{}</a></li>\n", month, escape(&month_title)));
+    }
+    for (index, image) in entries.iter().flat_map(|entry| &entry.images).enumerate() {
+        if let Some(media_type) = core_media_type(image.mime_type.as_deref()) {
+            manifest.push_str(&format!(
+                "<item id=\"img{}\" href=\"images/{}\" media-type=\"{}\"/>\n",
+                index,
+                escape(&image.stored_name),
+                media_type
+            ));
+        }
+    }
+
+    let nav = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n",
+        escape(title),
+        toc
+    );
+    files.push(("OEBPS/nav.xhtml".to_string(), xhtml(title, &nav)));
+    files.push((
+        "OEBPS/content.opf".to_string(),
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"en\">\n\
+             <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             <dc:identifier id=\"book-id\">urn:uuid:{}</dc:identifier>\n\
+             <dc:title>{}</dc:title>\n<dc:language>en</dc:language>\n<dc:creator>Journal Todo</dc:creator>\n\
+             <meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n\
+             <manifest>\n{}</manifest>\n<spine>\n{}</spine>\n</package>\n",
+            uuid::Uuid::new_v4(),
+            escape(title),
+            modified,
+            manifest,
+            spine
+        ),
+    ));
+    files
+}
+
+/// Write a year of the current workspace's journal to `path` as an EPUB, a chapter per month.
+/// Private and locked entries are left out.
+#[crate::metrics::command]
+pub async fn export_epub(app: AppHandle, year: i32, path: String) -> Result<EpubReport, String> {
+    let from = format!("{:04}-01-01", year);
+    let to = format!("{:04}-12-31", year);
+    let (mut entries, attachments_dir) = site_export::load_entries(&app, None, Some(&from), Some(&to)).await?;
+    if entries.is_empty() {
+        return Err(format!("No entries in {}", year));
+    }
+    for entry in &mut entries {
+        entry.images.retain(|image| {
+            core_media_type(image.mime_type.as_deref()).is_some() && attachments_dir.join(&image.stored_name).is_file()
+        });
+    }
+
+    let title = format!("Journal {}", year);
+    let modified = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let files = build(&title, &entries, &modified);
+    let images: Vec<(PathBuf, String)> = entries
+        .iter()
+        .flat_map(|entry| &entry.images)
+        .map(|image| (attachments_dir.join(&image.stored_name), image.stored_name.clone()))
+        .collect();
+    let report = EpubReport {
+        entries: entries.len(),
+        chapters: files.iter().filter(|(name, _)| name.ends_with(".xhtml")).count() - 1,
+        images: images.len(),
+    };
+
+    let write = move || -> Result<(), String> {
+        let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let mut zip = zip::ZipWriter::new(file);
+        // The mimetype entry has to be stored uncompressed
+        let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, contents) in &files {
+            zip.start_file(name.as_str(), if name == "mimetype" { stored } else { deflated })
+                .map_err(|e| e.to_string())?;
+            zip.write_all(contents.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        for (source, stored_name) in &images {
+            let bytes = std::fs::read(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+            zip.start_file(format!("OEBPS/images/{}", stored_name), stored)
+                .map_err(|e| e.to_string())?;
+            zip.write_all(&bytes).map_err(|e| e.to_string())?;
+        }
+        zip.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    };
+    tauri::async_runtime::spawn_blocking(write)
+        .await
+        .map_err(|e| e.to_string())??;
+    logger::info(&format!("Exported {} entries of {} as an EPUB", report.entries, year));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_export::ExportTodo;
+
+    #[test]
+    fn test_build_epub_files() {
+        let entries = vec![
+            Entry {
+                date: "2024-03-04".to_string(),
+                notes: Some("A & B".to_string()),
+                todos: vec![ExportTodo {
+                    workspace_id: "w".to_string(),
+                    page_date: "2024-03-04".to_string(),
+                    text: "Ship".to_string(),
+                    status: "done".to_string(),
+                    level: 0,
+                }],
+                tags: Vec::new(),
+                images: Vec::new(),
+            },
+            Entry {
+                date: "2024-05-01".to_string(),
+                notes: Some("May".to_string()),
+                todos: Vec::new(),
+                tags: Vec::new(),
+                images: Vec::new(),
+            },
+        ];
+        let files = build("Journal 2024", &entries, "2024-12-31T00:00:00Z");
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/style.css",
+                "OEBPS/2024-03.xhtml",
+                "OEBPS/2024-05.xhtml",
+                "OEBPS/nav.xhtml",
+                "OEBPS/content.opf"
+            ]
+        );
+        let march = &files[3].1;
+        assert!(march.contains("<h1>March 2024</h1>"));
+        assert!(march.contains("A &amp; B"));
+        assert!(march.contains("☑"));
+        assert!(files[6].1.contains("<itemref idref=\"m2024-05\"/>"));
+    }
+}
@@ -0,0 +1,177 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::db::DatabaseState;
+use crate::site_export::{self, escape, Entry};
+use crate::{http_api, logger, settings};
+
+/// The filter the local HTTP API serves the feed with; unset when it isn't served
+const SERVE_SETTING: &str = "feed.serve";
+/// Passing this instead of a path publishes the feed on the local HTTP API
+const SERVE: &str = "serve";
+const DEFAULT_TAG: &str = "public";
+const DEFAULT_TITLE: &str = "Journal";
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FeedFilter {
+    pub workspace_id: Option<String>,
+    /// Entries carrying this tag are published; "public" when unset
+    pub tag: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Newest entries kept in the feed
+    pub limit: Option<usize>,
+    pub title: Option<String>,
+    /// Where the entries are published, e.g. the static site export; entry links point below it
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedReport {
+    pub entries: usize,
+    pub path: Option<String>,
+    /// Address of the served feed
+    pub url: Option<String>,
+}
+
+fn timestamp(date: &str) -> String {
+    format!("{}T00:00:00Z", date)
+}
+
+/// An Atom feed of the entries with the filter's tag and notes, newest first
+fn build(entries: &[Entry], filter: &FeedFilter, now: &str) -> (String, usize) {
+    let tag = filter.tag.as_deref().unwrap_or(DEFAULT_TAG).to_lowercase();
+    let mut published: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| entry.tags.contains(&tag))
+        .filter(|entry| entry.notes.as_deref().is_some_and(|notes| !notes.trim().is_empty()))
+        .collect();
+    published.sort_by(|a, b| b.date.cmp(&a.date));
+    published.truncate(filter.limit.unwrap_or(DEFAULT_LIMIT));
+
+    let base_url = filter.base_url.as_deref().map(|url| url.trim_end_matches('/'));
+    let title = filter.title.as_deref().unwrap_or(DEFAULT_TITLE);
+    let updated = published.first().map(|entry| timestamp(&entry.date)).unwrap_or_else(|| now.to_string());
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <title>{}</title>\n<id>{}</id>\n<updated>{}</updated>\n<generator>Journal Todo</generator>\n",
+        escape(title),
+        escape(&base_url.map(str::to_string).unwrap_or_else(|| format!("urn:journal-todo:feed:{}", tag))),
+        updated
+    );
+    if let Some(base_url) = base_url {
+        out.push_str(&format!("<link href=\"{}/\"/>\n", escape(base_url)));
+    }
+    for entry in &published {
+        let link = base_url.map(|base| format!("{}/entries/{}.html", base, entry.date));
+        out.push_str("<entry>\n");
+        out.push_str(&format!("<title>{}</title>\n", escape(&site_export::long_date(&entry.date))));
+        out.push_str(&format!(
+            "<id>{}</id>\n",
+            escape(&link.clone().unwrap_or_else(|| format!("urn:journal-todo:entry:{}", entry.date)))
+        ));
+        if let Some(link) = &link {
+            out.push_str(&format!("<link href=\"{}\"/>\n", escape(link)));
+        }
+        out.push_str(&format!("<updated>{}</updated>\n", timestamp(&entry.date)));
+        for category in entry.tags.iter().filter(|entry_tag| **entry_tag != tag) {
+            out.push_str(&format!("<category term=\"{}\"/>\n", escape(category)));
+        }
+        let html = site_export::markdown_html(entry.notes.as_deref().unwrap_or_default());
+        out.push_str(&format!("<content type=\"html\">{}</content>\n", escape(&html)));
+        out.push_str("</entry>\n");
+    }
+    out.push_str("</feed>\n");
+    (out, published.len())
+}
+
+async fn render(app: &AppHandle, filter: &FeedFilter) -> Result<(String, usize), String> {
+    let (entries, _) = site_export::load_entries(
+        app,
+        filter.workspace_id.clone(),
+        filter.from.as_deref(),
+        filter.to.as_deref(),
+    )
+    .await?;
+    let now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    Ok(build(&entries, filter, &now))
+}
+
+/// The feed as currently served, rendered fresh so new posts show up; None when not served
+pub(crate) async fn served_feed(app: &AppHandle) -> Result<Option<String>, String> {
+    let filter: Option<FeedFilter> = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        settings::get(&pool, SERVE_SETTING).await?
+    };
+    match filter {
+        Some(filter) => Ok(Some(render(app, &filter).await?.0)),
+        None => Ok(None),
+    }
+}
+
+/// Generate an Atom feed of the entries tagged "public" (or `filter.tag`). Pass a file path to
+/// write it there, or "serve" to publish it at `/feed.xml` on the local HTTP API, behind a key in
+/// the returned URL that only opens the feed. Private and locked entries are never included.
+#[crate::metrics::command]
+pub async fn export_feed(
+    app: AppHandle,
+    path_or_serve: String,
+    filter: Option<FeedFilter>,
+) -> Result<FeedReport, String> {
+    let filter = filter.unwrap_or_default();
+    let (feed, entries) = render(&app, &filter).await?;
+    if path_or_serve == SERVE {
+        settings::set(&app, SERVE_SETTING, &filter).await?;
+        let url = http_api::feed_url(&app).await?;
+        logger::info(&format!("Serving a feed of {} entries on the local HTTP API", entries));
+        return Ok(FeedReport { entries, path: None, url: Some(url) });
+    }
+    std::fs::write(&path_or_serve, feed).map_err(|e| format!("Failed to write {}: {}", path_or_serve, e))?;
+    logger::info(&format!("Exported a feed of {} entries to {}", entries, path_or_serve));
+    Ok(FeedReport { entries, path: Some(path_or_serve), url: None })
+}
+
+/// Take the feed off the local HTTP API
+#[crate::metrics::command]
+pub async fn stop_serving_feed(app: AppHandle) -> Result<(), String> {
+    settings::remove(&app, SERVE_SETTING).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, notes: &str, tags: &[&str]) -> Entry {
+        Entry {
+            date: date.to_string(),
+            notes: Some(notes.to_string()),
+            todos: Vec::new(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_feed_holds_public_entries_newest_first() {
+        let entries = vec![
+            entry("2024-03-01", "First **post** #public", &["public", "writing"]),
+            entry("2024-03-02", "Secret", &["work"]),
+            entry("2024-03-03", "Second & last #public", &["public"]),
+        ];
+        let filter = FeedFilter { base_url: Some("https://me.example/".to_string()), ..Default::default() };
+        let (feed, count) = build(&entries, &filter, "2024-04-01T00:00:00Z");
+        assert_eq!(count, 2);
+        assert!(!feed.contains("Secret"));
+        assert!(feed.contains("<updated>2024-03-03T00:00:00Z</updated>\n<generator>"));
+        assert!(feed.find("2024-03-03.html").unwrap() < feed.find("2024-03-01.html").unwrap());
+        assert!(feed.contains("<id>https://me.example/entries/2024-03-01.html</id>"));
+        assert!(feed.contains("<category term=\"writing\"/>"));
+        assert!(feed.contains("&lt;strong&gt;post&lt;/strong&gt;"));
+        assert!(feed.contains("Second &amp;amp; last"));
+    }
+}
@@ -0,0 +1,430 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::search::like_pattern;
+
+/// Emitted when stored results may be stale and filters should be re-evaluated
+pub const FILTERS_INVALIDATED_EVENT: &str = "filters://invalidated";
+
+/// Tables whose changes can affect filter results
+const WATCHED_TABLES: &[&str] = &["todos", "todo_reminders", "saved_filters"];
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// Which days a filter covers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DateSpec {
+    Between { from: String, to: String },
+    Today,
+    ThisWeek,
+    ThisMonth,
+    LastDays { days: i64 },
+}
+
+/// A user-defined todo filter; all present conditions must match
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilterDefinition {
+    pub workspace_id: Option<String>,
+    /// "todo" or "done"
+    pub status: Option<String>,
+    /// Todos must carry all of these tags
+    pub tags: Vec<String>,
+    pub date: Option<DateSpec>,
+    /// Substring of the todo text
+    pub text: Option<String>,
+    /// Only incomplete todos from past days or with a past due time
+    pub overdue: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedFilter {
+    pub id: String,
+    pub name: String,
+    pub definition: FilterDefinition,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Pagination {
+    pub offset: i64,
+    pub limit: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredTodo {
+    pub id: String,
+    pub workspace_id: String,
+    pub page_date: String,
+    pub text: String,
+    pub status: String,
+    #[sqlx(json)]
+    pub tags: Vec<String>,
+    pub order: String,
+    pub level: i64,
+    pub parent_id: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterPage {
+    pub items: Vec<FilteredTodo>,
+    pub total: i64,
+}
+
+/// Evaluated saved filters, cleared whenever a watched table changes
+#[derive(Default)]
+pub struct FilterCache {
+    pages: Mutex<HashMap<(String, Pagination), FilterPage>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bind {
+    Text(String),
+    Int(i64),
+}
+
+/// A WHERE clause and its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFilter {
+    pub condition: String,
+    pub binds: Vec<Bind>,
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Resolve a date spec to an inclusive range relative to `today`
+pub(crate) fn date_range(spec: &DateSpec, today: NaiveDate) -> (String, String) {
+    match spec {
+        DateSpec::Between { from, to } => (from.clone(), to.clone()),
+        DateSpec::Today => (format_date(today), format_date(today)),
+        DateSpec::ThisWeek => {
+            let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            (format_date(monday), format_date(monday + Duration::days(6)))
+        }
+        DateSpec::ThisMonth => {
+            let first = today.with_day(1).unwrap_or(today);
+            let next = first + Duration::days(31);
+            let last = next.with_day(1).unwrap_or(next) - Duration::days(1);
+            (format_date(first), format_date(last))
+        }
+        DateSpec::LastDays { days } => (
+            format_date(today - Duration::days((*days - 1).max(0))),
+            format_date(today),
+        ),
+    }
+}
+
+/// Compile a filter definition into SQL against `todos t`
+pub fn compile(
+    definition: &FilterDefinition,
+    today: NaiveDate,
+    now_ms: i64,
+) -> Result<CompiledFilter, String> {
+    let mut conditions = vec!["trim(t.text) != ''".to_string()];
+    let mut binds = Vec::new();
+
+    if let Some(workspace_id) = &definition.workspace_id {
+        conditions.push("t.workspace_id = ?".to_string());
+        binds.push(Bind::Text(workspace_id.clone()));
+    }
+    if let Some(status) = &definition.status {
+        if status != "todo" && status != "done" {
+            return Err(format!("Invalid status in filter: {}", status));
+        }
+        conditions.push("t.status = ?".to_string());
+        binds.push(Bind::Text(status.clone()));
+    }
+    for tag in &definition.tags {
+        conditions.push("EXISTS (SELECT 1 FROM json_each(t.tags) WHERE value = ?)".to_string());
+        binds.push(Bind::Text(tag.trim_start_matches('#').to_lowercase()));
+    }
+    if let Some(spec) = &definition.date {
+        let (from, to) = date_range(spec, today);
+        conditions.push("t.page_date BETWEEN ? AND ?".to_string());
+        binds.push(Bind::Text(from));
+        binds.push(Bind::Text(to));
+    }
+    if let Some(text) = definition
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        conditions.push("t.text LIKE ? ESCAPE '\\'".to_string());
+        binds.push(Bind::Text(like_pattern(text)));
+    }
+    if definition.overdue {
+        conditions.push(
+            "t.status != 'done' AND (t.page_date < ? OR EXISTS (
+                SELECT 1 FROM todo_reminders r WHERE r.todo_id = t.id AND r.due_at < ?))"
+                .to_string(),
+        );
+        binds.push(Bind::Text(format_date(today)));
+        binds.push(Bind::Int(now_ms));
+    }
+
+    Ok(CompiledFilter {
+        condition: conditions.join(" AND "),
+        binds,
+    })
+}
+
+async fn run_filter(
+    pool: &sqlx::SqlitePool,
+    definition: &FilterDefinition,
+    pagination: Pagination,
+) -> Result<FilterPage, String> {
+    let compiled = compile(
+        definition,
+        Local::now().date_naive(),
+        Utc::now().timestamp_millis(),
+    )?;
+
+    let count_sql = format!("SELECT COUNT(*) FROM todos t WHERE {}", compiled.condition);
+    let mut count_query = sqlx::query_as::<_, (i64,)>(&count_sql);
+    for bind in &compiled.binds {
+        count_query = match bind {
+            Bind::Text(value) => count_query.bind(value),
+            Bind::Int(value) => count_query.bind(value),
+        };
+    }
+    let (total,) = count_query
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let items_sql = format!(
+        "SELECT t.id, t.workspace_id, t.page_date, t.text, t.status, t.tags, t.`order`, t.level, t.parent_id, t.created_at, t.updated_at
+         FROM todos t WHERE {}
+         ORDER BY t.page_date DESC, t.`order`
+         LIMIT ? OFFSET ?",
+        compiled.condition
+    );
+    let mut items_query = sqlx::query_as::<_, FilteredTodo>(&items_sql);
+    for bind in &compiled.binds {
+        items_query = match bind {
+            Bind::Text(value) => items_query.bind(value),
+            Bind::Int(value) => items_query.bind(value),
+        };
+    }
+    let items = items_query
+        .bind(pagination.limit.clamp(1, 500))
+        .bind(pagination.offset.max(0))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(FilterPage { items, total })
+}
+
+/// Drop cached results and tell the frontend when filter inputs change
+pub fn start(app: AppHandle) {
+    let listener_app = app.clone();
+    changes::listen(&app, WATCHED_TABLES, move |change| {
+        if let Ok(mut pages) = listener_app.state::<FilterCache>().pages.lock() {
+            pages.clear();
+        }
+        let _ = listener_app.emit(FILTERS_INVALIDATED_EVENT, change);
+    });
+}
+
+async fn load(pool: &sqlx::SqlitePool, id: &str) -> Result<SavedFilter, String> {
+    let row: Option<(String, String, String, i64, i64)> = sqlx::query_as(
+        "SELECT id, name, definition, created_at, updated_at FROM saved_filters WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let (id, name, definition, created_at, updated_at) =
+        row.ok_or_else(|| format!("Filter not found: {}", id))?;
+    Ok(SavedFilter {
+        definition: serde_json::from_str(&definition)
+            .map_err(|e| format!("Invalid definition for filter {}: {}", name, e))?,
+        id,
+        name,
+        created_at,
+        updated_at,
+    })
+}
+
+#[crate::metrics::command]
+pub async fn list_filters(state: State<'_, DatabaseState>) -> Result<Vec<SavedFilter>, String> {
+    let pool = state.pool.lock().await;
+    let ids: Vec<(String,)> = sqlx::query_as("SELECT id FROM saved_filters ORDER BY name")
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut filters = Vec::with_capacity(ids.len());
+    for (id,) in ids {
+        filters.push(load(&pool, &id).await?);
+    }
+    Ok(filters)
+}
+
+/// Create a filter, or update it when `id` is given
+#[crate::metrics::command]
+pub async fn save_filter(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: Option<String>,
+    name: String,
+    definition: FilterDefinition,
+) -> Result<SavedFilter, String> {
+    if name.trim().is_empty() {
+        return Err("Filter name cannot be empty".to_string());
+    }
+    // Reject definitions that can't be compiled before storing them
+    compile(&definition, Local::now().date_naive(), 0)?;
+
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let now = Utc::now().timestamp_millis();
+    let saved = {
+        let pool = state.pool.lock().await;
+        sqlx::query(
+            "INSERT INTO saved_filters (id, name, definition, created_at, updated_at) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, definition = excluded.definition, updated_at = excluded.updated_at",
+        )
+        .bind(&id)
+        .bind(name.trim())
+        .bind(serde_json::to_string(&definition).map_err(|e| e.to_string())?)
+        .bind(now)
+        .bind(now)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        load(&pool, &id).await?
+    };
+    changes::notify(&app, vec!["saved_filters".to_string()]);
+    Ok(saved)
+}
+
+#[crate::metrics::command]
+pub async fn delete_filter(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: String,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        sqlx::query("DELETE FROM saved_filters WHERE id = ?")
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["saved_filters".to_string()]);
+    Ok(())
+}
+
+/// Todos matching a saved filter, served from cache until the data changes
+#[crate::metrics::command]
+pub async fn evaluate_filter(
+    state: State<'_, DatabaseState>,
+    cache: State<'_, FilterCache>,
+    id: String,
+    pagination: Option<Pagination>,
+) -> Result<FilterPage, String> {
+    let pagination = pagination.unwrap_or(Pagination {
+        offset: 0,
+        limit: DEFAULT_PAGE_SIZE,
+    });
+    let key = (id.clone(), pagination);
+    if let Some(page) = cache.pages.lock().map_err(|e| e.to_string())?.get(&key) {
+        return Ok(page.clone());
+    }
+
+    let page = {
+        let pool = state.pool.lock().await;
+        let filter = load(&pool, &id).await?;
+        run_filter(&pool, &filter.definition, pagination).await?
+    };
+    cache
+        .pages
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(key, page.clone());
+    Ok(page)
+}
+
+/// Evaluate an unsaved definition, e.g. while the user edits a filter
+#[crate::metrics::command]
+pub async fn preview_filter(
+    state: State<'_, DatabaseState>,
+    definition: FilterDefinition,
+    pagination: Option<Pagination>,
+) -> Result<FilterPage, String> {
+    let pool = state.pool.lock().await;
+    run_filter(
+        &pool,
+        &definition,
+        pagination.unwrap_or(Pagination {
+            offset: 0,
+            limit: DEFAULT_PAGE_SIZE,
+        }),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_combines_conditions() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let definition = FilterDefinition {
+            tags: vec!["#Work".to_string()],
+            date: Some(DateSpec::ThisWeek),
+            overdue: true,
+            ..Default::default()
+        };
+        let compiled = compile(&definition, today, 1000).unwrap();
+        assert_eq!(
+            compiled.binds,
+            vec![
+                Bind::Text("work".to_string()),
+                Bind::Text("2024-05-13".to_string()),
+                Bind::Text("2024-05-19".to_string()),
+                Bind::Text("2024-05-15".to_string()),
+                Bind::Int(1000),
+            ]
+        );
+        assert_eq!(
+            compiled.condition.matches('?').count(),
+            compiled.binds.len()
+        );
+    }
+
+    #[test]
+    fn test_relative_date_ranges() {
+        let today = NaiveDate::from_ymd_opt(2024, 2, 10).unwrap();
+        assert_eq!(
+            date_range(&DateSpec::ThisMonth, today),
+            ("2024-02-01".to_string(), "2024-02-29".to_string())
+        );
+        assert_eq!(
+            date_range(&DateSpec::LastDays { days: 7 }, today),
+            ("2024-02-04".to_string(), "2024-02-10".to_string())
+        );
+        let status = FilterDefinition {
+            status: Some("archived".to_string()),
+            ..Default::default()
+        };
+        assert!(compile(&status, today, 0).is_err());
+    }
+}
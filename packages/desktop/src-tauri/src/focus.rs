@@ -0,0 +1,524 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+
+use crate::app_config::AppConfig;
+use crate::db::DatabaseState;
+use crate::{confirm, logger, settings, timers};
+
+/// Hold notifications while a pomodoro is running
+const DURING_POMODORO_SETTING: &str = "notifications.quiet_during_pomodoro";
+/// Hold notifications while the OS is in do-not-disturb / focus / presentation mode
+const RESPECT_OS_SETTING: &str = "notifications.respect_do_not_disturb";
+
+/// Sites and apps blocked during a focus block
+const BLOCKED_SITES_SETTING: &str = "focus.blocked_sites";
+const BLOCKED_APPS_SETTING: &str = "focus.blocked_apps";
+/// The block list the user agreed to, in a native dialog, having the hosts file changed for and
+/// closing. Kept in the app config with the running block, since the webview can write any setting;
+/// a list that differs from it needs asking again.
+const CONSENT_KEY: &str = "focus.blocking_consent";
+/// The running focus block, kept so it can be undone after a crash
+const ACTIVE_BLOCK_KEY: &str = "focus.active_block";
+
+/// Lines of the hosts file between these markers belong to the app
+const HOSTS_BEGIN: &str = "# journal-todo focus begin";
+const HOSTS_END: &str = "# journal-todo focus end";
+const MAX_BLOCK_MINUTES: u32 = 8 * 60;
+const MAX_APP_NAME_LEN: usize = 64;
+/// How often blocked apps are closed again and the block's end is checked
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusBlock {
+    pub sites: Vec<String>,
+    pub apps: Vec<String>,
+    pub started_at: i64,
+    pub ends_at: i64,
+    /// Whether the sites were added to the hosts file, to be taken out again
+    pub hosts_changed: bool,
+}
+
+/// Sites and apps to block, as the user agreed to them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct BlockList {
+    sites: Vec<String>,
+    apps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusBlocking {
+    /// Whether the user agreed to blocking exactly the current sites and apps
+    pub consent: bool,
+    pub sites: Vec<String>,
+    pub apps: Vec<String>,
+    pub active: Option<FocusBlock>,
+}
+
+/// Why notifications are being held, if they are, phrased to follow "reminders ..."
+pub async fn quiet_reason(app: &AppHandle) -> Result<Option<&'static str>, String> {
+    let respect_os = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        if settings::get_or(&pool, DURING_POMODORO_SETTING, true).await {
+            let running = timers::active(&pool)
+                .await?
+                .iter()
+                .any(|session| session.kind == "pomodoro" && session.paused_at.is_none());
+            if running {
+                return Ok(Some("during your focus session"));
+            }
+        }
+        settings::get_or(&pool, RESPECT_OS_SETTING, true).await
+    };
+    if respect_os {
+        let os_quiet = tauri::async_runtime::spawn_blocking(os_do_not_disturb)
+            .await
+            .map_err(|e| e.to_string())?;
+        if os_quiet {
+            return Ok(Some("while Do Not Disturb was on"));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether macOS has a Focus mode on: it keeps an assertion record per active mode
+#[cfg(any(target_os = "macos", test))]
+fn focus_assertions_active(json: &str) -> bool {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return false;
+    };
+    value["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry["storeAssertionRecords"].as_array())
+        .any(|records| !records.is_empty())
+}
+
+/// Whether `gsettings` output means GNOME is hiding notification banners
+#[cfg(any(all(unix, not(target_os = "macos")), test))]
+fn banners_hidden(output: &str) -> bool {
+    output.trim() == "false"
+}
+
+#[cfg(target_os = "macos")]
+fn os_do_not_disturb() -> bool {
+    let Some(home) = dirs::home_dir() else {
+        return false;
+    };
+    std::fs::read_to_string(home.join("Library/DoNotDisturb/DB/Assertions.json"))
+        .map(|json| focus_assertions_active(&json))
+        .unwrap_or(false)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn os_do_not_disturb() -> bool {
+    std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .output()
+        .map(|output| output.status.success() && banners_hidden(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(false)
+}
+
+/// Quiet hours and presentation mode aren't readable without extra APIs here
+#[cfg(not(unix))]
+fn os_do_not_disturb() -> bool {
+    false
+}
+
+/// A site as a bare host name: `https://www.Example.com/feed` becomes `example.com`
+fn normalize_site(site: &str) -> Option<String> {
+    let site = site.trim().to_lowercase();
+    let host = site.split_once("://").map_or(site.as_str(), |(_, rest)| rest);
+    let host = host.split(['/', '?', '#', ':']).next().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host).trim_matches('.');
+    let valid = host.contains('.') && host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then(|| host.to_string())
+}
+
+/// An app name that is safe to hand to `osascript`, `taskkill` and `pkill`: letters, digits,
+/// spaces, `.`, `_` and `-`, starting with a letter or digit so it can't pass as an option
+fn valid_app_name(name: &str) -> bool {
+    name.len() <= MAX_APP_NAME_LEN
+        && name.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '.' | '_' | '-'))
+}
+
+/// The hosts file without the app's block; unchanged when it has none
+fn strip_hosts_block(hosts: &str) -> String {
+    if !hosts.lines().any(|line| line.trim() == HOSTS_BEGIN) {
+        return hosts.to_string();
+    }
+    let mut out = Vec::new();
+    let mut inside = false;
+    for line in hosts.lines() {
+        match line.trim() {
+            HOSTS_BEGIN => inside = true,
+            HOSTS_END => inside = false,
+            _ if !inside => out.push(line),
+            _ => {}
+        }
+    }
+    let newline = if hosts.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = out.join(newline).trim_end().to_string();
+    out.push_str(newline);
+    out
+}
+
+/// The hosts file with `sites` and their `www.` names pointing nowhere
+fn add_hosts_block(hosts: &str, sites: &[String]) -> String {
+    let newline = if hosts.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = strip_hosts_block(hosts);
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push_str(newline);
+    }
+    out.push_str(HOSTS_BEGIN);
+    out.push_str(newline);
+    for site in sites {
+        for name in [site.clone(), format!("www.{}", site)] {
+            out.push_str(&format!("0.0.0.0 {name}{newline}:: {name}{newline}"));
+        }
+    }
+    out.push_str(HOSTS_END);
+    out.push_str(newline);
+    out
+}
+
+fn hosts_path() -> PathBuf {
+    if cfg!(windows) {
+        let root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string());
+        PathBuf::from(root).join(r"System32\drivers\etc\hosts")
+    } else {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+/// Replace the hosts file, asking the OS for administrator rights when the app has none; the
+/// user can decline, which fails the change
+fn write_hosts(content: &str) -> Result<(), String> {
+    let path = hosts_path();
+    match std::fs::write(&path, content) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() != std::io::ErrorKind::PermissionDenied => {
+            return Err(format!("Failed to write {}: {}", path.display(), e))
+        }
+        Err(_) => {}
+    }
+    let staged = std::env::temp_dir().join(format!("journal-todo-hosts-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&staged, content).map_err(|e| e.to_string())?;
+    let (staged_path, hosts) = (staged.display().to_string(), path.display().to_string());
+    let status = if cfg!(target_os = "macos") {
+        let script = format!(
+            "do shell script \"cp \" & quoted form of \"{}\" & \" /etc/hosts\" with administrator privileges",
+            staged_path
+        );
+        Command::new("osascript").args(["-e", &script]).status()
+    } else if cfg!(windows) {
+        let script = format!(
+            "Start-Process -FilePath cmd -ArgumentList '/c copy /Y \"{}\" \"{}\"' -Verb RunAs -Wait \
+             -WindowStyle Hidden",
+            staged_path.replace('\'', "''"),
+            hosts.replace('\'', "''")
+        );
+        Command::new("powershell").args(["-NoProfile", "-Command", &script]).status()
+    } else {
+        Command::new("pkexec").arg("cp").arg(&staged).arg(&path).status()
+    };
+    let _ = std::fs::remove_file(&staged);
+    let written = status.is_ok_and(|status| status.success())
+        && std::fs::read_to_string(&path).is_ok_and(|written| written == content);
+    if !written {
+        return Err("Changing the hosts file was not permitted".to_string());
+    }
+    // Best effort: browsers may still hold cached addresses for a while
+    if cfg!(target_os = "macos") {
+        let _ = Command::new("dscacheutil").arg("-flushcache").status();
+    } else if cfg!(windows) {
+        let _ = Command::new("ipconfig").arg("/flushdns").status();
+    }
+    Ok(())
+}
+
+/// Ask a running app to quit, the way the user would; apps not running are left alone
+fn quit_app(name: &str) {
+    if !valid_app_name(name) {
+        logger::error(&format!("Not closing {:?}: not an app name", name));
+        return;
+    }
+    let status = if cfg!(target_os = "macos") {
+        let script = format!("if application \"{0}\" is running then tell application \"{0}\" to quit", name);
+        Command::new("osascript").args(["-e", &script]).output()
+    } else if cfg!(windows) {
+        let image = if name.to_lowercase().ends_with(".exe") { name.to_string() } else { format!("{}.exe", name) };
+        Command::new("taskkill").args(["/IM", &image]).output()
+    } else {
+        // pkill matches a regular expression, where `.` is the only character allowed in names
+        // that means something else
+        Command::new("pkill").args(["-x", &name.replace('.', "\\.")]).output()
+    };
+    if let Err(e) = status {
+        logger::error(&format!("Failed to close {}: {}", name, e));
+    }
+}
+
+fn active_block(app: &AppHandle) -> Option<FocusBlock> {
+    app.state::<AppConfig>().get(ACTIVE_BLOCK_KEY)
+}
+
+/// Undo a focus block: take the sites out of the hosts file and forget the block
+async fn end_block(app: &AppHandle, block: Option<&FocusBlock>) -> Result<(), String> {
+    // Without a record of the block, the hosts file is cleaned anyway in case one was lost
+    if block.is_none_or(|block| block.hosts_changed) {
+        tauri::async_runtime::spawn_blocking(|| {
+            let hosts = std::fs::read_to_string(hosts_path()).map_err(|e| e.to_string())?;
+            let stripped = strip_hosts_block(&hosts);
+            if stripped == hosts { Ok(()) } else { write_hosts(&stripped) }
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    app.state::<AppConfig>().remove(ACTIVE_BLOCK_KEY)
+}
+
+/// Keep blocked apps closed during a focus block and end it on time, also when the app was
+/// closed while it ran
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(ENFORCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(block) = active_block(&app) else {
+                continue;
+            };
+            if Utc::now().timestamp_millis() >= block.ends_at {
+                match end_block(&app, Some(&block)).await {
+                    Ok(()) => logger::info("Focus block ended"),
+                    Err(e) => logger::error(&format!("Failed to end the focus block: {}", e)),
+                }
+                continue;
+            }
+            let apps = block.apps.clone();
+            let _ = tauri::async_runtime::spawn_blocking(move || apps.iter().for_each(|name| quit_app(name))).await;
+        }
+    })
+}
+
+/// End a running focus block when the app quits, so nothing stays blocked without it
+pub async fn release(app: &AppHandle) -> Result<(), String> {
+    match active_block(app) {
+        Some(block) => end_block(app, Some(&block)).await,
+        None => Ok(()),
+    }
+}
+
+/// The block list, consent and running focus block
+#[crate::metrics::command]
+pub async fn get_focus_blocking(app: AppHandle, state: State<'_, DatabaseState>) -> Result<FocusBlocking, String> {
+    let config = app.state::<AppConfig>();
+    let pool = state.pool.lock().await;
+    let list = BlockList {
+        sites: settings::get_or(&pool, BLOCKED_SITES_SETTING, Vec::new()).await,
+        apps: settings::get_or(&pool, BLOCKED_APPS_SETTING, Vec::new()).await,
+    };
+    Ok(FocusBlocking {
+        consent: config.get::<BlockList>(CONSENT_KEY).is_some_and(|agreed| agreed == list),
+        sites: list.sites,
+        apps: list.apps,
+        active: config.get(ACTIVE_BLOCK_KEY),
+    })
+}
+
+/// Set the sites (host names or URLs) and apps (app names on macOS, process names elsewhere)
+/// blocked during focus blocks; takes effect from the next block, once the user agrees to the
+/// new list
+#[crate::metrics::command]
+pub async fn set_focus_blocklist(
+    app: AppHandle,
+    sites: Vec<String>,
+    apps: Vec<String>,
+) -> Result<FocusBlocking, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for site in &sites {
+        let host = normalize_site(site).ok_or_else(|| format!("Not a site: {}", site))?;
+        if !normalized.contains(&host) {
+            normalized.push(host);
+        }
+    }
+    let mut names: Vec<String> = Vec::new();
+    for name in apps.iter().map(|name| name.trim()).filter(|name| !name.is_empty()) {
+        if !valid_app_name(name) {
+            return Err(format!("Not an app name: {}", name));
+        }
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    settings::set(&app, BLOCKED_SITES_SETTING, &normalized).await?;
+    settings::set(&app, BLOCKED_APPS_SETTING, &names).await?;
+    get_focus_blocking(app.clone(), app.state()).await
+}
+
+/// Refuse a block list that didn't come through `set_focus_blocklist`: the settings can be
+/// written around it
+fn check_block_list(list: &BlockList) -> Result<(), String> {
+    if let Some(site) = list.sites.iter().find(|site| normalize_site(site).as_ref() != Some(*site)) {
+        return Err(format!("Not a site: {}", site));
+    }
+    if let Some(name) = list.apps.iter().find(|name| !valid_app_name(name)) {
+        return Err(format!("Not an app name: {}", name));
+    }
+    Ok(())
+}
+
+/// What the consent dialog asks, listing every site and app it covers
+fn consent_message(list: &BlockList) -> String {
+    let listed = |names: &[String]| if names.is_empty() { "none".to_string() } else { names.join(", ") };
+    format!(
+        "Allow focus blocks to add these sites to the hosts file and to close these apps while they run?\n\n\
+         Sites: {}\nApps: {}",
+        listed(&list.sites),
+        listed(&list.apps)
+    )
+}
+
+/// Ask the user in a native dialog for consent to focus blocks changing the hosts file for and
+/// closing the current block list, and record the list they agreed to. Withdrawing it needs no
+/// dialog and ends a running block.
+#[crate::metrics::command]
+pub async fn set_focus_blocking_consent(app: AppHandle, consent: bool) -> Result<(), String> {
+    if !consent {
+        if let Some(block) = active_block(&app) {
+            end_block(&app, Some(&block)).await?;
+        }
+        return app.state::<AppConfig>().remove(CONSENT_KEY);
+    }
+    let status = get_focus_blocking(app.clone(), app.state()).await?;
+    let list = BlockList { sites: status.sites, apps: status.apps };
+    check_block_list(&list)?;
+    if !confirm::ask("Focus blocking", &consent_message(&list)).await {
+        return Err("Focus blocking was not allowed".to_string());
+    }
+    app.state::<AppConfig>().set(CONSENT_KEY, &list)
+}
+
+/// Block the configured sites and apps for `minutes`. Needs the user's consent; the OS may
+/// also ask for administrator rights to change the hosts file.
+#[crate::metrics::command]
+pub async fn start_focus_blocking(app: AppHandle, minutes: u32) -> Result<FocusBlock, String> {
+    let status = get_focus_blocking(app.clone(), app.state()).await?;
+    if !status.consent {
+        return Err("Focus blocking needs your consent to the current sites and apps first".to_string());
+    }
+    if status.active.is_some() {
+        return Err("A focus block is already running".to_string());
+    }
+    if status.sites.is_empty() && status.apps.is_empty() {
+        return Err("No sites or apps to block".to_string());
+    }
+    check_block_list(&BlockList { sites: status.sites.clone(), apps: status.apps.clone() })?;
+    let minutes = minutes.clamp(1, MAX_BLOCK_MINUTES);
+    let started_at = Utc::now().timestamp_millis();
+    let block = FocusBlock {
+        hosts_changed: !status.sites.is_empty(),
+        sites: status.sites,
+        apps: status.apps,
+        started_at,
+        ends_at: started_at + minutes as i64 * 60_000,
+    };
+    // Recorded first, so a crash while the hosts file changes still gets cleaned up
+    app.state::<AppConfig>().set(ACTIVE_BLOCK_KEY, &block)?;
+    let (sites, apps) = (block.sites.clone(), block.apps.clone());
+    let applied = tauri::async_runtime::spawn_blocking(move || {
+        if !sites.is_empty() {
+            let hosts = std::fs::read_to_string(hosts_path()).map_err(|e| e.to_string())?;
+            write_hosts(&add_hosts_block(&hosts, &sites))?;
+        }
+        apps.iter().for_each(|name| quit_app(name));
+        Ok::<(), String>(())
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if let Err(e) = applied {
+        app.state::<AppConfig>().remove(ACTIVE_BLOCK_KEY)?;
+        return Err(e);
+    }
+    logger::info(&format!("Focus block started for {} minutes", minutes));
+    Ok(block)
+}
+
+/// End the focus block now and undo its changes, also when its record was lost
+#[crate::metrics::command]
+pub async fn emergency_stop_focus(app: AppHandle) -> Result<(), String> {
+    let block = active_block(&app);
+    end_block(&app, block.as_ref()).await?;
+    logger::info("Focus block stopped early");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_os_do_not_disturb_parsing() {
+        assert!(focus_assertions_active(
+            r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":{"assertionDetailsModeIdentifier":"com.apple.donotdisturb.mode.default"}}]}]}"#
+        ));
+        assert!(!focus_assertions_active(r#"{"data":[{"storeAssertionRecords":[]}]}"#));
+        assert!(!focus_assertions_active(r#"{"data":[{}]}"#));
+        assert!(!focus_assertions_active("not json"));
+
+        assert!(banners_hidden("false\n"));
+        assert!(!banners_hidden("true\n"));
+    }
+
+    #[test]
+    fn test_hosts_block_round_trip() {
+        assert_eq!(normalize_site("https://www.Example.com/feed?x"), Some("example.com".to_string()));
+        assert_eq!(normalize_site("news.ycombinator.com:443"), Some("news.ycombinator.com".to_string()));
+        assert_eq!(normalize_site("localhost"), None);
+
+        let hosts = "127.0.0.1 localhost\r\n::1 localhost\r\n";
+        let blocked = add_hosts_block(hosts, &["example.com".to_string()]);
+        assert!(blocked.contains("0.0.0.0 www.example.com\r\n:: www.example.com\r\n# journal-todo focus end\r\n"));
+        assert_eq!(add_hosts_block(&blocked, &["example.com".to_string()]), blocked);
+        assert_eq!(strip_hosts_block(&blocked), hosts);
+
+        // Without a block the file is left exactly as it is, so nothing needs writing
+        let untouched = "127.0.0.1 localhost\n\n# trailing comment  ";
+        assert_eq!(strip_hosts_block(untouched), untouched);
+        let blocked = add_hosts_block(untouched, &["example.com".to_string()]);
+        assert!(blocked.starts_with("127.0.0.1 localhost\n\n# trailing comment  \n# journal-todo focus begin\n"));
+    }
+
+    #[test]
+    fn test_app_names_are_checked() {
+        for name in ["Slack", "Google Chrome", "steam.exe", "discord_canary", "com.valvesoftware.Steam"] {
+            assert!(valid_app_name(name), "{name}");
+        }
+        let too_long = "a".repeat(MAX_APP_NAME_LEN + 1);
+        for name in ["", "-f", "/IM", "a\"b", "x*", "Slack; rm -rf ~", "a\nb", "é", too_long.as_str()] {
+            assert!(!valid_app_name(name), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn test_consent_lists_what_is_blocked() {
+        let list = BlockList { sites: vec!["example.com".to_string()], apps: Vec::new() };
+        let message = consent_message(&list);
+        assert!(message.contains("Sites: example.com\nApps: none"));
+
+        assert!(check_block_list(&list).is_ok());
+        let injected = BlockList { sites: vec!["example.com\n1.2.3.4 bank.com".to_string()], apps: Vec::new() };
+        assert!(check_block_list(&injected).is_err());
+        let injected = BlockList { sites: Vec::new(), apps: vec!["-9 1".to_string()] };
+        assert!(check_block_list(&injected).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Port of the `fractional-indexing` package used by the frontend for todo order keys,
+//! so keys generated here sort the same way as keys generated in JS.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const ZERO: u8 = DIGITS[0];
+const SMALLEST_INTEGER: &str = "A00000000000000000000000000";
+
+fn digit_index(c: u8) -> Result<usize, String> {
+    DIGITS
+        .iter()
+        .position(|&d| d == c)
+        .ok_or_else(|| format!("invalid order key digit: {}", c as char))
+}
+
+fn integer_length(head: u8) -> Result<usize, String> {
+    match head {
+        b'a'..=b'z' => Ok((head - b'a') as usize + 2),
+        b'A'..=b'Z' => Ok((b'Z' - head) as usize + 2),
+        _ => Err(format!("invalid order key head: {}", head as char)),
+    }
+}
+
+fn integer_part(key: &str) -> Result<&str, String> {
+    let head = *key.as_bytes().first().ok_or("empty order key")?;
+    let length = integer_length(head)?;
+    if length > key.len() {
+        return Err(format!("invalid order key: {}", key));
+    }
+    Ok(&key[..length])
+}
+
+fn validate_key(key: &str) -> Result<(), String> {
+    if key == SMALLEST_INTEGER {
+        return Err(format!("invalid order key: {}", key));
+    }
+    let integer = integer_part(key)?;
+    if key.as_bytes()[integer.len()..].last() == Some(&ZERO) {
+        return Err(format!("invalid order key: {}", key));
+    }
+    key.bytes().try_for_each(|c| digit_index(c).map(|_| ()))
+}
+
+/// A digit string strictly between `a` and `b` (`None` meaning the end)
+fn midpoint(a: &str, b: Option<&str>) -> Result<String, String> {
+    if let Some(b) = b {
+        if a >= b {
+            return Err(format!("{} >= {}", a, b));
+        }
+    }
+    if a.as_bytes().last() == Some(&ZERO) || b.and_then(|b| b.as_bytes().last()) == Some(&ZERO) {
+        return Err("trailing zero".to_string());
+    }
+
+    if let Some(b) = b {
+        let (ab, bb) = (a.as_bytes(), b.as_bytes());
+        let mut n = 0;
+        while n < bb.len() && ab.get(n).copied().unwrap_or(ZERO) == bb[n] {
+            n += 1;
+        }
+        if n > 0 {
+            let rest_a = if n < a.len() { &a[n..] } else { "" };
+            return Ok(format!("{}{}", &b[..n], midpoint(rest_a, Some(&b[n..]))?));
+        }
+    }
+
+    let digit_a = match a.as_bytes().first() {
+        Some(&c) => digit_index(c)?,
+        None => 0,
+    };
+    let digit_b = match b.and_then(|b| b.as_bytes().first()) {
+        Some(&c) => digit_index(c)?,
+        None => DIGITS.len(),
+    };
+
+    if digit_b - digit_a > 1 {
+        let mid = (digit_a + digit_b).div_ceil(2);
+        Ok((DIGITS[mid] as char).to_string())
+    } else if let Some(b) = b.filter(|b| b.len() > 1) {
+        Ok(b[..1].to_string())
+    } else {
+        let rest = if a.is_empty() { "" } else { &a[1..] };
+        Ok(format!("{}{}", DIGITS[digit_a] as char, midpoint(rest, None)?))
+    }
+}
+
+fn increment_integer(x: &str) -> Result<Option<String>, String> {
+    let bytes = x.as_bytes();
+    if bytes.len() != integer_length(bytes[0])? {
+        return Err(format!("invalid integer part: {}", x));
+    }
+    let head = bytes[0];
+    let mut digits = bytes[1..].to_vec();
+
+    let mut carry = true;
+    for d in digits.iter_mut().rev() {
+        let next = digit_index(*d)? + 1;
+        if next == DIGITS.len() {
+            *d = ZERO;
+        } else {
+            *d = DIGITS[next];
+            carry = false;
+            break;
+        }
+    }
+
+    if !carry {
+        return Ok(Some(format!("{}{}", head as char, String::from_utf8_lossy(&digits))));
+    }
+    match head {
+        b'Z' => Ok(Some(format!("a{}", ZERO as char))),
+        b'z' => Ok(None),
+        _ => {
+            let h = head + 1;
+            if h > b'a' {
+                digits.push(ZERO);
+            } else {
+                digits.pop();
+            }
+            Ok(Some(format!("{}{}", h as char, String::from_utf8_lossy(&digits))))
+        }
+    }
+}
+
+fn decrement_integer(x: &str) -> Result<Option<String>, String> {
+    let bytes = x.as_bytes();
+    if bytes.len() != integer_length(bytes[0])? {
+        return Err(format!("invalid integer part: {}", x));
+    }
+    let head = bytes[0];
+    let last = DIGITS[DIGITS.len() - 1];
+    let mut digits = bytes[1..].to_vec();
+
+    let mut borrow = true;
+    for d in digits.iter_mut().rev() {
+        let index = digit_index(*d)?;
+        if index == 0 {
+            *d = last;
+        } else {
+            *d = DIGITS[index - 1];
+            borrow = false;
+            break;
+        }
+    }
+
+    if !borrow {
+        return Ok(Some(format!("{}{}", head as char, String::from_utf8_lossy(&digits))));
+    }
+    match head {
+        b'a' => Ok(Some(format!("Z{}", last as char))),
+        b'A' => Ok(None),
+        _ => {
+            let h = head - 1;
+            if h < b'Z' {
+                digits.push(last);
+            } else {
+                digits.pop();
+            }
+            Ok(Some(format!("{}{}", h as char, String::from_utf8_lossy(&digits))))
+        }
+    }
+}
+
+/// Generate an order key that sorts strictly between `a` and `b`.
+/// `None` means the start (for `a`) or the end (for `b`) of the list.
+pub fn key_between(a: Option<&str>, b: Option<&str>) -> Result<String, String> {
+    if let Some(a) = a {
+        validate_key(a)?;
+    }
+    if let Some(b) = b {
+        validate_key(b)?;
+    }
+    if let (Some(a), Some(b)) = (a, b) {
+        if a >= b {
+            return Err(format!("{} >= {}", a, b));
+        }
+    }
+
+    match (a, b) {
+        (None, None) => Ok(format!("a{}", ZERO as char)),
+        (None, Some(b)) => {
+            let ib = integer_part(b)?;
+            let fb = &b[ib.len()..];
+            if ib == SMALLEST_INTEGER {
+                return Ok(format!("{}{}", ib, midpoint("", Some(fb))?));
+            }
+            if ib < b {
+                return Ok(ib.to_string());
+            }
+            decrement_integer(ib)?.ok_or_else(|| "cannot decrement any more".to_string())
+        }
+        (Some(a), None) => {
+            let ia = integer_part(a)?;
+            let fa = &a[ia.len()..];
+            match increment_integer(ia)? {
+                Some(i) => Ok(i),
+                None => Ok(format!("{}{}", ia, midpoint(fa, None)?)),
+            }
+        }
+        (Some(a), Some(b)) => {
+            let ia = integer_part(a)?;
+            let fa = &a[ia.len()..];
+            let ib = integer_part(b)?;
+            let fb = &b[ib.len()..];
+            if ia == ib {
+                return Ok(format!("{}{}", ia, midpoint(fa, Some(fb))?));
+            }
+            let i = increment_integer(ia)?.ok_or_else(|| "cannot increment any more".to_string())?;
+            if i.as_str() < b {
+                Ok(i)
+            } else {
+                Ok(format!("{}{}", ia, midpoint(fa, None)?))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_between_matches_js_implementation() {
+        assert_eq!(key_between(None, None).unwrap(), "a0");
+        assert_eq!(key_between(Some("a0"), None).unwrap(), "a1");
+        assert_eq!(key_between(None, Some("a0")).unwrap(), "Zz");
+        assert_eq!(key_between(Some("a0"), Some("a1")).unwrap(), "a0V");
+        assert_eq!(key_between(Some("a1"), Some("a2")).unwrap(), "a1V");
+        assert_eq!(key_between(Some("a0V"), Some("a1")).unwrap(), "a0l");
+        assert_eq!(key_between(Some("az"), None).unwrap(), "b00");
+        assert_eq!(key_between(Some("Zz"), Some("a0")).unwrap(), "ZzV");
+    }
+
+    #[test]
+    fn test_key_between_rejects_invalid_input() {
+        assert!(key_between(Some("a1"), Some("a0")).is_err());
+        assert!(key_between(Some("a00"), None).is_err());
+        assert!(key_between(Some("!"), None).is_err());
+    }
+}
@@ -0,0 +1,410 @@
+use chrono::{Local, NaiveDate};
+use git2::{Commit, ErrorCode, IndexAddOption, Repository, Signature};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::db::DatabaseState;
+use crate::hooks::{self, HookEvent};
+use crate::sync::{self, SyncState};
+use crate::{logger, settings};
+
+/// Directory of the repository entries are exported to; created and initialized if missing
+const REPO_PATH_SETTING: &str = "git_export.repo_path";
+/// Export and commit on the schedule, not only on demand
+const AUTO_SETTING: &str = "git_export.auto";
+
+const COMMITTER_NAME: &str = "Journal Todo";
+const COMMITTER_EMAIL: &str = "journal-todo@localhost";
+/// Name the export reports its sync status under
+const SYNC_PROVIDER: &str = "git";
+/// Files written between progress reports
+const PROGRESS_EVERY: usize = 50;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ExportPage {
+    workspace_id: String,
+    date: String,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct ExportTodo {
+    pub workspace_id: String,
+    pub page_date: String,
+    pub text: String,
+    pub status: String,
+    pub level: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitExportReport {
+    pub written: usize,
+    pub removed: usize,
+    /// None when nothing changed since the last export
+    pub commit: Option<String>,
+}
+
+/// An entry's file in the last export commit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedCopy {
+    pub commit: String,
+    pub path: String,
+}
+
+/// One entry as Markdown: the date as heading, the notes, then the todos as a nested checklist
+pub(crate) fn render(date: &str, notes: Option<&str>, todos: &[&ExportTodo]) -> String {
+    let title = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|day| day.format("%A, %B %-d, %Y").to_string())
+        .unwrap_or_else(|_| date.to_string());
+    let mut out = format!("# {}\n", title);
+    if let Some(notes) = notes.map(str::trim).filter(|notes| !notes.is_empty()) {
+        out.push('\n');
+        out.push_str(notes);
+        out.push('\n');
+    }
+    if !todos.is_empty() {
+        out.push_str("\n## Todos\n\n");
+        for todo in todos {
+            let check = if todo.status == "done" { 'x' } else { ' ' };
+            let indent = "  ".repeat(todo.level.max(0) as usize);
+            out.push_str(&format!("{}- [{}] {}\n", indent, check, todo.text.trim()));
+        }
+    }
+    out
+}
+
+/// Folder name for a workspace; names that clash get the id appended
+fn workspace_dirs(workspaces: &[(String, String)]) -> HashMap<String, String> {
+    let mut seen = HashSet::new();
+    workspaces
+        .iter()
+        .map(|(id, name)| {
+            let mut dir: String = name
+                .chars()
+                .map(|c| if c.is_alphanumeric() || " -_".contains(c) { c } else { '-' })
+                .collect::<String>()
+                .trim()
+                .to_string();
+            if dir.is_empty() || !seen.insert(dir.to_lowercase()) {
+                dir = format!("{} {}", dir, id).trim().to_string();
+            }
+            (id.clone(), dir)
+        })
+        .collect()
+}
+
+/// Whether `path`, relative to the repo, is an `<workspace>/<year>/<date>.md` file this export writes
+fn is_exported_file(relative: &Path) -> bool {
+    let parts: Vec<&str> = relative.iter().filter_map(|part| part.to_str()).collect();
+    let [_, year, file] = parts.as_slice() else {
+        return false;
+    };
+    file.strip_suffix(".md")
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .is_some_and(|day| day.format("%Y").to_string() == *year)
+}
+
+/// Exported files currently in the repo, relative to it
+fn exported_files(repo_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let children = |dir: &Path| -> Result<Vec<std::fs::DirEntry>, String> {
+        match std::fs::read_dir(dir) {
+            Ok(entries) => entries.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    };
+    for workspace in children(repo_dir)? {
+        if workspace.file_name() == ".git" || !workspace.path().is_dir() {
+            continue;
+        }
+        for year in children(&workspace.path())?.into_iter().filter(|year| year.path().is_dir()) {
+            for file in children(&year.path())? {
+                if let Ok(relative) = file.path().strip_prefix(repo_dir) {
+                    if is_exported_file(relative) {
+                        files.push(relative.to_path_buf());
+                    }
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Stage everything and commit it, if the tree changed; returns the new commit's id
+fn commit_all(repo_dir: &Path, message: &str) -> Result<Option<String>, git2::Error> {
+    let repo = match Repository::open(repo_dir) {
+        Ok(repo) => repo,
+        Err(e) if e.code() == ErrorCode::NotFound => Repository::init(repo_dir)?,
+        Err(e) => return Err(e),
+    };
+    let mut index = repo.index()?;
+    index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+    index.update_all(["*"], None)?;
+    index.write()?;
+    let tree_id = index.write_tree()?;
+
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let unchanged = match &parent {
+        Some(parent) => parent.tree_id() == tree_id,
+        None => index.is_empty(),
+    };
+    if unchanged {
+        return Ok(None);
+    }
+
+    let tree = repo.find_tree(tree_id)?;
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now(COMMITTER_NAME, COMMITTER_EMAIL))?;
+    let parents: Vec<&Commit> = parent.iter().collect();
+    let id = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+    Ok(Some(id.to_string()))
+}
+
+/// Write every entry as Markdown under `repo_dir`, drop files of entries that are gone, and commit.
+/// Private and locked entries aren't exported, so a plaintext mirror never holds what the app hides.
+async fn export(app: &AppHandle, repo_dir: PathBuf) -> Result<GitExportReport, String> {
+    sync::report(app, SYNC_PROVIDER, SyncState::Syncing);
+    let result = write_export(app, repo_dir).await;
+    match &result {
+        Ok(_) => sync::report(app, SYNC_PROVIDER, SyncState::Idle),
+        Err(e) => sync::report(app, SYNC_PROVIDER, SyncState::Error { message: e.clone() }),
+    }
+    result
+}
+
+async fn write_export(app: &AppHandle, repo_dir: PathBuf) -> Result<GitExportReport, String> {
+    let (workspaces, pages, todos) = {
+        let state = app.state::<DatabaseState>();
+        // One snapshot, so the pages and todos exported belong together
+        let mut snapshot = state.open_reporting_snapshot().await?;
+        let workspaces: Vec<(String, String)> = sqlx::query_as("SELECT id, name FROM workspaces ORDER BY created_at")
+            .fetch_all(&mut *snapshot)
+            .await
+            .map_err(|e| e.to_string())?;
+        let pages: Vec<ExportPage> = sqlx::query_as(
+            "SELECT p.workspace_id, p.date, COALESCE(p.notes, zstd_decompress(p.notes_zstd)) AS notes FROM pages p
+             WHERE NOT EXISTS (SELECT 1 FROM private_pages pp WHERE pp.workspace_id = p.workspace_id AND pp.page_date = p.date)
+               AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = p.workspace_id AND lp.page_date = p.date)",
+        )
+        .fetch_all(&mut *snapshot)
+        .await
+        .map_err(|e| e.to_string())?;
+        let todos: Vec<ExportTodo> = sqlx::query_as(
+            "SELECT workspace_id, page_date, text, status, level FROM todos ORDER BY workspace_id, page_date, `order`",
+        )
+        .fetch_all(&mut *snapshot)
+        .await
+        .map_err(|e| e.to_string())?;
+        (workspaces, pages, todos)
+    };
+
+    let dirs = workspace_dirs(&workspaces);
+    let mut by_page: HashMap<(&str, &str), Vec<&ExportTodo>> = HashMap::new();
+    for todo in &todos {
+        by_page
+            .entry((todo.workspace_id.as_str(), todo.page_date.as_str()))
+            .or_default()
+            .push(todo);
+    }
+    let mut files: HashMap<PathBuf, String> = HashMap::new();
+    for page in &pages {
+        let todos = by_page
+            .get(&(page.workspace_id.as_str(), page.date.as_str()))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let has_notes = page.notes.as_deref().is_some_and(|notes| !notes.trim().is_empty());
+        let (Some(dir), Some(year)) = (dirs.get(&page.workspace_id), page.date.get(..4)) else {
+            continue;
+        };
+        if has_notes || !todos.is_empty() {
+            let path = PathBuf::from(dir).join(year).join(format!("{}.md", page.date));
+            files.insert(path, render(&page.date, page.notes.as_deref(), todos));
+        }
+    }
+
+    let message = format!("Journal export {}", Local::now().format("%Y-%m-%d %H:%M"));
+    let progress_app = app.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let mut report = GitExportReport::default();
+        for stale in exported_files(&repo_dir)?.into_iter().filter(|path| !files.contains_key(path)) {
+            std::fs::remove_file(repo_dir.join(&stale)).map_err(|e| e.to_string())?;
+            report.removed += 1;
+        }
+        let total = Some(files.len() as u64);
+        for (index, (relative, contents)) in files.iter().enumerate() {
+            if index % PROGRESS_EVERY == 0 {
+                let state = SyncState::Progress { done: index as u64, total };
+                sync::report(&progress_app, SYNC_PROVIDER, state);
+            }
+            let path = repo_dir.join(relative);
+            if std::fs::read_to_string(&path).is_ok_and(|existing| existing == *contents) {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, contents).map_err(|e| e.to_string())?;
+            report.written += 1;
+        }
+        report.commit = commit_all(&repo_dir, &message).map_err(|e| e.to_string())?;
+        Ok::<_, String>(report)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    if report.commit.is_some() {
+        hooks::fire(app, HookEvent::GitExportFinished, &report);
+    }
+    Ok(report)
+}
+
+async fn repo_path(app: &AppHandle) -> Result<Option<PathBuf>, String> {
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    Ok(settings::get::<String>(&pool, REPO_PATH_SETTING)
+        .await?
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from))
+}
+
+/// Export the journal to the configured repository and commit it now
+#[crate::metrics::command]
+pub async fn export_journal_to_git(app: AppHandle) -> Result<GitExportReport, String> {
+    let repo_dir = repo_path(&app)
+        .await?
+        .ok_or_else(|| format!("No repository configured in {}", REPO_PATH_SETTING))?;
+    let report = export(&app, repo_dir).await?;
+    if let Some(commit) = &report.commit {
+        logger::info(&format!("Committed journal export {}", commit));
+    }
+    Ok(report)
+}
+
+/// Files of the given entries (workspace id and date) in the repository's last commit
+fn find_copies(
+    repo_dir: &Path,
+    paths: Vec<((String, String), String)>,
+) -> Result<HashMap<(String, String), ExportedCopy>, git2::Error> {
+    let repo = match Repository::open(repo_dir) {
+        Ok(repo) => repo,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    let commit = match repo.head() {
+        Ok(head) => head.peel_to_commit()?,
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    let tree = commit.tree()?;
+    let commit = commit.id().to_string();
+    Ok(paths
+        .into_iter()
+        .filter(|(_, path)| tree.get_path(Path::new(path)).is_ok())
+        .map(|(entry, path)| {
+            let copy = ExportedCopy {
+                commit: commit.clone(),
+                path,
+            };
+            (entry, copy)
+        })
+        .collect())
+}
+
+/// Where the last export holds each of the given entries, keyed by workspace id and date.
+/// Empty when no repository is configured; private and locked entries are never in it.
+pub(crate) async fn exported_copies(
+    app: &AppHandle,
+    entries: &[(String, String)],
+) -> Result<HashMap<(String, String), ExportedCopy>, String> {
+    let Some(repo_dir) = repo_path(app).await? else {
+        return Ok(HashMap::new());
+    };
+    let workspaces: Vec<(String, String)> = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        sqlx::query_as("SELECT id, name FROM workspaces ORDER BY created_at")
+            .fetch_all(&*pool)
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    let dirs = workspace_dirs(&workspaces);
+    let paths: Vec<((String, String), String)> = entries
+        .iter()
+        .filter_map(|(workspace_id, date)| {
+            let path = format!("{}/{}/{}.md", dirs.get(workspace_id)?, date.get(..4)?, date);
+            Some(((workspace_id.clone(), date.clone()), path))
+        })
+        .collect();
+    tauri::async_runtime::spawn_blocking(move || find_copies(&repo_dir, paths).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Scheduled export, when a repository is configured and automatic commits are on
+pub async fn export_job(app: AppHandle) -> Result<(), String> {
+    let auto = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        settings::get_or(&pool, AUTO_SETTING, true).await
+    };
+    let Some(repo_dir) = repo_path(&app).await?.filter(|_| auto) else {
+        return Ok(());
+    };
+    let report = export(&app, repo_dir).await?;
+    if let Some(commit) = &report.commit {
+        logger::info(&format!("Committed journal export {}", commit));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(text: &str, status: &str, level: i64) -> ExportTodo {
+        ExportTodo {
+            workspace_id: "w".to_string(),
+            page_date: "2024-03-04".to_string(),
+            text: text.to_string(),
+            status: status.to_string(),
+            level,
+        }
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let todos = [todo("Plan week", "done", 0), todo("Book room", "todo", 1)];
+        let todos: Vec<&ExportTodo> = todos.iter().collect();
+        assert_eq!(
+            render("2024-03-04", Some("Good start.\n"), &todos),
+            "# Monday, March 4, 2024\n\nGood start.\n\n## Todos\n\n- [x] Plan week\n  - [ ] Book room\n"
+        );
+        assert_eq!(render("2024-03-04", Some("  "), &[]), "# Monday, March 4, 2024\n");
+    }
+
+    #[test]
+    fn test_export_paths() {
+        let dirs = workspace_dirs(&[
+            ("a".to_string(), "Work/Life".to_string()),
+            ("b".to_string(), "work-life".to_string()),
+            ("c".to_string(), ".git".to_string()),
+        ]);
+        assert_eq!(dirs["a"], "Work-Life");
+        assert_eq!(dirs["b"], "work-life b");
+        assert_eq!(dirs["c"], "-git");
+
+        assert!(is_exported_file(Path::new("Work/2024/2024-03-04.md")));
+        assert!(!is_exported_file(Path::new("Work/2023/2024-03-04.md")));
+        assert!(!is_exported_file(Path::new("Work/2024/notes.md")));
+        assert!(!is_exported_file(Path::new("2024-03-04.md")));
+    }
+}
@@ -0,0 +1,396 @@
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::dispatch;
+use crate::filters::{date_range, DateSpec};
+use crate::timers::TimeSession;
+
+const GOAL_COLUMNS: &str = "id, workspace_id, name, target_date, target_minutes, archived, created_at, updated_at";
+
+/// Todos of a goal's workspace on days `?2` to `?3` that its links (goal `?1`) pick out, with
+/// the weight of the link that picked them
+const LINKED_TODOS: &str = "SELECT l.kind, l.target, l.weight, t.id AS todo_id, t.status
+     FROM goal_links l
+     JOIN goals g ON g.id = l.goal_id
+     JOIN todos t ON t.workspace_id = g.workspace_id
+      AND ((l.kind = 'todo' AND t.id = l.target)
+        OR (l.kind = 'tag' AND EXISTS (SELECT 1 FROM json_each(t.tags) WHERE value = l.target)))
+     WHERE l.goal_id = ?1 AND t.page_date BETWEEN ?2 AND ?3 AND trim(t.text) != ''";
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Goal {
+    pub id: String,
+    pub workspace_id: String,
+    pub name: String,
+    pub target_date: Option<String>,
+    /// Time the goal should take, for the time-invested progress
+    pub target_minutes: Option<i64>,
+    pub archived: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// What counts towards a goal: one todo, or every todo carrying a tag (e.g. a recurring habit)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalLink {
+    /// "todo" or "tag"
+    pub kind: String,
+    pub target: String,
+    /// How much its todos count relative to the goal's other links
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkProgress {
+    #[serde(flatten)]
+    pub link: GoalLink,
+    pub todos: usize,
+    pub done: usize,
+    pub time_invested_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalProgress {
+    pub goal_id: String,
+    pub from: String,
+    pub to: String,
+    pub todos: usize,
+    pub done: usize,
+    /// Done todos' share of all linked todos, each counted by its link's weight; 0 to 1
+    pub weighted_completion: f64,
+    /// Tracked time on the linked todos, pauses excluded
+    pub time_invested_ms: i64,
+    /// Time invested against `target_minutes`, when the goal has one; may exceed 1
+    pub time_progress: Option<f64>,
+    pub links: Vec<LinkProgress>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Summary {
+    todos: usize,
+    done: usize,
+    weighted_completion: f64,
+    time_invested_ms: i64,
+    time_progress: Option<f64>,
+    links: Vec<LinkProgress>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct LinkedTodo {
+    kind: String,
+    target: String,
+    weight: f64,
+    todo_id: String,
+    status: String,
+}
+
+/// Progress from the todos each link picks out and the tracked time per todo. A todo picked
+/// out by several links counts once towards the total, with the highest of their weights.
+fn summarize(
+    links: Vec<GoalLink>,
+    linked: &[LinkedTodo],
+    time_by_todo: &HashMap<String, i64>,
+    target_minutes: Option<i64>,
+) -> Summary {
+    let mut todos: HashMap<&str, (f64, bool)> = HashMap::new();
+    for todo in linked {
+        let entry = todos.entry(&todo.todo_id).or_insert((todo.weight, todo.status == "done"));
+        entry.0 = entry.0.max(todo.weight);
+    }
+    let total_weight: f64 = todos.values().map(|(weight, _)| weight).sum();
+    let done_weight: f64 = todos.values().filter(|(_, done)| *done).map(|(weight, _)| weight).sum();
+    let weighted_completion = if total_weight > 0.0 { done_weight / total_weight } else { 0.0 };
+    let time_invested_ms: i64 = todos.keys().filter_map(|id| time_by_todo.get(*id)).sum();
+    let time_progress = target_minutes
+        .filter(|minutes| *minutes > 0)
+        .map(|minutes| time_invested_ms as f64 / (minutes * 60_000) as f64);
+
+    let links = links
+        .into_iter()
+        .map(|link| {
+            let matched: Vec<&LinkedTodo> = linked
+                .iter()
+                .filter(|todo| todo.kind == link.kind && todo.target == link.target)
+                .collect();
+            LinkProgress {
+                todos: matched.len(),
+                done: matched.iter().filter(|todo| todo.status == "done").count(),
+                time_invested_ms: matched.iter().filter_map(|todo| time_by_todo.get(&todo.todo_id)).sum(),
+                link,
+            }
+        })
+        .collect();
+    Summary {
+        todos: todos.len(),
+        done: todos.values().filter(|(_, done)| *done).count(),
+        weighted_completion,
+        time_invested_ms,
+        time_progress,
+        links,
+    }
+}
+
+async fn load(pool: &SqlitePool, id: &str) -> Result<Goal, String> {
+    sqlx::query_as(&format!("SELECT {} FROM goals WHERE id = ?", GOAL_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Goal not found: {}", id))
+}
+
+async fn links(pool: &SqlitePool, goal_id: &str) -> Result<Vec<GoalLink>, String> {
+    sqlx::query_as("SELECT kind, target, weight FROM goal_links WHERE goal_id = ? ORDER BY kind, target")
+        .bind(goal_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Goals of the current workspace, archived ones last
+#[crate::metrics::command]
+pub async fn list_goals(state: State<'_, DatabaseState>) -> Result<Vec<Goal>, String> {
+    let pool = state.pool.lock().await;
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let workspace_id = dispatch::current_workspace_id(&mut conn).await?;
+    sqlx::query_as(&format!(
+        "SELECT {} FROM goals WHERE workspace_id = ? ORDER BY archived, name",
+        GOAL_COLUMNS
+    ))
+    .bind(&workspace_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Create a goal in the current workspace, or update the one with `id`
+#[crate::metrics::command]
+pub async fn save_goal(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: Option<String>,
+    name: String,
+    target_date: Option<String>,
+    target_minutes: Option<i64>,
+    archived: Option<bool>,
+) -> Result<Goal, String> {
+    if name.trim().is_empty() {
+        return Err("Goal name cannot be empty".to_string());
+    }
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let now = Utc::now().timestamp_millis();
+    let goal = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        let workspace_id = dispatch::current_workspace_id(&mut conn).await?;
+        sqlx::query(
+            "INSERT INTO goals (id, workspace_id, name, target_date, target_minutes, archived, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, target_date = excluded.target_date,
+               target_minutes = excluded.target_minutes, archived = excluded.archived, updated_at = excluded.updated_at",
+        )
+        .bind(&id)
+        .bind(&workspace_id)
+        .bind(name.trim())
+        .bind(&target_date)
+        .bind(target_minutes)
+        .bind(archived.unwrap_or(false))
+        .bind(now)
+        .bind(now)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        drop(conn);
+        load(&pool, &id).await?
+    };
+    changes::notify(&app, vec!["goals".to_string()]);
+    Ok(goal)
+}
+
+#[crate::metrics::command]
+pub async fn delete_goal(app: AppHandle, state: State<'_, DatabaseState>, id: String) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        for sql in ["DELETE FROM goal_links WHERE goal_id = ?", "DELETE FROM goals WHERE id = ?"] {
+            sqlx::query(sql)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["goals".to_string(), "goal_links".to_string()]);
+    Ok(())
+}
+
+/// Count a todo or a tag towards a goal, replacing the weight if it already counts
+#[crate::metrics::command]
+pub async fn link_goal(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    goal_id: String,
+    link: GoalLink,
+) -> Result<Vec<GoalLink>, String> {
+    let target = match link.kind.as_str() {
+        "todo" => link.target.trim().to_string(),
+        "tag" => link.target.trim().trim_start_matches('#').to_lowercase(),
+        kind => return Err(format!("Invalid goal link kind: {}", kind)),
+    };
+    if target.is_empty() {
+        return Err("Goal link target cannot be empty".to_string());
+    }
+    if link.weight.is_nan() || link.weight <= 0.0 {
+        return Err("Goal link weight must be positive".to_string());
+    }
+    let linked = {
+        let pool = state.pool.lock().await;
+        load(&pool, &goal_id).await?;
+        sqlx::query(
+            "INSERT INTO goal_links (goal_id, kind, target, weight) VALUES (?, ?, ?, ?)
+             ON CONFLICT(goal_id, kind, target) DO UPDATE SET weight = excluded.weight",
+        )
+        .bind(&goal_id)
+        .bind(&link.kind)
+        .bind(&target)
+        .bind(link.weight)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        links(&pool, &goal_id).await?
+    };
+    changes::notify(&app, vec!["goal_links".to_string()]);
+    Ok(linked)
+}
+
+#[crate::metrics::command]
+pub async fn unlink_goal(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    goal_id: String,
+    kind: String,
+    target: String,
+) -> Result<Vec<GoalLink>, String> {
+    let linked = {
+        let pool = state.pool.lock().await;
+        sqlx::query("DELETE FROM goal_links WHERE goal_id = ? AND kind = ? AND target = ?")
+            .bind(&goal_id)
+            .bind(&kind)
+            .bind(&target)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        links(&pool, &goal_id).await?
+    };
+    changes::notify(&app, vec!["goal_links".to_string()]);
+    Ok(linked)
+}
+
+/// A goal's progress over the days in `range` (all time when unset): weighted completion of
+/// its linked todos and the time tracked on them
+#[crate::metrics::command]
+pub async fn get_goal_progress(
+    state: State<'_, DatabaseState>,
+    id: String,
+    range: Option<DateSpec>,
+) -> Result<GoalProgress, String> {
+    let (from, to) = match &range {
+        Some(spec) => date_range(spec, Local::now().date_naive()),
+        None => ("0000-00-00".to_string(), "9999-99-99".to_string()),
+    };
+    let pool = state.pool.lock().await;
+    let goal = load(&pool, &id).await?;
+    let goal_links = links(&pool, &id).await?;
+    let linked: Vec<LinkedTodo> = sqlx::query_as(LINKED_TODOS)
+        .bind(&id)
+        .bind(&from)
+        .bind(&to)
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let sessions: Vec<TimeSession> = sqlx::query_as(&format!(
+        "SELECT id, todo_id, kind, started_at, ended_at, paused_at, paused_ms FROM time_sessions
+         WHERE todo_id IN (SELECT todo_id FROM ({}))",
+        LINKED_TODOS
+    ))
+    .bind(&id)
+    .bind(&from)
+    .bind(&to)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    drop(pool);
+
+    let now = Utc::now().timestamp_millis();
+    let mut time_by_todo: HashMap<String, i64> = HashMap::new();
+    for session in &sessions {
+        if let Some(todo_id) = &session.todo_id {
+            *time_by_todo.entry(todo_id.clone()).or_default() += session.elapsed_ms(now);
+        }
+    }
+    let summary = summarize(goal_links, &linked, &time_by_todo, goal.target_minutes);
+    Ok(GoalProgress {
+        goal_id: goal.id,
+        from,
+        to,
+        todos: summary.todos,
+        done: summary.done,
+        weighted_completion: summary.weighted_completion,
+        time_invested_ms: summary.time_invested_ms,
+        time_progress: summary.time_progress,
+        links: summary.links,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(kind: &str, target: &str, weight: f64) -> GoalLink {
+        GoalLink { kind: kind.to_string(), target: target.to_string(), weight }
+    }
+
+    fn linked(link: &GoalLink, todo_id: &str, status: &str) -> LinkedTodo {
+        LinkedTodo {
+            kind: link.kind.clone(),
+            target: link.target.clone(),
+            weight: link.weight,
+            todo_id: todo_id.to_string(),
+            status: status.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_summarize_weights_completion_and_time() {
+        let habit = link("tag", "run", 1.0);
+        let milestone = link("todo", "b", 3.0);
+        let todos = vec![
+            linked(&habit, "a", "done"),
+            linked(&habit, "b", "todo"),
+            linked(&milestone, "b", "todo"),
+            linked(&habit, "c", "done"),
+        ];
+        let time = HashMap::from([("a".to_string(), 30 * 60_000), ("b".to_string(), 30 * 60_000)]);
+        let summary = summarize(vec![habit.clone(), milestone], &todos, &time, Some(120));
+        // b counts once, with the milestone's weight of 3
+        assert_eq!((summary.todos, summary.done), (3, 2));
+        assert!((summary.weighted_completion - 2.0 / 5.0).abs() < 1e-9);
+        assert_eq!(summary.time_invested_ms, 60 * 60_000);
+        assert_eq!(summary.time_progress, Some(0.5));
+        let per_link = &summary.links;
+        assert_eq!((per_link[0].todos, per_link[0].done, per_link[0].time_invested_ms), (3, 2, 60 * 60_000));
+        assert_eq!((per_link[1].todos, per_link[1].done), (1, 0));
+
+        let empty = summarize(vec![habit], &[], &HashMap::new(), None);
+        assert_eq!((empty.weighted_completion, empty.time_progress), (0.0, None));
+    }
+}
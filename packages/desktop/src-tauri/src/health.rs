@@ -0,0 +1,162 @@
+use serde::Serialize;
+use std::fs::OpenOptions;
+use tauri::{AppHandle, Manager};
+
+use crate::db::database::InitStatus;
+use crate::db::{DatabaseState, Migration};
+use crate::logger;
+
+/// Below this much free space on the database's disk the check reports a problem
+const MIN_FREE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Why database setup failed, kept so the app can still start and report it
+pub struct SetupError(pub String);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckStatus {
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+impl CheckStatus {
+    fn from_result(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self { ok: true, error: None },
+            Err(e) => Self {
+                ok: false,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationStatus {
+    pub ok: bool,
+    pub pending: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskStatus {
+    pub ok: bool,
+    pub free_bytes: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    /// Every check passed
+    pub ok: bool,
+    pub setup_error: Option<String>,
+    pub database: CheckStatus,
+    pub migrations: MigrationStatus,
+    pub disk: DiskStatus,
+    pub log: CheckStatus,
+}
+
+/// Whether the log file can still be appended to
+fn check_log() -> Result<(), String> {
+    let path = logger::get_log_path().ok_or_else(|| "Logger not initialized".to_string())?;
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .map(|_| ())
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+async fn check_database(state: &DatabaseState) -> (CheckStatus, MigrationStatus, DiskStatus) {
+    let pool = state.pool.lock().await;
+
+    let database = CheckStatus::from_result(
+        sqlx::query("SELECT 1")
+            .execute(&*pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+    );
+
+    let migrations = match Migration::new(pool.clone(), state.migrations_dir.clone()).pending().await {
+        Ok(pending) => MigrationStatus {
+            ok: pending.is_empty(),
+            pending,
+            error: None,
+        },
+        Err(e) => MigrationStatus {
+            ok: false,
+            pending: Vec::new(),
+            error: Some(e),
+        },
+    };
+    drop(pool);
+
+    let db_path = state.db_path.lock().await.clone();
+    let dir = db_path.parent().unwrap_or(&db_path);
+    let disk = match fs4::available_space(dir) {
+        Ok(free) if free < MIN_FREE_BYTES => DiskStatus {
+            ok: false,
+            free_bytes: Some(free),
+            error: Some(format!("Only {} MB free", free / 1024 / 1024)),
+        },
+        Ok(free) => DiskStatus {
+            ok: true,
+            free_bytes: Some(free),
+            error: None,
+        },
+        Err(e) => DiskStatus {
+            ok: false,
+            free_bytes: None,
+            error: Some(e.to_string()),
+        },
+    };
+
+    (database, migrations, disk)
+}
+
+/// Status of the database, migrations, disk space and log file for the splash screen
+#[crate::metrics::command]
+pub async fn health_check(app: AppHandle) -> Result<HealthReport, String> {
+    let setup_error = app.try_state::<SetupError>().map(|e| e.0.clone());
+    let log = CheckStatus::from_result(check_log());
+
+    let status = app.try_state::<DatabaseState>().map(|state| (state.status(), state));
+    let (database, migrations, disk) = match status {
+        Some((InitStatus::Ready, state)) => check_database(&state).await,
+        other => {
+            let error = Some(match other {
+                Some((InitStatus::Initializing, _)) => "Database is still initializing".to_string(),
+                Some((InitStatus::Failed(e), _)) => e,
+                _ => "Database is not initialized".to_string(),
+            });
+            (
+                CheckStatus {
+                    ok: false,
+                    error: error.clone(),
+                },
+                MigrationStatus {
+                    ok: false,
+                    pending: Vec::new(),
+                    error: error.clone(),
+                },
+                DiskStatus {
+                    ok: false,
+                    free_bytes: None,
+                    error,
+                },
+            )
+        }
+    };
+
+    Ok(HealthReport {
+        ok: setup_error.is_none() && database.ok && migrations.ok && disk.ok && log.ok,
+        setup_error,
+        database,
+        migrations,
+        disk,
+        log,
+    })
+}
@@ -0,0 +1,400 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::app_config::AppConfig;
+use crate::db::DatabaseState;
+use crate::{confirm, logger};
+
+/// Emitted whenever a hook is saved, removed or has run
+pub const HOOKS_CHANGED_EVENT: &str = "hooks://changed";
+
+/// Hooks live in the app config, never in the journal: anything there can be written by
+/// the webview or arrive with an import, and must not get to decide what runs
+const HOOKS_CONFIG_KEY: &str = "hooks";
+
+const DEFAULT_TIMEOUT_SECS: i64 = 30;
+const MAX_TIMEOUT_SECS: i64 = 600;
+/// Output kept in the log and on the hook, from the end where errors usually are
+const MAX_OUTPUT_CHARS: usize = 4000;
+
+/// Lifecycle events scripts can run on
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HookEvent {
+    AppStarted,
+    EntryCreated,
+    /// Covers the scheduled and on-demand git export, the journal's plaintext backup
+    GitExportFinished,
+    RolloverFinished,
+    EmailImported,
+}
+
+impl HookEvent {
+    fn name(self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    fn parse(name: &str) -> Result<HookEvent, String> {
+        serde_json::from_value(serde_json::Value::String(name.to_string()))
+            .map_err(|_| format!("Unknown hook event: {}", name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    pub id: String,
+    pub name: String,
+    pub event: String,
+    /// Run by `sh -c`, or `cmd /C` on Windows
+    pub command: String,
+    pub enabled: bool,
+    /// Whether the user allowed `command` in a native dialog; cleared when it changes
+    #[serde(default)]
+    #[sqlx(default)]
+    pub confirmed: bool,
+    pub timeout_secs: i64,
+    pub last_run_at: Option<i64>,
+    /// None when the last run timed out or couldn't start
+    pub last_exit_code: Option<i64>,
+    pub last_output: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookResult {
+    pub exit_code: Option<i64>,
+    pub output: String,
+}
+
+/// The last `max` characters of `text`
+fn tail(text: &str, max: usize) -> String {
+    let count = text.chars().count();
+    if count <= max {
+        return text.to_string();
+    }
+    let tail: String = text.chars().skip(count - max).collect();
+    format!("…{}", tail)
+}
+
+/// Run the hook's command with the event in `JOURNAL_TODO_EVENT` and the payload
+/// as JSON in `JOURNAL_TODO_PAYLOAD` and on stdin
+async fn run(hook: &Hook, event: &str, payload: &str) -> HookResult {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&hook.command);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&hook.command);
+        command
+    };
+    command
+        .env("JOURNAL_TODO_EVENT", event)
+        .env("JOURNAL_TODO_PAYLOAD", payload)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // A timed-out script is killed when its future is dropped
+        .kill_on_drop(true);
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return HookResult {
+                exit_code: None,
+                output: format!("Failed to start: {}", e),
+            }
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(payload.as_bytes()).await;
+    }
+    let timeout = Duration::from_secs(hook.timeout_secs.clamp(1, MAX_TIMEOUT_SECS) as u64);
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            HookResult {
+                exit_code: output.status.code().map(i64::from),
+                output: tail(text.trim_end(), MAX_OUTPUT_CHARS),
+            }
+        }
+        Ok(Err(e)) => HookResult {
+            exit_code: None,
+            output: format!("Failed to run: {}", e),
+        },
+        Err(_) => HookResult {
+            exit_code: None,
+            output: format!("Timed out after {}s", timeout.as_secs()),
+        },
+    }
+}
+
+fn all(app: &AppHandle) -> Vec<Hook> {
+    app.state::<AppConfig>().get_or(HOOKS_CONFIG_KEY, Vec::new())
+}
+
+fn find(app: &AppHandle, id: &str) -> Result<Hook, String> {
+    all(app)
+        .into_iter()
+        .find(|hook| hook.id == id)
+        .ok_or_else(|| format!("Hook not found: {}", id))
+}
+
+/// Change one hook in the app config and tell the frontend
+fn update(app: &AppHandle, id: &str, change: impl FnOnce(&mut Hook)) -> Result<Hook, String> {
+    let updated = app.state::<AppConfig>().update(HOOKS_CONFIG_KEY, |hooks: &mut Vec<Hook>| {
+        let hook = hooks
+            .iter_mut()
+            .find(|hook| hook.id == id)
+            .ok_or_else(|| format!("Hook not found: {}", id))?;
+        change(hook);
+        Ok(hook.clone())
+    })?;
+    let _ = app.emit(HOOKS_CHANGED_EVENT, ());
+    Ok(updated)
+}
+
+/// Show the command in a native dialog, which the webview can neither draw nor answer
+async fn confirm_command(name: &str, event: &str, command: &str) -> Result<(), String> {
+    let message = format!("Allow the hook \"{}\" to run this command on {}?\n\n{}", name, event, command);
+    if confirm::ask("Run a hook command", &message).await {
+        Ok(())
+    } else {
+        Err(format!("Running the command of hook {} was not allowed", name))
+    }
+}
+
+async fn run_and_record(app: &AppHandle, hook: &Hook, event: &str, payload: &str) -> HookResult {
+    let result = run(hook, event, payload).await;
+    let summary = format!(
+        "Hook {} on {} exited with {}",
+        hook.name,
+        event,
+        result.exit_code.map(|code| code.to_string()).unwrap_or_else(|| "no status".to_string())
+    );
+    match result.exit_code {
+        Some(0) if result.output.is_empty() => logger::info(&summary),
+        Some(0) => logger::info(&format!("{}:\n{}", summary, result.output)),
+        _ => logger::error(&format!("{}:\n{}", summary, result.output)),
+    }
+
+    let recorded = update(app, &hook.id, |recorded| {
+        recorded.last_run_at = Some(Utc::now().timestamp_millis());
+        recorded.last_exit_code = result.exit_code;
+        recorded.last_output = Some(result.output.clone());
+    });
+    if let Err(e) = recorded {
+        logger::error(&format!("Failed to record run of hook {}: {}", hook.name, e));
+    }
+    result
+}
+
+/// Run the enabled, confirmed hooks for `event` in the background, one after another
+pub fn fire<T: Serialize>(app: &AppHandle, event: HookEvent, payload: T) {
+    let payload = serde_json::to_string(&payload).unwrap_or_else(|_| "{}".to_string());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let event = event.name();
+        let mut hooks: Vec<Hook> = all(&app)
+            .into_iter()
+            .filter(|hook| hook.event == event && hook.enabled && hook.confirmed)
+            .collect();
+        hooks.sort_by_key(|hook| hook.created_at);
+        for hook in &hooks {
+            run_and_record(&app, hook, &event, &payload).await;
+        }
+    });
+}
+
+/// Move hooks left in the journal's `hooks` table by earlier versions into the app config,
+/// disabled until their commands are confirmed, and drop the table
+pub async fn import_from_journal(app: &AppHandle) {
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let exists: Result<Option<String>, sqlx::Error> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'hooks'")
+            .fetch_optional(&*pool)
+            .await;
+    if !matches!(exists, Ok(Some(_))) {
+        return;
+    }
+    let found: Vec<Hook> = match sqlx::query_as(
+        "SELECT id, name, event, command, enabled, timeout_secs, last_run_at, last_exit_code, last_output, \
+         created_at, updated_at FROM hooks",
+    )
+    .fetch_all(&*pool)
+    .await
+    {
+        Ok(found) => found,
+        Err(e) => return logger::error(&format!("Failed to read hooks from the journal: {}", e)),
+    };
+
+    let moved = app.state::<AppConfig>().update(HOOKS_CONFIG_KEY, |hooks: &mut Vec<Hook>| {
+        let mut moved = 0;
+        for mut hook in found {
+            if hooks.iter().any(|known| known.id == hook.id) {
+                continue;
+            }
+            hook.enabled = false;
+            hook.confirmed = false;
+            hooks.push(hook);
+            moved += 1;
+        }
+        Ok(moved)
+    });
+    match moved {
+        Ok(moved) => {
+            if moved > 0 {
+                logger::info(&format!("Moved {} hooks out of the journal, disabled until confirmed", moved));
+                let _ = app.emit(HOOKS_CHANGED_EVENT, ());
+            }
+            if let Err(e) = sqlx::query("DROP TABLE hooks").execute(&*pool).await {
+                logger::error(&format!("Failed to drop the journal's hooks table: {}", e));
+            }
+        }
+        Err(e) => logger::error(&format!("Failed to move hooks out of the journal: {}", e)),
+    }
+}
+
+#[crate::metrics::command]
+pub fn list_hooks(app: AppHandle) -> Vec<Hook> {
+    let mut hooks = all(&app);
+    hooks.sort_by(|a, b| (&a.event, &a.name).cmp(&(&b.event, &b.name)));
+    hooks
+}
+
+/// Create or change a hook. A new or changed command only stays enabled once the user
+/// allows it in a native dialog.
+#[crate::metrics::command]
+pub async fn save_hook(
+    app: AppHandle,
+    id: Option<String>,
+    name: String,
+    event: String,
+    command: String,
+    enabled: Option<bool>,
+    timeout_secs: Option<i64>,
+) -> Result<Hook, String> {
+    if name.trim().is_empty() {
+        return Err("Hook name cannot be empty".to_string());
+    }
+    if command.trim().is_empty() {
+        return Err("Hook command cannot be empty".to_string());
+    }
+    HookEvent::parse(&event)?;
+    let timeout_secs = timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS);
+    if !(1..=MAX_TIMEOUT_SECS).contains(&timeout_secs) {
+        return Err(format!("Timeout must be between 1 and {} seconds", MAX_TIMEOUT_SECS));
+    }
+
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let enabled = enabled.unwrap_or(true);
+    let existing = all(&app).into_iter().find(|hook| hook.id == id);
+    let mut confirmed = existing
+        .as_ref()
+        .is_some_and(|hook| hook.confirmed && hook.command == command);
+    if enabled && !confirmed {
+        confirm_command(name.trim(), &event, &command).await?;
+        confirmed = true;
+    }
+
+    let now = Utc::now().timestamp_millis();
+    let saved = app.state::<AppConfig>().update(HOOKS_CONFIG_KEY, |hooks: &mut Vec<Hook>| {
+        let hook = match hooks.iter_mut().find(|hook| hook.id == id) {
+            Some(hook) => hook,
+            None => {
+                hooks.push(Hook {
+                    id: id.clone(),
+                    name: String::new(),
+                    event: String::new(),
+                    command: String::new(),
+                    enabled: false,
+                    confirmed: false,
+                    timeout_secs,
+                    last_run_at: None,
+                    last_exit_code: None,
+                    last_output: None,
+                    created_at: now,
+                    updated_at: now,
+                });
+                hooks.last_mut().expect("just pushed")
+            }
+        };
+        hook.name = name.trim().to_string();
+        hook.event = event.clone();
+        hook.command = command.clone();
+        hook.enabled = enabled;
+        hook.confirmed = confirmed;
+        hook.timeout_secs = timeout_secs;
+        hook.updated_at = now;
+        Ok(hook.clone())
+    })?;
+    let _ = app.emit(HOOKS_CHANGED_EVENT, ());
+    Ok(saved)
+}
+
+#[crate::metrics::command]
+pub async fn set_hook_enabled(app: AppHandle, id: String, enabled: bool) -> Result<Hook, String> {
+    let hook = find(&app, &id)?;
+    if enabled && !hook.confirmed {
+        confirm_command(&hook.name, &hook.event, &hook.command).await?;
+    }
+    let command = hook.command;
+    update(&app, &id, |hook| {
+        hook.enabled = enabled;
+        hook.confirmed |= enabled && hook.command == command;
+        hook.updated_at = Utc::now().timestamp_millis();
+    })
+}
+
+#[crate::metrics::command]
+pub fn delete_hook(app: AppHandle, id: String) -> Result<(), String> {
+    app.state::<AppConfig>().update(HOOKS_CONFIG_KEY, |hooks: &mut Vec<Hook>| {
+        hooks.retain(|hook| hook.id != id);
+        Ok(())
+    })?;
+    let _ = app.emit(HOOKS_CHANGED_EVENT, ());
+    Ok(())
+}
+
+/// Run a hook once with an empty payload, enabled or not, to try it out
+#[crate::metrics::command]
+pub async fn run_hook(app: AppHandle, id: String) -> Result<HookResult, String> {
+    let mut hook = find(&app, &id)?;
+    if !hook.confirmed {
+        confirm_command(&hook.name, &hook.event, &hook.command).await?;
+        let command = hook.command.clone();
+        hook = update(&app, &id, |hook| hook.confirmed |= hook.command == command)?;
+        if !hook.confirmed {
+            return Err(format!("The command of hook {} changed before it could run", hook.name));
+        }
+    }
+    Ok(run_and_record(&app, &hook, &hook.event, "{}").await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_names_and_output_tail() {
+        assert_eq!(HookEvent::GitExportFinished.name(), "gitExportFinished");
+        assert_eq!(HookEvent::parse("entryCreated").unwrap(), HookEvent::EntryCreated);
+        assert!(HookEvent::parse("backupFinished").is_err());
+
+        assert_eq!(tail("short", 10), "short");
+        assert_eq!(tail("abcdef", 3), "…def");
+    }
+}
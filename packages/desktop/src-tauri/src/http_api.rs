@@ -0,0 +1,432 @@
+use axum::extract::{Query, Request, State as AxumState};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::db::DatabaseState;
+use crate::dispatch::{self, Action, ActionResult, Clip};
+use crate::site_export::escape;
+use crate::{automation, feed_export, logger, settings};
+
+/// Off unless turned on: the API can write to the journal
+const ENABLED_SETTING: &str = "http_api.enabled";
+const PORT_SETTING: &str = "http_api.port";
+const TOKEN_SETTING: &str = "http_api.token";
+/// Key in the feed's URL; kept apart from the token since feed readers can't keep a secret
+const FEED_KEY_SETTING: &str = "http_api.feed_key";
+/// Browser origins besides the API's own allowed to call it, e.g. `chrome-extension://<id>`
+const ALLOWED_ORIGINS_SETTING: &str = "http_api.allowed_origins";
+const DEFAULT_PORT: u16 = 27831;
+/// How long a clip confirmation page can be submitted
+const CONFIRMATION_TTL: Duration = Duration::from_secs(600);
+
+/// Confirmation pages are the API's own and can't be framed, run scripts or post elsewhere
+const CONFIRMATION_CSP: &str =
+    "default-src 'none'; style-src 'unsafe-inline'; form-action 'self'; frame-ancestors 'none'";
+
+/// Connection details for the local API, for clients like the web clipper
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipperInfo {
+    pub enabled: bool,
+    pub endpoint: String,
+    /// For browser extensions and other clients that keep it to themselves
+    pub token: String,
+    /// Opens the clip in a confirmation window served by the API, so no token is involved
+    pub bookmarklet: String,
+}
+
+struct ApiConfig {
+    enabled: bool,
+    port: u16,
+    token: String,
+    feed_key: String,
+    allowed_origins: Vec<String>,
+}
+
+/// A random key stored under `setting`, generated on first use
+async fn generated_key(pool: &sqlx::SqlitePool, setting: &str) -> Result<String, String> {
+    if let Some(key) = settings::get::<String>(pool, setting).await? {
+        return Ok(key);
+    }
+    let key = uuid::Uuid::new_v4().simple().to_string();
+    settings::write_value(pool, setting, &json!(key)).await?;
+    Ok(key)
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "ok": false, "error": self.1 }))).into_response()
+    }
+}
+
+/// Read the API settings and keys, generating the keys on first use
+async fn load_config(app: &AppHandle) -> Result<ApiConfig, String> {
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+
+    let enabled = settings::get_or(&pool, ENABLED_SETTING, false).await;
+    let port = settings::get_or(&pool, PORT_SETTING, DEFAULT_PORT).await;
+    let allowed_origins = settings::get_or(&pool, ALLOWED_ORIGINS_SETTING, Vec::<String>::new()).await;
+    let token = generated_key(&pool, TOKEN_SETTING).await?;
+    let feed_key = generated_key(&pool, FEED_KEY_SETTING).await?;
+    Ok(ApiConfig {
+        enabled,
+        port,
+        token,
+        feed_key,
+        allowed_origins,
+    })
+}
+
+/// Compared in constant time, so response timing doesn't give the token away
+fn token_matches(provided: &str, token: &str) -> bool {
+    provided.as_bytes().ct_eq(token.as_bytes()).into()
+}
+
+fn check_token(headers: &HeaderMap, token: &str) -> Result<(), ApiError> {
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if provided.is_some_and(|provided| token_matches(provided, token)) {
+        Ok(())
+    } else {
+        Err(ApiError(StatusCode::UNAUTHORIZED, "Invalid or missing token".to_string()))
+    }
+}
+
+/// Requests from outside a browser carry no `Origin`; browsers send one on cross-origin calls
+/// and form posts, and only the API's own pages and the allowed origins get through
+fn origin_allowed(origin: Option<&str>, allowed: &[String]) -> bool {
+    origin.is_none_or(|origin| allowed.iter().any(|allowed| allowed == origin))
+}
+
+fn own_origins(port: u16) -> [String; 2] {
+    [format!("http://127.0.0.1:{}", port), format!("http://localhost:{}", port)]
+}
+
+#[derive(Clone)]
+struct ApiState {
+    app: AppHandle,
+    token: String,
+    feed_key: String,
+    origins: Arc<Vec<String>>,
+    /// One-time keys of the confirmation pages handed out, with when they were
+    confirmations: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+async fn check_origin(
+    AxumState(state): AxumState<ApiState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let origin = request.headers().get(header::ORIGIN).map(|origin| origin.to_str().unwrap_or_default());
+    if !origin_allowed(origin, &state.origins) {
+        return Err(ApiError(StatusCode::FORBIDDEN, "Origin not allowed".to_string()));
+    }
+    Ok(next.run(request).await)
+}
+
+async fn health(AxumState(state): AxumState<ApiState>) -> Json<serde_json::Value> {
+    Json(json!({ "ok": true, "version": state.app.package_info().version.to_string() }))
+}
+
+async fn clip(
+    AxumState(state): AxumState<ApiState>,
+    headers: HeaderMap,
+    Json(clip): Json<Clip>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_token(&headers, &state.token)?;
+    if clip.url.trim().is_empty() {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "Missing url".to_string()));
+    }
+
+    let result = dispatch::dispatch(&state.app, clip.into_action())
+        .await
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(json!({ "ok": true, "result": result })))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ConfirmedClip {
+    key: String,
+    url: String,
+    title: Option<String>,
+    selection: Option<String>,
+    kind: Option<String>,
+}
+
+fn page(title: &str, body: &str) -> Response {
+    let html = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>{title}</title>\
+<style>body{{font:14px system-ui,sans-serif;margin:24px;max-width:440px}}p{{word-break:break-word}}\
+blockquote{{margin:0;padding-left:12px;border-left:3px solid #ccc;white-space:pre-wrap}}</style>\
+</head><body>{body}</body></html>",
+        title = escape(title),
+        body = body
+    );
+    let headers = [
+        (header::CONTENT_SECURITY_POLICY, CONFIRMATION_CSP),
+        (header::X_FRAME_OPTIONS, "DENY"),
+        (header::CACHE_CONTROL, "no-store"),
+    ];
+    (headers, Html(html)).into_response()
+}
+
+fn confirmation_page(clip: &Clip, key: &str) -> Response {
+    let field = |name: &str, value: Option<&str>| {
+        format!("<input type=\"hidden\" name=\"{}\" value=\"{}\">", name, escape(value.unwrap_or_default()))
+    };
+    let title = clip.title.as_deref().filter(|title| !title.trim().is_empty()).unwrap_or(&clip.url);
+    let selection = clip
+        .selection
+        .as_deref()
+        .filter(|selection| !selection.trim().is_empty())
+        .map(|selection| format!("<blockquote>{}</blockquote>", escape(selection)))
+        .unwrap_or_default();
+    let body = format!(
+        "<h3>Clip to your journal?</h3><p><strong>{}</strong><br>{}</p>{}\
+<form method=\"post\" action=\"/clip/confirm\">{}{}{}{}{}\
+<p><button type=\"submit\">Clip</button></p></form>",
+        escape(title),
+        escape(&clip.url),
+        selection,
+        field("key", Some(key)),
+        field("url", Some(&clip.url)),
+        field("title", clip.title.as_deref()),
+        field("selection", clip.selection.as_deref()),
+        field("kind", clip.kind.as_deref()),
+    );
+    page("Clip to journal", &body)
+}
+
+/// The bookmarklet's window: shows the clip and asks before anything is written, with a key
+/// that works once instead of the API token
+async fn confirm_clip(AxumState(state): AxumState<ApiState>, Query(clip): Query<Clip>) -> Response {
+    let key = uuid::Uuid::new_v4().simple().to_string();
+    if let Ok(mut confirmations) = state.confirmations.lock() {
+        confirmations.retain(|_, issued| issued.elapsed() < CONFIRMATION_TTL);
+        confirmations.insert(key.clone(), Instant::now());
+    }
+    confirmation_page(&clip, &key)
+}
+
+async fn clip_confirmed(AxumState(state): AxumState<ApiState>, Form(form): Form<ConfirmedClip>) -> Response {
+    let issued = state.confirmations.lock().ok().and_then(|mut confirmations| confirmations.remove(&form.key));
+    if issued.is_none_or(|issued| issued.elapsed() >= CONFIRMATION_TTL) {
+        let message = "<p>This clip has expired or was already saved. Use the bookmarklet again.</p>";
+        return (StatusCode::FORBIDDEN, page("Clip expired", message)).into_response();
+    }
+    if form.url.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, page("Nothing to clip", "<p>The page has no address.</p>")).into_response();
+    }
+    let clip = Clip {
+        title: form.title,
+        url: form.url,
+        selection: form.selection,
+        kind: form.kind.filter(|kind| !kind.is_empty()),
+    };
+    match dispatch::dispatch(&state.app, clip.into_action()).await {
+        Ok(_) => page("Clipped", "<p>Clipped to your journal. You can close this window.</p>"),
+        Err(e) => {
+            let message = format!("<p>Couldn't clip the page: {}</p>", escape(&e));
+            (StatusCode::INTERNAL_SERVER_ERROR, page("Clip failed", &message)).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct QuickAdd {
+    text: String,
+    date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Complete {
+    id: String,
+}
+
+/// Launcher endpoints answer with just the fields a result list needs
+async fn run_for_launcher(state: &ApiState, action: Action) -> Result<Json<serde_json::Value>, ApiError> {
+    let result = dispatch::dispatch(&state.app, action)
+        .await
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(match result {
+        ActionResult::TodoCreated { todo_id, date } | ActionResult::TodoCompleted { todo_id, date } => {
+            json!({ "ok": true, "id": todo_id, "date": date })
+        }
+        _ => json!({ "ok": true }),
+    }))
+}
+
+async fn quick_add(
+    AxumState(state): AxumState<ApiState>,
+    headers: HeaderMap,
+    Json(body): Json<QuickAdd>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_token(&headers, &state.token)?;
+    run_for_launcher(&state, Action::CreateTodo { text: body.text, date: body.date }).await
+}
+
+async fn today(AxumState(state): AxumState<ApiState>, headers: HeaderMap) -> Result<Json<serde_json::Value>, ApiError> {
+    check_token(&headers, &state.token)?;
+    let list = automation::today_list(&state.app)
+        .await
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let todos: Vec<serde_json::Value> = list
+        .todos
+        .into_iter()
+        .map(|todo| json!({ "id": todo.id, "text": todo.text, "done": todo.status == "done", "level": todo.level }))
+        .collect();
+    Ok(Json(json!({ "ok": true, "date": list.date, "todos": todos })))
+}
+
+async fn complete(
+    AxumState(state): AxumState<ApiState>,
+    headers: HeaderMap,
+    Json(body): Json<Complete>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_token(&headers, &state.token)?;
+    run_for_launcher(&state, Action::CompleteTodo { id: body.id }).await
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FeedQuery {
+    key: Option<String>,
+}
+
+/// The feed from `export_feed`. Readers can't send a token, so the URL carries the feed key,
+/// which opens nothing but the feed.
+async fn feed(
+    AxumState(state): AxumState<ApiState>,
+    Query(query): Query<FeedQuery>,
+) -> Result<Response, ApiError> {
+    if !query.key.is_some_and(|key| token_matches(&key, &state.feed_key)) {
+        return Err(ApiError(StatusCode::UNAUTHORIZED, "Invalid or missing feed key".to_string()));
+    }
+    match feed_export::served_feed(&state.app).await {
+        Ok(Some(feed)) => Ok(([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], feed).into_response()),
+        Ok(None) => Err(ApiError(StatusCode::NOT_FOUND, "No feed is being served".to_string())),
+        Err(e) => Err(ApiError(StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+/// Start the local HTTP API on 127.0.0.1 when enabled in settings
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let config = match load_config(&app).await {
+            Ok(config) => config,
+            Err(e) => {
+                logger::error(&format!("Failed to load HTTP API config: {}", e));
+                return;
+            }
+        };
+        if !config.enabled {
+            logger::info("Local HTTP API disabled");
+            return;
+        }
+        let port = config.port;
+
+        // Only listed origins, like the web clipper extension, may call from a browser
+        let cors = CorsLayer::new()
+            .allow_origin(AllowOrigin::list(
+                config.allowed_origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            ))
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+        let mut origins = config.allowed_origins;
+        origins.extend(own_origins(port));
+        let state = ApiState {
+            app,
+            token: config.token,
+            feed_key: config.feed_key,
+            origins: Arc::new(origins),
+            confirmations: Arc::default(),
+        };
+        let router = Router::new()
+            .route("/health", get(health))
+            .route("/clip", post(clip))
+            .route("/clip/confirm", get(confirm_clip).post(clip_confirmed))
+            // For launchers like Raycast and Alfred; these work with the window closed and need
+            // the token like every route that reads or writes the journal
+            .route("/launcher/add", post(quick_add))
+            .route("/launcher/today", get(today))
+            .route("/launcher/complete", post(complete))
+            .route("/feed.xml", get(feed))
+            .layer(middleware::from_fn_with_state(state.clone(), check_origin))
+            .layer(cors)
+            .with_state(state);
+
+        let listener = match tokio::net::TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                logger::error(&format!("Failed to bind HTTP API on port {}: {}", port, e));
+                return;
+            }
+        };
+        logger::info(&format!("Local HTTP API listening on 127.0.0.1:{}", port));
+        if let Err(e) = axum::serve(listener, router).await {
+            logger::error(&format!("HTTP API stopped: {}", e));
+        }
+    })
+}
+
+/// Where the served feed can be fetched, key included
+pub(crate) async fn feed_url(app: &AppHandle) -> Result<String, String> {
+    let config = load_config(app).await?;
+    Ok(format!("http://127.0.0.1:{}/feed.xml?key={}", config.port, config.feed_key))
+}
+
+/// Endpoint, token and a ready-to-use bookmarklet for clipping pages. The bookmarklet runs in
+/// the clipped page, so it only opens the API's confirmation window and never sees the token.
+#[crate::metrics::command]
+pub async fn get_clipper_info(app: AppHandle) -> Result<ClipperInfo, String> {
+    let config = load_config(&app).await?;
+    let endpoint = format!("http://127.0.0.1:{}/clip", config.port);
+    let bookmarklet = format!(
+        "javascript:(()=>{{window.open('{endpoint}/confirm?'+new URLSearchParams({{title:document.title,\
+url:location.href,selection:String(getSelection())}}),'journal-clip','width=480,height=420')}})()",
+        endpoint = endpoint
+    );
+    Ok(ClipperInfo {
+        enabled: config.enabled,
+        endpoint,
+        token: config.token,
+        bookmarklet,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_and_origin_checks() {
+        assert!(token_matches("3f2a", "3f2a"));
+        assert!(!token_matches("3f2b", "3f2a"));
+        assert!(!token_matches("3f2", "3f2a"));
+        assert!(!token_matches("", "3f2a"));
+
+        let mut allowed = own_origins(27831).to_vec();
+        allowed.push("chrome-extension://abc".to_string());
+        assert!(origin_allowed(None, &allowed));
+        assert!(origin_allowed(Some("http://127.0.0.1:27831"), &allowed));
+        assert!(origin_allowed(Some("chrome-extension://abc"), &allowed));
+        assert!(!origin_allowed(Some("https://example.com"), &allowed));
+        assert!(!origin_allowed(Some("null"), &allowed));
+    }
+}
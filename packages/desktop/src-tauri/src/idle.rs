@@ -0,0 +1,106 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, settings, timers};
+
+/// Pause running timers after this many minutes without input; 0 disables
+const THRESHOLD_SETTING: &str = "idle.threshold_minutes";
+const DEFAULT_THRESHOLD_MINUTES: u64 = 5;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Emitted when timers were paused because the user went idle
+pub const IDLE_PAUSED_EVENT: &str = "idle://paused";
+/// Emitted when the user is back, so the UI can ask whether they were still working
+pub const IDLE_RETURNED_EVENT: &str = "idle://returned";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IdleEvent {
+    session_ids: Vec<String>,
+    idle_since: i64,
+}
+
+/// Time since the last keyboard or mouse input
+pub fn idle_time() -> Result<Duration, String> {
+    user_idle::UserIdle::get_time()
+        .map(|idle| idle.duration())
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// Start the background task that pauses timers while the user is away
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        // Timers paused by the current idle period, and when it started
+        let mut paused: Option<IdleEvent> = None;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut logged_error = false;
+
+        loop {
+            interval.tick().await;
+            let idle = match tauri::async_runtime::spawn_blocking(idle_time).await {
+                Ok(Ok(idle)) => idle,
+                Ok(Err(e)) => {
+                    if !logged_error {
+                        logger::error(&format!("Idle detection unavailable: {}", e));
+                        logged_error = true;
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    logger::error(&format!("Idle detection task failed: {}", e));
+                    continue;
+                }
+            };
+
+            let threshold = {
+                let state = app.state::<DatabaseState>();
+                let pool = state.pool.lock().await;
+                settings::get_or(&pool, THRESHOLD_SETTING, DEFAULT_THRESHOLD_MINUTES).await
+            };
+            if threshold == 0 {
+                continue;
+            }
+
+            match paused.take() {
+                None if idle >= Duration::from_secs(threshold * 60) => {
+                    let idle_since = Utc::now().timestamp_millis() - idle.as_millis() as i64;
+                    match pause_timers(&app, idle_since).await {
+                        Ok(event) => paused = event,
+                        Err(e) => logger::error(&format!("Failed to pause timers: {}", e)),
+                    }
+                }
+                Some(event) if idle < POLL_INTERVAL * 2 => {
+                    logger::info("User returned from idle");
+                    let _ = app.emit(IDLE_RETURNED_EVENT, event);
+                }
+                other => paused = other,
+            }
+        }
+    })
+}
+
+/// Pause running timers as of when the user went idle
+async fn pause_timers(app: &AppHandle, idle_since: i64) -> Result<Option<IdleEvent>, String> {
+    let session_ids = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        timers::pause_running(&pool, idle_since).await?
+    };
+    if session_ids.is_empty() {
+        return Ok(None);
+    }
+
+    logger::info(&format!("Paused {} timer(s) after inactivity", session_ids.len()));
+    changes::notify(app, vec!["time_sessions".to_string()]);
+    let event = IdleEvent {
+        session_ids,
+        idle_since,
+    };
+    let _ = app.emit(IDLE_PAUSED_EVENT, event.clone());
+    Ok(Some(event))
+}
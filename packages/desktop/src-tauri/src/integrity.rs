@@ -0,0 +1,215 @@
+use ring::digest::{digest, SHA256};
+use serde::Serialize;
+use sqlx::SqliteConnection;
+use tauri::{AppHandle, Manager};
+
+use crate::db::DatabaseState;
+use crate::git_export::{self, ExportedCopy};
+use crate::logger;
+
+/// What an entry's hash covers, as SQL over a `pages` row; compressed notes hash as their text
+pub(crate) const PAGE_CONTENT_SQL: &str = "COALESCE(notes, zstd_decompress(notes_zstd))";
+/// What a todo's hash covers, as SQL over a `todos` row; reordering leaves it alone
+pub(crate) const TODO_CONTENT_SQL: &str = "json_array(workspace_id, page_date, text, status, tags, level, parent_id)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrityProblem {
+    /// The content no longer matches its hash
+    Mismatch,
+    /// No hash was stored
+    Missing,
+    /// Compressed notes that can't be decompressed
+    Unreadable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityIssue {
+    pub table: String,
+    /// The todo's id, or the entry's date
+    pub id: String,
+    pub workspace_id: String,
+    pub date: String,
+    pub problem: IntegrityProblem,
+    /// The last git export's copy of the entry, to restore it from
+    pub restore_from: Option<ExportedCopy>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub pages: usize,
+    pub todos: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+/// Hex SHA-256 of the text, as the `sha256` SQL function stores it
+pub fn hash(text: &str) -> String {
+    digest(&SHA256, text.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn issue(table: &str, id: &str, workspace_id: &str, date: &str, problem: IntegrityProblem) -> IntegrityIssue {
+    IntegrityIssue {
+        table: table.to_string(),
+        id: id.to_string(),
+        workspace_id: workspace_id.to_string(),
+        date: date.to_string(),
+        problem,
+        restore_from: None,
+    }
+}
+
+/// Recompute every entry's and todo's hash. Notes are hashed here rather than in SQL, so
+/// compressed notes that fail to decompress are reported instead of failing the query.
+async fn check(conn: &mut SqliteConnection) -> Result<IntegrityReport, String> {
+    type PageRow = (String, String, Option<Vec<u8>>, Option<Vec<u8>>, Option<String>);
+    let pages: Vec<PageRow> =
+        sqlx::query_as("SELECT workspace_id, date, notes, notes_zstd, content_hash FROM pages ORDER BY date")
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    let mut report = IntegrityReport {
+        pages: pages.len(),
+        ..Default::default()
+    };
+    for (workspace_id, date, notes, compressed, stored) in pages {
+        let text = match (notes, compressed) {
+            (Some(notes), _) => Ok(String::from_utf8_lossy(&notes).into_owned()),
+            (None, Some(compressed)) => crate::compression::decompress(&compressed),
+            (None, None) => Ok(String::new()),
+        };
+        let problem = match (text, stored) {
+            (Err(_), _) => IntegrityProblem::Unreadable,
+            (Ok(_), None) => IntegrityProblem::Missing,
+            (Ok(text), Some(stored)) if hash(&text) != stored => IntegrityProblem::Mismatch,
+            _ => continue,
+        };
+        report.issues.push(issue("pages", &date, &workspace_id, &date, problem));
+    }
+
+    let (todos,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM todos")
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    report.todos = todos as usize;
+    let bad_todos: Vec<(String, String, String, bool)> = sqlx::query_as(&format!(
+        "SELECT id, workspace_id, page_date, content_hash IS NULL FROM todos
+         WHERE content_hash IS NOT sha256({})
+         ORDER BY page_date, id",
+        TODO_CONTENT_SQL
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    for (id, workspace_id, date, missing) in bad_todos {
+        let problem = if missing { IntegrityProblem::Missing } else { IntegrityProblem::Mismatch };
+        report.issues.push(issue("todos", &id, &workspace_id, &date, problem));
+    }
+    Ok(report)
+}
+
+/// Recompute the content hash of every journal entry and todo and report the ones that don't
+/// match, e.g. from disk corruption or edits made outside the app. Each issue points at the
+/// git export's copy of its entry when there is one, to restore from.
+#[crate::metrics::command]
+pub async fn verify_data_integrity(app: AppHandle) -> Result<IntegrityReport, String> {
+    let mut report = {
+        let state = app.state::<DatabaseState>();
+        let mut snapshot = state.open_reporting_snapshot().await?;
+        check(&mut snapshot).await?
+    };
+    if report.issues.is_empty() {
+        return Ok(report);
+    }
+
+    let mut entries: Vec<(String, String)> = report
+        .issues
+        .iter()
+        .map(|issue| (issue.workspace_id.clone(), issue.date.clone()))
+        .collect();
+    entries.sort();
+    entries.dedup();
+    match git_export::exported_copies(&app, &entries).await {
+        Ok(copies) => {
+            for issue in &mut report.issues {
+                issue.restore_from = copies.get(&(issue.workspace_id.clone(), issue.date.clone())).cloned();
+            }
+        }
+        Err(e) => logger::error(&format!("Failed to look up exported copies: {}", e)),
+    }
+    logger::error(&format!(
+        "Integrity check found {} rows that don't match their hash, {} restorable from the git export",
+        report.issues.len(),
+        report.issues.iter().filter(|issue| issue.restore_from.is_some()).count()
+    ));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migration::{add_content_hashes, apply_bundled};
+    use sqlx::Connection;
+
+    #[tokio::test]
+    async fn test_hashes_follow_writes_and_catch_tampering() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        crate::db::functions::register(&mut conn).await.unwrap();
+        for statement in [
+            "CREATE TABLE pages (workspace_id TEXT, date TEXT, notes TEXT)",
+            "CREATE TABLE todos (id TEXT, workspace_id TEXT, page_date TEXT, text TEXT, status TEXT, tags TEXT,
+                level INTEGER, parent_id TEXT)",
+            "INSERT INTO pages VALUES ('w', '2024-03-04', 'Before hashing')",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+        for file in ["0002_lively_nocturne.sql", "0003_steady_warpath.sql"] {
+            apply_bundled(&mut conn, file).await.unwrap();
+        }
+        add_content_hashes(&mut conn).await.unwrap();
+        for statement in [
+            "INSERT INTO pages (workspace_id, date, notes) VALUES ('w', '2024-03-05', 'Day two')",
+            "INSERT INTO todos (id, workspace_id, page_date, text, status, tags, level, parent_id)
+                VALUES ('a', 'w', '2024-03-05', 'Write', 'todo', '[]', 0, NULL),
+                ('b', 'w', '2024-03-05', 'Read', 'todo', '[]', 0, NULL)",
+            "UPDATE todos SET status = 'done' WHERE id = 'a'",
+            "UPDATE pages SET notes = 'Day two, edited' WHERE date = '2024-03-05'",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+        let (stored,): (String,) = sqlx::query_as("SELECT content_hash FROM pages WHERE date = '2024-03-05'")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(stored, hash("Day two, edited"));
+        let report = check(&mut conn).await.unwrap();
+        assert_eq!((report.pages, report.todos, report.issues.len()), (2, 2, 0));
+
+        // Writes that get around the triggers
+        for statement in [
+            "DROP TRIGGER pages_content_hash_updated",
+            "UPDATE pages SET notes = 'Tampered' WHERE date = '2024-03-04'",
+            "UPDATE todos SET content_hash = NULL WHERE id = 'b'",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+        let report = check(&mut conn).await.unwrap();
+        let issues: Vec<(&str, &str, IntegrityProblem)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.table.as_str(), issue.id.as_str(), issue.problem))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                ("pages", "2024-03-04", IntegrityProblem::Mismatch),
+                ("todos", "b", IntegrityProblem::Missing),
+            ]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use chrono::Utc;
+use rand::Rng;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::db::DatabaseState;
+use crate::{attachments, daily_entry, email_inbox, git_export, logger, rollover};
+
+/// How often the scheduler looks for due jobs
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+/// First retry delay after a failure, doubled per consecutive failure up to the job's interval
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+const MINUTE: Duration = Duration::from_secs(60);
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+/// Periodic work run by the scheduler
+pub struct Job {
+    pub name: &'static str,
+    pub interval: Duration,
+    /// Up to this much is added to each run, so jobs don't all fire at once
+    pub jitter: Duration,
+    pub run: fn(AppHandle) -> JobFuture,
+}
+
+/// Every scheduled job, by name
+pub(crate) fn jobs() -> Vec<Job> {
+    vec![
+        Job {
+            name: "attachments.cleanup",
+            interval: DAY,
+            jitter: HOUR,
+            run: |app| Box::pin(attachments::cleanup_job(app)),
+        },
+        Job {
+            name: "email.inbox",
+            interval: MINUTE * 5,
+            jitter: MINUTE,
+            run: |app| Box::pin(email_inbox::poll_job(app)),
+        },
+        Job {
+            name: "journal.daily_entry",
+            interval: MINUTE,
+            jitter: Duration::ZERO,
+            run: |app| Box::pin(daily_entry::create_job(app)),
+        },
+        Job {
+            name: "journal.git_export",
+            interval: HOUR,
+            jitter: MINUTE * 5,
+            run: |app| Box::pin(git_export::export_job(app)),
+        },
+        Job {
+            name: "todos.rollover",
+            interval: MINUTE * 5,
+            jitter: Duration::ZERO,
+            run: |app| Box::pin(rollover::rollover_job(app)),
+        },
+    ]
+}
+
+/// Jobs currently running, so a slow job isn't started twice
+static RUNNING: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn try_mark_running(name: &'static str) -> bool {
+    match RUNNING.lock() {
+        Ok(mut running) if !running.contains(&name) => {
+            running.push(name);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn mark_finished(name: &str) {
+    if let Ok(mut running) = RUNNING.lock() {
+        running.retain(|running| *running != name);
+    }
+}
+
+fn is_running(name: &str) -> bool {
+    RUNNING.lock().map(|running| running.contains(&name)).unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+struct JobRun {
+    name: String,
+    last_run_at: Option<i64>,
+    last_success_at: Option<i64>,
+    last_error: Option<String>,
+    failures: i64,
+    next_run_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    pub name: String,
+    pub interval_secs: u64,
+    pub last_run_at: Option<i64>,
+    pub last_success_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub failures: i64,
+    pub next_run_at: Option<i64>,
+    pub running: bool,
+}
+
+fn jitter_ms(jitter: Duration) -> i64 {
+    let max = jitter.as_millis() as i64;
+    if max == 0 {
+        0
+    } else {
+        rand::thread_rng().gen_range(0..=max)
+    }
+}
+
+/// Delay before the next attempt after `failures` consecutive failures
+fn backoff(failures: i64, interval: Duration) -> Duration {
+    let exponent = failures.clamp(1, 20) as u32 - 1;
+    RETRY_DELAY.saturating_mul(1 << exponent).min(interval)
+}
+
+async fn load_runs(pool: &SqlitePool) -> Result<Vec<JobRun>, String> {
+    sqlx::query_as("SELECT name, last_run_at, last_success_at, last_error, failures, next_run_at FROM job_runs")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn save_run(pool: &SqlitePool, run: &JobRun) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO job_runs (name, last_run_at, last_success_at, last_error, failures, next_run_at)
+         VALUES (?, ?, ?, ?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET
+            last_run_at = excluded.last_run_at,
+            last_success_at = excluded.last_success_at,
+            last_error = excluded.last_error,
+            failures = excluded.failures,
+            next_run_at = excluded.next_run_at",
+    )
+    .bind(&run.name)
+    .bind(run.last_run_at)
+    .bind(run.last_success_at)
+    .bind(&run.last_error)
+    .bind(run.failures)
+    .bind(run.next_run_at)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Run one job and record the outcome and its next run. The job must already be marked running.
+async fn execute(app: &AppHandle, job: &Job, mut run: JobRun) -> JobRun {
+    let started = Utc::now().timestamp_millis();
+    let result = (job.run)(app.clone()).await;
+    let finished = Utc::now().timestamp_millis();
+    mark_finished(job.name);
+
+    run.last_run_at = Some(started);
+    match result {
+        Ok(()) => {
+            run.last_success_at = Some(finished);
+            run.last_error = None;
+            run.failures = 0;
+            run.next_run_at = finished + job.interval.as_millis() as i64 + jitter_ms(job.jitter);
+        }
+        Err(e) => {
+            logger::error(&format!("Job {} failed: {}", job.name, e));
+            run.last_error = Some(e);
+            run.failures += 1;
+            run.next_run_at = finished + backoff(run.failures, job.interval).as_millis() as i64;
+        }
+    }
+
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    if let Err(e) = save_run(&pool, &run).await {
+        logger::error(&format!("Failed to record run of job {}: {}", job.name, e));
+    }
+    run
+}
+
+/// Start every due job that isn't already running, each on its own task
+async fn run_due(app: &AppHandle) -> Result<(), String> {
+    let now = Utc::now().timestamp_millis();
+    let runs = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let runs = load_runs(&pool).await?;
+        // Jobs seen for the first time get a jittered first run instead of all starting now
+        for job in jobs() {
+            if !runs.iter().any(|run| run.name == job.name) {
+                let run = JobRun {
+                    name: job.name.to_string(),
+                    next_run_at: now + jitter_ms(job.jitter),
+                    ..Default::default()
+                };
+                save_run(&pool, &run).await?;
+            }
+        }
+        runs
+    };
+
+    for job in jobs() {
+        let Some(run) = runs.iter().find(|run| run.name == job.name) else {
+            continue;
+        };
+        if run.next_run_at > now || !try_mark_running(job.name) {
+            continue;
+        }
+        let app = app.clone();
+        let run = run.clone();
+        tauri::async_runtime::spawn(async move {
+            execute(&app, &job, run).await;
+        });
+    }
+    Ok(())
+}
+
+/// Start the scheduler that runs registered jobs when they're due
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due(&app).await {
+                logger::error(&format!("Job scheduler failed: {}", e));
+            }
+        }
+    })
+}
+
+fn status(job: &Job, run: Option<&JobRun>) -> JobStatus {
+    JobStatus {
+        name: job.name.to_string(),
+        interval_secs: job.interval.as_secs(),
+        last_run_at: run.and_then(|r| r.last_run_at),
+        last_success_at: run.and_then(|r| r.last_success_at),
+        last_error: run.and_then(|r| r.last_error.clone()),
+        failures: run.map(|r| r.failures).unwrap_or(0),
+        next_run_at: run.map(|r| r.next_run_at),
+        running: is_running(job.name),
+    }
+}
+
+/// Run a job right away, whatever its schedule; its next run counts from now
+#[crate::metrics::command]
+pub async fn run_job_now(app: AppHandle, name: String) -> Result<JobStatus, String> {
+    let job = jobs()
+        .into_iter()
+        .find(|job| job.name == name)
+        .ok_or_else(|| format!("Unknown job: {}", name))?;
+    let run = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        load_runs(&pool).await?.into_iter().find(|run| run.name == name)
+    };
+    if !try_mark_running(job.name) {
+        return Err(format!("Job {} is already running", name));
+    }
+    let run = run.unwrap_or_else(|| JobRun {
+        name: job.name.to_string(),
+        ..Default::default()
+    });
+    let run = execute(&app, &job, run).await;
+    Ok(status(&job, Some(&run)))
+}
+
+#[crate::metrics::command]
+pub async fn get_job_status(app: AppHandle) -> Result<Vec<JobStatus>, String> {
+    let runs = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        load_runs(&pool).await?
+    };
+    Ok(jobs()
+        .iter()
+        .map(|job| status(job, runs.iter().find(|run| run.name == job.name)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_interval() {
+        assert_eq!(backoff(1, DAY), RETRY_DELAY);
+        assert_eq!(backoff(3, DAY), RETRY_DELAY * 4);
+        assert_eq!(backoff(50, DAY), DAY);
+        assert_eq!(backoff(4, Duration::from_secs(120)), Duration::from_secs(120));
+        assert!(jitter_ms(HOUR) <= HOUR.as_millis() as i64);
+        assert_eq!(jitter_ms(Duration::ZERO), 0);
+    }
+}
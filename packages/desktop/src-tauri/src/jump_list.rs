@@ -0,0 +1,317 @@
+use chrono::{Local, NaiveDate};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::logger;
+
+/// Recent journal days listed after the fixed tasks
+const RECENT_ENTRIES: i64 = 5;
+/// Also refresh periodically so "recent" follows the date rolling over
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A jump list / dock menu entry, opened through the deep link dispatcher
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub title: String,
+    pub url: String,
+}
+
+/// The recent dates last applied, so edits that don't change them are skipped
+static APPLIED: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+fn tasks() -> Vec<Item> {
+    vec![
+        Item {
+            title: "New Todo".to_string(),
+            url: "journal-todo://new-todo".to_string(),
+        },
+        Item {
+            title: "Today's Journal".to_string(),
+            url: "journal-todo://open".to_string(),
+        },
+    ]
+}
+
+fn recent_items(dates: &[String]) -> Vec<Item> {
+    dates
+        .iter()
+        .filter_map(|date| {
+            let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+            Some(Item {
+                title: day.format("%a, %b %-d, %Y").to_string(),
+                url: format!("journal-todo://open?date={}", date),
+            })
+        })
+        .collect()
+}
+
+/// Days before today with notes or todos, newest first; private and locked entries are left out
+async fn recent_dates(app: &AppHandle) -> Result<Vec<String>, String> {
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT DISTINCT p.date FROM pages p
+         WHERE p.date < ?1
+           AND (trim(COALESCE(p.notes, '')) != ''
+                OR EXISTS (SELECT 1 FROM todos t WHERE t.workspace_id = p.workspace_id AND t.page_date = p.date))
+           AND NOT EXISTS (SELECT 1 FROM private_pages pp WHERE pp.workspace_id = p.workspace_id AND pp.page_date = p.date)
+           AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = p.workspace_id AND lp.page_date = p.date)
+         ORDER BY p.date DESC
+         LIMIT ?2",
+    )
+    .bind(today)
+    .bind(RECENT_ENTRIES)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().map(|(date,)| date).collect())
+}
+
+async fn refresh(app: &AppHandle) {
+    let dates = match recent_dates(app).await {
+        Ok(dates) => dates,
+        Err(e) => return logger::error(&format!("Failed to load recent entries: {}", e)),
+    };
+    match APPLIED.lock() {
+        Ok(mut applied) if applied.as_ref() != Some(&dates) => *applied = Some(dates.clone()),
+        _ => return,
+    }
+    if let Err(e) = apply(app, tasks(), recent_items(&dates)).await {
+        logger::error(&format!("Failed to update jump list: {}", e));
+    }
+}
+
+/// Keep the Windows jump list / macOS dock menu in line with recent entries
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    let listener_app = app.clone();
+    changes::listen(&app, &["pages", "todos"], move |_| {
+        let app = listener_app.clone();
+        tauri::async_runtime::spawn(async move { refresh(&app).await });
+    });
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            refresh(&app).await;
+        }
+    })
+}
+
+#[cfg(target_os = "windows")]
+async fn apply(_app: &AppHandle, tasks: Vec<Item>, recent: Vec<Item>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || windows_jump_list::apply(&tasks, &recent))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(target_os = "macos")]
+async fn apply(app: &AppHandle, tasks: Vec<Item>, recent: Vec<Item>) -> Result<(), String> {
+    dock_menu::set_app(app);
+    app.run_on_main_thread(move || dock_menu::apply(&tasks, &recent))
+        .map_err(|e| e.to_string())
+}
+
+/// No jump list or dock menu to fill elsewhere
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+async fn apply(_app: &AppHandle, _tasks: Vec<Item>, _recent: Vec<Item>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Jump list entries relaunch the app with the deep link as argument, which
+/// the single-instance plugin forwards to the running app
+#[cfg(target_os = "windows")]
+mod windows_jump_list {
+    use windows::core::{Interface, HSTRING};
+    use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+    use windows::Win32::System::Com::StructuredStorage::PROPVARIANT;
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED};
+    use windows::Win32::UI::Shell::Common::{IObjectArray, IObjectCollection};
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::{DestinationList, EnumerableObjectCollection, ICustomDestinationList, IShellLinkW, ShellLink};
+
+    use super::Item;
+
+    unsafe fn link(exe: &HSTRING, item: &Item) -> windows::core::Result<IShellLinkW> {
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+        link.SetPath(exe)?;
+        link.SetArguments(&HSTRING::from(item.url.as_str()))?;
+        link.SetIconLocation(exe, 0)?;
+        let store: IPropertyStore = link.cast()?;
+        store.SetValue(&PKEY_Title, &PROPVARIANT::from(item.title.as_str()))?;
+        store.Commit()?;
+        Ok(link)
+    }
+
+    unsafe fn collection(exe: &HSTRING, items: &[Item]) -> windows::core::Result<IObjectArray> {
+        let collection: IObjectCollection = CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+        for item in items {
+            collection.AddObject(&link(exe, item)?)?;
+        }
+        collection.cast()
+    }
+
+    pub fn apply(tasks: &[Item], recent: &[Item]) -> Result<(), String> {
+        let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+        let exe = HSTRING::from(exe.as_os_str());
+        unsafe {
+            // Already initialized on a reused blocking thread is fine
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            let result = (|| {
+                let list: ICustomDestinationList = CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+                let mut slots = 0u32;
+                let _removed: IObjectArray = list.BeginList(&mut slots)?;
+                if !recent.is_empty() {
+                    list.AppendCategory(&HSTRING::from("Recent Entries"), &collection(&exe, recent)?)?;
+                }
+                list.AddUserTasks(&collection(&exe, tasks)?)?;
+                list.CommitList()
+            })();
+            result.map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// AppKit asks the app delegate for `applicationDockMenu:`; Tao's delegate doesn't
+/// implement it, so it is added to the delegate's class at runtime
+#[cfg(target_os = "macos")]
+mod dock_menu {
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject, Imp, Sel};
+    use objc2::{define_class, msg_send, sel, MainThreadMarker, MainThreadOnly};
+    use objc2_app_kit::{NSApplication, NSMenu, NSMenuItem};
+    use objc2_foundation::{NSObject, NSString};
+    use std::cell::RefCell;
+    use std::sync::{Once, OnceLock};
+    use tauri::AppHandle;
+
+    use super::Item;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+    static INSTALL: Once = Once::new();
+
+    struct DockMenu {
+        menu: Retained<NSMenu>,
+        /// Menu items only hold their target weakly
+        _target: Retained<MenuTarget>,
+        /// Deep links by item tag
+        urls: Vec<String>,
+    }
+
+    thread_local! {
+        static MENU: RefCell<Option<DockMenu>> = const { RefCell::new(None) };
+    }
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[thread_kind = MainThreadOnly]
+        #[name = "JournalTodoDockMenuTarget"]
+        struct MenuTarget;
+
+        impl MenuTarget {
+            #[unsafe(method(openItem:))]
+            fn open_item(&self, sender: &NSMenuItem) {
+                let tag = sender.tag();
+                let url = MENU.with_borrow(|menu| {
+                    menu.as_ref()
+                        .and_then(|menu| usize::try_from(tag).ok().and_then(|tag| menu.urls.get(tag).cloned()))
+                });
+                if let (Some(app), Some(url)) = (APP.get(), url.and_then(|url| url::Url::parse(&url).ok())) {
+                    crate::dispatch::handle_deep_links(app, vec![url]);
+                }
+            }
+        }
+    );
+
+    unsafe extern "C-unwind" fn application_dock_menu(_this: &AnyObject, _cmd: Sel, _sender: &AnyObject) -> *mut NSMenu {
+        MENU.with_borrow(|menu| {
+            menu.as_ref()
+                .map_or(std::ptr::null_mut(), |menu| Retained::as_ptr(&menu.menu) as *mut NSMenu)
+        })
+    }
+
+    fn install(mtm: MainThreadMarker) {
+        let app = NSApplication::sharedApplication(mtm);
+        let Some(delegate) = app.delegate() else {
+            crate::logger::error("No app delegate to add the dock menu to");
+            return;
+        };
+        let delegate: &AnyObject = AsRef::<AnyObject>::as_ref(&*delegate);
+        let class = delegate.class() as *const AnyClass as *mut AnyClass;
+        let added = unsafe {
+            let imp: Imp = std::mem::transmute(
+                application_dock_menu as unsafe extern "C-unwind" fn(&AnyObject, Sel, &AnyObject) -> *mut NSMenu,
+            );
+            objc2::ffi::class_addMethod(class, sel!(applicationDockMenu:), imp, c"@@:@".as_ptr())
+        };
+        if !added.as_bool() {
+            crate::logger::error("Failed to add the dock menu to the app delegate");
+        }
+    }
+
+    pub fn set_app(app: &AppHandle) {
+        let _ = APP.set(app.clone());
+    }
+
+    pub fn apply(tasks: &[Item], recent: &[Item]) {
+        let Some(mtm) = MainThreadMarker::new() else {
+            return;
+        };
+        INSTALL.call_once(|| install(mtm));
+
+        let target: Retained<MenuTarget> = unsafe { msg_send![MenuTarget::alloc(mtm), init] };
+        let menu = NSMenu::new(mtm);
+        let mut urls = Vec::new();
+        for (i, group) in [tasks, recent].into_iter().enumerate() {
+            if i > 0 && !group.is_empty() {
+                menu.addItem(&NSMenuItem::separatorItem(mtm));
+            }
+            for item in group {
+                let entry = unsafe {
+                    NSMenuItem::initWithTitle_action_keyEquivalent(
+                        NSMenuItem::alloc(mtm),
+                        &NSString::from_str(&item.title),
+                        Some(sel!(openItem:)),
+                        &NSString::from_str(""),
+                    )
+                };
+                unsafe { entry.setTarget(Some(&target)) };
+                entry.setTag(urls.len() as isize);
+                menu.addItem(&entry);
+                urls.push(item.url.clone());
+            }
+        }
+        MENU.with_borrow_mut(|current| {
+            *current = Some(DockMenu {
+                menu,
+                _target: target,
+                urls,
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items_are_deep_links() {
+        let recent = recent_items(&["2024-03-04".to_string(), "garbage".to_string()]);
+        assert_eq!(
+            recent,
+            vec![Item {
+                title: "Mon, Mar 4, 2024".to_string(),
+                url: "journal-todo://open?date=2024-03-04".to_string(),
+            }]
+        );
+        for item in tasks().iter().chain(&recent) {
+            let url = url::Url::parse(&item.url).unwrap();
+            assert!(crate::dispatch::from_deep_link(&url).is_ok(), "{}", item.url);
+        }
+    }
+}
@@ -1,36 +1,162 @@
+mod ai;
+mod app_config;
+mod archive;
+mod attachments;
+mod automation;
+mod badge;
+mod bootstrap;
+mod bulk;
+mod calendar;
+mod compression;
+mod confirm;
+mod crypto;
+mod daily_entry;
 mod db;
+mod diagnostics;
+mod dispatch;
+mod duplicates;
+mod email_inbox;
+mod epub_export;
+mod feed_export;
+mod filters;
+mod focus;
+mod fractional_index;
+mod git_export;
+mod goals;
+mod health;
+mod hooks;
+mod http_api;
+mod idle;
+mod integrity;
+mod jobs;
+mod jump_list;
+mod lifecycle;
+mod link_preview;
+mod locale;
+mod location;
 mod logger;
+mod markdown;
+mod merge;
+mod metrics;
+mod mood;
+mod ocr;
+mod os_index;
+mod pairing;
+mod palette;
+mod print;
+mod planner;
+mod privacy;
+mod profiles;
+mod reminders;
+mod reorder;
+mod review;
+mod rollover;
+mod rules;
+mod search;
+mod secrets;
+mod settings;
+mod site_export;
+mod sync;
+mod tags;
+mod telemetry;
+mod theme;
+mod timers;
+mod timestamps;
+mod todo_tree;
+mod tray;
+mod updater;
+mod voice_memo;
+mod weather;
+mod widget;
+mod window_manager;
+mod window_state;
+mod workspaces;
 
-use db::{DatabaseState, Migration, execute_single_sql, execute_batch_sql};
+use db::{DatabaseState, execute_single_sql, execute_batch_sql, dev_reset_database};
 use std::path::{Path, PathBuf};
 use tauri::Manager;
+use tauri_plugin_deep_link::DeepLinkExt;
+use profiles::ProfileManager;
+use workspaces::WorkspaceManager;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-#[tauri::command]
+#[crate::metrics::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-#[tauri::command]
+#[crate::metrics::command]
 fn open_devtools(webview: tauri::Webview) {
     #[cfg(debug_assertions)]
     webview.open_devtools();
 }
 
 /// Get the log file path for debugging
-#[tauri::command]
+#[crate::metrics::command]
 fn get_log_path() -> Option<String> {
     logger::get_log_path().map(|p| p.to_string_lossy().to_string())
 }
 
+/// Longest stack trace written to the log for a single frontend error
+const MAX_FRONTEND_STACK_LEN: usize = 8 * 1024;
+
+/// An error caught by the webview's `window.onerror` / `unhandledrejection` handlers
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FrontendError {
+    /// "error" or "unhandledrejection"
+    kind: Option<String>,
+    message: String,
+    stack: Option<String>,
+    source: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+/// Write a frontend error with its stack trace into the backend log
+#[crate::metrics::command]
+fn report_frontend_error(webview: tauri::Webview, payload: FrontendError) {
+    let mut entry = format!(
+        "[{}] {}: {}",
+        webview.label(),
+        payload.kind.as_deref().unwrap_or("error"),
+        payload.message
+    );
+    if let Some(source) = &payload.source {
+        entry.push_str(&format!(" at {}:{}:{}", source, payload.line.unwrap_or(0), payload.column.unwrap_or(0)));
+    }
+    if let Some(stack) = &payload.stack {
+        let stack: String = stack.chars().take(MAX_FRONTEND_STACK_LEN).collect();
+        entry.push('\n');
+        entry.push_str(&stack);
+    }
+    logger::frontend_error(&entry);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Initialize logger FIRST with fallback location
+    // Initialize logger FIRST in the platform log directory
     // This ensures we can log even if app_data_dir fails
     let log_path = logger::init_early();
     logger::info(&format!("Early log initialized at: {}", log_path.display()));
     
-    tauri::Builder::default()
+    let builder = tauri::Builder::default();
+
+    // Must be registered first so a second launch (e.g. from a deep link) is forwarded to us
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+        // Automation verbs run in the background without raising the window
+        if automation::handle(app, &argv) {
+            return;
+        }
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }));
+
+    builder
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -55,10 +181,6 @@ pub fn run() {
                     fallback
                 }
             };
-            
-            // Re-initialize logger in proper location
-            logger::init(Some(&app_data_dir));
-            logger::info("Logger re-initialized in app data directory");
 
             // Determine database path based on build mode
             #[cfg(debug_assertions)]
@@ -78,6 +200,25 @@ pub fn run() {
                 app_data_dir.join("journal.db")
             };
 
+            // The active profile decides which workspaces, databases and logs are used
+            let profile_manager = ProfileManager::load(&app_data_dir, &db_path);
+            profiles::select_on_startup(&profile_manager);
+            let profile = profile_manager.active();
+            if let Some(log_dir) = profile_manager.log_dir(&profile) {
+                logger::switch_dir(Some(&log_dir));
+            }
+            logger::info(&format!("Active profile: {}", profile.name));
+
+            // The active workspace decides which database is opened
+            let workspace_manager = WorkspaceManager::load(&profile.dir, &profile_manager.default_db_path(&profile));
+            let active_workspace = workspace_manager.active();
+            logger::info(&format!("Active workspace: {}", active_workspace.name));
+            let db_path = active_workspace.db_path;
+            app.manage(app_config::AppConfig::load(&app_data_dir));
+            app.manage(profile_manager);
+            app.manage(workspace_manager);
+            app.manage(window_manager::WindowPayloads::default());
+
             let db_path_str = match db_path.to_str() {
                 Some(s) => s.to_string(),
                 None => {
@@ -174,54 +315,228 @@ pub fn run() {
                 return Err(format!("Migrations directory not found: {}", migrations_dir.display()).into());
             }
 
-            // Initialize database
+            // Initialize the database in the background so the window shows right away;
+            // anything using it waits on the pool until it is ready
             logger::info("Initializing database...");
-            
-            let result = tauri::async_runtime::block_on(async {
-                logger::info("Creating database connection...");
-                let db_state = match DatabaseState::new(&db_path_str).await {
-                    Ok(state) => {
-                        logger::info("Database connection created");
-                        state
-                    }
-                    Err(e) => {
-                        logger::error(&format!("Failed to create database: {}", e));
-                        return Err(format!("Failed to initialize database: {}", e));
-                    }
-                };
-
-                logger::info("Running migrations...");
-                let pool = db_state.pool.lock().await;
-                let migration = Migration::new((*pool).clone(), migrations_dir.clone());
-                if let Err(e) = migration.run().await {
-                    logger::error(&format!("Migration failed: {}", e));
-                    return Err(format!("Failed to run migrations: {}", e));
-                }
-                drop(pool);
-                
-                logger::info("Migrations completed");
-                Ok(db_state)
-            });
+            let (db_state, init_guard) = tauri::async_runtime::block_on(DatabaseState::lazy(
+                db_path.clone(),
+                migrations_dir.clone(),
+            ));
+            app.manage(db_state);
+            app.manage(voice_memo::VoiceRecorder::default());
+            app.manage(filters::FilterCache::default());
+            app.manage(updater::PendingUpdate::default());
+            app.manage(db::write_queue::WriteQueue::default());
+            app.manage(lifecycle::BackgroundTasks::default());
+            app.manage(bootstrap::BootstrapCache::default());
+            app.manage(sync::SyncTracker::default());
+            app.manage(pairing::PendingPairings::default());
 
-            match result {
-                Ok(db_state) => {
-                    app.manage(db_state);
-                    logger::info("Setup complete - database ready");
-                    Ok(())
-                }
-                Err(e) => {
-                    logger::error(&format!("Setup failed: {}", e));
-                    Err(e.into())
-                }
+            if let Err(e) = tray::init(app) {
+                logger::error(&format!("Failed to create tray icon: {}", e));
             }
+
+            #[cfg(any(windows, target_os = "linux"))]
+            if let Err(e) = app.deep_link().register_all() {
+                logger::error(&format!("Failed to register deep link schemes: {}", e));
+            }
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                dispatch::handle_deep_links(&handle, event.urls());
+            });
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                dispatch::handle_deep_links(app.handle(), urls);
+            }
+            automation::handle(app.handle(), &std::env::args().collect::<Vec<_>>());
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                lifecycle::initialize(&handle, init_guard).await;
+            });
+            logger::info("Setup complete - database initializing");
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             greet,
             open_devtools,
             get_log_path,
+            metrics::get_command_metrics,
+            metrics::reset_command_metrics,
+            db::profiler::get_query_profile,
+            db::profiler::reset_query_profile,
+            report_frontend_error,
             execute_single_sql,
-            execute_batch_sql
+            execute_batch_sql,
+            dev_reset_database,
+            db::mock::generate_mock_data,
+            db::commands::get_db_status,
+            db::functions::generate_id,
+            db::write_queue::queue_sql,
+            db::write_queue::flush_write_queue,
+            db::stats::get_db_stats,
+            db::pragmas::benchmark_pragmas,
+            db::benchmark::run_db_benchmark,
+            db::attach::attach_database,
+            db::attach::detach_database,
+            db::attach::list_attached_databases,
+            ai::summarize_entries,
+            ai::suggest_todo_breakdown,
+            ai::get_ai_results,
+            attachments::add_attachment,
+            attachments::list_attachments,
+            attachments::get_attachment_path,
+            attachments::delete_attachment,
+            attachments::cleanup_attachments,
+            jobs::run_job_now,
+            jobs::get_job_status,
+            git_export::export_journal_to_git,
+            site_export::export_site,
+            epub_export::export_epub,
+            feed_export::export_feed,
+            feed_export::stop_serving_feed,
+            print::print_entry,
+            email_inbox::check_email_inbox,
+            rules::list_rules,
+            rules::save_rule,
+            rules::delete_rule,
+            rules::get_rule_runs,
+            hooks::list_hooks,
+            hooks::save_hook,
+            hooks::set_hook_enabled,
+            hooks::delete_hook,
+            hooks::run_hook,
+            theme::get_system_theme,
+            palette::list_actions,
+            dispatch::dispatch_action,
+            todo_tree::get_todo_tree,
+            todo_tree::get_todo_rollups,
+            reorder::reorder_item,
+            bulk::bulk_update,
+            tags::get_tag_usage,
+            tags::rename_tag,
+            tags::merge_tags,
+            compression::compress_old_entries,
+            integrity::verify_data_integrity,
+            sync::get_sync_status,
+            pairing::create_pairing_payload,
+            pairing::accept_pairing_payload,
+            pairing::list_paired_devices,
+            pairing::revoke_device,
+            crypto::get_e2e_key_status,
+            crypto::create_e2e_key,
+            crypto::export_recovery_phrase,
+            crypto::verify_recovery_phrase,
+            crypto::restore_e2e_key,
+            crypto::rotate_e2e_key,
+            bootstrap::get_bootstrap_data,
+            diagnostics::create_support_bundle,
+            filters::list_filters,
+            filters::save_filter,
+            filters::delete_filter,
+            filters::evaluate_filter,
+            filters::preview_filter,
+            goals::list_goals,
+            goals::save_goal,
+            goals::delete_goal,
+            goals::link_goal,
+            goals::unlink_goal,
+            goals::get_goal_progress,
+            health::health_check,
+            http_api::get_clipper_info,
+            link_preview::fetch_link_preview,
+            markdown::convert_html_to_markdown,
+            markdown::render_markdown,
+            location::get_current_location,
+            location::set_entry_location,
+            location::get_entry_location,
+            location::get_entries_near,
+            mood::set_entry_mood,
+            mood::get_entry_mood,
+            mood::get_mood_trends,
+            mood::get_mood_correlations,
+            ocr::extract_text,
+            ocr::get_tesseract_path,
+            ocr::set_tesseract_path,
+            reminders::set_todo_reminder,
+            reminders::clear_todo_reminder,
+            reminders::handle_reminder_action,
+            search::global_search,
+            archive::archive_entries,
+            archive::search_archive,
+            merge::merge_database,
+            duplicates::find_duplicates,
+            duplicates::merge_records,
+            secrets::set_secret,
+            secrets::delete_secret,
+            secrets::has_secret,
+            privacy::get_app_lock_status,
+            privacy::set_app_lock_passphrase,
+            privacy::unlock_private_entries,
+            privacy::lock_private_entries,
+            privacy::set_entry_private,
+            privacy::get_private_entry_notes,
+            privacy::set_private_entry_notes,
+            privacy::lock_entry,
+            privacy::unlock_entry,
+            privacy::remove_entry_lock,
+            settings::get_setting,
+            settings::set_setting,
+            settings::get_all_settings,
+            settings::export_settings,
+            settings::import_settings,
+            telemetry::get_telemetry_status,
+            telemetry::get_telemetry_data,
+            telemetry::set_telemetry_enabled,
+            timers::start_timer,
+            timers::pause_timer,
+            timers::resume_timer,
+            timers::stop_timer,
+            timers::get_active_timers,
+            planner::plan_day,
+            planner::accept_plan,
+            planner::get_day_plan,
+            calendar::get_calendar_events,
+            review::generate_review,
+            focus::get_focus_blocking,
+            focus::set_focus_blocklist,
+            focus::set_focus_blocking_consent,
+            focus::start_focus_blocking,
+            focus::emergency_stop_focus,
+            locale::get_locale_info,
+            timestamps::now,
+            timestamps::convert_timestamp,
+            timestamps::normalize_timestamps,
+            updater::check_for_updates,
+            updater::install_update,
+            updater::set_update_channel,
+            voice_memo::start_voice_memo,
+            voice_memo::stop_voice_memo,
+            voice_memo::cancel_voice_memo,
+            voice_memo::get_voice_memo,
+            weather::get_weather,
+            widget::get_widget_state,
+            widget::toggle_today_widget,
+            widget::set_widget_pinned,
+            widget::set_widget_click_through,
+            window_manager::open_window,
+            window_manager::get_window_payload,
+            workspaces::list_workspaces,
+            workspaces::get_active_workspace,
+            workspaces::create_workspace,
+            workspaces::switch_workspace,
+            workspaces::set_database_location,
+            workspaces::get_workspace_settings,
+            workspaces::update_workspace_settings,
+            profiles::list_profiles,
+            profiles::create_profile,
+            profiles::switch_profile,
+            profiles::set_choose_profile_on_startup
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                tauri::async_runtime::block_on(lifecycle::shutdown(app));
+            }
+        });
 }
@@ -0,0 +1,139 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::OwnedMutexGuard;
+
+use crate::db::write_queue::WriteQueue;
+use crate::db::{self, DatabaseState};
+use crate::hooks::{self, HookEvent};
+use crate::{
+    badge, bootstrap, filters, focus, health, http_api, idle, jobs, jump_list, logger, os_index, reminders, rules, telemetry,
+    theme, tray, weather, widget, window_state,
+};
+
+/// Emitted once the database is migrated and usable, or failed to open
+pub const DB_READY_EVENT: &str = "db://ready";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DbReady {
+    ok: bool,
+    error: Option<String>,
+}
+
+/// Long-running background tasks, stopped when the app shuts down
+#[derive(Default)]
+pub struct BackgroundTasks {
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    shut_down: AtomicBool,
+}
+
+impl BackgroundTasks {
+    pub fn register(&self, handle: JoinHandle<()>) {
+        if let Ok(mut handles) = self.handles.lock() {
+            handles.push(handle);
+        }
+    }
+
+    fn abort_all(&self) {
+        if let Ok(mut handles) = self.handles.lock() {
+            for handle in handles.drain(..) {
+                handle.abort();
+            }
+        }
+    }
+}
+
+/// Open and migrate the database, then start everything that depends on it
+pub async fn initialize(app: &AppHandle, guard: OwnedMutexGuard<SqlitePool>) {
+    let state = app.state::<DatabaseState>();
+    if let Err(e) = state.initialize(guard).await {
+        logger::error(&format!("Setup failed: {}", e));
+        // The window stays up so the frontend can report it through health_check
+        app.manage(health::SetupError(e.clone()));
+        let _ = app.emit(
+            DB_READY_EVENT,
+            DbReady {
+                ok: false,
+                error: Some(e),
+            },
+        );
+        return;
+    }
+    logger::info("Database ready");
+    logger::watch_settings(app).await;
+    db::commands::watch_settings(app).await;
+    bootstrap::start(app.clone());
+    if let Err(e) = bootstrap::preload(app).await {
+        logger::error(&format!("Failed to preload bootstrap data: {}", e));
+    }
+
+    let tasks = app.state::<BackgroundTasks>();
+    tasks.register(db::write_queue::start(app.clone()));
+    tasks.register(reminders::start(app.clone()));
+    tasks.register(badge::start(app.clone()));
+    tasks.register(http_api::start(app.clone()));
+    tasks.register(weather::start(app.clone()));
+    tasks.register(idle::start(app.clone()));
+    tasks.register(telemetry::start(app.clone()));
+    tasks.register(jobs::start(app.clone()));
+    tasks.register(tray::start(app.clone()));
+    tasks.register(jump_list::start(app.clone()));
+    tasks.register(os_index::start(app.clone()));
+    tasks.register(rules::start(app.clone()));
+    tasks.register(focus::start(app.clone()));
+    filters::start(app.clone());
+
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window_state::restore(&window).await {
+            logger::error(&format!("Failed to restore window state: {}", e));
+        }
+        window_state::track(&window);
+        theme::track(&window);
+    }
+    if let Err(e) = widget::restore(app).await {
+        logger::error(&format!("Failed to restore today widget: {}", e));
+    }
+
+    let _ = app.emit(DB_READY_EVENT, DbReady { ok: true, error: None });
+    hooks::import_from_journal(app).await;
+    hooks::fire(app, HookEvent::AppStarted, serde_json::json!({ "version": app.package_info().version.to_string() }));
+}
+
+/// Flush pending writes, stop background tasks, checkpoint the WAL and close the database.
+/// Only the first call does anything.
+pub async fn shutdown(app: &AppHandle) {
+    let Some(tasks) = app.try_state::<BackgroundTasks>() else {
+        return;
+    };
+    if tasks.shut_down.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    logger::info("Shutting down...");
+
+    if let Err(e) = focus::release(app).await {
+        logger::error(&format!("Failed to end the focus block on exit: {}", e));
+    }
+
+    if let Some(queue) = app.try_state::<WriteQueue>() {
+        if let Err(e) = queue.flush(app).await {
+            logger::error(&format!("Failed to flush writes on exit: {}", e));
+        }
+    }
+
+    let Some(state) = app.try_state::<DatabaseState>() else {
+        tasks.abort_all();
+        return;
+    };
+    // Waiting for the pool means no task is stopped halfway through a statement
+    let pool = state.pool.lock().await;
+    tasks.abort_all();
+    state.close_reporting().await;
+    match db::database::checkpoint_and_close(&pool).await {
+        Ok(()) => logger::info("Database closed cleanly"),
+        Err(e) => logger::error(&format!("Failed to close database cleanly: {}", e)),
+    }
+}
@@ -0,0 +1,257 @@
+use chrono::Utc;
+use futures_util::StreamExt;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+use crate::db::DatabaseState;
+use crate::logger;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const MAX_REDIRECTS: usize = 5;
+/// Cached previews older than this are fetched again
+const CACHE_TTL_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+    pub fetched_at: i64,
+}
+
+/// Whether an address is reachable from the public internet
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_ipv4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ipv4(v4),
+            None => is_public_ipv6(v6),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // Carrier-grade NAT 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // "This network" 0.0.0.0/8
+        || a == 0)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local fe80::/10
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// DNS resolver that refuses to hand out non-public addresses
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Reject URLs that aren't http(s) or that point at a non-public IP literal
+fn check_url(url: &Url) -> Result<(), String> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("Unsupported URL scheme: {}", url.scheme()));
+    }
+    match url.host() {
+        Some(url::Host::Ipv4(ip)) if !is_public_ipv4(ip) => Err("URL points to a private address".to_string()),
+        Some(url::Host::Ipv6(ip)) if !is_public_ip(IpAddr::V6(ip)) => {
+            Err("URL points to a private address".to_string())
+        }
+        Some(url::Host::Domain(domain)) if domain.eq_ignore_ascii_case("localhost") => {
+            Err("URL points to a private address".to_string())
+        }
+        Some(_) => Ok(()),
+        None => Err("URL has no host".to_string()),
+    }
+}
+
+fn build_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .no_proxy()
+        .dns_resolver(Arc::new(PublicOnlyResolver))
+        .redirect(reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("Too many redirects")
+            } else if let Err(e) = check_url(attempt.url()) {
+                attempt.error(e)
+            } else {
+                attempt.follow()
+            }
+        }))
+        .user_agent(concat!("JournalTodo/", env!("CARGO_PKG_VERSION"), " (link preview)"))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Download at most `MAX_BODY_BYTES` of an HTML page
+async fn fetch_html(url: &Url) -> Result<(Url, String), String> {
+    let response = build_client()?
+        .get(url.clone())
+        .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+
+    let final_url = response.url().clone();
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| v.contains("html"));
+    if !is_html {
+        return Err("URL does not point to an HTML page".to_string());
+    }
+
+    let mut body = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        let remaining = MAX_BODY_BYTES - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+        if body.len() >= MAX_BODY_BYTES {
+            break;
+        }
+    }
+
+    Ok((final_url, String::from_utf8_lossy(&body).into_owned()))
+}
+
+/// Extract title, description, image and site name from page metadata
+fn parse_preview(url: &str, base: &Url, html: &str) -> LinkPreview {
+    let document = Html::parse_document(html);
+
+    let meta = |attr: &str, names: &[&str]| -> Option<String> {
+        names.iter().find_map(|name| {
+            let selector = Selector::parse(&format!("meta[{}=\"{}\"]", attr, name)).ok()?;
+            document
+                .select(&selector)
+                .filter_map(|el| el.value().attr("content"))
+                .map(|content| content.trim().to_string())
+                .find(|content| !content.is_empty())
+        })
+    };
+
+    let title = meta("property", &["og:title"])
+        .or_else(|| meta("name", &["twitter:title"]))
+        .or_else(|| {
+            let selector = Selector::parse("title").ok()?;
+            document
+                .select(&selector)
+                .next()
+                .map(|el| el.text().collect::<String>().trim().to_string())
+                .filter(|t| !t.is_empty())
+        });
+    let description = meta("property", &["og:description"])
+        .or_else(|| meta("name", &["description", "twitter:description"]));
+    let image_url = meta("property", &["og:image", "og:image:url"])
+        .or_else(|| meta("name", &["twitter:image"]))
+        .and_then(|image| base.join(&image).ok())
+        .map(|image| image.to_string());
+    let site_name = meta("property", &["og:site_name"]);
+
+    LinkPreview {
+        url: url.to_string(),
+        title,
+        description,
+        image_url,
+        site_name,
+        fetched_at: Utc::now().timestamp_millis(),
+    }
+}
+
+async fn cached_preview(pool: &SqlitePool, url: &str) -> Result<Option<LinkPreview>, String> {
+    sqlx::query_as(
+        "SELECT url, title, description, image_url, site_name, fetched_at FROM link_previews WHERE url = ? AND fetched_at > ?",
+    )
+    .bind(url)
+    .bind(Utc::now().timestamp_millis() - CACHE_TTL_MS)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn store_preview(pool: &SqlitePool, preview: &LinkPreview) -> Result<(), String> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO link_previews (url, title, description, image_url, site_name, fetched_at)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&preview.url)
+    .bind(&preview.title)
+    .bind(&preview.description)
+    .bind(&preview.image_url)
+    .bind(&preview.site_name)
+    .bind(preview.fetched_at)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Fetch page metadata for a link preview, served from cache when fresh
+#[crate::metrics::command]
+pub async fn fetch_link_preview(
+    state: State<'_, DatabaseState>,
+    url: String,
+) -> Result<LinkPreview, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    check_url(&parsed)?;
+    let key = parsed.to_string();
+
+    {
+        let pool = state.pool.lock().await;
+        if let Some(preview) = cached_preview(&pool, &key).await? {
+            return Ok(preview);
+        }
+    }
+
+    let (final_url, html) = fetch_html(&parsed).await.map_err(|e| {
+        logger::error(&format!("Link preview failed: {}", e));
+        e
+    })?;
+    let preview = parse_preview(&key, &final_url, &html);
+
+    let pool = state.pool.lock().await;
+    store_preview(&pool, &preview).await?;
+    Ok(preview)
+}
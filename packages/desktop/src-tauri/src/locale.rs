@@ -0,0 +1,295 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/// Regions whose weeks start on Sunday or Saturday, after CLDR's week data; the rest start on Monday
+const SUNDAY_FIRST: &[&str] = &[
+    "AG", "AS", "BD", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU", "HK", "HN", "ID",
+    "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX", "MZ", "NI", "NP", "PA", "PE", "PH",
+    "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW", "UM", "US", "VE", "VI", "WS", "YE", "ZA", "ZW",
+];
+const SATURDAY_FIRST: &[&str] =
+    &["AE", "AF", "BH", "DJ", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY"];
+const HOUR12: &[&str] = &["AU", "BD", "CA", "EG", "IN", "KR", "NZ", "PH", "PK", "SA", "TW", "US"];
+/// Languages writing 1.234,5 or 1 234,5 rather than 1,234.5
+const DECIMAL_COMMA: &[&str] = &[
+    "bg", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "it", "lt", "lv", "nb", "nl", "no", "pl",
+    "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk",
+];
+const SPACE_GROUPING: &[&str] =
+    &["bg", "cs", "et", "fi", "fr", "hu", "lt", "lv", "nb", "no", "pl", "ru", "sk", "sv", "uk"];
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocaleInfo {
+    /// BCP 47 tag, e.g. "de-DE"
+    pub locale: String,
+    pub language: String,
+    pub region: Option<String>,
+    /// IANA name, e.g. "Europe/Berlin"; "UTC" when the OS doesn't tell
+    pub timezone: String,
+    pub utc_offset_minutes: i32,
+    /// 0 (Sunday) to 6, counted like `Date.getDay()` and the `start_of_week` SQL function
+    pub first_day_of_week: u32,
+    /// Short date pattern in CLDR letters, e.g. "dd.MM.yyyy"
+    pub date_format: String,
+    /// "HH:mm" or "h:mm a"
+    pub time_format: String,
+    pub hour12: bool,
+    pub decimal_separator: String,
+    pub grouping_separator: String,
+}
+
+/// What the OS reports besides its locale; anything missing follows the locale's region
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+struct OsPreferences {
+    locale: Option<String>,
+    first_day_of_week: Option<u32>,
+    date_format: Option<String>,
+    hour12: Option<bool>,
+    decimal_separator: Option<String>,
+    grouping_separator: Option<String>,
+}
+
+/// `en_US.UTF-8@euro` or `en-us` as `en-US`; None for the C and POSIX locales
+fn normalize_locale(raw: &str) -> Option<String> {
+    let tag = raw.trim().split(['.', '@']).next().unwrap_or_default().replace('_', "-");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    let mut parts = tag.split('-');
+    let language = parts.next()?.to_lowercase();
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let rest: Vec<String> = parts
+        .map(|part| match part.len() {
+            2 => part.to_uppercase(),
+            4 => part[..1].to_uppercase() + &part[1..].to_lowercase(),
+            _ => part.to_string(),
+        })
+        .collect();
+    Some(std::iter::once(language).chain(rest).collect::<Vec<_>>().join("-"))
+}
+
+fn region_of(locale: &str) -> Option<String> {
+    locale
+        .split('-')
+        .skip(1)
+        .find(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_uppercase()))
+        .map(str::to_string)
+}
+
+fn region_date_format(region: &str) -> &'static str {
+    match region {
+        "US" | "PH" | "BZ" | "FM" | "MH" | "PW" => "MM/dd/yyyy",
+        "CN" | "JP" | "TW" | "ZA" => "yyyy/MM/dd",
+        "KR" | "HU" => "yyyy. MM. dd.",
+        "SE" | "LT" | "CA" => "yyyy-MM-dd",
+        "NL" => "dd-MM-yyyy",
+        "AT" | "BG" | "BY" | "CH" | "CZ" | "DE" | "DK" | "EE" | "FI" | "HR" | "KZ" | "LV" | "NO" | "PL" | "RO" | "RS"
+        | "RU" | "SI" | "SK" | "TR" | "UA" => "dd.MM.yyyy",
+        _ => "dd/MM/yyyy",
+    }
+}
+
+fn separators(language: &str, region: Option<&str>) -> (&'static str, &'static str) {
+    match (language, region) {
+        ("de", Some("CH" | "LI")) => (".", "\u{2019}"),
+        ("es", Some("MX" | "US")) => (".", ","),
+        _ if SPACE_GROUPING.contains(&language) => (",", "\u{a0}"),
+        _ if DECIMAL_COMMA.contains(&language) => (",", "."),
+        _ => (".", ","),
+    }
+}
+
+/// What a glibc `strftime` date format such as `%d.%m.%Y` looks like in CLDR letters
+#[cfg(any(not(any(target_os = "macos", windows)), test))]
+fn strftime_to_pattern(format: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        out.push_str(match chars.next()? {
+            'd' => "dd",
+            'e' => "d",
+            'm' => "MM",
+            'Y' => "yyyy",
+            'y' => "yy",
+            'b' | 'h' => "MMM",
+            'B' => "MMMM",
+            'a' => "EEE",
+            'A' => "EEEE",
+            'F' => "yyyy-MM-dd",
+            'D' => "MM/dd/yy",
+            '%' => "%",
+            _ => return None,
+        });
+    }
+    Some(out)
+}
+
+/// First day of the week from glibc's `week-1stday` (a date in the week origin) and
+/// `first_weekday` (its 1-based day)
+#[cfg(any(not(any(target_os = "macos", windows)), test))]
+fn glibc_first_day(week_first: &str, first_weekday: &str) -> Option<u32> {
+    use chrono::Datelike;
+    let origin = chrono::NaiveDate::parse_from_str(week_first.trim(), "%Y%m%d").ok()?;
+    let offset: u32 = first_weekday.trim().parse().ok().filter(|day| (1..=7).contains(day))?;
+    Some((origin.weekday().num_days_from_sunday() + offset - 1) % 7)
+}
+
+/// `defaults read -g AppleFirstWeekday` output, `{ gregorian = 2; }` for Monday
+#[cfg(any(target_os = "macos", test))]
+fn apple_first_day(output: &str) -> Option<u32> {
+    let value = output.split("gregorian").nth(1)?.trim_start().strip_prefix('=')?;
+    let day: u32 = value.trim_end_matches(|c: char| c == ';' || c == '}' || c.is_whitespace()).trim().parse().ok()?;
+    (1..=7).contains(&day).then(|| day - 1)
+}
+
+/// The OS's preferences filled in from the locale's region where the OS is silent
+fn resolve(os: OsPreferences, timezone: String, utc_offset_minutes: i32) -> LocaleInfo {
+    let locale = os.locale.as_deref().and_then(normalize_locale).unwrap_or_else(|| "en-US".to_string());
+    let language = locale.split('-').next().unwrap_or("en").to_string();
+    let region = region_of(&locale);
+    let region_code = region.as_deref().unwrap_or_default();
+    let region_first_day = if SUNDAY_FIRST.contains(&region_code) {
+        0
+    } else if SATURDAY_FIRST.contains(&region_code) {
+        6
+    } else {
+        1
+    };
+    let first_day_of_week = os.first_day_of_week.filter(|day| *day < 7).unwrap_or(region_first_day);
+    let hour12 = os.hour12.unwrap_or(HOUR12.contains(&region_code));
+    let (decimal, grouping) = separators(&language, region.as_deref());
+    LocaleInfo {
+        date_format: os
+            .date_format
+            .filter(|format| !format.trim().is_empty())
+            .unwrap_or_else(|| region_date_format(region_code).to_string()),
+        time_format: if hour12 { "h:mm a" } else { "HH:mm" }.to_string(),
+        hour12,
+        decimal_separator: os.decimal_separator.unwrap_or_else(|| decimal.to_string()),
+        grouping_separator: os.grouping_separator.unwrap_or_else(|| grouping.to_string()),
+        first_day_of_week,
+        timezone,
+        utc_offset_minutes,
+        locale,
+        language,
+        region,
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn os_preferences() -> OsPreferences {
+    let read = |key: &str| run("defaults", &["read", "-g", key]);
+    let hour12 = match (read("AppleICUForce24HourTime").as_deref(), read("AppleICUForce12HourTime").as_deref()) {
+        (Some("1"), _) => Some(false),
+        (_, Some("1")) => Some(true),
+        _ => None,
+    };
+    OsPreferences {
+        locale: read("AppleLocale"),
+        first_day_of_week: read("AppleFirstWeekday").as_deref().and_then(apple_first_day),
+        hour12,
+        ..Default::default()
+    }
+}
+
+#[cfg(windows)]
+fn os_preferences() -> OsPreferences {
+    // .NET short date patterns use the same letters as CLDR for days, months and years
+    let script = "$c = Get-Culture; @{ locale = $c.Name; firstDayOfWeek = [int]$c.DateTimeFormat.FirstDayOfWeek; \
+                  dateFormat = $c.DateTimeFormat.ShortDatePattern; \
+                  hour12 = $c.DateTimeFormat.ShortTimePattern.Contains('h'); \
+                  decimalSeparator = $c.NumberFormat.NumberDecimalSeparator; \
+                  groupingSeparator = $c.NumberFormat.NumberGroupSeparator } | ConvertTo-Json -Compress";
+    run("powershell", &["-NoProfile", "-Command", script])
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn os_preferences() -> OsPreferences {
+    let env = |names: &[&str]| {
+        names.iter().find_map(|name| std::env::var(name).ok().filter(|value| normalize_locale(value).is_some()))
+    };
+    let locale = env(&["LC_ALL", "LC_MESSAGES", "LANG"]);
+    let mut preferences = OsPreferences { locale, ..Default::default() };
+    // The C locale's formats say nothing about the user
+    if env(&["LC_ALL", "LC_TIME", "LANG"]).is_none() {
+        return preferences;
+    }
+    let keywords = ["d_fmt", "t_fmt", "decimal_point", "thousands_sep", "week-1stday", "first_weekday"];
+    let Some(output) = run("locale", &keywords) else {
+        return preferences;
+    };
+    let values: Vec<&str> = output.lines().collect();
+    if let [date, time, decimal, grouping, week_first, first_weekday] = values[..] {
+        preferences.date_format = strftime_to_pattern(date);
+        preferences.hour12 = Some(time.contains("%I") || time.contains("%l") || time.contains("%r"));
+        preferences.decimal_separator = Some(decimal.to_string()).filter(|value| !value.is_empty());
+        preferences.grouping_separator = Some(grouping.to_string()).filter(|value| !value.is_empty());
+        preferences.first_day_of_week = glibc_first_day(week_first, first_weekday);
+    }
+    preferences
+}
+
+/// The OS locale with its date, time and number formats, time zone and first day of the week,
+/// for calendars and date parsing to follow the system rather than the browser's guess
+#[crate::metrics::command]
+pub async fn get_locale_info() -> Result<LocaleInfo, String> {
+    let os = tauri::async_runtime::spawn_blocking(os_preferences).await.map_err(|e| e.to_string())?;
+    let timezone = iana_time_zone::get_timezone().unwrap_or_else(|_| "UTC".to_string());
+    let offset = Local::now().offset().local_minus_utc() / 60;
+    Ok(resolve(os, timezone, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_defaults_follow_the_region() {
+        assert_eq!(normalize_locale("de_DE.UTF-8@euro").as_deref(), Some("de-DE"));
+        assert_eq!(normalize_locale("zh_hant_tw").as_deref(), Some("zh-Hant-TW"));
+        assert_eq!(normalize_locale("C.UTF-8"), None);
+
+        let german = resolve(
+            OsPreferences { locale: Some("de_DE.UTF-8".to_string()), ..Default::default() },
+            "Europe/Berlin".to_string(),
+            60,
+        );
+        assert_eq!((german.first_day_of_week, german.date_format.as_str()), (1, "dd.MM.yyyy"));
+        assert_eq!((german.decimal_separator.as_str(), german.grouping_separator.as_str()), (",", "."));
+        assert!(!german.hour12 && german.time_format == "HH:mm");
+
+        let american = resolve(
+            OsPreferences { locale: Some("en_US".to_string()), first_day_of_week: Some(1), ..Default::default() },
+            "America/New_York".to_string(),
+            -300,
+        );
+        assert_eq!((american.first_day_of_week, american.region.as_deref()), (1, Some("US")));
+        assert!(american.hour12 && american.date_format == "MM/dd/yyyy");
+    }
+
+    #[test]
+    fn test_os_formats() {
+        assert_eq!(strftime_to_pattern("%d.%m.%Y").as_deref(), Some("dd.MM.yyyy"));
+        assert_eq!(strftime_to_pattern("%x"), None);
+        // glibc's week origin is a Sunday; first_weekday 2 makes weeks start on Monday
+        assert_eq!(glibc_first_day("19971130", "2"), Some(1));
+        assert_eq!(glibc_first_day("19971130", "1"), Some(0));
+        assert_eq!(apple_first_day("{\n    gregorian = 2;\n}"), Some(1));
+        assert_eq!(apple_first_day("does not exist"), None);
+    }
+}
@@ -0,0 +1,360 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, settings};
+
+/// The user has to opt in before the app asks the OS for their location
+const ENABLED_SETTING: &str = "location.enabled";
+/// Fall back to an IP-based lookup where no OS location service is available
+const IP_FALLBACK_SETTING: &str = "location.ip_fallback";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Accuracy radius in meters, when known
+    pub accuracy: Option<f64>,
+    pub place_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryLocation {
+    pub workspace_id: String,
+    pub page_date: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub accuracy: Option<f64>,
+    pub place_name: Option<String>,
+    pub recorded_at: i64,
+    /// Distance from the queried point, filled in by `get_entries_near`
+    #[sqlx(skip)]
+    pub distance_km: Option<f64>,
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("JournalTodo/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Ask GeoClue (the freedesktop location service) for the current position
+#[cfg(target_os = "linux")]
+async fn os_location() -> Result<Option<Location>, String> {
+    use futures_util::StreamExt;
+    use zbus::zvariant::OwnedObjectPath;
+
+    const SERVICE: &str = "org.freedesktop.GeoClue2";
+    // GeoClue "city" accuracy level is enough for tagging journal entries
+    const ACCURACY_CITY: u32 = 4;
+
+    let map_err = |e: zbus::Error| match e {
+        zbus::Error::MethodError(name, _, _) if name.as_str().ends_with("AccessDenied") => {
+            "Location permission denied".to_string()
+        }
+        e => e.to_string(),
+    };
+
+    let conn = match zbus::Connection::system().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            logger::info(&format!("No system bus for location service: {}", e));
+            return Ok(None);
+        }
+    };
+    let manager = zbus::Proxy::new(
+        &conn,
+        SERVICE,
+        "/org/freedesktop/GeoClue2/Manager",
+        "org.freedesktop.GeoClue2.Manager",
+    )
+    .await
+    .map_err(map_err)?;
+    let client_path: OwnedObjectPath = match manager.call("GetClient", &()).await {
+        Ok(path) => path,
+        Err(zbus::Error::MethodError(name, _, _)) if name.as_str().ends_with("ServiceUnknown") => {
+            return Ok(None)
+        }
+        Err(e) => return Err(map_err(e)),
+    };
+
+    let client = zbus::Proxy::new(
+        &conn,
+        SERVICE,
+        client_path,
+        "org.freedesktop.GeoClue2.Client",
+    )
+    .await
+    .map_err(map_err)?;
+    client
+        .set_property("DesktopId", "journal-todo")
+        .await
+        .map_err(|e| e.to_string())?;
+    client
+        .set_property("RequestedAccuracyLevel", ACCURACY_CITY)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut updates = client
+        .receive_signal("LocationUpdated")
+        .await
+        .map_err(map_err)?;
+    let () = client.call("Start", &()).await.map_err(map_err)?;
+    let update = tokio::time::timeout(REQUEST_TIMEOUT, updates.next()).await;
+    let _: Result<(), _> = client.call("Stop", &()).await;
+
+    let message = update
+        .map_err(|_| "Timed out waiting for location".to_string())?
+        .ok_or("Location service closed the connection")?;
+    let (_, location_path): (OwnedObjectPath, OwnedObjectPath) =
+        message.body().deserialize().map_err(|e| e.to_string())?;
+
+    let location = zbus::Proxy::new(
+        &conn,
+        SERVICE,
+        location_path,
+        "org.freedesktop.GeoClue2.Location",
+    )
+    .await
+    .map_err(map_err)?;
+    let property = |name: &'static str| {
+        let location = &location;
+        async move {
+            location
+                .get_property::<f64>(name)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    };
+    Ok(Some(Location {
+        latitude: property("Latitude").await?,
+        longitude: property("Longitude").await?,
+        accuracy: property("Accuracy").await.ok(),
+        place_name: None,
+    }))
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn os_location() -> Result<Option<Location>, String> {
+    Ok(None)
+}
+
+/// Approximate location from the public IP address
+async fn ip_location() -> Result<Location, String> {
+    let response: serde_json::Value = http_client()?
+        .get("https://ipapi.co/json/")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("IP location lookup failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let latitude = response["latitude"]
+        .as_f64()
+        .ok_or("IP location lookup returned no coordinates")?;
+    let longitude = response["longitude"]
+        .as_f64()
+        .ok_or("IP location lookup returned no coordinates")?;
+    let place_name = [&response["city"], &response["country_name"]]
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(Location {
+        latitude,
+        longitude,
+        accuracy: None,
+        place_name: Some(place_name).filter(|p| !p.is_empty()),
+    })
+}
+
+/// Look up a human readable place name through OpenStreetMap Nominatim
+async fn reverse_geocode(latitude: f64, longitude: f64) -> Result<Option<String>, String> {
+    let response: serde_json::Value = http_client()?
+        .get("https://nominatim.openstreetmap.org/reverse")
+        .query(&[
+            ("format", "jsonv2"),
+            ("zoom", "10"),
+            ("lat", &latitude.to_string()),
+            ("lon", &longitude.to_string()),
+        ])
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Reverse geocoding failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let address = &response["address"];
+    let place = ["city", "town", "village", "county", "state"]
+        .iter()
+        .find_map(|key| address[*key].as_str());
+    Ok(match (place, address["country"].as_str()) {
+        (Some(place), Some(country)) => Some(format!("{}, {}", place, country)),
+        (Some(place), None) => Some(place.to_string()),
+        _ => response["display_name"].as_str().map(str::to_string),
+    })
+}
+
+/// Great-circle distance between two points
+fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (d_lat, d_lon) = ((lat2 - lat1).to_radians(), (lon2 - lon1).to_radians());
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Current position from the OS location service, with a place name when available
+#[crate::metrics::command]
+pub async fn get_current_location(state: State<'_, DatabaseState>) -> Result<Location, String> {
+    let (enabled, ip_fallback) = {
+        let pool = state.pool.lock().await;
+        (
+            settings::get_or(&pool, ENABLED_SETTING, false).await,
+            settings::get_or(&pool, IP_FALLBACK_SETTING, true).await,
+        )
+    };
+    if !enabled {
+        return Err("Location access is turned off in settings".to_string());
+    }
+
+    let mut location = match os_location().await? {
+        Some(location) => location,
+        None if ip_fallback => ip_location().await?,
+        None => return Err("No location service available".to_string()),
+    };
+    if location.place_name.is_none() {
+        location.place_name = reverse_geocode(location.latitude, location.longitude)
+            .await
+            .unwrap_or_else(|e| {
+                logger::error(&e);
+                None
+            });
+    }
+    Ok(location)
+}
+
+/// Tag a journal day with a location
+#[crate::metrics::command]
+pub async fn set_entry_location(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    location: Option<Location>,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        match location {
+            Some(location) => {
+                if !(-90.0..=90.0).contains(&location.latitude)
+                    || !(-180.0..=180.0).contains(&location.longitude)
+                {
+                    return Err("Coordinates out of range".to_string());
+                }
+                sqlx::query(
+                    "INSERT OR REPLACE INTO page_locations (workspace_id, page_date, latitude, longitude, accuracy, place_name, recorded_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(&workspace_id)
+                .bind(&date)
+                .bind(location.latitude)
+                .bind(location.longitude)
+                .bind(location.accuracy)
+                .bind(&location.place_name)
+                .bind(Utc::now().timestamp_millis())
+                .execute(&*pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+            None => {
+                sqlx::query("DELETE FROM page_locations WHERE workspace_id = ? AND page_date = ?")
+                    .bind(&workspace_id)
+                    .bind(&date)
+                    .execute(&*pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    changes::notify(&app, vec!["page_locations".to_string()]);
+    Ok(())
+}
+
+#[crate::metrics::command]
+pub async fn get_entry_location(
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+) -> Result<Option<EntryLocation>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as(
+        "SELECT workspace_id, page_date, latitude, longitude, accuracy, place_name, recorded_at
+         FROM page_locations WHERE workspace_id = ? AND page_date = ?",
+    )
+    .bind(workspace_id)
+    .bind(date)
+    .fetch_optional(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Entries tagged within `radius` km of a point, nearest first
+#[crate::metrics::command]
+pub async fn get_entries_near(
+    state: State<'_, DatabaseState>,
+    lat: f64,
+    lon: f64,
+    radius: f64,
+) -> Result<Vec<EntryLocation>, String> {
+    // Bounding box prefilter; exact distances are computed below
+    let lat_delta = (radius / EARTH_RADIUS_KM).to_degrees();
+    let lon_delta = match lat.to_radians().cos() {
+        cos if cos > 1e-6 => (lat_delta / cos).min(180.0),
+        _ => 180.0,
+    };
+
+    let candidates: Vec<EntryLocation> = {
+        let pool = state.pool.lock().await;
+        sqlx::query_as(
+            "SELECT workspace_id, page_date, latitude, longitude, accuracy, place_name, recorded_at
+             FROM page_locations WHERE latitude BETWEEN ? AND ?",
+        )
+        .bind(lat - lat_delta)
+        .bind(lat + lat_delta)
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())?
+    };
+
+    let mut entries: Vec<EntryLocation> = candidates
+        .into_iter()
+        .filter(|entry| {
+            let d_lon = (entry.longitude - lon).abs();
+            d_lon.min(360.0 - d_lon) <= lon_delta
+        })
+        .filter_map(|mut entry| {
+            let distance = haversine_km(lat, lon, entry.latitude, entry.longitude);
+            entry.distance_km = Some(distance);
+            (distance <= radius).then_some(entry)
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        a.distance_km
+            .partial_cmp(&b.distance_km)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(entries)
+}
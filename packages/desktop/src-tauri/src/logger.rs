@@ -1,31 +1,104 @@
 use chrono::Local;
+use regex::Regex;
+use sqlparser::dialect::SQLiteDialect;
+use sqlparser::tokenizer::{Token, Tokenizer};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use tauri::{AppHandle, Listener, Manager};
+
+use crate::db::DatabaseState;
+use crate::settings::{self, SettingChange, SETTINGS_CHANGED_EVENT};
 
 static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
 static LOG_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+static REDACT_PATTERNS: RwLock<Vec<Regex>> = RwLock::new(Vec::new());
+
+const LOG_FILE_NAME: &str = "journal-todo.log";
+/// Directory name under the platform log location
+const APP_DIR_NAME: &str = "journal-todo";
+/// Bundle identifier from tauri.conf.json, which names the app data directory
+const APP_IDENTIFIER: &str = "com.journal-todo.app";
+
+/// JSON array of regexes whose matches never reach the log file
+const REDACT_PATTERNS_SETTING: &str = "logging.redact_patterns";
+/// Shorter parameter values are left alone so they don't mangle the message
+const MIN_REDACTED_PARAM_LEN: usize = 3;
+
+/// Conventional log directory for the platform
+fn platform_log_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|home| home.join("Library/Logs").join(APP_DIR_NAME))
+    } else if cfg!(target_os = "windows") {
+        dirs::data_local_dir().map(|dir| dir.join(APP_DIR_NAME).join("logs"))
+    } else {
+        // $XDG_STATE_HOME, or ~/.local/state
+        dirs::state_dir().map(|dir| dir.join(APP_DIR_NAME))
+    }
+}
 
 /// Get a fallback log directory that should always work
 fn get_fallback_log_dir() -> PathBuf {
-    // Try multiple fallback locations
-    if let Ok(home) = std::env::var("USERPROFILE") {
-        return PathBuf::from(home).join(".journal-todo");
-    }
-    if let Ok(home) = std::env::var("HOME") {
-        return PathBuf::from(home).join(".journal-todo");
+    if let Some(dir) = platform_log_dir() {
+        return dir;
     }
+    // Try multiple fallback locations
     if let Ok(temp) = std::env::var("TEMP") {
-        return PathBuf::from(temp).join("journal-todo");
+        return PathBuf::from(temp).join(APP_DIR_NAME);
     }
     if let Ok(tmp) = std::env::var("TMP") {
-        return PathBuf::from(tmp).join("journal-todo");
+        return PathBuf::from(tmp).join(APP_DIR_NAME);
     }
     // Last resort: current directory
     PathBuf::from(".")
 }
 
+/// Where earlier versions wrote the log: a home dotfolder, then the app data directory
+fn legacy_log_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for var in ["USERPROFILE", "HOME"] {
+        if let Ok(home) = std::env::var(var) {
+            dirs.push(PathBuf::from(home).join(".journal-todo"));
+        }
+    }
+    if let Some(data) = dirs::data_dir() {
+        dirs.push(data.join(APP_IDENTIFIER));
+    }
+    dirs
+}
+
+/// Move a legacy log into `target`, keeping its lines ahead of any newer ones
+fn merge_log(source: &Path, target: &Path) -> std::io::Result<()> {
+    if !target.exists() && std::fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    // Across filesystems, or onto an existing log
+    let mut merged = std::fs::read(source)?;
+    if target.exists() {
+        merged.extend(std::fs::read(target)?);
+    }
+    std::fs::write(target, merged)?;
+    std::fs::remove_file(source)
+}
+
+/// Bring logs left in legacy locations into `dir`; returns what happened, for the log
+fn migrate_legacy_logs(dir: &Path) -> Vec<String> {
+    let target = dir.join(LOG_FILE_NAME);
+    let mut notes = Vec::new();
+    for legacy in legacy_log_dirs() {
+        let source = legacy.join(LOG_FILE_NAME);
+        if legacy == dir || !source.is_file() {
+            continue;
+        }
+        match merge_log(&source, &target) {
+            Ok(()) => notes.push(format!("Migrated log from {}", source.display())),
+            Err(e) => notes.push(format!("Failed to migrate log from {}: {}", source.display(), e)),
+        }
+    }
+    notes
+}
+
 /// Initialize the logger - tries the given directory first, then fallback
 pub fn init(log_dir: Option<&PathBuf>) -> PathBuf {
     let dir = match log_dir {
@@ -41,7 +114,41 @@ pub fn init(log_dir: Option<&PathBuf>) -> PathBuf {
         let _ = std::fs::create_dir_all(&fallback);
     }
 
-    let log_path = dir.join("journal-todo.log");
+    let migrated = migrate_legacy_logs(&dir);
+    let log_path = open_log_file(&dir);
+
+    // Write startup marker
+    log("========================================");
+    log(&format!(
+        "Application started at {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S")
+    ));
+    log(&format!("Log directory: {}", dir.display()));
+    for note in migrated {
+        log(&note);
+    }
+    log("========================================");
+
+    log_path
+}
+
+/// Send later lines to the log in `log_dir`, or the platform log directory when `None`,
+/// e.g. for a profile that keeps its own logs. Legacy logs are left where they are.
+pub fn switch_dir(log_dir: Option<&Path>) -> PathBuf {
+    let dir = log_dir.map(Path::to_path_buf).unwrap_or_else(get_fallback_log_dir);
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error(&format!("Failed to create log directory {}: {}", dir.display(), e));
+        return get_log_path().unwrap_or_default();
+    }
+    info(&format!("Continuing the log in {}", dir.display()));
+    let log_path = open_log_file(&dir);
+    info(&format!("Log directory: {}", dir.display()));
+    log_path
+}
+
+/// Append to the log file in `dir`, or in the fallback directory if that fails
+fn open_log_file(dir: &Path) -> PathBuf {
+    let log_path = dir.join(LOG_FILE_NAME);
 
     // Open log file in append mode
     match OpenOptions::new().create(true).append(true).open(&log_path) {
@@ -55,7 +162,7 @@ pub fn init(log_dir: Option<&PathBuf>) -> PathBuf {
         Err(e) => {
             eprintln!("Warning: Failed to open log file {:?}: {}", log_path, e);
             // Try fallback location
-            let fallback_path = get_fallback_log_dir().join("journal-todo.log");
+            let fallback_path = get_fallback_log_dir().join(LOG_FILE_NAME);
             if let Ok(file) = OpenOptions::new()
                 .create(true)
                 .append(true)
@@ -69,16 +176,6 @@ pub fn init(log_dir: Option<&PathBuf>) -> PathBuf {
             }
         }
     }
-
-    // Write startup marker
-    log("========================================");
-    log(&format!(
-        "Application started at {}",
-        Local::now().format("%Y-%m-%d %H:%M:%S")
-    ));
-    log(&format!("Log directory: {}", dir.display()));
-    log("========================================");
-
     log_path
 }
 
@@ -87,10 +184,90 @@ pub fn init_early() -> PathBuf {
     init(None)
 }
 
-/// Log a message to the file
+/// Compile the patterns redacted from every log line, skipping invalid ones
+pub fn set_redact_patterns(patterns: &[String]) {
+    let compiled = patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                error(&format!("Invalid log redaction pattern {:?}: {}", pattern, e));
+                None
+            }
+        })
+        .collect();
+    if let Ok(mut guard) = REDACT_PATTERNS.write() {
+        *guard = compiled;
+    }
+}
+
+/// Load the redaction patterns from settings and follow changes to them
+pub async fn watch_settings(app: &AppHandle) {
+    app.listen(SETTINGS_CHANGED_EVENT, |event| {
+        if let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) {
+            if change.key == REDACT_PATTERNS_SETTING {
+                let patterns: Vec<String> = change.value.and_then(|v| serde_json::from_value(v).ok()).unwrap_or_default();
+                set_redact_patterns(&patterns);
+            }
+        }
+    });
+
+    let patterns: Vec<String> = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        settings::get_or(&pool, REDACT_PATTERNS_SETTING, Vec::new()).await
+    };
+    set_redact_patterns(&patterns);
+}
+
+/// Replace matches of the configured patterns
+fn scrub(message: &str) -> String {
+    let Ok(patterns) = REDACT_PATTERNS.read() else {
+        return message.to_string();
+    };
+    patterns
+        .iter()
+        .fold(message.to_string(), |text, regex| regex.replace_all(&text, "[redacted]").into_owned())
+}
+
+/// A statement with its string and number literals replaced by `?`
+pub fn redact_sql(sql: &str) -> String {
+    let Ok(tokens) = Tokenizer::new(&SQLiteDialect {}, sql).tokenize() else {
+        return "[unparsable statement]".to_string();
+    };
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::SingleQuotedString(_)
+            | Token::NationalStringLiteral(_)
+            | Token::HexStringLiteral(_)
+            | Token::Number(..) => "?".to_string(),
+            token => token.to_string(),
+        })
+        .collect()
+}
+
+/// Replace the statement parameters found in a message, e.g. an error echoing a value
+pub fn redact_params(message: &str, params: &[serde_json::Value]) -> String {
+    params
+        .iter()
+        .filter_map(|param| match param {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => Some(param.to_string()),
+            _ => None,
+        })
+        .filter(|value| value.chars().count() >= MIN_REDACTED_PARAM_LEN)
+        .fold(message.to_string(), |text, value| text.replace(&value, "[param]"))
+}
+
+/// Log a message to the file, after redacting the configured patterns
 pub fn log(message: &str) {
+    let message = scrub(message);
     let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-    let formatted = format!("[{}] {}\n", timestamp, message);
+    let formatted = match crate::metrics::current_request_id() {
+        Some(id) => format!("[{}] [req {}] {}\n", timestamp, id, message),
+        None => format!("[{}] {}\n", timestamp, message),
+    };
 
     // Also print to console for dev mode
     print!("{}", formatted);
@@ -107,6 +284,12 @@ pub fn log(message: &str) {
 /// Log an error message
 pub fn error(message: &str) {
     log(&format!("ERROR: {}", message));
+    crate::telemetry::record_error(message);
+}
+
+/// Log an error raised in the webview
+pub fn frontend_error(message: &str) {
+    log(&format!("FRONTEND ERROR: {}", message));
 }
 
 /// Log an info message
@@ -118,3 +301,25 @@ pub fn info(message: &str) {
 pub fn get_log_path() -> Option<PathBuf> {
     LOG_PATH.lock().ok().and_then(|guard| guard.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_sql_hides_literals() {
+        assert_eq!(
+            redact_sql("UPDATE pages SET notes = 'dear diary' WHERE id = 42 AND date = ?"),
+            "UPDATE pages SET notes = ? WHERE id = ? AND date = ?"
+        );
+    }
+
+    #[test]
+    fn test_redact_params_hides_bound_values() {
+        let params = vec!["met Alice".into(), 7.into(), "ok".into()];
+        assert_eq!(
+            redact_params("CHECK failed for 'met Alice' (7, ok)", &params),
+            "CHECK failed for '[param]' (7, ok)"
+        );
+    }
+}
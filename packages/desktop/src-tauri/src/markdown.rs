@@ -0,0 +1,367 @@
+use ammonia::UrlRelative;
+use ego_tree::NodeRef;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use scraper::{ElementRef, Html, Node};
+use serde::Deserialize;
+use url::Url;
+
+/// Link and image schemes rendered; relative links are kept as they are
+const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto", "tel"];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RenderOptions {
+    /// Pass raw HTML in the text through, sanitized, instead of showing it as text
+    pub allow_html: bool,
+    /// Turn every line break in the text into a `<br>`, as the editor shows them
+    pub hard_breaks: bool,
+}
+
+fn is_safe_url(url: &str) -> bool {
+    match Url::parse(url) {
+        Ok(url) => SAFE_SCHEMES.contains(&url.scheme()),
+        Err(_) => !url.trim_start().contains(':'),
+    }
+}
+
+/// Markdown to HTML. Links and images with scripting schemes lose their target; raw HTML is
+/// shown as text unless `allow_html` is set, in which case the whole output is sanitized.
+pub fn render_html(markdown: &str, options: &RenderOptions) -> String {
+    let parser_options = Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES;
+    let mut dropped_link = false;
+    let events = Parser::new_ext(markdown, parser_options).filter_map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) if !options.allow_html => Some(Event::Text(raw)),
+        Event::SoftBreak if options.hard_breaks => Some(Event::HardBreak),
+        Event::Start(Tag::Link { ref dest_url, .. }) if !is_safe_url(dest_url) => {
+            dropped_link = true;
+            None
+        }
+        Event::End(TagEnd::Link) if dropped_link => {
+            dropped_link = false;
+            None
+        }
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) if !is_safe_url(&dest_url) => {
+            Some(Event::Start(Tag::Image { link_type, dest_url: CowStr::Borrowed(""), title, id }))
+        }
+        event => Some(event),
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    if !options.allow_html {
+        return out;
+    }
+    let mut builder = ammonia::Builder::default();
+    builder
+        .add_tags(&["input"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("code", &["class"]);
+    builder.clean(&out).to_string()
+}
+
+/// The text of Markdown without its formatting, for places that can't show HTML such as
+/// notification bodies
+pub fn to_plain_text(markdown: &str) -> String {
+    let mut out = String::new();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::TaskListMarker(checked) => out.push_str(if checked { "☑ " } else { "☐ " }),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock)
+                if !out.ends_with('\n') =>
+            {
+                out.push('\n')
+            }
+            _ => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// Render journal Markdown to sanitized HTML, the same way exports do
+#[crate::metrics::command]
+pub fn render_markdown(text: String, options: Option<RenderOptions>) -> String {
+    render_html(&text, &options.unwrap_or_default())
+}
+
+/// Tags kept when pasting; anything else is unwrapped to its text
+const PASTE_TAGS: &[&str] = &[
+    "a", "b", "blockquote", "br", "code", "del", "div", "em", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img",
+    "input", "li", "ol", "p", "pre", "s", "strike", "strong", "table", "tbody", "td", "tfoot", "th", "thead", "tr",
+    "ul",
+];
+
+/// Strip pasted HTML down to the structure Markdown can express, dropping scripts, styles,
+/// event handlers and relative links that would point nowhere in the journal
+fn sanitize_paste(html: &str) -> String {
+    let mut builder = ammonia::Builder::default();
+    builder
+        .tags(PASTE_TAGS.iter().copied().collect())
+        .add_tag_attributes("input", &["type", "checked"])
+        .add_clean_content_tags(&["title"])
+        .url_relative(UrlRelative::Deny);
+    builder.clean(html).to_string()
+}
+
+/// Whitespace in HTML text collapses to single spaces
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                out.push(' ');
+            }
+            in_space = true;
+        } else {
+            out.push(c);
+            in_space = false;
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Surround inline content with a marker, leaving its outer spaces outside
+fn wrap(marker: &str, content: &str) -> String {
+    let core = content.trim();
+    if core.is_empty() {
+        return content.to_string();
+    }
+    let lead = &content[..content.len() - content.trim_start().len()];
+    let trail = &content[content.trim_end().len()..];
+    format!("{}{}{}{}{}", lead, marker, core, marker, trail)
+}
+
+fn block(content: &str) -> String {
+    let content = content.trim();
+    if content.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}\n\n", content)
+    }
+}
+
+/// Drop blank-only lines down to single separators between blocks
+fn tidy(markdown: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    for line in markdown.lines() {
+        let line = if line.trim().is_empty() { "" } else { line };
+        if line.is_empty() && out.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        out.push(line);
+    }
+    out.join("\n").trim().to_string()
+}
+
+fn indent(content: &str, prefix: &str, rest: &str) -> String {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match (i, line.is_empty()) {
+            (0, _) => format!("{}{}", prefix, line),
+            (_, true) => rest.trim_end().to_string(),
+            _ => format!("{}{}", rest, line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A code span fenced with more backticks than any run inside it
+fn code_span(code: &str) -> String {
+    let longest = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    let pad = if longest > 0 { " " } else { "" };
+    format!("{}{}{}{}{}", fence, pad, code, pad, fence)
+}
+
+fn link_target(url: &str) -> String {
+    if url.contains([' ', '(', ')']) {
+        format!("<{}>", url)
+    } else {
+        url.to_string()
+    }
+}
+
+fn children(node: NodeRef<Node>) -> String {
+    node.children().map(render).collect()
+}
+
+fn render(node: NodeRef<Node>) -> String {
+    match node.value() {
+        Node::Text(text) => escape(&collapse_whitespace(text)),
+        Node::Element(_) => ElementRef::wrap(node).map(element).unwrap_or_default(),
+        _ => children(node),
+    }
+}
+
+fn element(el: ElementRef) -> String {
+    let name = el.value().name();
+    match name {
+        "p" => block(
+            &children(*el)
+                .lines()
+                .map(str::trim_start)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            let level = name[1..].parse().unwrap_or(1);
+            let text = collapse_whitespace(&children(*el));
+            block(&format!("{} {}", "#".repeat(level), text.trim()))
+        }
+        "div" => block(&children(*el)),
+        "br" => "  \n".to_string(),
+        "hr" => block("---"),
+        "strong" | "b" => wrap("**", &children(*el)),
+        "em" | "i" => wrap("_", &children(*el)),
+        "s" | "del" | "strike" => wrap("~~", &children(*el)),
+        "code" => code_span(&el.text().collect::<String>()),
+        "pre" => {
+            let code = el.text().collect::<String>();
+            let fence = if code.contains("```") { "~~~" } else { "```" };
+            block(&format!("{}\n{}\n{}", fence, code.trim_end_matches('\n'), fence))
+        }
+        "a" => {
+            let text = children(*el);
+            match el.value().attr("href") {
+                Some(href) if text.trim().is_empty() => format!("<{}>", href),
+                Some(href) => format!("[{}]({})", text.trim(), link_target(href)),
+                None => text,
+            }
+        }
+        "img" => match el.value().attr("src") {
+            Some(src) => format!("![{}]({})", escape(el.value().attr("alt").unwrap_or_default()), link_target(src)),
+            None => String::new(),
+        },
+        "input" if el.value().attr("type") == Some("checkbox") => {
+            if el.value().attr("checked").is_some() { "[x] " } else { "[ ] " }.to_string()
+        }
+        "input" => String::new(),
+        "ul" | "ol" => list(el),
+        "blockquote" => block(&indent(&tidy(&children(*el)), "> ", "> ")),
+        "table" => table(el),
+        _ => children(*el),
+    }
+}
+
+fn list(el: ElementRef) -> String {
+    let ordered = el.value().name() == "ol";
+    let mut number: u64 = el.value().attr("start").and_then(|start| start.parse().ok()).unwrap_or(1);
+    let mut out = String::new();
+    for child in el.children() {
+        let Some(item) = ElementRef::wrap(child) else { continue };
+        let content = tidy(&render(child)).replace("\n\n", "\n");
+        if item.value().name() != "li" {
+            // Editors paste nested lists straight into their parent list
+            out.push_str(&indent(&content, "  ", "  "));
+            out.push('\n');
+            continue;
+        }
+        let marker = if ordered { format!("{}. ", number) } else { "- ".to_string() };
+        number += 1;
+        out.push_str(&indent(&content, &marker, &" ".repeat(marker.len())));
+        out.push('\n');
+    }
+    block(&out)
+}
+
+fn table(el: ElementRef) -> String {
+    let rows: Vec<Vec<String>> = el
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|row| row.value().name() == "tr")
+        .map(|row| {
+            row.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                .map(|cell| collapse_whitespace(&children(*cell)).trim().replace('|', "\\|"))
+                .collect()
+        })
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if columns == 0 {
+        return String::new();
+    }
+    let line = |cells: &[String]| {
+        let padded = (0..columns).map(|i| cells.get(i).map(String::as_str).unwrap_or(""));
+        format!("| {} |", padded.collect::<Vec<_>>().join(" | "))
+    };
+    let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    block(&lines.join("\n"))
+}
+
+/// Markdown for HTML pasted from a browser or word processor
+pub fn html_to_markdown(html: &str) -> String {
+    let fragment = Html::parse_fragment(&sanitize_paste(html));
+    tidy(&render(*fragment.root_element()))
+}
+
+/// Convert rich text from the clipboard to Markdown, so pasted content comes out the same on
+/// every platform
+#[crate::metrics::command]
+pub fn convert_html_to_markdown(html: String) -> String {
+    html_to_markdown(&html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_html_drops_unsafe_content() {
+        let markdown = "[a](javascript:alert(1)) [b](https://example.com) <b onclick=\"x()\">c</b>\nnext\n\n- [x] done";
+        let html = render_html(markdown, &RenderOptions::default());
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("<a href=\"https://example.com\">b</a>"));
+        assert!(html.contains("&lt;b onclick"));
+
+        let options = RenderOptions { allow_html: true, hard_breaks: true };
+        let html = render_html(markdown, &options);
+        assert!(html.contains("<b>c</b>"));
+        assert!(html.contains("<br>"));
+        assert!(html.contains("checked"));
+        assert!(!html.contains("onclick"));
+    }
+
+    #[test]
+    fn test_to_plain_text() {
+        assert_eq!(to_plain_text("Call **Sam** about `fix`\n- [ ] draft"), "Call Sam about fix\n☐ draft");
+    }
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"<!--StartFragment--><h2 style="color:red">Plan</h2>
+            <p>Call <b>Sam</b> about <a href="https://example.com/a">the <i>draft</i></a><br>
+            then rest</p><script>alert(1)</script>
+            <ul><li>one</li><li><input type="checkbox" checked>two<ul><li>deep</li></ul></li></ul>
+            <ol start="3"><li>three</li></ol><a href="/relative">here</a> <code>a`b</code>"#;
+        assert_eq!(
+            html_to_markdown(html),
+            "## Plan\n\nCall **Sam** about [the _draft_](https://example.com/a)  \nthen rest\n\n\
+             - one\n- [x] two\n  - deep\n\n3. three\n\nhere `` a`b ``"
+        );
+    }
+
+    #[test]
+    fn test_tables_and_code_blocks() {
+        let html = "<table><tr><th>Day</th><th>Mood</th></tr><tr><td>Mon</td><td>a|b</td></tr></table>\
+                    <pre><code>fn main() {\n    *x\n}\n</code></pre><p>2 * 3_</p>";
+        assert_eq!(
+            html_to_markdown(html),
+            "| Day | Mood |\n| --- | --- |\n| Mon | a\\|b |\n\n```\nfn main() {\n    *x\n}\n```\n\n2 \\* 3\\_"
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, SqliteConnection};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, State};
+
+use crate::db::{attach, changes, DatabaseState};
+use crate::fractional_index;
+use crate::privacy;
+
+/// Schema name the other database is attached under while merging
+const SOURCE_ALIAS: &str = "merge_source";
+
+/// Which notes win when a journal entry exists on both sides with different notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeStrategy {
+    KeepLocal,
+    KeepIncoming,
+    /// Whichever side was updated last
+    Newest,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    pub workspace_id: String,
+    pub date: String,
+    /// "keptLocal", "tookIncoming", or "skipped" for encrypted incoming notes
+    pub resolution: &'static str,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    pub workspaces_created: u64,
+    pub pages_imported: u64,
+    pub todos_imported: u64,
+    /// Incoming todos already present locally with the same content and creation time
+    pub duplicate_todos: u64,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+#[derive(sqlx::FromRow)]
+struct WorkspaceRow {
+    id: String,
+    name: String,
+    current_date_key: String,
+    created_at: i64,
+    updated_at: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct PageRow {
+    workspace_id: String,
+    date: String,
+    notes: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct TodoRow {
+    id: String,
+    workspace_id: String,
+    page_date: String,
+    text: String,
+    status: String,
+    tags: String,
+    level: i64,
+    parent_id: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+/// Identity of a todo across databases: its workspace, a hash of where and what it says, and when it was made
+fn todo_key(workspace_id: &str, page_date: &str, text: &str, created_at: i64) -> (String, String, i64) {
+    let hash = digest(&SHA256, format!("{}\0{}", page_date, text).as_bytes());
+    let hex = hash.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    (workspace_id.to_string(), hex, created_at)
+}
+
+/// Local workspace for each incoming one, matched by name or created
+async fn merge_workspaces(
+    conn: &mut SqliteConnection,
+    report: &mut MergeReport,
+) -> Result<HashMap<String, String>, String> {
+    let incoming: Vec<WorkspaceRow> = sqlx::query_as(
+        "SELECT id, name, current_date_key, created_at, updated_at FROM merge_source.workspaces",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut workspaces = HashMap::new();
+    for workspace in incoming {
+        let local: Option<(String,)> = sqlx::query_as("SELECT id FROM main.workspaces WHERE name = ?")
+            .bind(&workspace.name)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        let id = match local {
+            Some((id,)) => id,
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                sqlx::query(
+                    "INSERT INTO main.workspaces (id, name, current_date_key, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(&id)
+                .bind(&workspace.name)
+                .bind(&workspace.current_date_key)
+                .bind(workspace.created_at)
+                .bind(workspace.updated_at)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+                report.workspaces_created += 1;
+                id
+            }
+        };
+        workspaces.insert(workspace.id, id);
+    }
+    Ok(workspaces)
+}
+
+async fn merge_pages(
+    conn: &mut SqliteConnection,
+    workspaces: &HashMap<String, String>,
+    strategy: MergeStrategy,
+    report: &mut MergeReport,
+) -> Result<(), String> {
+    let incoming: Vec<PageRow> = sqlx::query_as(
+        "SELECT workspace_id, date, notes, created_at, updated_at FROM merge_source.pages ORDER BY date",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    for page in incoming {
+        let Some(workspace_id) = workspaces.get(&page.workspace_id) else {
+            continue;
+        };
+        let conflict = |resolution| MergeConflict {
+            workspace_id: workspace_id.clone(),
+            date: page.date.clone(),
+            resolution,
+        };
+        // Encrypted under the other machine's keys, which never travel with the file
+        if page.notes.as_deref().is_some_and(privacy::is_encrypted) {
+            report.conflicts.push(conflict("skipped"));
+            continue;
+        }
+
+        let local: Option<(Option<String>, i64, bool)> = sqlx::query_as(
+            "SELECT notes, updated_at,
+                EXISTS (SELECT 1 FROM main.private_pages WHERE workspace_id = pages.workspace_id AND page_date = pages.date)
+                OR EXISTS (SELECT 1 FROM main.locked_pages WHERE workspace_id = pages.workspace_id AND page_date = pages.date)
+             FROM main.pages WHERE workspace_id = ? AND date = ?",
+        )
+        .bind(workspace_id)
+        .bind(&page.date)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let Some((local_notes, local_updated_at, protected)) = local else {
+            sqlx::query("INSERT INTO main.pages (workspace_id, date, notes, created_at, updated_at) VALUES (?, ?, ?, ?, ?)")
+                .bind(workspace_id)
+                .bind(&page.date)
+                .bind(&page.notes)
+                .bind(page.created_at)
+                .bind(page.updated_at)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            report.pages_imported += 1;
+            continue;
+        };
+
+        let incoming_notes = page.notes.as_deref().unwrap_or_default();
+        let local_notes = local_notes.unwrap_or_default();
+        if incoming_notes.is_empty() || incoming_notes == local_notes {
+            continue;
+        }
+        let take_incoming = !protected
+            && (local_notes.is_empty()
+                || match strategy {
+                    MergeStrategy::KeepLocal => false,
+                    MergeStrategy::KeepIncoming => true,
+                    MergeStrategy::Newest => page.updated_at > local_updated_at,
+                });
+        if !local_notes.is_empty() {
+            report.conflicts.push(conflict(if take_incoming { "tookIncoming" } else { "keptLocal" }));
+        }
+        if take_incoming {
+            sqlx::query("UPDATE main.pages SET notes = ?, updated_at = ? WHERE workspace_id = ? AND date = ?")
+                .bind(incoming_notes)
+                .bind(page.updated_at.max(local_updated_at))
+                .bind(workspace_id)
+                .bind(&page.date)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Import todos under new ids, skipping ones already present and appending the
+/// rest after each page's local todos in their original order
+async fn merge_todos(
+    conn: &mut SqliteConnection,
+    workspaces: &HashMap<String, String>,
+    report: &mut MergeReport,
+) -> Result<(), String> {
+    let local: Vec<(String, String, String, String, i64)> =
+        sqlx::query_as("SELECT id, workspace_id, page_date, text, created_at FROM main.todos")
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    let existing: HashMap<_, _> = local
+        .into_iter()
+        .map(|(id, workspace_id, page_date, text, created_at)| (todo_key(&workspace_id, &page_date, &text, created_at), id))
+        .collect();
+
+    let incoming: Vec<TodoRow> = sqlx::query_as(
+        "SELECT id, workspace_id, page_date, text, status, tags, level, parent_id, created_at, updated_at
+         FROM merge_source.todos ORDER BY workspace_id, page_date, `order`",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // New id for every incoming todo, or the local id of its duplicate
+    let mut ids = HashMap::new();
+    let mut new_todos = Vec::new();
+    for todo in incoming {
+        let Some(workspace_id) = workspaces.get(&todo.workspace_id) else {
+            continue;
+        };
+        match existing.get(&todo_key(workspace_id, &todo.page_date, &todo.text, todo.created_at)) {
+            Some(id) => {
+                ids.insert(todo.id.clone(), id.clone());
+                report.duplicate_todos += 1;
+            }
+            None => {
+                ids.insert(todo.id.clone(), uuid::Uuid::new_v4().to_string());
+                new_todos.push((workspace_id.clone(), todo));
+            }
+        }
+    }
+
+    let mut last_order: HashMap<(String, String), Option<String>> = HashMap::new();
+    for (workspace_id, todo) in new_todos {
+        let page = (workspace_id.clone(), todo.page_date.clone());
+        if !last_order.contains_key(&page) {
+            // Todos whose page didn't come across still need one
+            sqlx::query(
+                "INSERT OR IGNORE INTO main.pages (workspace_id, date, notes, created_at, updated_at) VALUES (?, ?, NULL, ?, ?)",
+            )
+            .bind(&workspace_id)
+            .bind(&todo.page_date)
+            .bind(todo.created_at)
+            .bind(todo.created_at)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+            let last: Option<(String,)> = sqlx::query_as(
+                "SELECT `order` FROM main.todos WHERE workspace_id = ? AND page_date = ? ORDER BY `order` DESC LIMIT 1",
+            )
+            .bind(&workspace_id)
+            .bind(&todo.page_date)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+            last_order.insert(page.clone(), last.map(|(order,)| order));
+        }
+        let previous = last_order.get_mut(&page).expect("inserted above");
+        let order = fractional_index::key_between(previous.as_deref(), None)?;
+        *previous = Some(order.clone());
+
+        let parent_id = todo.parent_id.as_ref().and_then(|parent| ids.get(parent));
+        sqlx::query(
+            "INSERT INTO main.todos (id, workspace_id, page_date, text, status, tags, `order`, level, parent_id, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&ids[&todo.id])
+        .bind(&workspace_id)
+        .bind(&todo.page_date)
+        .bind(&todo.text)
+        .bind(&todo.status)
+        .bind(&todo.tags)
+        .bind(order)
+        .bind(if parent_id.is_some() { todo.level } else { 0 })
+        .bind(parent_id)
+        .bind(todo.created_at)
+        .bind(todo.updated_at)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        report.todos_imported += 1;
+    }
+    Ok(())
+}
+
+/// Merge the attached source database into main in one transaction
+async fn merge_attached(conn: &mut SqliteConnection, strategy: MergeStrategy) -> Result<MergeReport, String> {
+    let mut report = MergeReport::default();
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let workspaces = merge_workspaces(&mut tx, &mut report).await?;
+    merge_pages(&mut tx, &workspaces, strategy, &mut report).await?;
+    merge_todos(&mut tx, &workspaces, &mut report).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// Import workspaces, journal entries and todos (with their tags) from another
+/// journal database, e.g. one copied from a second machine
+#[crate::metrics::command]
+pub async fn merge_database(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    path: String,
+    strategy: MergeStrategy,
+) -> Result<MergeReport, String> {
+    let path = PathBuf::from(&path)
+        .canonicalize()
+        .map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    if path == state.db_path.lock().await.canonicalize().unwrap_or_default() {
+        return Err("Cannot merge the database into itself".to_string());
+    }
+
+    let report = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        let attached = attach::attach_to(&mut conn, SOURCE_ALIAS, &path).await?;
+        let result = merge_attached(&mut conn, strategy).await;
+        if attached {
+            if let Err(e) = attach::detach_from(&mut conn, SOURCE_ALIAS).await {
+                crate::logger::error(&e);
+            }
+        }
+        result?
+    };
+
+    crate::logger::info(&format!(
+        "Merged {}: {} pages, {} todos ({} duplicates), {} conflicts",
+        path.display(),
+        report.pages_imported,
+        report.todos_imported,
+        report.duplicate_todos,
+        report.conflicts.len()
+    ));
+    changes::notify(&app, vec!["workspaces".to_string(), "pages".to_string(), "todos".to_string()]);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &[&str] = &[
+        "CREATE TABLE {}.workspaces (id TEXT PRIMARY KEY, name TEXT, current_date_key TEXT, created_at INTEGER, updated_at INTEGER)",
+        "CREATE TABLE {}.pages (workspace_id TEXT, date TEXT, notes TEXT, created_at INTEGER, updated_at INTEGER,
+            PRIMARY KEY (workspace_id, date))",
+        "CREATE TABLE {}.todos (id TEXT PRIMARY KEY, workspace_id TEXT, page_date TEXT, text TEXT, status TEXT, tags TEXT,
+            `order` TEXT, level INTEGER, parent_id TEXT, created_at INTEGER, updated_at INTEGER)",
+    ];
+
+    #[tokio::test]
+    async fn test_merge_remaps_and_deduplicates() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("ATTACH DATABASE ':memory:' AS merge_source").execute(&mut conn).await.unwrap();
+        for schema in ["main", SOURCE_ALIAS] {
+            for statement in SCHEMA {
+                sqlx::query(&statement.replace("{}", schema)).execute(&mut conn).await.unwrap();
+            }
+        }
+        for statement in [
+            "CREATE TABLE main.private_pages (workspace_id TEXT, page_date TEXT)",
+            "CREATE TABLE main.locked_pages (workspace_id TEXT, page_date TEXT)",
+            "INSERT INTO main.workspaces VALUES ('local', 'Personal', '2024-05-01', 0, 0)",
+            "INSERT INTO main.pages VALUES ('local', '2024-05-01', 'written here', 0, 10)",
+            "INSERT INTO main.todos VALUES ('l1', 'local', '2024-05-01', 'shared', 'todo', '[]', 'a0', 0, NULL, 5, 5)",
+            "INSERT INTO merge_source.workspaces VALUES ('other', 'Personal', '2024-05-02', 0, 0)",
+            "INSERT INTO merge_source.pages VALUES ('other', '2024-05-01', 'written there', 0, 20),
+                ('other', '2024-05-02', 'only there', 0, 0)",
+            "INSERT INTO merge_source.todos VALUES ('o1', 'other', '2024-05-01', 'shared', 'todo', '[]', 'a0', 0, NULL, 5, 5),
+                ('o2', 'other', '2024-05-01', 'child', 'done', '[\"work\"]', 'a1', 1, 'o1', 6, 6)",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+
+        let report = merge_attached(&mut conn, MergeStrategy::KeepLocal).await.unwrap();
+        assert_eq!(report.workspaces_created, 0);
+        assert_eq!(report.pages_imported, 1);
+        assert_eq!((report.todos_imported, report.duplicate_todos), (1, 1));
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].resolution, "keptLocal");
+
+        let (parent_id, order, tags): (String, String, String) =
+            sqlx::query_as("SELECT parent_id, `order`, tags FROM main.todos WHERE text = 'child'")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(parent_id, "l1", "the child hangs under the local copy of its parent");
+        assert!(order.as_str() > "a0");
+        assert_eq!(tags, "[\"work\"]");
+    }
+}
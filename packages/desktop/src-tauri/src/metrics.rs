@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::logger;
+
+pub use journal_todo_macros::command;
+
+/// Commands slower than this are written to the log
+const SLOW_COMMAND: Duration = Duration::from_millis(500);
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+static METRICS: Mutex<Option<HashMap<&'static str, CommandMetrics>>> = Mutex::new(None);
+
+tokio::task_local! {
+    static REQUEST_ID: u64;
+}
+
+/// Timings of one command since launch
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandMetrics {
+    pub name: &'static str,
+    pub calls: u64,
+    pub errors: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    pub last_ms: f64,
+    pub last_request_id: u64,
+}
+
+/// Id of the command invocation the current code runs in, if any
+pub fn current_request_id() -> Option<u64> {
+    REQUEST_ID.try_with(|id| *id).ok()
+}
+
+fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+fn record(name: &'static str, request_id: u64, elapsed: Duration, error: Option<String>) {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    if let Ok(mut guard) = METRICS.lock() {
+        let entry = guard
+            .get_or_insert_with(HashMap::new)
+            .entry(name)
+            .or_insert_with(|| CommandMetrics { name, ..Default::default() });
+        entry.calls += 1;
+        entry.total_ms += ms;
+        entry.max_ms = entry.max_ms.max(ms);
+        entry.last_ms = ms;
+        entry.last_request_id = request_id;
+        if error.is_some() {
+            entry.errors += 1;
+        }
+    }
+
+    if let Some(error) = error {
+        logger::info(&format!("[req {}] {} failed after {:.1}ms: {}", request_id, name, ms, error));
+    } else if elapsed >= SLOW_COMMAND {
+        logger::info(&format!("[req {}] {} took {:.1}ms", request_id, name, ms));
+    }
+}
+
+async fn run<T>(name: &'static str, fut: impl Future<Output = T>, error: impl FnOnce(&T) -> Option<String>) -> T {
+    let request_id = next_request_id();
+    let started = Instant::now();
+    let output = REQUEST_ID.scope(request_id, fut).await;
+    record(name, request_id, started.elapsed(), error(&output));
+    output
+}
+
+fn run_sync<T>(name: &'static str, f: impl FnOnce() -> T, error: impl FnOnce(&T) -> Option<String>) -> T {
+    let request_id = next_request_id();
+    let started = Instant::now();
+    let output = REQUEST_ID.sync_scope(request_id, f);
+    record(name, request_id, started.elapsed(), error(&output));
+    output
+}
+
+/// Time an async command that cannot fail
+#[allow(dead_code)] // every async command returns a Result today
+pub async fn track<T>(name: &'static str, fut: impl Future<Output = T>) -> T {
+    run(name, fut, |_| None).await
+}
+
+/// Time an async command, counting `Err` as a failure
+pub async fn track_result<T, E: Display>(
+    name: &'static str,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    run(name, fut, |output| output.as_ref().err().map(|e| e.to_string())).await
+}
+
+/// Time a sync command that cannot fail
+pub fn track_sync<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    run_sync(name, f, |_| None)
+}
+
+/// Time a sync command, counting `Err` as a failure
+pub fn track_result_sync<T, E: Display>(name: &'static str, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    run_sync(name, f, |output| output.as_ref().err().map(|e| e.to_string()))
+}
+
+/// Per-command timings, slowest total first
+#[command]
+pub fn get_command_metrics() -> Vec<CommandMetrics> {
+    let mut metrics: Vec<CommandMetrics> = METRICS
+        .lock()
+        .ok()
+        .and_then(|guard| guard.as_ref().map(|m| m.values().cloned().collect()))
+        .unwrap_or_default();
+    metrics.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    metrics
+}
+
+/// Start measuring from scratch
+#[command]
+pub fn reset_command_metrics() {
+    if let Ok(mut guard) = METRICS.lock() {
+        *guard = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_are_counted_per_command() {
+        let name = "metrics::tests::flaky";
+        let _ = track_result_sync(name, || Ok::<_, String>(1));
+        let _ = track_result_sync(name, || {
+            assert!(current_request_id().is_some());
+            Err::<i32, _>("boom".to_string())
+        });
+
+        let metrics = get_command_metrics();
+        let flaky = metrics.iter().find(|m| m.name == name).unwrap();
+        assert_eq!(flaky.calls, 2);
+        assert_eq!(flaky.errors, 1);
+        assert!(current_request_id().is_none());
+    }
+}
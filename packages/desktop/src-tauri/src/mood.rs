@@ -0,0 +1,235 @@
+use chrono::Utc;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use crate::ai::DateRange;
+use crate::db::{changes, DatabaseState};
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct MoodBucket {
+    /// Day ("2024-05-01"), ISO week ("2024-W18"), month ("2024-05") or year ("2024")
+    pub period: String,
+    pub average: f64,
+    pub min: i64,
+    pub max: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WeekdayMood {
+    /// 0 = Sunday ... 6 = Saturday
+    pub weekday: i64,
+    pub average: f64,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletedTodosMood {
+    pub completed: i64,
+    pub average: f64,
+    pub days: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoodCorrelations {
+    pub by_weekday: Vec<WeekdayMood>,
+    pub by_completed_todos: Vec<CompletedTodosMood>,
+    /// Pearson correlation between mood and todos completed that day, if computable
+    pub completed_todos_coefficient: Option<f64>,
+}
+
+/// Pearson correlation coefficient of paired samples
+fn pearson(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let (mean_x, mean_y) = pairs
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / n, sy + y / n));
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(cov / (var_x.sqrt() * var_y.sqrt()))
+}
+
+/// Set (1-5) or clear the mood of a journal day
+#[crate::metrics::command]
+pub async fn set_entry_mood(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    mood: Option<i64>,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        match mood {
+            Some(mood) if !(1..=5).contains(&mood) => {
+                return Err(format!("Mood must be between 1 and 5, got {}", mood));
+            }
+            Some(mood) => {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO page_moods (workspace_id, page_date, mood, updated_at) VALUES (?, ?, ?, ?)",
+                )
+                .bind(&workspace_id)
+                .bind(&date)
+                .bind(mood)
+                .bind(Utc::now().timestamp_millis())
+                .execute(&*pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+            None => {
+                sqlx::query("DELETE FROM page_moods WHERE workspace_id = ? AND page_date = ?")
+                    .bind(&workspace_id)
+                    .bind(&date)
+                    .execute(&*pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    changes::notify(&app, vec!["page_moods".to_string()]);
+    Ok(())
+}
+
+#[crate::metrics::command]
+pub async fn get_entry_mood(
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+) -> Result<Option<i64>, String> {
+    let pool = state.pool.lock().await;
+    let row: Option<(i64,)> =
+        sqlx::query_as("SELECT mood FROM page_moods WHERE workspace_id = ? AND page_date = ?")
+            .bind(workspace_id)
+            .bind(date)
+            .fetch_optional(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    Ok(row.map(|(mood,)| mood))
+}
+
+/// Average mood per day, week, month or year
+#[crate::metrics::command]
+pub async fn get_mood_trends(
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    range: DateRange,
+    granularity: String,
+) -> Result<Vec<MoodBucket>, String> {
+    let period = match granularity.as_str() {
+        "day" => "page_date",
+        // %W counts weeks from the first Monday, which is close enough for charting
+        "week" => "strftime('%Y-W%W', page_date)",
+        "month" => "strftime('%Y-%m', page_date)",
+        "year" => "strftime('%Y', page_date)",
+        other => return Err(format!("Unknown granularity: {}", other)),
+    };
+
+    let pool = state.pool.lock().await;
+    sqlx::query_as(&format!(
+        "SELECT {period} AS period, AVG(mood) AS average, MIN(mood) AS min, MAX(mood) AS max, COUNT(*) AS count
+         FROM page_moods
+         WHERE workspace_id = ? AND page_date BETWEEN ? AND ?
+         GROUP BY period ORDER BY period",
+        period = period
+    ))
+    .bind(workspace_id)
+    .bind(range.from)
+    .bind(range.to)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// How mood relates to the weekday and to the number of completed todos
+#[crate::metrics::command]
+pub async fn get_mood_correlations(
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    range: DateRange,
+) -> Result<MoodCorrelations, String> {
+    let pool = state.pool.lock().await;
+
+    let by_weekday: Vec<WeekdayMood> = sqlx::query_as(
+        "SELECT CAST(strftime('%w', page_date) AS INTEGER) AS weekday, AVG(mood) AS average, COUNT(*) AS count
+         FROM page_moods
+         WHERE workspace_id = ? AND page_date BETWEEN ? AND ?
+         GROUP BY weekday ORDER BY weekday",
+    )
+    .bind(&workspace_id)
+    .bind(&range.from)
+    .bind(&range.to)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let daily: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT m.mood,
+                (SELECT COUNT(*) FROM todos t
+                 WHERE t.workspace_id = m.workspace_id AND t.page_date = m.page_date AND t.status = 'done')
+         FROM page_moods m
+         WHERE m.workspace_id = ? AND m.page_date BETWEEN ? AND ?",
+    )
+    .bind(&workspace_id)
+    .bind(&range.from)
+    .bind(&range.to)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut by_completed_todos: Vec<CompletedTodosMood> = Vec::new();
+    for &(mood, completed) in &daily {
+        match by_completed_todos.iter_mut().find(|b| b.completed == completed) {
+            Some(bucket) => {
+                bucket.average += (mood as f64 - bucket.average) / (bucket.days + 1) as f64;
+                bucket.days += 1;
+            }
+            None => by_completed_todos.push(CompletedTodosMood {
+                completed,
+                average: mood as f64,
+                days: 1,
+            }),
+        }
+    }
+    by_completed_todos.sort_by_key(|b| b.completed);
+
+    let pairs: Vec<(f64, f64)> = daily
+        .iter()
+        .map(|&(mood, completed)| (completed as f64, mood as f64))
+        .collect();
+
+    Ok(MoodCorrelations {
+        by_weekday,
+        by_completed_todos,
+        completed_todos_coefficient: pearson(&pairs),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pearson() {
+        assert_eq!(pearson(&[(1.0, 2.0)]), None);
+        assert_eq!(pearson(&[(1.0, 2.0), (1.0, 3.0)]), None);
+
+        let r = pearson(&[(1.0, 2.0), (2.0, 4.0), (3.0, 6.0)]).unwrap();
+        assert!((r - 1.0).abs() < 1e-9);
+        let r = pearson(&[(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)]).unwrap();
+        assert!((r + 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+use crate::app_config::AppConfig;
+use crate::attachments;
+use crate::db::{changes, DatabaseState};
+use crate::{confirm, logger, settings};
+
+/// Path of the tesseract executable, kept in the app config since it is run as is;
+/// without one `tesseract` is looked up on PATH
+const TESSERACT_PATH_KEY: &str = "ocr.tesseract_path";
+const DEFAULT_TESSERACT: &str = "tesseract";
+/// Tesseract language code(s), e.g. "eng" or "eng+deu"
+const LANGUAGE_SETTING: &str = "ocr.language";
+const OCR_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Recognize text in an image using the tesseract command line tool
+async fn recognize(tesseract: &str, language: &str, image: &std::path::Path) -> Result<String, String> {
+    let mut command = tokio::process::Command::new(tesseract);
+    command
+        .arg(image)
+        .arg("stdout")
+        .arg("-l")
+        .arg(language)
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(OCR_TIMEOUT, command.output())
+        .await
+        .map_err(|_| "OCR timed out".to_string())?
+        .map_err(|e| format!("Failed to run {} (is tesseract installed?): {}", tesseract, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run OCR on an image attachment, store the text and add it to the search index
+#[crate::metrics::command]
+pub async fn extract_text(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    attachment_id: String,
+) -> Result<String, String> {
+    let attachment = attachments::get(&state, &attachment_id).await?;
+    if !attachment
+        .mime_type
+        .as_deref()
+        .is_some_and(|m| m.starts_with("image/") && m != "image/svg+xml")
+    {
+        return Err(format!("{} is not a raster image", attachment.file_name));
+    }
+
+    let tesseract = app.state::<AppConfig>().get_or(TESSERACT_PATH_KEY, DEFAULT_TESSERACT.to_string());
+    let language = {
+        let pool = state.pool.lock().await;
+        settings::get_or(&pool, LANGUAGE_SETTING, "eng".to_string()).await
+    };
+
+    let path = attachments::file_path(&state, &attachment).await;
+    let text = recognize(&tesseract, &language, &path).await.map_err(|e| {
+        logger::error(&format!("OCR failed for {}: {}", attachment.file_name, e));
+        e
+    })?;
+
+    {
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query("UPDATE attachments SET ocr_text = ? WHERE id = ?")
+            .bind(&text)
+            .bind(&attachment_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("UPDATE attachments_fts SET ocr_text = ? WHERE attachment_id = ?")
+            .bind(&text)
+            .bind(&attachment_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+
+    changes::notify(&app, vec!["attachments".to_string()]);
+    Ok(text)
+}
+
+/// The tesseract executable OCR runs
+#[crate::metrics::command]
+pub fn get_tesseract_path(app: AppHandle) -> String {
+    app.state::<AppConfig>().get_or(TESSERACT_PATH_KEY, DEFAULT_TESSERACT.to_string())
+}
+
+/// Run OCR with another tesseract executable once the user allows it in a native dialog,
+/// or go back to the one on PATH with None
+#[crate::metrics::command]
+pub async fn set_tesseract_path(app: AppHandle, path: Option<String>) -> Result<(), String> {
+    let path = path.map(|path| path.trim().to_string()).filter(|path| !path.is_empty());
+    if let Some(path) = &path {
+        if !std::path::Path::new(path).is_absolute() {
+            return Err("The tesseract path must be absolute".to_string());
+        }
+        let message = format!("Allow OCR to run this program on your images?\n\n{}", path);
+        if !confirm::ask("Use another tesseract", &message).await {
+            return Err("Using this tesseract was not allowed".to_string());
+        }
+    }
+    app.state::<AppConfig>().update(TESSERACT_PATH_KEY, |current: &mut Option<String>| {
+        *current = path;
+        Ok(())
+    })
+}
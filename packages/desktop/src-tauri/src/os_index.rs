@@ -0,0 +1,290 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, privacy, settings};
+
+/// Write entries where Spotlight / Windows Search picks them up; off by default as it exposes entry text
+const ENABLED_SETTING: &str = "os_index.enabled";
+/// Longest summary put in an indexed entry's name
+const SUMMARY_CHARS: usize = 60;
+
+/// Serializes syncs, so a burst of edits doesn't race on the same files
+static SYNC: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct IndexedEntry {
+    workspace_id: String,
+    date: String,
+    notes: Option<String>,
+    first_todo: Option<String>,
+}
+
+/// Where the platform indexer looks: Spotlight indexes ~/Library/Caches/Metadata,
+/// Windows Search the user's Documents
+fn index_dir(app: &AppHandle) -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        Some(dirs::home_dir()?.join("Library/Caches/Metadata").join(&app.config().identifier))
+    } else if cfg!(target_os = "windows") {
+        Some(dirs::document_dir()?.join("Journal Todo").join("Search"))
+    } else {
+        None
+    }
+}
+
+fn extension() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "webloc"
+    } else {
+        "url"
+    }
+}
+
+fn deep_link(date: &str) -> String {
+    format!("journal-todo://open?date={}", date)
+}
+
+/// The first line of the notes, or the first todo, without markdown markers
+fn summary(entry: &IndexedEntry) -> Option<String> {
+    let notes = entry.notes.as_deref().filter(|notes| !privacy::is_encrypted(notes));
+    let line = notes
+        .into_iter()
+        .flat_map(str::lines)
+        .chain(entry.first_todo.as_deref())
+        .map(|line| line.trim_start_matches(['#', '-', '*', '>', ' ']).trim())
+        .find(|line| !line.is_empty())?;
+    let mut summary: String = line.chars().take(SUMMARY_CHARS).collect();
+    if line.chars().count() > SUMMARY_CHARS {
+        summary.push('…');
+    }
+    Some(summary)
+}
+
+/// Characters no platform allows in a file name are replaced
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_end_matches(['.', ' '])
+        .to_string()
+}
+
+/// Indexed under its name, so the date and summary are what search matches
+fn file_name(entry: &IndexedEntry) -> Option<String> {
+    let day = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d").ok()?;
+    let title = match summary(entry) {
+        Some(summary) => format!("Journal {} – {}", day.format("%a, %b %-d, %Y"), summary),
+        None => format!("Journal {}", day.format("%a, %b %-d, %Y")),
+    };
+    Some(format!("{}.{}", sanitize(&title), extension()))
+}
+
+/// A link file that opens the entry through the deep link handler
+fn contents(date: &str) -> String {
+    let url = deep_link(date);
+    if cfg!(target_os = "macos") {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n\t<key>URL</key>\n\t<string>{}</string>\n</dict>\n</plist>\n",
+            url
+        )
+    } else {
+        format!("[InternetShortcut]\r\nURL={}\r\n", url)
+    }
+}
+
+/// Files to write and files to remove to turn `existing` into `desired`
+fn plan(desired: &HashMap<String, String>, existing: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut write: Vec<String> = desired
+        .keys()
+        .filter(|name| !existing.contains(name))
+        .cloned()
+        .collect();
+    write.sort();
+    let remove = existing
+        .iter()
+        .filter(|name| !desired.contains_key(*name))
+        .cloned()
+        .collect();
+    (write, remove)
+}
+
+/// Entries with content, leaving out private and locked ones
+async fn load_entries(app: &AppHandle) -> Result<Vec<IndexedEntry>, String> {
+    let state = app.state::<DatabaseState>();
+    let mut snapshot = state.open_reporting_snapshot().await?;
+    sqlx::query_as(
+        "SELECT p.workspace_id, p.date, COALESCE(p.notes, zstd_decompress(p.notes_zstd)) AS notes,
+                (SELECT t.text FROM todos t WHERE t.workspace_id = p.workspace_id AND t.page_date = p.date
+                 ORDER BY t.`order` LIMIT 1) AS first_todo
+         FROM pages p
+         WHERE (p.notes_compressed = 1 OR trim(COALESCE(p.notes, '')) != ''
+                OR EXISTS (SELECT 1 FROM todos t WHERE t.workspace_id = p.workspace_id AND t.page_date = p.date))
+           AND NOT EXISTS (SELECT 1 FROM private_pages pp WHERE pp.workspace_id = p.workspace_id AND pp.page_date = p.date)
+           AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = p.workspace_id AND lp.page_date = p.date)",
+    )
+    .fetch_all(&mut *snapshot)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+fn existing_files(dir: &Path) -> Result<Vec<String>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if entry.file_type().map_err(|e| e.to_string())?.is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// Apply the plan for one workspace's directory
+fn write_dir(dir: &Path, desired: &HashMap<String, String>) -> Result<usize, String> {
+    let (write, remove) = plan(desired, &existing_files(dir)?);
+    if !write.is_empty() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    for name in &remove {
+        std::fs::remove_file(dir.join(name)).map_err(|e| e.to_string())?;
+    }
+    for name in &write {
+        std::fs::write(dir.join(name), &desired[name]).map_err(|e| e.to_string())?;
+    }
+    Ok(write.len() + remove.len())
+}
+
+/// Bring the index directory in line with the current entries, or remove it when indexing is off
+pub async fn sync(app: &AppHandle) -> Result<(), String> {
+    let Some(root) = index_dir(app) else {
+        return Ok(());
+    };
+    let _guard = SYNC.lock().await;
+    let enabled = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        settings::get_or(&pool, ENABLED_SETTING, false).await
+    };
+    if !enabled {
+        match std::fs::remove_dir_all(&root) {
+            Ok(()) => logger::info("Removed entries from the system search index"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        return Ok(());
+    }
+
+    let mut workspaces: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for entry in load_entries(app).await? {
+        if let Some(name) = file_name(&entry) {
+            workspaces
+                .entry(entry.workspace_id.clone())
+                .or_default()
+                .insert(name, contents(&entry.date));
+        }
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        // Workspaces without indexed entries anymore are emptied too
+        for name in existing_dirs(&root)? {
+            workspaces.entry(name).or_default();
+        }
+        let changed: usize = workspaces
+            .iter()
+            .map(|(workspace_id, desired)| write_dir(&root.join(sanitize(workspace_id)), desired))
+            .sum::<Result<usize, String>>()?;
+        if changed > 0 {
+            logger::info(&format!("Updated {} entries in the system search index", changed));
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn existing_dirs(root: &Path) -> Result<Vec<String>, String> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// Keep the system search index in sync as entries are edited, made private or deleted
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    let listener_app = app.clone();
+    changes::listen(
+        &app,
+        &["pages", "todos", "private_pages", "locked_pages", "settings"],
+        move |_| {
+            let app = listener_app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = sync(&app).await {
+                    logger::error(&format!("Failed to update system search index: {}", e));
+                }
+            });
+        },
+    );
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = sync(&app).await {
+            logger::error(&format!("Failed to update system search index: {}", e));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(notes: Option<&str>, first_todo: Option<&str>) -> IndexedEntry {
+        IndexedEntry {
+            workspace_id: "w".to_string(),
+            date: "2024-03-04".to_string(),
+            notes: notes.map(str::to_string),
+            first_todo: first_todo.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_indexed_names() {
+        assert_eq!(summary(&entry(Some("\n# Standup: ship v2?\nmore"), None)).as_deref(), Some("Standup: ship v2?"));
+        assert_eq!(summary(&entry(Some("  "), Some("Call mom"))).as_deref(), Some("Call mom"));
+        assert_eq!(summary(&entry(Some("enc:v1:abc"), None)), None);
+        assert_eq!(summary(&entry(Some(&"a".repeat(80)), None)).unwrap().chars().count(), SUMMARY_CHARS + 1);
+
+        let name = file_name(&entry(Some("Standup: ship v2?"), None)).unwrap();
+        assert!(name.starts_with("Journal Mon, Mar 4, 2024 – Standup- ship v2-."));
+        assert!(file_name(&IndexedEntry { date: "bad".to_string(), ..entry(None, None) }).is_none());
+    }
+
+    #[test]
+    fn test_plan_diffs_files() {
+        let desired: HashMap<String, String> =
+            [("a.url".to_string(), String::new()), ("b.url".to_string(), String::new())].into();
+        let (write, remove) = plan(&desired, &["b.url".to_string(), "old.url".to_string()]);
+        assert_eq!(write, vec!["a.url".to_string()]);
+        assert_eq!(remove, vec!["old.url".to_string()]);
+    }
+}
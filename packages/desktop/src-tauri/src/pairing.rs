@@ -0,0 +1,353 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::digest::{digest, SHA256};
+use ring::hkdf;
+use ring::rand::SystemRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::DatabaseState;
+use crate::{logger, secrets, settings};
+
+const DEVICE_ID_SETTING: &str = "sync.device_id";
+/// Name other devices list this one under; the host name by default
+const DEVICE_NAME_SETTING: &str = "sync.device_name";
+/// Where other devices reach this one, set by whichever sync transport is in use
+const ENDPOINT_SETTING: &str = "sync.endpoint";
+/// Marks scanned text as one of our payloads
+const PAYLOAD_PREFIX: &str = "journal-todo-pair:";
+const PAYLOAD_VERSION: u32 = 1;
+/// How long an offer can be answered
+const OFFER_TTL_MS: i64 = 10 * 60 * 1000;
+const HKDF_INFO: &[u8] = b"journal-todo pairing v1";
+const QR_SIZE: u32 = 240;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum PayloadKind {
+    /// Shown by the device that starts pairing
+    Offer,
+    /// Shown back by the device that scanned the offer
+    Answer,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PairingPayload {
+    version: u32,
+    kind: PayloadKind,
+    pairing_id: String,
+    device_id: String,
+    device_name: String,
+    endpoint: Option<String>,
+    /// X25519 public key, base64url; the shared secret itself never leaves either device
+    public_key: String,
+    expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedDevice {
+    pub id: String,
+    pub name: String,
+    pub endpoint: Option<String>,
+    pub paired_at: i64,
+}
+
+/// A payload for the other device, as text and as an SVG QR code
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingCode {
+    pub payload: String,
+    pub qr_svg: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingResult {
+    pub device: PairedDevice,
+    /// The same on both devices, for the user to compare
+    pub verification_code: String,
+    /// After accepting an offer: the answer for the offering device to scan
+    pub answer: Option<PairingCode>,
+}
+
+/// Offers made on this device, with the key to finish each once its answer is scanned
+#[derive(Default)]
+pub struct PendingPairings(Mutex<HashMap<String, (EphemeralPrivateKey, i64)>>);
+
+fn encode(payload: &PairingPayload) -> Result<String, String> {
+    let json = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    Ok(format!("{}{}", PAYLOAD_PREFIX, URL_SAFE_NO_PAD.encode(json)))
+}
+
+fn decode(text: &str) -> Result<PairingPayload, String> {
+    let invalid = || "Not a Journal Todo pairing code".to_string();
+    let encoded = text.trim().strip_prefix(PAYLOAD_PREFIX).ok_or_else(invalid)?;
+    let json = URL_SAFE_NO_PAD.decode(encoded).map_err(|_| invalid())?;
+    let payload: PairingPayload = serde_json::from_slice(&json).map_err(|_| invalid())?;
+    if payload.version != PAYLOAD_VERSION {
+        return Err(format!("Pairing code version {} isn't supported", payload.version));
+    }
+    Ok(payload)
+}
+
+fn pairing_code(payload: &PairingPayload) -> Result<PairingCode, String> {
+    let text = encode(payload)?;
+    let qr_svg = QrCode::new(text.as_bytes())
+        .map_err(|e| format!("Failed to make QR code: {}", e))?
+        .render::<svg::Color>()
+        .min_dimensions(QR_SIZE, QR_SIZE)
+        .build();
+    Ok(PairingCode {
+        payload: text,
+        qr_svg,
+        expires_at: payload.expires_at,
+    })
+}
+
+fn new_key() -> Result<(EphemeralPrivateKey, String), String> {
+    let key = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new())
+        .map_err(|_| "No secure random source available".to_string())?;
+    let public = key.compute_public_key().map_err(|_| "Failed to make pairing key".to_string())?;
+    Ok((key, URL_SAFE_NO_PAD.encode(public.as_ref())))
+}
+
+/// The secret both devices arrive at, bound to the pairing and the two device ids
+fn derive_secret(
+    key: EphemeralPrivateKey,
+    peer_public_key: &str,
+    pairing_id: &str,
+    device_ids: [&str; 2],
+) -> Result<[u8; 32], String> {
+    let peer = URL_SAFE_NO_PAD
+        .decode(peer_public_key)
+        .map_err(|_| "Invalid device key in pairing code".to_string())?;
+    let mut ids = device_ids;
+    ids.sort();
+    let info = [HKDF_INFO, ids[0].as_bytes(), ids[1].as_bytes()];
+    agreement::agree_ephemeral(key, &UnparsedPublicKey::new(&X25519, peer), |material| {
+        let mut secret = [0u8; 32];
+        hkdf::Salt::new(hkdf::HKDF_SHA256, pairing_id.as_bytes())
+            .extract(material)
+            .expand(&info, hkdf::HKDF_SHA256)
+            .and_then(|okm| okm.fill(&mut secret))
+            .map(|_| secret)
+    })
+    .and_then(|secret| secret)
+    .map_err(|_| "Key agreement with the other device failed".to_string())
+}
+
+/// Six digits the user can compare on both screens
+fn verification_code(secret: &[u8]) -> String {
+    let hash = digest(&SHA256, secret);
+    let bytes = hash.as_ref();
+    let number = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) % 1_000_000;
+    format!("{:06}", number)
+}
+
+fn secret_name(device_id: &str) -> String {
+    format!("sync.device.{}", device_id)
+}
+
+/// This device's id, name and endpoint, making up the id on first use
+async fn identity(app: &AppHandle) -> Result<(String, String, Option<String>), String> {
+    let (id, name, endpoint) = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        (
+            settings::get::<String>(&pool, DEVICE_ID_SETTING).await?,
+            settings::get::<String>(&pool, DEVICE_NAME_SETTING).await?,
+            settings::get::<String>(&pool, ENDPOINT_SETTING).await?,
+        )
+    };
+    let id = match id {
+        Some(id) => id,
+        None => {
+            let id = uuid::Uuid::new_v4().to_string();
+            settings::set(app, DEVICE_ID_SETTING, &id).await?;
+            id
+        }
+    };
+    let name = name
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "Journal Todo".to_string());
+    Ok((id, name, endpoint))
+}
+
+async fn save_device(app: &AppHandle, payload: &PairingPayload, secret: &[u8]) -> Result<PairedDevice, String> {
+    secrets::set(&secret_name(&payload.device_id), &URL_SAFE_NO_PAD.encode(secret))?;
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    sqlx::query_as(
+        "INSERT INTO paired_devices (id, name, endpoint, paired_at) VALUES (?, ?, ?, ?)
+         ON CONFLICT (id) DO UPDATE SET
+             name = excluded.name, endpoint = excluded.endpoint, paired_at = excluded.paired_at
+         RETURNING id, name, endpoint, paired_at",
+    )
+    .bind(&payload.device_id)
+    .bind(&payload.device_name)
+    .bind(&payload.endpoint)
+    .bind(Utc::now().timestamp_millis())
+    .fetch_one(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// The secret shared with a paired device, for the sync transports to authenticate and
+/// encrypt with
+#[allow(dead_code)] // no sync transport uses it yet
+pub(crate) fn shared_secret(device_id: &str) -> Result<Option<Vec<u8>>, String> {
+    secrets::get(&secret_name(device_id))?
+        .map(|secret| URL_SAFE_NO_PAD.decode(secret).map_err(|e| e.to_string()))
+        .transpose()
+}
+
+/// Start pairing: a code with this device's key and endpoint for the other device to scan.
+/// It expires after ten minutes.
+#[crate::metrics::command]
+pub async fn create_pairing_payload(
+    app: AppHandle,
+    pending: State<'_, PendingPairings>,
+) -> Result<PairingCode, String> {
+    let (device_id, device_name, endpoint) = identity(&app).await?;
+    let (key, public_key) = new_key()?;
+    let now = Utc::now().timestamp_millis();
+    let payload = PairingPayload {
+        version: PAYLOAD_VERSION,
+        kind: PayloadKind::Offer,
+        pairing_id: uuid::Uuid::new_v4().to_string(),
+        device_id,
+        device_name,
+        endpoint,
+        public_key,
+        expires_at: now + OFFER_TTL_MS,
+    };
+    let code = pairing_code(&payload)?;
+    let mut pending = pending.0.lock().unwrap();
+    pending.retain(|_, (_, expires_at)| *expires_at > now);
+    pending.insert(payload.pairing_id.clone(), (key, payload.expires_at));
+    Ok(code)
+}
+
+/// Accept a scanned code. For an offer this pairs with the offering device and returns the
+/// answer to show it; for the answer to one of our offers it finishes the pairing.
+#[crate::metrics::command]
+pub async fn accept_pairing_payload(
+    app: AppHandle,
+    pending: State<'_, PendingPairings>,
+    payload: String,
+) -> Result<PairingResult, String> {
+    let payload = decode(&payload)?;
+    if payload.expires_at <= Utc::now().timestamp_millis() {
+        return Err("This pairing code has expired".to_string());
+    }
+    let (device_id, device_name, endpoint) = identity(&app).await?;
+    if payload.device_id == device_id {
+        return Err("This pairing code is from this device".to_string());
+    }
+    let device_ids = [device_id.as_str(), payload.device_id.as_str()];
+
+    let (secret, answer) = match payload.kind {
+        PayloadKind::Offer => {
+            let (key, public_key) = new_key()?;
+            let secret = derive_secret(key, &payload.public_key, &payload.pairing_id, device_ids)?;
+            let answer = PairingPayload {
+                version: PAYLOAD_VERSION,
+                kind: PayloadKind::Answer,
+                pairing_id: payload.pairing_id.clone(),
+                device_id: device_id.clone(),
+                device_name,
+                endpoint,
+                public_key,
+                expires_at: payload.expires_at,
+            };
+            (secret, Some(pairing_code(&answer)?))
+        }
+        PayloadKind::Answer => {
+            let (key, _) = pending
+                .0
+                .lock()
+                .unwrap()
+                .remove(&payload.pairing_id)
+                .ok_or_else(|| "This answers a pairing that wasn't started here, or has expired".to_string())?;
+            (derive_secret(key, &payload.public_key, &payload.pairing_id, device_ids)?, None)
+        }
+    };
+
+    let device = save_device(&app, &payload, &secret).await?;
+    logger::info(&format!("Paired with device {} ({})", device.name, device.id));
+    Ok(PairingResult {
+        device,
+        verification_code: verification_code(&secret),
+        answer,
+    })
+}
+
+#[crate::metrics::command]
+pub async fn list_paired_devices(state: State<'_, DatabaseState>) -> Result<Vec<PairedDevice>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as("SELECT id, name, endpoint, paired_at FROM paired_devices ORDER BY paired_at")
+        .fetch_all(&*pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Forget a paired device and the secret shared with it; it has to pair again to sync
+#[crate::metrics::command]
+pub async fn revoke_device(state: State<'_, DatabaseState>, id: String) -> Result<(), String> {
+    secrets::delete(&secret_name(&id))?;
+    let pool = state.pool.lock().await;
+    let removed = sqlx::query("DELETE FROM paired_devices WHERE id = ?")
+        .bind(&id)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+    if removed == 0 {
+        return Err(format!("Device not found: {}", id));
+    }
+    logger::info(&format!("Revoked paired device {}", id));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_both_devices_derive_the_same_secret() {
+        let (offer_key, offer_public) = new_key().unwrap();
+        let (answer_key, answer_public) = new_key().unwrap();
+        let offering = derive_secret(offer_key, &answer_public, "p", ["a", "b"]).unwrap();
+        let answering = derive_secret(answer_key, &offer_public, "p", ["b", "a"]).unwrap();
+        assert_eq!(offering, answering);
+        assert_eq!(verification_code(&offering), verification_code(&answering));
+        assert_eq!(verification_code(&offering).len(), 6);
+    }
+
+    #[test]
+    fn test_payload_round_trips_through_the_code() {
+        let payload = PairingPayload {
+            version: PAYLOAD_VERSION,
+            kind: PayloadKind::Offer,
+            pairing_id: "p".to_string(),
+            device_id: "a".to_string(),
+            device_name: "Laptop".to_string(),
+            endpoint: Some("192.168.1.4:27832".to_string()),
+            public_key: new_key().unwrap().1,
+            expires_at: 1,
+        };
+        let code = pairing_code(&payload).unwrap();
+        assert!(code.qr_svg.contains("<svg"));
+        assert_eq!(decode(&code.payload).unwrap(), payload);
+        assert!(decode("https://example.com").is_err());
+    }
+}
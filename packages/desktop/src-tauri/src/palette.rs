@@ -0,0 +1,326 @@
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+
+use crate::jobs;
+use crate::workspaces::WorkspaceManager;
+
+/// Something the command palette can run, as a backend command and its arguments
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteAction {
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    /// Extra words the action is found by besides its title
+    pub keywords: Vec<String>,
+    pub command: String,
+    pub args: serde_json::Value,
+    /// JSON pointer into `args` where the palette puts text the user types after picking the
+    /// action, e.g. a new todo's text
+    pub input: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionMatch {
+    #[serde(flatten)]
+    pub action: PaletteAction,
+    pub score: i64,
+    /// Character positions in the title that matched the query, for highlighting
+    pub matched: Vec<usize>,
+}
+
+fn action(
+    id: &str,
+    title: &str,
+    category: &str,
+    keywords: &[&str],
+    command: &str,
+    args: serde_json::Value,
+) -> PaletteAction {
+    PaletteAction {
+        id: id.to_string(),
+        title: title.to_string(),
+        category: category.to_string(),
+        keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+        command: command.to_string(),
+        args,
+        input: None,
+    }
+}
+
+fn with_input(mut action: PaletteAction, pointer: &str) -> PaletteAction {
+    action.input = Some(pointer.to_string());
+    action
+}
+
+/// Actions that are always available
+fn static_actions() -> Vec<PaletteAction> {
+    vec![
+        action(
+            "journal.open_today",
+            "Open today's entry",
+            "Journal",
+            &["go", "journal", "day"],
+            "dispatch_action",
+            json!({ "action": { "type": "openEntry" } }),
+        ),
+        with_input(
+            action(
+                "todos.create",
+                "Create todo",
+                "Todos",
+                &["add", "task", "new"],
+                "dispatch_action",
+                json!({ "action": { "type": "createTodo", "text": "" } }),
+            ),
+            "/action/text",
+        ),
+        action(
+            "todos.new",
+            "Start a new todo on today's page",
+            "Todos",
+            &["add", "task"],
+            "dispatch_action",
+            json!({ "action": { "type": "newTodo" } }),
+        ),
+        with_input(
+            action(
+                "journal.append_note",
+                "Append to today's notes",
+                "Journal",
+                &["write", "note", "add"],
+                "dispatch_action",
+                json!({ "action": { "type": "appendNote", "text": "" } }),
+            ),
+            "/action/text",
+        ),
+        action(
+            "window.board",
+            "Open board window",
+            "Windows",
+            &["kanban", "window"],
+            "open_window",
+            json!({ "kind": "board" }),
+        ),
+        action(
+            "window.today_widget",
+            "Toggle today widget",
+            "Windows",
+            &["widget", "floating", "show", "hide"],
+            "toggle_today_widget",
+            json!({}),
+        ),
+        action(
+            "backup.git_export",
+            "Back up journal to git",
+            "Backup",
+            &["export", "git", "markdown", "backup"],
+            "export_journal_to_git",
+            json!({}),
+        ),
+        action(
+            "email.check_inbox",
+            "Check email inbox",
+            "Import",
+            &["mail", "imap", "import"],
+            "check_email_inbox",
+            json!({}),
+        ),
+        action(
+            "attachments.cleanup",
+            "Find unused attachments",
+            "Maintenance",
+            &["files", "cleanup", "orphans"],
+            "cleanup_attachments",
+            json!({ "fix": false }),
+        ),
+        action(
+            "privacy.lock",
+            "Lock private entries",
+            "Privacy",
+            &["secure", "hide"],
+            "lock_private_entries",
+            json!({}),
+        ),
+        action(
+            "app.check_updates",
+            "Check for updates",
+            "App",
+            &["update", "upgrade", "version"],
+            "check_for_updates",
+            json!({}),
+        ),
+        action(
+            "app.devtools",
+            "Open developer tools",
+            "App",
+            &["debug", "inspect", "console"],
+            "open_devtools",
+            json!({}),
+        ),
+    ]
+}
+
+/// Every action, with ones built from the registered jobs and the workspaces that can be switched to
+fn all_actions(app: &AppHandle) -> Vec<PaletteAction> {
+    let mut actions = static_actions();
+    for job in jobs::jobs() {
+        actions.push(action(
+            &format!("jobs.run.{}", job.name),
+            &format!("Run job: {}", job.name),
+            "Jobs",
+            &["job", "schedule", "now"],
+            "run_job_now",
+            json!({ "name": job.name }),
+        ));
+    }
+
+    let manager = app.state::<WorkspaceManager>();
+    let active_id = manager.active().id;
+    for workspace in manager.list() {
+        if workspace.id == active_id {
+            continue;
+        }
+        actions.push(action(
+            &format!("workspace.switch.{}", workspace.id),
+            &format!("Switch to workspace: {}", workspace.name),
+            "Workspaces",
+            &["vault", "open", "change"],
+            "switch_workspace",
+            json!({ "id": workspace.id }),
+        ));
+    }
+    actions
+}
+
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    index == 0 || !chars[index - 1].is_alphanumeric()
+}
+
+/// Positions of the query characters in order, greedily from the left. With `word_starts`,
+/// a match continues the previous one or starts a word whenever the text allows it.
+fn positions(query: &[char], chars: &[char], lower: &[char], word_starts: bool) -> Option<Vec<usize>> {
+    let mut matched: Vec<usize> = Vec::with_capacity(query.len());
+    let mut next = 0;
+    for wanted in query {
+        let consecutive = !matched.is_empty() && lower.get(next) == Some(wanted);
+        let found = if consecutive {
+            next
+        } else {
+            let word_start = (next..lower.len())
+                .filter(|_| word_starts)
+                .find(|&i| lower[i] == *wanted && is_word_start(chars, i));
+            word_start.or_else(|| (next..lower.len()).find(|&i| lower[i] == *wanted))?
+        };
+        matched.push(found);
+        next = found + 1;
+    }
+    Some(matched)
+}
+
+/// Score `text` against `query` when every query character appears in it in order, ignoring
+/// case and spaces in the query. Consecutive matches and matches at the start of words score
+/// higher, and so do short texts that match early. Returns the score and the matched positions.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let matched = positions(&query, &chars, &lower, true).or_else(|| positions(&query, &chars, &lower, false))?;
+
+    let mut score = matched.len() as i64;
+    for (index, &position) in matched.iter().enumerate() {
+        if is_word_start(&chars, position) {
+            score += 8;
+        }
+        if index > 0 && position == matched[index - 1] + 1 {
+            score += 5;
+        }
+    }
+    score -= matched[0].min(10) as i64;
+    score -= ((chars.len() - matched.len()) / 8) as i64;
+    Some((score, matched))
+}
+
+/// Score an action by its title, or failing that by its keywords and category
+fn score(query: &str, action: &PaletteAction) -> Option<(i64, Vec<usize>)> {
+    let title = fuzzy_match(query, &action.title);
+    let other = action
+        .keywords
+        .iter()
+        .chain(std::iter::once(&action.category))
+        .filter_map(|word| fuzzy_match(query, word))
+        .map(|(score, _)| score - 4)
+        .max();
+    match (title, other) {
+        (Some((title_score, matched)), Some(other_score)) if other_score > title_score => {
+            Some((other_score, matched))
+        }
+        (Some(title), _) => Some(title),
+        (None, Some(other_score)) => Some((other_score, Vec::new())),
+        (None, None) => None,
+    }
+}
+
+fn rank(query: &str, actions: Vec<PaletteAction>) -> Vec<ActionMatch> {
+    let mut matches: Vec<ActionMatch> = actions
+        .into_iter()
+        .filter_map(|action| {
+            score(query, &action).map(|(score, matched)| ActionMatch { action, score, matched })
+        })
+        .collect();
+    // Stable, so equal scores (and everything, for an empty query) keep registry order
+    matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+    matches
+}
+
+/// Every action the command palette can run, best match for `query` first
+#[crate::metrics::command]
+pub fn list_actions(app: AppHandle, query: Option<String>) -> Vec<ActionMatch> {
+    rank(query.as_deref().unwrap_or(""), all_actions(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "Anything"), Some((0, Vec::new())));
+        assert!(fuzzy_match("xyz", "Create todo").is_none());
+
+        let (_, matched) = fuzzy_match("ct", "Create todo").unwrap();
+        assert_eq!(matched, vec![0, 7]);
+        let (_, matched) = fuzzy_match("TODO", "Create todo").unwrap();
+        assert_eq!(matched, vec![7, 8, 9, 10]);
+
+        let word_starts = fuzzy_match("ct", "Create todo").unwrap().0;
+        let mid_word = fuzzy_match("ct", "Function test").unwrap().0;
+        assert!(word_starts > mid_word);
+    }
+
+    #[test]
+    fn test_rank_actions() {
+        let ranked = rank("git", static_actions());
+        assert_eq!(ranked[0].action.id, "backup.git_export");
+
+        let ranked = rank("task", static_actions());
+        assert!(ranked[0].action.id.starts_with("todos."));
+        assert!(ranked[0].matched.is_empty());
+
+        let ranked = rank("", static_actions());
+        assert_eq!(ranked.len(), static_actions().len());
+        assert_eq!(ranked[0].action.id, "journal.open_today");
+    }
+}
@@ -0,0 +1,386 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::{calendar, dispatch};
+
+const DEFAULT_DAY_START: &str = "09:00";
+const DEFAULT_DAY_END: &str = "18:00";
+const DEFAULT_ESTIMATE_MINUTES: u32 = 30;
+const DEFAULT_BREAK_MINUTES: u32 = 5;
+
+/// A span of the day, as "HH:MM" times
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Busy {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlanConstraints {
+    /// "HH:MM"; 09:00 and 18:00 when unset
+    pub day_start: Option<String>,
+    pub day_end: Option<String>,
+    /// Meetings and other calendar events to plan around
+    pub busy: Vec<Busy>,
+    /// Gap left after each block
+    pub break_minutes: Option<u32>,
+    /// Minutes per todo id, overriding `~45m` style estimates in the text
+    pub estimates: HashMap<String, u32>,
+    /// Priority per todo id, higher first, overriding `!`/`!!`/`!!!` in the text
+    pub priorities: HashMap<String, u32>,
+    /// Also plan around the day's events in the OS calendar
+    pub use_calendar: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeBlock {
+    pub todo_id: String,
+    pub text: String,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DayPlan {
+    pub date: String,
+    pub blocks: Vec<TimeBlock>,
+    /// Todos that didn't fit in the free time, highest priority first
+    pub unscheduled: Vec<String>,
+    /// Free time left after the blocks and breaks
+    pub free_minutes: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate {
+    id: String,
+    text: String,
+    priority: u32,
+    minutes: u32,
+}
+
+fn parse_time(time: &str) -> Result<u32, String> {
+    let (hours, minutes) = time
+        .trim()
+        .split_once(':')
+        .and_then(|(h, m)| Some((h.parse::<u32>().ok()?, m.parse::<u32>().ok()?)))
+        .filter(|(h, m)| *h <= 24 && *m < 60 && h * 60 + m <= 24 * 60)
+        .ok_or_else(|| format!("Invalid time: {}", time))?;
+    Ok(hours * 60 + minutes)
+}
+
+fn format_time(minute: u32) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// `!`, `!!` or `!!!` as a word in the text
+fn text_priority(text: &str) -> u32 {
+    text.split_whitespace()
+        .filter(|word| !word.is_empty() && word.len() <= 3 && word.chars().all(|c| c == '!'))
+        .map(|word| word.len() as u32)
+        .max()
+        .unwrap_or(0)
+}
+
+/// A `~30m`, `~1h` or `~1h30m` estimate in the text
+fn text_estimate(text: &str) -> Option<u32> {
+    text.split_whitespace().find_map(|word| {
+        let spec = word.strip_prefix('~')?;
+        let (hours, rest) = match spec.split_once('h') {
+            Some((hours, rest)) => (hours.parse::<u32>().ok()?, rest),
+            None => (0, spec),
+        };
+        let minutes = match rest.strip_suffix('m') {
+            Some(minutes) => minutes.parse::<u32>().ok()?,
+            None if rest.is_empty() => 0,
+            None => return None,
+        };
+        Some(hours * 60 + minutes).filter(|total| *total > 0)
+    })
+}
+
+/// The day's free spans between `start` and `end` once busy blocks are taken out
+fn free_spans(start: u32, end: u32, busy: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut busy = busy.to_vec();
+    busy.sort_unstable();
+    let mut spans = Vec::new();
+    let mut cursor = start;
+    for (busy_start, busy_end) in busy {
+        if busy_start > cursor {
+            spans.push((cursor, busy_start.min(end)));
+        }
+        cursor = cursor.max(busy_end);
+        if cursor >= end {
+            break;
+        }
+    }
+    if cursor < end {
+        spans.push((cursor, end));
+    }
+    spans.retain(|(from, to)| to > from);
+    spans
+}
+
+/// Place todos, highest priority first, each in the earliest free span it fits in whole
+fn schedule(
+    mut candidates: Vec<Candidate>,
+    mut spans: Vec<(u32, u32)>,
+    break_minutes: u32,
+) -> (Vec<(Candidate, u32)>, Vec<Candidate>) {
+    // Stable, so equal priorities keep their order on the page
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.priority));
+    let mut placed = Vec::new();
+    let mut unplaced = Vec::new();
+    for candidate in candidates {
+        match spans.iter_mut().find(|(from, to)| to - from >= candidate.minutes) {
+            Some(span) => {
+                let start = span.0;
+                span.0 = (start + candidate.minutes + break_minutes).min(span.1);
+                placed.push((candidate, start));
+            }
+            None => unplaced.push(candidate),
+        }
+    }
+    placed.sort_by_key(|(_, start)| *start);
+    (placed, unplaced)
+}
+
+fn plan(
+    date: &str,
+    candidates: Vec<Candidate>,
+    constraints: &PlanConstraints,
+    earliest: Option<u32>,
+) -> Result<DayPlan, String> {
+    let mut day_start = parse_time(constraints.day_start.as_deref().unwrap_or(DEFAULT_DAY_START))?;
+    let day_end = parse_time(constraints.day_end.as_deref().unwrap_or(DEFAULT_DAY_END))?;
+    if day_end <= day_start {
+        return Err("The day has to end after it starts".to_string());
+    }
+    if let Some(earliest) = earliest {
+        day_start = day_start.max(earliest);
+    }
+    let busy = constraints
+        .busy
+        .iter()
+        .map(|busy| Ok((parse_time(&busy.start)?, parse_time(&busy.end)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let spans = free_spans(day_start, day_end, &busy);
+    let break_minutes = constraints.break_minutes.unwrap_or(DEFAULT_BREAK_MINUTES);
+    let (placed, unplaced) = schedule(candidates, spans.clone(), break_minutes);
+
+    let free: u32 = spans.iter().map(|(from, to)| to - from).sum();
+    let used: u32 = placed.iter().map(|(candidate, _)| candidate.minutes).sum();
+    Ok(DayPlan {
+        date: date.to_string(),
+        blocks: placed
+            .into_iter()
+            .map(|(candidate, start)| TimeBlock {
+                start: format_time(start),
+                end: format_time(start + candidate.minutes),
+                todo_id: candidate.id,
+                text: candidate.text,
+            })
+            .collect(),
+        unscheduled: unplaced.into_iter().map(|candidate| candidate.id).collect(),
+        free_minutes: free.saturating_sub(used),
+    })
+}
+
+/// The day's timed calendar events as busy spans, cut to the day
+async fn calendar_busy(app: &AppHandle, date: &str) -> Result<Vec<Busy>, String> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))?;
+    let Some(midnight) = day.and_hms_opt(0, 0, 0).and_then(|time| Local.from_local_datetime(&time).earliest()) else {
+        return Ok(Vec::new());
+    };
+    let (from, to) = (midnight.timestamp_millis(), (midnight + Duration::days(1)).timestamp_millis());
+    let time_of_day = |ms: i64| {
+        DateTime::from_timestamp_millis(ms.clamp(from, to))
+            .map(|time| time.with_timezone(&Local))
+            .filter(|time| time.date_naive() == day)
+            .map_or_else(|| "24:00".to_string(), |time| time.format("%H:%M").to_string())
+    };
+    Ok(calendar::events_between(app, from, to)
+        .await?
+        .into_iter()
+        .filter(|event| !event.all_day)
+        .map(|event| Busy { start: time_of_day(event.start), end: time_of_day(event.end) })
+        .collect())
+}
+
+/// Propose time blocks for a day's open todos around the busy times in `constraints`, and the
+/// day's calendar events when `use_calendar` is set. Nothing is stored until the plan is accepted.
+#[crate::metrics::command]
+pub async fn plan_day(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    date: String,
+    constraints: Option<PlanConstraints>,
+) -> Result<DayPlan, String> {
+    let mut constraints = constraints.unwrap_or_default();
+    if constraints.use_calendar {
+        constraints.busy.extend(calendar_busy(&app, &date).await?);
+    }
+    let todos: Vec<(String, String)> = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        let workspace_id = dispatch::current_workspace_id(&mut conn).await?;
+        sqlx::query_as(
+            "SELECT id, text FROM todos
+             WHERE workspace_id = ? AND page_date = ? AND status != 'done' AND trim(text) != ''
+             ORDER BY `order`",
+        )
+        .bind(&workspace_id)
+        .bind(&date)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?
+    };
+    let candidates = todos
+        .into_iter()
+        .map(|(id, text)| Candidate {
+            priority: constraints.priorities.get(&id).copied().unwrap_or_else(|| text_priority(&text)),
+            minutes: constraints
+                .estimates
+                .get(&id)
+                .copied()
+                .or_else(|| text_estimate(&text))
+                .unwrap_or(DEFAULT_ESTIMATE_MINUTES)
+                .max(1),
+            id,
+            text,
+        })
+        .collect();
+    // Time already gone today can't be planned
+    let now = Local::now();
+    let earliest = (now.format("%Y-%m-%d").to_string() == date).then(|| now.hour() * 60 + now.minute());
+    plan(&date, candidates, &constraints, earliest)
+}
+
+/// Store the blocks as the day's plan, replacing any accepted before
+#[crate::metrics::command]
+pub async fn accept_plan(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    date: String,
+    blocks: Vec<TimeBlock>,
+) -> Result<Vec<TimeBlock>, String> {
+    for block in &blocks {
+        if parse_time(&block.end)? <= parse_time(&block.start)? {
+            return Err(format!("Block for {} ends before it starts", block.todo_id));
+        }
+    }
+    {
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let workspace_id = dispatch::current_workspace_id(&mut tx).await?;
+        sqlx::query("DELETE FROM time_blocks WHERE workspace_id = ? AND page_date = ?")
+            .bind(&workspace_id)
+            .bind(&date)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let now = Utc::now().timestamp_millis();
+        for block in &blocks {
+            sqlx::query(
+                "INSERT INTO time_blocks (id, workspace_id, page_date, todo_id, start_time, end_time, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(&workspace_id)
+            .bind(&date)
+            .bind(&block.todo_id)
+            .bind(&block.start)
+            .bind(&block.end)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["time_blocks".to_string()]);
+    Ok(blocks)
+}
+
+/// The accepted plan for a day, in time order
+#[crate::metrics::command]
+pub async fn get_day_plan(state: State<'_, DatabaseState>, date: String) -> Result<Vec<TimeBlock>, String> {
+    let pool = state.pool.lock().await;
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let workspace_id = dispatch::current_workspace_id(&mut conn).await?;
+    let rows: Vec<(String, String, String, String)> = sqlx::query_as(
+        "SELECT b.todo_id, COALESCE(t.text, ''), b.start_time, b.end_time FROM time_blocks b
+         LEFT JOIN todos t ON t.id = b.todo_id
+         WHERE b.workspace_id = ? AND b.page_date = ?
+         ORDER BY b.start_time",
+    )
+    .bind(&workspace_id)
+    .bind(&date)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows
+        .into_iter()
+        .map(|(todo_id, text, start, end)| TimeBlock { todo_id, text, start, end })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: &str, text: &str) -> Candidate {
+        Candidate {
+            id: id.to_string(),
+            text: text.to_string(),
+            priority: text_priority(text),
+            minutes: text_estimate(text).unwrap_or(DEFAULT_ESTIMATE_MINUTES),
+        }
+    }
+
+    #[test]
+    fn test_text_markers() {
+        assert_eq!(text_priority("Ship it !!"), 2);
+        assert_eq!(text_priority("Wow! great"), 0);
+        assert_eq!(text_estimate("Write report ~1h30m"), Some(90));
+        assert_eq!(text_estimate("Call ~45m"), Some(45));
+        assert_eq!(text_estimate("Roughly ~2h"), Some(120));
+        assert_eq!(text_estimate("~soon"), None);
+    }
+
+    #[test]
+    fn test_plan_fills_free_time_by_priority() {
+        let constraints = PlanConstraints {
+            day_start: Some("09:00".to_string()),
+            day_end: Some("12:00".to_string()),
+            busy: vec![Busy { start: "10:00".to_string(), end: "11:00".to_string() }],
+            break_minutes: Some(0),
+            ..Default::default()
+        };
+        let candidates = vec![
+            candidate("low", "Tidy desk ~30m"),
+            candidate("big", "Deep work ~1h !!"),
+            candidate("urgent", "Reply to Sam ~15m !!!"),
+            candidate("huge", "Rewrite everything ~2h"),
+        ];
+        let day = plan("2024-03-04", candidates, &constraints, None).unwrap();
+        let blocks: Vec<(&str, &str, &str)> =
+            day.blocks.iter().map(|b| (b.todo_id.as_str(), b.start.as_str(), b.end.as_str())).collect();
+        assert_eq!(
+            blocks,
+            [("urgent", "09:00", "09:15"), ("low", "09:15", "09:45"), ("big", "11:00", "12:00")]
+        );
+        assert_eq!(day.unscheduled, ["huge"]);
+        assert_eq!(day.free_minutes, 15);
+
+        let late = plan("2024-03-04", vec![candidate("a", "a")], &constraints, Some(11 * 60 + 40)).unwrap();
+        assert!(late.blocks.is_empty());
+        assert_eq!(late.unscheduled, ["a"]);
+    }
+}
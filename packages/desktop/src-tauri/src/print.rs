@@ -0,0 +1,486 @@
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::git_export;
+use crate::logger;
+use crate::site_export::{self, load_entries};
+
+const MARGIN: f32 = 56.0;
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 10.0;
+const LIST_INDENT: f32 = 18.0;
+const PRINT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Helvetica advance widths for ' ' through '~', in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556,
+    556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334,
+    260, 334, 584,
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Paper {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl Paper {
+    fn size(self) -> (f32, f32) {
+        match self {
+            Paper::A4 => (595.0, 842.0),
+            Paper::Letter => (612.0, 792.0),
+        }
+    }
+
+    fn media(self) -> &'static str {
+        match self {
+            Paper::A4 => "A4",
+            Paper::Letter => "Letter",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrinterOptions {
+    /// Printer name as the OS knows it; the default printer when unset
+    pub printer: Option<String>,
+    pub copies: Option<u32>,
+    pub paper: Paper,
+    /// Leave out the date header and page numbers
+    pub no_headers: bool,
+    /// Write the PDF here instead of printing it
+    pub save_to: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintReport {
+    pub pages: usize,
+    pub path: String,
+    pub printed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+
+    fn width(self, text: &str, size: f32) -> f32 {
+        let units: f32 = text
+            .chars()
+            .map(|c| match (self, c as u32) {
+                (Font::Mono, _) => 600.0,
+                (font, code @ 32..=126) => {
+                    let width = f32::from(HELVETICA_WIDTHS[(code - 32) as usize]);
+                    if font == Font::Bold { width * 1.06 } else { width }
+                }
+                _ => 556.0,
+            })
+            .sum();
+        units * size / 1000.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Marker {
+    Bullet,
+    Number(u64),
+    Task(bool),
+}
+
+/// A block of the entry as it is laid out: a heading, paragraph, list item or code block
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    font: Font,
+    size: f32,
+    text: String,
+    depth: usize,
+    marker: Option<Marker>,
+}
+
+impl Block {
+    fn new(font: Font, size: f32, depth: usize) -> Self {
+        Block { font, size, text: String::new(), depth, marker: None }
+    }
+}
+
+/// The entry's Markdown as blocks, parsed the same way the HTML renderer parses it
+fn blocks(markdown: &str) -> Vec<Block> {
+    let mut out = Vec::new();
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut current: Option<Block> = None;
+    let flush = |current: &mut Option<Block>, out: &mut Vec<Block>| {
+        if let Some(block) = current.take() {
+            if !block.text.trim().is_empty() || block.marker.is_some() {
+                out.push(Block { text: block.text.trim_end().to_string(), ..block });
+            }
+        }
+    };
+    for event in Parser::new_ext(markdown, Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                flush(&mut current, &mut out);
+                let size = match level {
+                    HeadingLevel::H1 => 18.0,
+                    HeadingLevel::H2 => 14.0,
+                    _ => 12.0,
+                };
+                current = Some(Block::new(Font::Bold, size, 0));
+            }
+            Event::Start(Tag::Paragraph) if current.is_none() => {
+                current = Some(Block::new(Font::Regular, BODY_SIZE, lists.len()));
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                flush(&mut current, &mut out);
+                current = Some(Block::new(Font::Mono, CODE_SIZE, lists.len()));
+            }
+            Event::Start(Tag::List(start)) => {
+                flush(&mut current, &mut out);
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                flush(&mut current, &mut out);
+                lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                flush(&mut current, &mut out);
+                let mut block = Block::new(Font::Regular, BODY_SIZE, lists.len().saturating_sub(1));
+                block.marker = Some(match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        Marker::Number(*number - 1)
+                    }
+                    _ => Marker::Bullet,
+                });
+                current = Some(block);
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(block) = current.as_mut() {
+                    block.marker = Some(Marker::Task(checked));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                current
+                    .get_or_insert_with(|| Block::new(Font::Regular, BODY_SIZE, lists.len()))
+                    .text
+                    .push_str(&text);
+            }
+            Event::SoftBreak => {
+                if let Some(block) = current.as_mut() {
+                    block.text.push(' ');
+                }
+            }
+            Event::HardBreak => {
+                if let Some(block) = current.as_mut() {
+                    block.text.push('\n');
+                }
+            }
+            Event::End(TagEnd::Heading(_) | TagEnd::Paragraph | TagEnd::Item | TagEnd::CodeBlock) => {
+                flush(&mut current, &mut out);
+            }
+            _ => {}
+        }
+    }
+    flush(&mut current, &mut out);
+    out
+}
+
+/// Break text into lines no wider than `width`; code keeps its own lines
+fn wrap(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        if font == Font::Mono {
+            lines.push(paragraph.to_string());
+            continue;
+        }
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if !line.is_empty() && font.width(&candidate, size) > width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// A PDF string literal in WinAnsi, the encoding of the standard fonts; characters it lacks
+/// print as '?'
+fn pdf_string(text: &str) -> String {
+    let mut out = String::from("(");
+    for c in text.chars() {
+        let byte = match c {
+            ' '..='~' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '\t' => b' ',
+            _ => b'?',
+        };
+        match byte {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push(')');
+    out
+}
+
+fn text_op(font: Font, size: f32, x: f32, y: f32, text: &str) -> String {
+    format!("BT /{} {} Tf {:.1} {:.1} Td {} Tj ET\n", font.resource(), size, x, y, pdf_string(text))
+}
+
+/// Page content streams for the blocks, each page headed by `header` and numbered
+fn layout(blocks: &[Block], paper: Paper, header: Option<&str>) -> Vec<String> {
+    let (page_width, page_height) = paper.size();
+    let mut pages = vec![String::new()];
+    let mut y = page_height - MARGIN;
+    for (i, block) in blocks.iter().enumerate() {
+        let line_height = block.size * 1.4;
+        let indent = block.depth as f32 * LIST_INDENT + if block.marker.is_some() { LIST_INDENT } else { 0.0 };
+        let x = MARGIN + indent;
+        let lines = wrap(&block.text, block.font, block.size, page_width - MARGIN - x);
+        let space_before = if i == 0 { 0.0 } else if block.marker.is_some() { 2.0 } else { block.size * 0.6 };
+        y -= space_before;
+        for (n, line) in lines.iter().enumerate() {
+            if y - line_height < MARGIN {
+                pages.push(String::new());
+                y = page_height - MARGIN;
+            }
+            y -= line_height;
+            let page = pages.last_mut().expect("there is always a page");
+            if n == 0 {
+                let marker_x = x - LIST_INDENT;
+                match &block.marker {
+                    Some(Marker::Bullet) => page.push_str(&text_op(Font::Regular, block.size, marker_x + 4.0, y, "•")),
+                    Some(Marker::Number(number)) => {
+                        page.push_str(&text_op(Font::Regular, block.size, marker_x, y, &format!("{}.", number)))
+                    }
+                    Some(Marker::Task(checked)) => {
+                        let side = block.size * 0.75;
+                        page.push_str(&format!("0.7 w {:.1} {:.1} {:.1} {:.1} re S\n", marker_x, y, side, side));
+                        if *checked {
+                            page.push_str(&format!(
+                                "{:.1} {:.1} m {:.1} {:.1} l {:.1} {:.1} l S\n",
+                                marker_x + side * 0.2,
+                                y + side * 0.5,
+                                marker_x + side * 0.45,
+                                y + side * 0.2,
+                                marker_x + side * 0.85,
+                                y + side * 0.85
+                            ));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            page.push_str(&text_op(block.font, block.size, x, y, line));
+        }
+    }
+
+    let total = pages.len();
+    if let Some(header) = header {
+        for (i, page) in pages.iter_mut().enumerate() {
+            page.push_str(&text_op(Font::Regular, 9.0, MARGIN, page_height - MARGIN / 2.0, header));
+            let number = format!("Page {} of {}", i + 1, total);
+            let x = (page_width - Font::Regular.width(&number, 9.0)) / 2.0;
+            page.push_str(&text_op(Font::Regular, 9.0, x, MARGIN / 2.0, &number));
+        }
+    }
+    pages
+}
+
+/// A PDF with one page per content stream, using the standard Helvetica and Courier fonts
+fn pdf(pages: &[String], paper: Paper) -> Vec<u8> {
+    let (width, height) = paper.size();
+    let first_page = 6;
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", first_page + i * 2)).collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()),
+    ];
+    for name in ["Helvetica", "Helvetica-Bold", "Courier"] {
+        objects.push(format!(
+            "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+            name
+        ));
+    }
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> >>",
+            width,
+            height,
+            first_page + i * 2 + 1
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        out.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+/// Hand a PDF to the OS print pipeline: CUPS `lp` on macOS and Linux, the shell's print verb
+/// on Windows
+async fn send_to_printer(path: &Path, options: &PrinterOptions, title: &str) -> Result<(), String> {
+    let copies = options.copies.unwrap_or(1).max(1);
+    let mut command = if cfg!(target_os = "windows") {
+        let (verb, args) = match &options.printer {
+            Some(printer) => ("PrintTo", format!(" -ArgumentList '\"{}\"'", printer.replace('\'', "''"))),
+            None => ("Print", String::new()),
+        };
+        let print = format!(
+            "Start-Process -FilePath '{}' -Verb {}{} -Wait",
+            path.display().to_string().replace('\'', "''"),
+            verb,
+            args
+        );
+        // The shell verb prints a single copy, so it runs once per copy
+        let script = vec![print; copies as usize].join("; ");
+        let mut command = tokio::process::Command::new("powershell");
+        command.arg("-NoProfile").arg("-Command").arg(script);
+        command
+    } else {
+        let mut command = tokio::process::Command::new("lp");
+        if let Some(printer) = &options.printer {
+            command.arg("-d").arg(printer);
+        }
+        command
+            .arg("-n")
+            .arg(copies.to_string())
+            .arg("-o")
+            .arg(format!("media={}", options.paper.media()))
+            .arg("-t")
+            .arg(title)
+            .arg(path);
+        command
+    };
+    command.kill_on_drop(true);
+
+    let output = tokio::time::timeout(PRINT_TIMEOUT, command.output())
+        .await
+        .map_err(|_| "Printing timed out".to_string())?
+        .map_err(|e| format!("Failed to start printing: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Printing failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// Lay out an entry as a PDF with its date as page header and page numbers as footer, then
+/// print it without a dialog. `id` is the entry's date; with `saveTo` set the PDF is only saved.
+#[crate::metrics::command]
+pub async fn print_entry(
+    app: AppHandle,
+    id: String,
+    printer_options: Option<PrinterOptions>,
+) -> Result<PrintReport, String> {
+    let options = printer_options.unwrap_or_default();
+    let (entries, _) = load_entries(&app, None, Some(&id), Some(&id)).await?;
+    let entry = entries
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Nothing to print for {}", id))?;
+    let todos: Vec<_> = entry.todos.iter().collect();
+    let markdown = git_export::render(&entry.date, entry.notes.as_deref(), &todos);
+    let title = site_export::long_date(&entry.date);
+    let pages = layout(&blocks(&markdown), options.paper, (!options.no_headers).then_some(title.as_str()));
+    let bytes = pdf(&pages, options.paper);
+
+    let path = match &options.save_to {
+        Some(path) => PathBuf::from(path),
+        None => std::env::temp_dir().join(format!("journal-todo-print-{}.pdf", entry.date)),
+    };
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let printed = options.save_to.is_none();
+    if printed {
+        send_to_printer(&path, &options, &title).await?;
+        logger::info(&format!("Printed entry {} ({} pages)", entry.date, pages.len()));
+    }
+    Ok(PrintReport {
+        pages: pages.len(),
+        path: path.to_string_lossy().into_owned(),
+        printed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_follow_the_markdown() {
+        let markdown = "# Monday\n\nSome *notes*\nhere\n\n## Todos\n\n- [x] done\n  - [ ] nested\n\n1. first\n2. second\n";
+        let blocks = blocks(markdown);
+        let texts: Vec<&str> = blocks.iter().map(|block| block.text.as_str()).collect();
+        assert_eq!(texts, ["Monday", "Some notes here", "Todos", "done", "nested", "first", "second"]);
+        assert_eq!(blocks[3].marker, Some(Marker::Task(true)));
+        assert_eq!((blocks[4].depth, blocks[4].marker.clone()), (1, Some(Marker::Task(false))));
+        assert_eq!(blocks[6].marker, Some(Marker::Number(2)));
+    }
+
+    #[test]
+    fn test_long_entries_span_pages() {
+        let markdown = "word ".repeat(4000);
+        let pages = layout(&blocks(&markdown), Paper::A4, Some("Monday (4)"));
+        assert!(pages.len() > 1);
+        assert!(pages[0].contains("(Page 1 of "));
+        assert!(pages[0].contains("(Monday \\(4\\))"));
+
+        let pdf = String::from_utf8(pdf(&pages, Paper::A4)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.contains(&format!("/Count {}", pages.len())));
+        assert_eq!(pdf_string("café ☕"), "(caf\\351 ?)");
+    }
+}
@@ -0,0 +1,547 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+
+use crate::db::commands::SqlResponse;
+use crate::db::{changes, DatabaseState};
+use crate::settings;
+
+/// Prefix of values produced by `encrypt_field`
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+/// Random salt the key is derived with
+const SALT_SETTING: &str = "privacy.key_salt";
+/// A known value encrypted with the key, to tell a wrong passphrase from a right one
+const CHECK_SETTING: &str = "privacy.key_check";
+const CHECK_PLAINTEXT: &str = "journal-todo";
+/// OWASP's recommendation for PBKDF2-HMAC-SHA256
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+
+type Key = [u8; 32];
+
+/// Key derived from the app-lock passphrase, while private entries are unlocked
+static UNLOCKED_KEY: Mutex<Option<Key>> = Mutex::new(None);
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).expect("iterations are non-zero"),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "No secure random source available".to_string())?;
+    Ok(bytes)
+}
+
+fn aead_key(key: &Key) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).expect("AES-256 keys are 32 bytes"))
+}
+
+/// Whether a value was produced by `encrypt_field`
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// AES-256-GCM with a random nonce, as `enc:v1:<base64 of nonce + ciphertext + tag>`
+fn encrypt_with(key: &Key, value: &str) -> Result<String, String> {
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let mut data = value.as_bytes().to_vec();
+    aead_key(key)
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend(data);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(payload)))
+}
+
+fn decrypt_with(key: &Key, value: &str) -> Result<String, String> {
+    let payload = value
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .ok_or_else(|| "Value is not encrypted".to_string())?;
+    let mut nonce = STANDARD.decode(payload).map_err(|e| e.to_string())?;
+    if nonce.len() < NONCE_LEN {
+        return Err("Encrypted value is truncated".to_string());
+    }
+    let mut data = nonce.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&nonce).map_err(|_| "Invalid nonce".to_string())?;
+    let plaintext = aead_key(key)
+        .open_in_place(nonce, Aad::empty(), &mut data)
+        .map_err(|_| "Wrong key or corrupted value".to_string())?;
+    String::from_utf8(plaintext.to_vec()).map_err(|e| e.to_string())
+}
+
+fn unlocked_key() -> Result<Key, String> {
+    UNLOCKED_KEY
+        .lock()
+        .ok()
+        .and_then(|key| *key)
+        .ok_or_else(|| "Private entries are locked".to_string())
+}
+
+fn set_unlocked_key(key: Option<Key>) {
+    if let Ok(mut unlocked) = UNLOCKED_KEY.lock() {
+        *unlocked = key;
+    }
+}
+
+/// Encrypt a value with the unlocked app-lock key
+pub fn encrypt_field(value: &str) -> Result<String, String> {
+    encrypt_with(&unlocked_key()?, value)
+}
+
+/// Decrypt a value from `encrypt_field` with the unlocked app-lock key
+pub fn decrypt_field(value: &str) -> Result<String, String> {
+    decrypt_with(&unlocked_key()?, value)
+}
+
+/// The key for `passphrase`, or `None` if no passphrase was ever set
+async fn verified_key(pool: &SqlitePool, passphrase: &str) -> Result<Option<Key>, String> {
+    let salt: Option<String> = settings::get(pool, SALT_SETTING).await?;
+    let check: Option<String> = settings::get(pool, CHECK_SETTING).await?;
+    let (Some(salt), Some(check)) = (salt, check) else {
+        return Ok(None);
+    };
+    verify_passphrase(passphrase, &salt, &check).map(Some)
+}
+
+/// The key for `passphrase` if it decrypts `check`, made with `new_check`
+fn verify_passphrase(passphrase: &str, salt: &str, check: &str) -> Result<Key, String> {
+    let key = derive_key(passphrase, &STANDARD.decode(salt).map_err(|e| e.to_string())?);
+    match decrypt_with(&key, check) {
+        Ok(plaintext) if plaintext == CHECK_PLAINTEXT => Ok(key),
+        _ => Err("Wrong passphrase".to_string()),
+    }
+}
+
+/// A fresh salt (base64), the key it derives from `passphrase`, and its check value
+fn new_check(passphrase: &str) -> Result<(String, Key, String), String> {
+    let salt = random_bytes::<SALT_LEN>()?;
+    let key = derive_key(passphrase, &salt);
+    let check = encrypt_with(&key, CHECK_PLAINTEXT)?;
+    Ok((STANDARD.encode(salt), key, check))
+}
+
+/// Whether the journal entry is flagged private
+async fn is_private(pool: &SqlitePool, workspace_id: &str, date: &str) -> Result<bool, String> {
+    let row: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM private_pages WHERE workspace_id = ? AND page_date = ?")
+        .bind(workspace_id)
+        .bind(date)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(row.is_some())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLockStatus {
+    pub passphrase_set: bool,
+    pub unlocked: bool,
+}
+
+#[crate::metrics::command]
+pub async fn get_app_lock_status(state: State<'_, DatabaseState>) -> Result<AppLockStatus, String> {
+    let pool = state.pool.lock().await;
+    let check: Option<String> = settings::get(&pool, CHECK_SETTING).await?;
+    Ok(AppLockStatus {
+        passphrase_set: check.is_some(),
+        unlocked: unlocked_key().is_ok(),
+    })
+}
+
+/// Set the app-lock passphrase, or change it given the current one.
+/// Private entries are re-encrypted with the new key in one transaction.
+#[crate::metrics::command]
+pub async fn set_app_lock_passphrase(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    current: Option<String>,
+    passphrase: String,
+) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+    {
+        let pool = state.pool.lock().await;
+        let old_key = verified_key(&pool, current.as_deref().unwrap_or_default()).await?;
+
+        let (salt, key, check) = new_check(&passphrase)?;
+
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        if let Some(old_key) = old_key {
+            let pages: Vec<(String, String, String)> = sqlx::query_as(
+                "SELECT p.workspace_id, p.date, p.notes FROM pages p
+                 JOIN private_pages pp ON pp.workspace_id = p.workspace_id AND pp.page_date = p.date
+                 WHERE p.notes IS NOT NULL",
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            for (workspace_id, date, notes) in pages {
+                let notes = encrypt_with(&key, &decrypt_with(&old_key, &notes)?)?;
+                sqlx::query("UPDATE pages SET notes = ? WHERE workspace_id = ? AND date = ?")
+                    .bind(notes)
+                    .bind(&workspace_id)
+                    .bind(&date)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        settings::write_value(&mut *tx, SALT_SETTING, &salt.into()).await?;
+        settings::write_value(&mut *tx, CHECK_SETTING, &check.into()).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        set_unlocked_key(Some(key));
+    }
+    crate::logger::info("App-lock passphrase changed");
+    changes::notify(&app, vec!["pages".to_string()]);
+    Ok(())
+}
+
+#[crate::metrics::command]
+pub async fn unlock_private_entries(state: State<'_, DatabaseState>, passphrase: String) -> Result<(), String> {
+    let pool = state.pool.lock().await;
+    let key = verified_key(&pool, &passphrase)
+        .await?
+        .ok_or_else(|| "No app-lock passphrase is set".to_string())?;
+    set_unlocked_key(Some(key));
+    Ok(())
+}
+
+/// Forget the key until the passphrase is entered again
+#[crate::metrics::command]
+pub fn lock_private_entries() {
+    set_unlocked_key(None);
+}
+
+/// Flag a journal entry private, encrypting its notes, or make it public again
+#[crate::metrics::command]
+pub async fn set_entry_private(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    private: bool,
+) -> Result<(), String> {
+    unlocked_key()?;
+    {
+        let pool = state.pool.lock().await;
+        if is_private(&pool, &workspace_id, &date).await? == private {
+            return Ok(());
+        }
+
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let notes: Option<(Option<String>,)> = sqlx::query_as(crate::compression::PAGE_NOTES_SQL)
+            .bind(&workspace_id)
+            .bind(&date)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let notes = match notes.and_then(|(notes,)| notes) {
+            Some(notes) if private && !is_encrypted(&notes) => Some(encrypt_field(&notes)?),
+            Some(notes) if !private && is_encrypted(&notes) => Some(decrypt_field(&notes)?),
+            notes => notes,
+        };
+        sqlx::query("UPDATE pages SET notes = ? WHERE workspace_id = ? AND date = ?")
+            .bind(notes)
+            .bind(&workspace_id)
+            .bind(&date)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let flag = if private {
+            sqlx::query("INSERT INTO private_pages (workspace_id, page_date, created_at) VALUES (?, ?, ?)")
+                .bind(&workspace_id)
+                .bind(&date)
+                .bind(Utc::now().timestamp_millis())
+        } else {
+            sqlx::query("DELETE FROM private_pages WHERE workspace_id = ? AND page_date = ?")
+                .bind(&workspace_id)
+                .bind(&date)
+        };
+        flag.execute(&mut *tx).await.map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["pages".to_string(), "private_pages".to_string()]);
+    Ok(())
+}
+
+/// Decrypted notes of a private journal entry
+#[crate::metrics::command]
+pub async fn get_private_entry_notes(
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+) -> Result<Option<String>, String> {
+    unlocked_key()?;
+    let pool = state.pool.lock().await;
+    if !is_private(&pool, &workspace_id, &date).await? {
+        return Err("Entry is not private".to_string());
+    }
+    let notes: Option<(Option<String>,)> = sqlx::query_as(crate::compression::PAGE_NOTES_SQL)
+        .bind(&workspace_id)
+        .bind(&date)
+        .fetch_optional(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    notes
+        .and_then(|(notes,)| notes)
+        .map(|notes| decrypt_field(&notes))
+        .transpose()
+}
+
+/// Save the notes of a private journal entry, encrypted
+#[crate::metrics::command]
+pub async fn set_private_entry_notes(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    notes: String,
+) -> Result<(), String> {
+    unlocked_key()?;
+    {
+        let pool = state.pool.lock().await;
+        if !is_private(&pool, &workspace_id, &date).await? {
+            return Err("Entry is not private".to_string());
+        }
+        sqlx::query("UPDATE pages SET notes = ?, updated_at = ? WHERE workspace_id = ? AND date = ?")
+            .bind(encrypt_field(&notes)?)
+            .bind(Utc::now().timestamp())
+            .bind(&workspace_id)
+            .bind(&date)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["pages".to_string()]);
+    Ok(())
+}
+
+/// Keys of entries opened with `unlock_entry` this session, by (workspace id, date)
+static UNLOCKED_ENTRIES: Mutex<Vec<(String, String, Key)>> = Mutex::new(Vec::new());
+
+fn unlocked_entry_key(workspace_id: &str, date: &str) -> Option<Key> {
+    let unlocked = UNLOCKED_ENTRIES.lock().ok()?;
+    unlocked
+        .iter()
+        .find(|(w, d, _)| w == workspace_id && d == date)
+        .map(|(_, _, key)| *key)
+}
+
+fn forget_entry_key(workspace_id: &str, date: &str) {
+    if let Ok(mut unlocked) = UNLOCKED_ENTRIES.lock() {
+        unlocked.retain(|(w, d, _)| !(w == workspace_id && d == date));
+    }
+}
+
+/// Salt and check value of an entry's own passphrase, if it has one
+async fn entry_lock(pool: &SqlitePool, workspace_id: &str, date: &str) -> Result<Option<(String, String)>, String> {
+    sqlx::query_as("SELECT salt, key_check FROM locked_pages WHERE workspace_id = ? AND page_date = ?")
+        .bind(workspace_id)
+        .bind(date)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Decrypt the values of entries opened with `unlock_entry` in a proxied result.
+/// Locked entries stay encrypted, so reads through the proxy never see them.
+pub fn reveal_unlocked_entries(response: &mut SqlResponse) {
+    let keys: Vec<Key> = match UNLOCKED_ENTRIES.lock() {
+        Ok(unlocked) if !unlocked.is_empty() => unlocked.iter().map(|(_, _, key)| *key).collect(),
+        _ => return,
+    };
+    for value in response.rows.iter_mut().flat_map(|row| row.rows.iter_mut()) {
+        let Some(text) = value.as_str().filter(|text| is_encrypted(text)) else {
+            continue;
+        };
+        if let Some(plaintext) = keys.iter().find_map(|key| decrypt_with(key, text).ok()) {
+            *value = serde_json::Value::String(plaintext);
+        }
+    }
+}
+
+/// Protect a journal entry with its own passphrase, encrypting its notes.
+/// For an entry that already has one, this locks it again after `unlock_entry`.
+#[crate::metrics::command]
+pub async fn lock_entry(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    passphrase: String,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        if let Some((salt, check)) = entry_lock(&pool, &workspace_id, &date).await? {
+            verify_passphrase(&passphrase, &salt, &check)?;
+            forget_entry_key(&workspace_id, &date);
+            return Ok(());
+        }
+        if passphrase.is_empty() {
+            return Err("Passphrase must not be empty".to_string());
+        }
+
+        let (salt, key, check) = new_check(&passphrase)?;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let notes: Option<(Option<String>,)> = sqlx::query_as(crate::compression::PAGE_NOTES_SQL)
+            .bind(&workspace_id)
+            .bind(&date)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some((notes,)) = notes else {
+            return Err(format!("No entry for {}", date));
+        };
+        sqlx::query("INSERT INTO locked_pages (workspace_id, page_date, salt, key_check, created_at) VALUES (?, ?, ?, ?, ?)")
+            .bind(&workspace_id)
+            .bind(&date)
+            .bind(salt)
+            .bind(check)
+            .bind(Utc::now().timestamp_millis())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("UPDATE pages SET notes = ? WHERE workspace_id = ? AND date = ?")
+            .bind(encrypt_with(&key, notes.as_deref().unwrap_or_default())?)
+            .bind(&workspace_id)
+            .bind(&date)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["pages".to_string(), "locked_pages".to_string()]);
+    Ok(())
+}
+
+/// Open a locked entry until `lock_entry` or restart; its notes stay encrypted
+/// on disk and are decrypted in proxy results only
+#[crate::metrics::command]
+pub async fn unlock_entry(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    passphrase: String,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        let (salt, check) = entry_lock(&pool, &workspace_id, &date)
+            .await?
+            .ok_or_else(|| "Entry is not locked".to_string())?;
+        let key = verify_passphrase(&passphrase, &salt, &check)?;
+        if unlocked_entry_key(&workspace_id, &date).is_none() {
+            if let Ok(mut unlocked) = UNLOCKED_ENTRIES.lock() {
+                unlocked.push((workspace_id, date, key));
+            }
+        }
+    }
+    // Cached results hold the encrypted notes
+    changes::notify(&app, vec!["pages".to_string()]);
+    Ok(())
+}
+
+/// Take an entry's own passphrase off, storing its notes decrypted again
+#[crate::metrics::command]
+pub async fn remove_entry_lock(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    workspace_id: String,
+    date: String,
+    passphrase: String,
+) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        let (salt, check) = entry_lock(&pool, &workspace_id, &date)
+            .await?
+            .ok_or_else(|| "Entry is not locked".to_string())?;
+        let key = verify_passphrase(&passphrase, &salt, &check)?;
+
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let (notes,): (Option<String>,) = sqlx::query_as(crate::compression::PAGE_NOTES_SQL)
+            .bind(&workspace_id)
+            .bind(&date)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let notes = notes.map(|notes| decrypt_with(&key, &notes)).transpose()?;
+        // The lock goes first; the trigger on `pages` rejects plaintext notes for locked entries
+        sqlx::query("DELETE FROM locked_pages WHERE workspace_id = ? AND page_date = ?")
+            .bind(&workspace_id)
+            .bind(&date)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("UPDATE pages SET notes = ? WHERE workspace_id = ? AND date = ?")
+            .bind(notes.filter(|notes| !notes.is_empty()))
+            .bind(&workspace_id)
+            .bind(&date)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        forget_entry_key(&workspace_id, &date);
+    }
+    changes::notify(&app, vec!["pages".to_string(), "locked_pages".to_string()]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::commands::SqlRow;
+
+    #[test]
+    fn test_field_round_trip() {
+        let key = derive_key("correct horse", b"0123456789abcdef");
+        let encrypted = encrypt_with(&key, "dear diary").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("diary"));
+        assert_ne!(encrypted, encrypt_with(&key, "dear diary").unwrap(), "nonces are random");
+        assert_eq!(decrypt_with(&key, &encrypted).unwrap(), "dear diary");
+
+        let wrong = derive_key("battery staple", b"0123456789abcdef");
+        assert!(decrypt_with(&wrong, &encrypted).is_err());
+    }
+
+    #[test]
+    fn test_unlocked_entries_are_revealed() {
+        let key = derive_key("entry pass", b"fedcba9876543210");
+        let locked = encrypt_with(&derive_key("other", b"fedcba9876543210"), "still secret").unwrap();
+        let mut response = SqlResponse {
+            rows: vec![SqlRow {
+                columns: vec!["notes".to_string(), "other".to_string()],
+                rows: vec![encrypt_with(&key, "opened").unwrap().into(), locked.clone().into()],
+            }],
+            ..Default::default()
+        };
+
+        UNLOCKED_ENTRIES.lock().unwrap().push(("ws".to_string(), "2024-05-01".to_string(), key));
+        reveal_unlocked_entries(&mut response);
+        forget_entry_key("ws", "2024-05-01");
+
+        assert_eq!(response.rows[0].rows[0], "opened");
+        assert_eq!(response.rows[0].rows[1], serde_json::Value::String(locked));
+    }
+}
@@ -0,0 +1,311 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::DatabaseState;
+use crate::logger;
+use crate::workspaces::{self, WorkspaceManager};
+
+const REGISTRY_FILE_NAME: &str = "profiles.json";
+const DEFAULT_PROFILE_ID: &str = "default";
+const PROFILES_DIR_NAME: &str = "profiles";
+const DATABASE_FILE_NAME: &str = "journal.db";
+const LOG_DIR_NAME: &str = "logs";
+/// Command line flag that opens a profile by name or id, e.g. `--profile=Alice`
+const PROFILE_ARG: &str = "--profile=";
+
+/// One person's journal: a directory holding their workspaces, databases, attachments and logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub id: String,
+    pub name: String,
+    pub dir: PathBuf,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileRegistry {
+    active_id: String,
+    /// Ask which profile to open on startup instead of opening the last one
+    #[serde(default)]
+    choose_on_startup: bool,
+    profiles: Vec<ProfileInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileList {
+    pub active_id: String,
+    pub choose_on_startup: bool,
+    pub profiles: Vec<ProfileInfo>,
+}
+
+/// Tracks profiles, persisted as `profiles.json` in the app data directory. The default
+/// profile is the app data directory itself, so data from before profiles stays where it is.
+pub struct ProfileManager {
+    registry_path: PathBuf,
+    app_data_dir: PathBuf,
+    /// Database of the default profile's default workspace, which differs in debug builds
+    default_db_path: PathBuf,
+    registry: Mutex<ProfileRegistry>,
+}
+
+impl ProfileManager {
+    pub fn load(app_data_dir: &Path, default_db_path: &Path) -> Self {
+        let registry_path = app_data_dir.join(REGISTRY_FILE_NAME);
+        let mut writable = true;
+        let mut registry = match std::fs::read_to_string(&registry_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                logger::error(&format!("Failed to parse profile registry: {}", e));
+                writable = workspaces::set_aside_corrupt(&registry_path);
+                ProfileRegistry::default()
+            }),
+            Err(_) => ProfileRegistry::default(),
+        };
+
+        if !registry.profiles.iter().any(|p| p.id == DEFAULT_PROFILE_ID) {
+            registry.profiles.insert(
+                0,
+                ProfileInfo {
+                    id: DEFAULT_PROFILE_ID.to_string(),
+                    name: "Default".to_string(),
+                    dir: app_data_dir.to_path_buf(),
+                    created_at: Utc::now().timestamp_millis(),
+                },
+            );
+        }
+        if !registry.profiles.iter().any(|p| p.id == registry.active_id) {
+            registry.active_id = DEFAULT_PROFILE_ID.to_string();
+        }
+
+        let manager = Self {
+            registry_path,
+            app_data_dir: app_data_dir.to_path_buf(),
+            default_db_path: default_db_path.to_path_buf(),
+            registry: Mutex::new(registry),
+        };
+        if !writable {
+            return manager;
+        }
+        if let Err(e) = manager.save() {
+            logger::error(&format!("Failed to save profile registry: {}", e));
+        }
+        manager
+    }
+
+    pub fn active(&self) -> ProfileInfo {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .profiles
+            .iter()
+            .find(|p| p.id == registry.active_id)
+            .cloned()
+            .expect("active profile is always registered")
+    }
+
+    fn get(&self, id: &str) -> Result<ProfileInfo, String> {
+        self.registry
+            .lock()
+            .unwrap()
+            .profiles
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Profile not found: {}", id))
+    }
+
+    /// The profile with this id, or else this name ignoring case
+    fn find(&self, id_or_name: &str) -> Option<ProfileInfo> {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .profiles
+            .iter()
+            .find(|p| p.id == id_or_name)
+            .or_else(|| registry.profiles.iter().find(|p| p.name.eq_ignore_ascii_case(id_or_name)))
+            .cloned()
+    }
+
+    pub fn list(&self) -> ProfileList {
+        let registry = self.registry.lock().unwrap();
+        ProfileList {
+            active_id: registry.active_id.clone(),
+            choose_on_startup: registry.choose_on_startup,
+            profiles: registry.profiles.clone(),
+        }
+    }
+
+    fn add(&self, name: &str) -> Result<ProfileInfo, String> {
+        let profile = {
+            let mut registry = self.registry.lock().unwrap();
+            if registry.profiles.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+                return Err(format!("A profile is already called {}", name));
+            }
+            let id = uuid::Uuid::new_v4().to_string();
+            let profile = ProfileInfo {
+                dir: self.app_data_dir.join(PROFILES_DIR_NAME).join(&id),
+                id,
+                name: name.to_string(),
+                created_at: Utc::now().timestamp_millis(),
+            };
+            registry.profiles.push(profile.clone());
+            profile
+        };
+        std::fs::create_dir_all(&profile.dir).map_err(|e| e.to_string())?;
+        self.save()?;
+        Ok(profile)
+    }
+
+    pub fn set_active(&self, id: &str) -> Result<(), String> {
+        self.registry.lock().unwrap().active_id = id.to_string();
+        self.save()
+    }
+
+    fn set_choose_on_startup(&self, enabled: bool) -> Result<(), String> {
+        self.registry.lock().unwrap().choose_on_startup = enabled;
+        self.save()
+    }
+
+    /// Database a profile's first workspace starts with
+    pub fn default_db_path(&self, profile: &ProfileInfo) -> PathBuf {
+        if profile.id == DEFAULT_PROFILE_ID {
+            self.default_db_path.clone()
+        } else {
+            profile.dir.join(DATABASE_FILE_NAME)
+        }
+    }
+
+    /// Where a profile logs; `None` for the platform log directory the default profile uses
+    pub fn log_dir(&self, profile: &ProfileInfo) -> Option<PathBuf> {
+        (profile.id != DEFAULT_PROFILE_ID).then(|| profile.dir.join(LOG_DIR_NAME))
+    }
+
+    /// Write the registry atomically
+    fn save(&self) -> Result<(), String> {
+        let content = {
+            let registry = self.registry.lock().unwrap();
+            serde_json::to_string_pretty(&*registry).map_err(|e| e.to_string())?
+        };
+
+        if let Some(parent) = self.registry_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let tmp_path = self.registry_path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, &self.registry_path).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// The profile asked for with `--profile=<name or id>`, if any
+pub fn requested_profile(args: impl IntoIterator<Item = String>) -> Option<String> {
+    args.into_iter()
+        .find_map(|arg| arg.strip_prefix(PROFILE_ARG).map(|value| value.trim().to_string()))
+        .filter(|value| !value.is_empty())
+}
+
+/// Make the profile named on the command line active, for this launch and the next ones
+pub fn select_on_startup(manager: &ProfileManager) {
+    let Some(requested) = requested_profile(std::env::args()) else {
+        return;
+    };
+    match manager.find(&requested) {
+        Some(profile) => {
+            if let Err(e) = manager.set_active(&profile.id) {
+                logger::error(&format!("Failed to select profile {}: {}", profile.name, e));
+            }
+        }
+        None => logger::error(&format!("No profile called {}, opening the last one", requested)),
+    }
+}
+
+#[crate::metrics::command]
+pub fn list_profiles(manager: State<'_, ProfileManager>) -> ProfileList {
+    manager.list()
+}
+
+/// Add a profile with its own empty directory; it's opened with `switch_profile`
+#[crate::metrics::command]
+pub fn create_profile(manager: State<'_, ProfileManager>, name: String) -> Result<ProfileInfo, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    let profile = manager.add(&name)?;
+    logger::info(&format!("Created profile {} at {}", profile.name, profile.dir.display()));
+    Ok(profile)
+}
+
+/// Close everything of the current profile and open the given one's active workspace, logging
+/// to its own directory from then on
+#[crate::metrics::command]
+pub async fn switch_profile(
+    app: AppHandle,
+    manager: State<'_, ProfileManager>,
+    workspaces: State<'_, WorkspaceManager>,
+    db_state: State<'_, DatabaseState>,
+    id: String,
+) -> Result<ProfileInfo, String> {
+    let profile = manager.get(&id)?;
+    let previous = manager.active();
+    logger::info(&format!("Switching to profile {}", profile.name));
+
+    workspaces.reload(&profile.dir, &manager.default_db_path(&profile));
+    let workspace = workspaces.active();
+    if let Err(e) = db_state.reopen(workspace.db_path.clone()).await {
+        logger::error(&format!("Failed to switch profile: {}", e));
+        workspaces.reload(&previous.dir, &manager.default_db_path(&previous));
+        return Err(e);
+    }
+    manager.set_active(&profile.id)?;
+    logger::switch_dir(manager.log_dir(&profile).as_deref());
+    logger::info(&format!("Opened profile {} with workspace {}", profile.name, workspace.name));
+
+    app.emit("db://reload", ()).map_err(|e| e.to_string())?;
+    Ok(profile)
+}
+
+/// Whether startup asks which profile to open
+#[crate::metrics::command]
+pub fn set_choose_profile_on_startup(manager: State<'_, ProfileManager>, enabled: bool) -> Result<(), String> {
+    manager.set_choose_on_startup(enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_are_kept_apart() {
+        let dir = std::env::temp_dir().join(format!("journal-todo-profiles-{}", uuid::Uuid::new_v4()));
+        let default_db = dir.join("journal-dev.db");
+        let manager = ProfileManager::load(&dir, &default_db);
+        let alice = manager.add("Alice").unwrap();
+        assert!(manager.add("alice").is_err());
+        assert!(alice.dir.starts_with(dir.join(PROFILES_DIR_NAME)));
+        assert_eq!(manager.default_db_path(&alice), alice.dir.join(DATABASE_FILE_NAME));
+        assert_eq!(manager.log_dir(&alice), Some(alice.dir.join(LOG_DIR_NAME)));
+
+        let default = manager.active();
+        assert_eq!(default.dir, dir);
+        assert_eq!(manager.default_db_path(&default), default_db);
+        assert_eq!(manager.log_dir(&default), None);
+
+        manager.set_active(&alice.id).unwrap();
+        let reloaded = ProfileManager::load(&dir, &default_db);
+        assert_eq!(reloaded.active().name, "Alice");
+        assert_eq!(reloaded.find("ALICE").map(|p| p.id), Some(alice.id));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_requested_profile() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(requested_profile(args(&["app", "--profile=Bob"])), Some("Bob".to_string()));
+        assert_eq!(requested_profile(args(&["app", "--profile="])), None);
+        assert_eq!(requested_profile(args(&["app"])), None);
+    }
+}
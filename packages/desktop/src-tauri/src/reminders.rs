@@ -0,0 +1,336 @@
+use chrono::{DateTime, Days, Local, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::{focus, logger, markdown, settings};
+
+/// How often the reminder loop checks for due todos
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Setting: notify this many minutes before a todo is due
+const LEAD_MINUTES_SETTING: &str = "reminders.lead_minutes";
+const DEFAULT_LEAD_MINUTES: i64 = 15;
+
+const SNOOZE_MINUTES: i64 = 10;
+/// Todos listed in the summary after a quiet period
+const SUMMARY_LINES: usize = 5;
+/// "HH:MM" local time "Snooze until tomorrow" reminders again
+const TOMORROW_TIME_SETTING: &str = "reminders.tomorrow_time";
+const DEFAULT_TOMORROW_TIME: &str = "09:00";
+
+/// Emitted after a notification action changed a todo or reminder
+pub const REMINDER_ACTION_EVENT: &str = "reminders://action";
+
+const ACTION_COMPLETE: &str = "complete";
+const ACTION_SNOOZE: &str = "snooze";
+const ACTION_SNOOZE_HOUR: &str = "snooze-1h";
+const ACTION_SNOOZE_TOMORROW: &str = "snooze-tomorrow";
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct DueTodo {
+    todo_id: String,
+    text: String,
+    due_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReminderAction {
+    todo_id: String,
+    action: String,
+}
+
+/// Start the background task that notifies about due and overdue todos
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        // Set while reminders are being held back, for the summary once they're let through
+        let mut held: Option<&'static str> = None;
+        loop {
+            interval.tick().await;
+            if let Err(e) = check_due_todos(&app, &mut held).await {
+                logger::error(&format!("Reminder check failed: {}", e));
+            }
+        }
+    })
+}
+
+/// Notify about due todos. While a focus session or do-not-disturb is on they
+/// stay unnotified, and whatever piled up is delivered as one summary after.
+async fn check_due_todos(app: &AppHandle, held: &mut Option<&'static str>) -> Result<(), String> {
+    if let Some(reason) = focus::quiet_reason(app).await? {
+        if held.is_none() {
+            logger::info(&format!("Holding reminders {}", reason));
+        }
+        *held = Some(reason);
+        return Ok(());
+    }
+    let held_during = held.take();
+
+    let now = Utc::now().timestamp_millis();
+    let due = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let lead_minutes = settings::get_or(&pool, LEAD_MINUTES_SETTING, DEFAULT_LEAD_MINUTES).await;
+        let due = find_due_todos(&pool, now + lead_minutes * 60_000).await?;
+
+        for todo in &due {
+            sqlx::query("UPDATE todo_reminders SET notified_at = ?, updated_at = ? WHERE todo_id = ?")
+                .bind(now)
+                .bind(now)
+                .bind(&todo.todo_id)
+                .execute(&*pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        due
+    };
+
+    match held_during {
+        Some(reason) if due.len() > 1 => notify_summary(app, reason, &due),
+        _ => {
+            for todo in due {
+                notify(app, todo, now);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One notification listing the reminders held back during a quiet period
+fn notify_summary(app: &AppHandle, reason: &str, due: &[DueTodo]) {
+    let mut body: Vec<String> = due
+        .iter()
+        .take(SUMMARY_LINES)
+        .map(|todo| markdown::to_plain_text(&todo.text))
+        .collect();
+    let more = due.len().saturating_sub(SUMMARY_LINES);
+    if more > 0 {
+        body.push(format!("and {} more", more));
+    }
+    let shown = notify_rust::Notification::new()
+        .appname(&app.package_info().name)
+        .summary(&format!("{} reminders {}", due.len(), reason))
+        .body(&body.join("\n"))
+        .show();
+    if let Err(e) = shown {
+        logger::error(&format!("Failed to show notification: {}", e));
+    }
+}
+
+/// Incomplete todos due before `until` that haven't been notified yet
+async fn find_due_todos(pool: &SqlitePool, until: i64) -> Result<Vec<DueTodo>, String> {
+    sqlx::query_as(
+        "SELECT r.todo_id, t.text, COALESCE(r.snoozed_until, r.due_at) AS due_at
+         FROM todo_reminders r
+         JOIN todos t ON t.id = r.todo_id
+         WHERE t.status != 'done'
+           AND r.notified_at IS NULL
+           AND COALESCE(r.snoozed_until, r.due_at) <= ?
+         ORDER BY due_at",
+    )
+    .bind(until)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Show a native notification and handle its action buttons where the platform supports them
+fn notify(app: &AppHandle, todo: DueTodo, now: i64) {
+    let summary = if todo.due_at < now {
+        "Todo overdue"
+    } else {
+        "Todo due soon"
+    };
+
+    let mut notification = notify_rust::Notification::new();
+    notification
+        .appname(&app.package_info().name)
+        .summary(summary)
+        .body(&markdown::to_plain_text(&todo.text));
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        notification
+            .action(ACTION_COMPLETE, "Complete")
+            .action(ACTION_SNOOZE, &format!("Snooze {} min", SNOOZE_MINUTES))
+            .action(ACTION_SNOOZE_HOUR, "Snooze 1 hour")
+            .action(ACTION_SNOOZE_TOMORROW, "Tomorrow");
+
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let shown = tauri::async_runtime::spawn_blocking(move || {
+                let handle = notification.show().map_err(|e| e.to_string())?;
+                let mut chosen = None;
+                handle.wait_for_action(|action| chosen = Some(action.to_string()));
+                Ok::<_, String>(chosen)
+            })
+            .await;
+
+            match shown {
+                // Dismissing the notification reports "__closed"
+                Ok(Ok(Some(action))) if action != "__closed" => {
+                    if let Err(e) = handle_action(&app, &todo.todo_id, &action).await {
+                        logger::error(&format!("Reminder action failed: {}", e));
+                    }
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => logger::error(&format!("Failed to show notification: {}", e)),
+                Err(e) => logger::error(&format!("Notification task failed: {}", e)),
+            }
+        });
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    {
+        if let Err(e) = notification.show().map(|_| ()) {
+            logger::error(&format!("Failed to show notification: {}", e));
+        }
+    }
+}
+
+/// When a snooze action brings the reminder back; `None` for other actions
+fn snooze_until(action: &str, now: DateTime<Local>, tomorrow_at: NaiveTime) -> Option<i64> {
+    let until = match action {
+        ACTION_SNOOZE => now + chrono::Duration::minutes(SNOOZE_MINUTES),
+        ACTION_SNOOZE_HOUR => now + chrono::Duration::hours(1),
+        ACTION_SNOOZE_TOMORROW => {
+            let tomorrow = now.date_naive().checked_add_days(Days::new(1))?.and_time(tomorrow_at);
+            // The time can fall into a DST gap, the hour after it can't
+            Local
+                .from_local_datetime(&tomorrow)
+                .earliest()
+                .or_else(|| Local.from_local_datetime(&(tomorrow + chrono::Duration::hours(1))).earliest())?
+        }
+        _ => return None,
+    };
+    Some(until.timestamp_millis())
+}
+
+/// Apply a notification action to the database
+async fn handle_action(app: &AppHandle, todo_id: &str, action: &str) -> Result<(), String> {
+    let now = Utc::now().timestamp_millis();
+    {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        if action == ACTION_COMPLETE {
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            sqlx::query("UPDATE todos SET status = 'done', updated_at = ? WHERE id = ?")
+                .bind(Utc::now().timestamp())
+                .bind(todo_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            sqlx::query("UPDATE todo_reminders SET snoozed_until = NULL, notified_at = ?, updated_at = ? WHERE todo_id = ?")
+                .bind(now)
+                .bind(now)
+                .bind(todo_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            tx.commit().await.map_err(|e| e.to_string())?;
+            changes::notify(app, vec!["todos".to_string(), "todo_reminders".to_string()]);
+        } else {
+            let tomorrow_time: String =
+                settings::get_or(&pool, TOMORROW_TIME_SETTING, DEFAULT_TOMORROW_TIME.to_string()).await;
+            let tomorrow_at = NaiveTime::parse_from_str(&tomorrow_time, "%H:%M")
+                .map_err(|_| format!("Invalid {}: {}", TOMORROW_TIME_SETTING, tomorrow_time))?;
+            let until = snooze_until(action, Local::now(), tomorrow_at)
+                .ok_or_else(|| format!("Unknown reminder action: {}", action))?;
+            sqlx::query(
+                "UPDATE todo_reminders SET snoozed_until = ?, notified_at = NULL, updated_at = ? WHERE todo_id = ?",
+            )
+            .bind(until)
+            .bind(now)
+            .bind(todo_id)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            changes::notify(app, vec!["todo_reminders".to_string()]);
+        }
+    }
+
+    app.emit(
+        REMINDER_ACTION_EVENT,
+        ReminderAction {
+            todo_id: todo_id.to_string(),
+            action: action.to_string(),
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Apply a reminder action chosen outside the native notification (e.g. an
+/// in-app toast on platforms without notification buttons): "complete",
+/// "snooze" (10 min), "snooze-1h" or "snooze-tomorrow"
+#[crate::metrics::command]
+pub async fn handle_reminder_action(app: AppHandle, todo_id: String, action: String) -> Result<(), String> {
+    handle_action(&app, &todo_id, &action).await
+}
+
+/// Set or replace the due time (unix ms) of a todo
+#[crate::metrics::command]
+pub async fn set_todo_reminder(
+    state: State<'_, DatabaseState>,
+    todo_id: String,
+    due_at: i64,
+) -> Result<(), String> {
+    let now = Utc::now().timestamp_millis();
+    let pool = state.pool.lock().await;
+    sqlx::query(
+        "INSERT INTO todo_reminders (todo_id, due_at, snoozed_until, notified_at, created_at, updated_at)
+         VALUES (?, ?, NULL, NULL, ?, ?)
+         ON CONFLICT(todo_id) DO UPDATE SET
+            due_at = excluded.due_at,
+            snoozed_until = NULL,
+            notified_at = NULL,
+            updated_at = excluded.updated_at",
+    )
+    .bind(&todo_id)
+    .bind(due_at)
+    .bind(now)
+    .bind(now)
+    .execute(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[crate::metrics::command]
+pub async fn clear_todo_reminder(
+    state: State<'_, DatabaseState>,
+    todo_id: String,
+) -> Result<(), String> {
+    let pool = state.pool.lock().await;
+    sqlx::query("DELETE FROM todo_reminders WHERE todo_id = ?")
+        .bind(&todo_id)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snooze_until() {
+        let now = Local.with_ymd_and_hms(2024, 3, 4, 22, 15, 0).unwrap();
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let minute = 60_000;
+        let at = now.timestamp_millis();
+
+        assert_eq!(snooze_until(ACTION_SNOOZE, now, nine), Some(at + 10 * minute));
+        assert_eq!(snooze_until(ACTION_SNOOZE_HOUR, now, nine), Some(at + 60 * minute));
+        assert_eq!(
+            snooze_until(ACTION_SNOOZE_TOMORROW, now, nine),
+            Some(Local.with_ymd_and_hms(2024, 3, 5, 9, 0, 0).unwrap().timestamp_millis())
+        );
+        assert_eq!(snooze_until(ACTION_COMPLETE, now, nine), None);
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{Row, SqliteConnection};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::fractional_index;
+use crate::logger;
+
+/// Keys longer than this get the whole list renumbered. Dropping items into the same gap over
+/// and over keeps growing the keys there.
+const MAX_KEY_LEN: usize = 16;
+
+/// A table whose rows are ordered by a fractional index key within a list
+struct Orderable {
+    table: &'static str,
+    order_column: &'static str,
+    /// Columns that together pick out the list a row belongs to
+    list_columns: &'static [&'static str],
+}
+
+const ORDERABLE: &[Orderable] = &[Orderable {
+    table: "todos",
+    order_column: "`order`",
+    list_columns: &["workspace_id", "page_date"],
+}];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderResult {
+    pub order: String,
+    /// True when the list was renumbered, so every item's key may have changed
+    pub rebalanced: bool,
+}
+
+/// The list's ids in order with `id` moved right after `before_id`, or right before `after_id`,
+/// or to the end when neither is given
+fn place(mut ids: Vec<String>, id: &str, before_id: Option<&str>, after_id: Option<&str>) -> Vec<String> {
+    ids.retain(|other| other != id);
+    let index = match (before_id, after_id) {
+        (Some(before_id), _) => ids.iter().position(|other| other == before_id).map(|i| i + 1),
+        (None, Some(after_id)) => ids.iter().position(|other| other == after_id),
+        (None, None) => None,
+    };
+    ids.insert(index.unwrap_or(ids.len()), id.to_string());
+    ids
+}
+
+/// Fresh, short keys for a list of `count` items, in order
+fn sequential_keys(count: usize) -> Result<Vec<String>, String> {
+    let mut keys: Vec<String> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let key = fractional_index::key_between(keys.last().map(String::as_str), None)?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+async fn reorder(
+    conn: &mut SqliteConnection,
+    orderable: &Orderable,
+    id: &str,
+    before_id: Option<&str>,
+    after_id: Option<&str>,
+) -> Result<ReorderResult, String> {
+    let Orderable {
+        table,
+        order_column,
+        list_columns,
+    } = orderable;
+    let list_row = sqlx::query(&format!("SELECT {} FROM {} WHERE id = ?", list_columns.join(", "), table))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Not found in {}: {}", table, id))?;
+    let list: Vec<String> = (0..list_columns.len())
+        .map(|i| list_row.try_get(i).map_err(|e| e.to_string()))
+        .collect::<Result<_, _>>()?;
+
+    let list_filter = list_columns
+        .iter()
+        .map(|column| format!("{} = ?", column))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let sql = format!(
+        "SELECT id, {} FROM {} WHERE {} ORDER BY {}, id",
+        order_column, table, list_filter, order_column
+    );
+    let mut query = sqlx::query_as(&sql);
+    for value in &list {
+        query = query.bind(value);
+    }
+    let items: Vec<(String, String)> = query.fetch_all(&mut *conn).await.map_err(|e| e.to_string())?;
+
+    let order_of = |neighbour: Option<&str>| -> Result<Option<String>, String> {
+        let Some(neighbour) = neighbour else {
+            return Ok(None);
+        };
+        if neighbour == id {
+            return Err("An item can't be placed next to itself".to_string());
+        }
+        items
+            .iter()
+            .find(|(other, _)| other == neighbour)
+            .map(|(_, order)| Some(order.clone()))
+            .ok_or_else(|| format!("{} isn't in the same list as {}", neighbour, id))
+    };
+    let mut before = order_of(before_id)?;
+    let after = order_of(after_id)?;
+    if before.is_none() && after.is_none() {
+        before = items.iter().rev().find(|(other, _)| other != id).map(|(_, order)| order.clone());
+    }
+
+    // Orderable tables are all Drizzle's, which keep seconds
+    let now = Utc::now().timestamp();
+    let update = format!("UPDATE {} SET {} = ?, updated_at = ? WHERE id = ?", table, order_column);
+    // Bad neighbour keys (equal, or out of order) are fixed by renumbering too
+    match fractional_index::key_between(before.as_deref(), after.as_deref()) {
+        Ok(order) if order.len() <= MAX_KEY_LEN => {
+            sqlx::query(&update)
+                .bind(&order)
+                .bind(now)
+                .bind(id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            return Ok(ReorderResult {
+                order,
+                rebalanced: false,
+            });
+        }
+        _ => {}
+    }
+
+    let ids = place(items.iter().map(|(other, _)| other.clone()).collect(), id, before_id, after_id);
+    let keys = sequential_keys(ids.len())?;
+    for (other, key) in ids.iter().zip(&keys) {
+        let unchanged = items.iter().any(|(item, order)| item == other && order == key);
+        if other != id && unchanged {
+            continue;
+        }
+        sqlx::query(&update)
+            .bind(key)
+            .bind(now)
+            .bind(other)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let order = ids
+        .iter()
+        .zip(keys)
+        .find(|(other, _)| *other == id)
+        .map(|(_, key)| key)
+        .unwrap_or_default();
+    Ok(ReorderResult {
+        order,
+        rebalanced: true,
+    })
+}
+
+/// Move an item between two neighbours in its list: just after `before_id` and just before
+/// `after_id`, either of which can be left out at the ends of the list. Only the moved item's key
+/// changes, unless the list has to be renumbered.
+#[crate::metrics::command]
+pub async fn reorder_item(
+    app: AppHandle,
+    table: String,
+    id: String,
+    before_id: Option<String>,
+    after_id: Option<String>,
+) -> Result<ReorderResult, String> {
+    let orderable = ORDERABLE
+        .iter()
+        .find(|orderable| orderable.table == table)
+        .ok_or_else(|| format!("Table can't be reordered: {}", table))?;
+    let result = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let result = reorder(&mut tx, orderable, &id, before_id.as_deref(), after_id.as_deref()).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        result
+    };
+    if result.rebalanced {
+        logger::info(&format!("Renumbered the {} list of {} to keep order keys short", table, id));
+    }
+    changes::notify(&app, vec![table]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Connection;
+
+    #[test]
+    fn test_place() {
+        let ids = || vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(place(ids(), "a", Some("c"), None), vec!["b", "c", "a"]);
+        assert_eq!(place(ids(), "c", None, Some("a")), vec!["c", "a", "b"]);
+        assert_eq!(place(ids(), "a", Some("b"), Some("c")), vec!["b", "a", "c"]);
+        assert_eq!(place(ids(), "b", None, None), vec!["a", "c", "b"]);
+        assert_eq!(sequential_keys(3).unwrap(), vec!["a0", "a1", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_reorder_and_rebalance() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for statement in [
+            "CREATE TABLE todos (id TEXT, workspace_id TEXT, page_date TEXT, `order` TEXT, updated_at INTEGER)",
+            "INSERT INTO todos VALUES ('a', 'w', 'd', 'a0', 0), ('b', 'w', 'd', 'a1', 0), ('c', 'w', 'd', 'a2', 0),
+                ('x', 'w', 'other', 'a0', 0)",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+        let todos = &ORDERABLE[0];
+
+        let moved = reorder(&mut conn, todos, "c", None, Some("a")).await.unwrap();
+        assert_eq!((moved.order.as_str(), moved.rebalanced), ("Zz", false));
+        assert!(reorder(&mut conn, todos, "a", Some("x"), None).await.is_err());
+
+        // Keep dropping an item just before "a" until the keys get too long
+        let mut moves = 0;
+        loop {
+            let (id, before_id) = if moves % 2 == 0 { ("b", "c") } else { ("c", "b") };
+            let moved = reorder(&mut conn, todos, id, Some(before_id), Some("a")).await.unwrap();
+            moves += 1;
+            if moved.rebalanced {
+                break;
+            }
+            assert!(moves < 1000);
+        }
+        let order: Vec<(String,)> = sqlx::query_as("SELECT id FROM todos WHERE page_date = 'd' ORDER BY `order`")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(order.len(), 3);
+        assert_eq!(order[2].0, "a");
+    }
+}
@@ -0,0 +1,408 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::filters::{date_range, DateSpec};
+use crate::integrity::PAGE_CONTENT_SQL;
+use crate::timers::TimeSession;
+use crate::{ai, dispatch, logger, privacy};
+
+/// Tags and tracked todos listed in a review
+const TOP_COUNT: usize = 5;
+
+/// Private and locked entries stay out of reviews, which are stored as plain notes
+const VISIBLE_PAGE: &str = "NOT EXISTS
+       (SELECT 1 FROM private_pages pp WHERE pp.workspace_id = ?1 AND pp.page_date = {date})
+     AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = ?1 AND lp.page_date = {date})";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReviewPeriod {
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReviewOptions {
+    /// The current workspace when unset
+    pub workspace_id: Option<String>,
+    /// A day in the reviewed week or month; today when unset
+    pub date: Option<String>,
+    /// Open the review with an AI summary, when an AI provider is configured
+    pub summarize: bool,
+    /// Tags the streamed tokens of the summary
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletedTodo {
+    pub id: String,
+    pub date: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedTodo {
+    pub todo_id: String,
+    pub text: String,
+    pub tracked_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Review {
+    pub period: ReviewPeriod,
+    pub from: String,
+    pub to: String,
+    pub completed: Vec<CompletedTodo>,
+    /// Todos of the period still open
+    pub open: usize,
+    /// Days of the period with notes or todos
+    pub entries: usize,
+    /// Longest run of consecutive days with an entry within the period
+    pub longest_streak: usize,
+    /// Run of days with an entry up to the period's last day, counting days before the period
+    pub current_streak: usize,
+    /// Timer sessions started in the period, pauses excluded
+    pub tracked_ms: i64,
+    pub top_tracked: Vec<TrackedTodo>,
+    pub tags: Vec<TagCount>,
+    pub summary: Option<String>,
+    /// The review as Markdown, as stored in the entry
+    pub document: String,
+    /// Day of the entry the review was stored in
+    pub stored_on: String,
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", date))
+}
+
+/// Longest streak within `from..=to` and the streak ending at `to`, from sorted days with entries
+fn streaks(days: &[NaiveDate], from: NaiveDate, to: NaiveDate) -> (usize, usize) {
+    let (mut longest, mut run, mut previous): (usize, usize, Option<NaiveDate>) = (0, 0, None);
+    for day in days.iter().filter(|day| **day <= to) {
+        run = if previous.is_some_and(|previous| *day - previous == Duration::days(1)) { run + 1 } else { 1 };
+        previous = Some(*day);
+        if *day >= from {
+            // A streak running into the period is only counted from its first day
+            longest = longest.max(run.min((*day - from).num_days() as usize + 1));
+        }
+    }
+    let current = if previous == Some(to) { run } else { 0 };
+    (longest, current)
+}
+
+/// The most used tags, most used first and then by name
+fn top_tags<'a>(tag_lists: impl IntoIterator<Item = &'a [String]>) -> Vec<TagCount> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for tags in tag_lists {
+        for tag in tags {
+            *counts.entry(tag.as_str()).or_default() += 1;
+        }
+    }
+    let mut tags: Vec<TagCount> =
+        counts.into_iter().map(|(tag, count)| TagCount { tag: tag.to_string(), count }).collect();
+    tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    tags.truncate(TOP_COUNT);
+    tags
+}
+
+fn format_duration(ms: i64) -> String {
+    let minutes = ms / 60_000;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+fn heading(review: &Review) -> String {
+    let kind = match review.period {
+        ReviewPeriod::Week => "Weekly",
+        ReviewPeriod::Month => "Monthly",
+    };
+    format!("## {} review ({} to {})", kind, review.from, review.to)
+}
+
+/// The review as a Markdown section. Tags are listed without `#` so the entry holding the
+/// review isn't tagged with them.
+fn render(review: &Review) -> String {
+    let days = parse_date(&review.to).and_then(|to| Ok((to - parse_date(&review.from)?).num_days() + 1)).unwrap_or(0);
+    let mut out = format!("{}\n\n", heading(review));
+    if let Some(summary) = &review.summary {
+        out.push_str(summary.trim());
+        out.push_str("\n\n");
+    }
+    out.push_str(&format!(
+        "- Completed: {} todos, {} still open\n- Entries: {} of {} days\n- Streak: {} days, longest {}\n",
+        review.completed.len(),
+        review.open,
+        review.entries,
+        days,
+        review.current_streak,
+        review.longest_streak
+    ));
+    if review.tracked_ms > 0 {
+        out.push_str(&format!("- Time tracked: {}\n", format_duration(review.tracked_ms)));
+    }
+    if !review.tags.is_empty() {
+        let tags: Vec<String> = review.tags.iter().map(|tag| format!("{} ({})", tag.tag, tag.count)).collect();
+        out.push_str(&format!("- Notable tags: {}\n", tags.join(", ")));
+    }
+    if !review.completed.is_empty() {
+        out.push_str("\n### Completed\n\n");
+        for todo in &review.completed {
+            out.push_str(&format!("- [x] {} ({})\n", todo.text.trim(), todo.date));
+        }
+    }
+    if !review.top_tracked.is_empty() {
+        out.push_str("\n### Most time\n\n");
+        for todo in &review.top_tracked {
+            out.push_str(&format!("- {}: {}\n", todo.text.trim(), format_duration(todo.tracked_ms)));
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Put the review into the notes, replacing the same period's earlier review up to the next
+/// heading of its level
+fn replace_section(notes: &str, heading: &str, section: &str) -> String {
+    let Some(start) = notes.lines().position(|line| line.trim_end() == heading) else {
+        if notes.trim().is_empty() {
+            return section.to_string();
+        }
+        return format!("{}\n\n{}", notes.trim_end(), section);
+    };
+    let lines: Vec<&str> = notes.lines().collect();
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.starts_with("## ") || line.starts_with("# "))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    let before = lines[..start].join("\n");
+    let after = lines[end..].join("\n");
+    [before.trim_end(), section, after.trim()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn local_millis(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .map_or(0, |time| time.timestamp_millis())
+}
+
+/// Compile a review of a week or month: completed todos, days journaled and streaks, tracked
+/// time, and notable tags, with an AI summary on top when asked for and configured. The review
+/// is stored in the notes of the period's last day (today for the current period); running it
+/// again replaces the earlier one.
+#[crate::metrics::command]
+pub async fn generate_review(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    period: ReviewPeriod,
+    options: Option<ReviewOptions>,
+) -> Result<Review, String> {
+    let options = options.unwrap_or_default();
+    let today = Local::now().date_naive();
+    let anchor = options.date.as_deref().map(parse_date).transpose()?.unwrap_or(today);
+    let spec = match period {
+        ReviewPeriod::Week => DateSpec::ThisWeek,
+        ReviewPeriod::Month => DateSpec::ThisMonth,
+    };
+    let (from, to) = date_range(&spec, anchor);
+    let (from_day, to_day) = (parse_date(&from)?, parse_date(&to)?);
+
+    let (workspace_id, todos, notes, days, sessions) = {
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        let workspace_id = match options.workspace_id.clone() {
+            Some(id) => id,
+            None => dispatch::current_workspace_id(&mut conn).await?,
+        };
+        let todos: Vec<(String, String, String, String, String)> = sqlx::query_as(&format!(
+            "SELECT t.id, t.page_date, t.text, t.status, t.tags FROM todos t
+             WHERE t.workspace_id = ?1 AND t.page_date BETWEEN ?2 AND ?3 AND trim(t.text) != '' AND {}
+             ORDER BY t.page_date, t.`order`",
+            VISIBLE_PAGE.replace("{date}", "t.page_date")
+        ))
+        .bind(&workspace_id)
+        .bind(&from)
+        .bind(&to)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        let notes: Vec<(Option<String>,)> = sqlx::query_as(&format!(
+            "SELECT {} FROM pages p WHERE p.workspace_id = ?1 AND p.date BETWEEN ?2 AND ?3 AND {}",
+            PAGE_CONTENT_SQL,
+            VISIBLE_PAGE.replace("{date}", "p.date")
+        ))
+        .bind(&workspace_id)
+        .bind(&from)
+        .bind(&to)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        // Every day up to the period's end, so a streak is counted from where it began
+        let days: Vec<(String,)> = sqlx::query_as(&format!(
+            "SELECT p.date FROM pages p WHERE p.workspace_id = ?1 AND p.date <= ?2
+               AND (trim(COALESCE({}, '')) != ''
+                 OR EXISTS (SELECT 1 FROM todos t WHERE t.workspace_id = p.workspace_id AND t.page_date = p.date
+                            AND trim(t.text) != ''))
+             ORDER BY p.date",
+            PAGE_CONTENT_SQL
+        ))
+        .bind(&workspace_id)
+        .bind(&to)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        let sessions: Vec<TimeSession> = sqlx::query_as(
+            "SELECT id, todo_id, kind, started_at, ended_at, paused_at, paused_ms FROM time_sessions
+             WHERE started_at >= ? AND started_at < ?
+               AND (todo_id IS NULL OR todo_id IN (SELECT id FROM todos WHERE workspace_id = ?))",
+        )
+        .bind(local_millis(from_day))
+        .bind(local_millis(to_day + Duration::days(1)))
+        .bind(&workspace_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+        (workspace_id, todos, notes, days, sessions)
+    };
+
+    let days: Vec<NaiveDate> = days.iter().filter_map(|(date,)| parse_date(date).ok()).collect();
+    let (longest_streak, current_streak) = streaks(&days, from_day, to_day);
+    let entries = days.iter().filter(|day| **day >= from_day).count();
+
+    let mut tag_lists: Vec<Vec<String>> = notes
+        .iter()
+        .filter_map(|(notes,)| notes.as_deref())
+        .filter(|notes| !privacy::is_encrypted(notes))
+        .map(dispatch::extract_tags)
+        .collect();
+    tag_lists.extend(todos.iter().map(|(.., tags)| serde_json::from_str(tags).unwrap_or_default()));
+    let tags = top_tags(tag_lists.iter().map(Vec::as_slice));
+
+    let now = Utc::now().timestamp_millis();
+    let mut by_todo: HashMap<&str, i64> = HashMap::new();
+    for session in &sessions {
+        if let Some(todo_id) = &session.todo_id {
+            *by_todo.entry(todo_id).or_default() += session.elapsed_ms(now);
+        }
+    }
+    let tracked_ms = sessions.iter().map(|session| session.elapsed_ms(now)).sum();
+    let mut top_tracked: Vec<TrackedTodo> = todos
+        .iter()
+        .filter_map(|(id, _, text, ..)| {
+            let tracked_ms = *by_todo.get(id.as_str())?;
+            Some(TrackedTodo { todo_id: id.clone(), text: text.clone(), tracked_ms })
+        })
+        .filter(|todo| todo.tracked_ms > 0)
+        .collect();
+    top_tracked.sort_by_key(|todo| std::cmp::Reverse(todo.tracked_ms));
+    top_tracked.truncate(TOP_COUNT);
+
+    let mut review = Review {
+        period,
+        from: from.clone(),
+        to: to.clone(),
+        completed: todos
+            .iter()
+            .filter(|(.., status, _)| status == "done")
+            .map(|(id, date, text, ..)| CompletedTodo { id: id.clone(), date: date.clone(), text: text.clone() })
+            .collect(),
+        open: todos.iter().filter(|(.., status, _)| status != "done").count(),
+        entries,
+        longest_streak,
+        current_streak,
+        tracked_ms,
+        top_tracked,
+        tags,
+        summary: None,
+        document: String::new(),
+        stored_on: to.clone().min(today.format("%Y-%m-%d").to_string()),
+    };
+    if options.summarize && ai::is_configured() {
+        match ai::summarize_review(&app, &state, options.request_id, format!("{}..{}", from, to), &render(&review)).await
+        {
+            Ok(result) => review.summary = Some(result.content),
+            Err(e) => logger::error(&format!("Review summary failed: {}", e)),
+        }
+    }
+    review.document = render(&review);
+
+    {
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        dispatch::ensure_page(&mut tx, &workspace_id, &review.stored_on).await?;
+        let (notes,): (Option<String>,) =
+            sqlx::query_as(&format!("SELECT {} FROM pages WHERE workspace_id = ? AND date = ?", PAGE_CONTENT_SQL))
+                .bind(&workspace_id)
+                .bind(&review.stored_on)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        let notes = notes.unwrap_or_default();
+        if privacy::is_encrypted(&notes) {
+            return Err(format!("The entry for {} is locked", review.stored_on));
+        }
+        sqlx::query("UPDATE pages SET notes = ?, updated_at = ? WHERE workspace_id = ? AND date = ?")
+            .bind(replace_section(&notes, &heading(&review), &review.document))
+            .bind(Utc::now().timestamp())
+            .bind(&workspace_id)
+            .bind(&review.stored_on)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["pages".to_string()]);
+    logger::info(&format!("Generated the review of {} to {} on {}", from, to, review.stored_on));
+    Ok(review)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str) -> NaiveDate {
+        parse_date(date).unwrap()
+    }
+
+    #[test]
+    fn test_streaks_count_from_before_the_period() {
+        let days: Vec<NaiveDate> = ["02", "03", "04", "06", "07", "08", "09", "10"]
+            .iter()
+            .map(|date| day(&format!("2024-03-{}", date)))
+            .collect();
+        assert_eq!(streaks(&days, day("2024-03-04"), day("2024-03-10")), (5, 5));
+        assert_eq!(streaks(&days, day("2024-03-04"), day("2024-03-05")), (1, 0));
+        assert_eq!(top_tags([&["a".to_string(), "b".to_string()][..], &["b".to_string()][..]])[0].tag, "b");
+        assert_eq!(format_duration(3_900_000), "1h 5m");
+    }
+
+    #[test]
+    fn test_replace_section_keeps_other_notes() {
+        let heading = "## Weekly review (2024-03-04 to 2024-03-10)";
+        let notes = format!("Morning\n\n{}\n\nold\n\n## Later\nEvening", heading);
+        let section = format!("{}\n\nnew", heading);
+        assert_eq!(replace_section(&notes, heading, &section), format!("Morning\n\n{}\n\n## Later\nEvening", section));
+        assert_eq!(replace_section("", heading, &section), section);
+        assert_eq!(replace_section("Notes\n", heading, &section), format!("Notes\n\n{}", section));
+    }
+}
@@ -0,0 +1,267 @@
+use chrono::{Duration, Local, Utc};
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{changes, DatabaseState};
+use crate::hooks::{self, HookEvent};
+use crate::{dispatch, fractional_index, logger, settings};
+
+/// "move" carries incomplete todos over to today, "overdue" tags them where they are; unset disables
+const MODE_SETTING: &str = "todos.rollover";
+/// Day the last rollover ran for; days from here up to yesterday are rolled over next
+const LAST_ROLLOVER_SETTING: &str = "todos.last_rollover_date";
+
+const MODE_MOVE: &str = "move";
+const MODE_OVERDUE: &str = "overdue";
+const OVERDUE_TAG: &str = "overdue";
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub(crate) struct RolloverTodo {
+    pub id: String,
+    pub workspace_id: String,
+    pub status: String,
+    pub level: i64,
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Move {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub level: i64,
+}
+
+#[derive(Debug, Clone)]
+struct RolloverSummary {
+    mode: String,
+    to_date: String,
+    todo_ids: Vec<String>,
+}
+
+/// Todos to carry over from `todos` (in page order): every incomplete one, with
+/// its subtree so children stay attached. An incomplete todo under a done
+/// parent becomes top-level on the new page, its subtree shifted up with it.
+fn plan_moves(todos: &[RolloverTodo]) -> Vec<Move> {
+    plan_subtree_moves(todos, |todo| todo.status != "done")
+}
+
+/// Todos to move from `todos` (in page order): every `selected` one, with its
+/// subtree. A selected todo whose parent stays behind becomes top-level.
+pub(crate) fn plan_subtree_moves(todos: &[RolloverTodo], selected: impl Fn(&RolloverTodo) -> bool) -> Vec<Move> {
+    let by_id: HashMap<&str, &RolloverTodo> = todos.iter().map(|t| (t.id.as_str(), t)).collect();
+    let mut moving: HashSet<&str> = HashSet::new();
+    // Levels each moved todo is shifted up by, set at the top of its moved subtree
+    let mut shift: HashMap<&str, i64> = HashMap::new();
+
+    for todo in todos {
+        let parent = todo.parent_id.as_deref().and_then(|id| by_id.get(id));
+        if selected(todo) || parent.is_some_and(|p| moving.contains(p.id.as_str())) {
+            moving.insert(&todo.id);
+        }
+    }
+
+    let mut moves = Vec::new();
+    for todo in todos.iter().filter(|t| moving.contains(t.id.as_str())) {
+        let parent = todo.parent_id.as_deref().filter(|id| moving.contains(id));
+        let offset = match parent {
+            Some(parent) => shift.get(parent).copied().unwrap_or(0),
+            None => todo.level,
+        };
+        shift.insert(&todo.id, offset);
+        moves.push(Move {
+            id: todo.id.clone(),
+            parent_id: parent.map(str::to_string),
+            level: todo.level - offset,
+        });
+    }
+    moves
+}
+
+/// Put `moves` at the end of a day's page, in order
+pub(crate) async fn move_to(
+    conn: &mut SqliteConnection,
+    workspace_id: &str,
+    today: &str,
+    moves: &[Move],
+) -> Result<(), String> {
+    dispatch::ensure_page(conn, workspace_id, today).await?;
+    let mut last_order: Option<String> = sqlx::query_as(
+        "SELECT `order` FROM todos WHERE workspace_id = ? AND page_date = ? ORDER BY `order` DESC LIMIT 1",
+    )
+    .bind(workspace_id)
+    .bind(today)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?
+    .map(|(order,): (String,)| order);
+
+    let now = Utc::now().timestamp();
+    for todo in moves {
+        let order = fractional_index::key_between(last_order.as_deref(), None)?;
+        sqlx::query("UPDATE todos SET page_date = ?, `order` = ?, parent_id = ?, level = ?, updated_at = ? WHERE id = ?")
+            .bind(today)
+            .bind(&order)
+            .bind(&todo.parent_id)
+            .bind(todo.level)
+            .bind(now)
+            .bind(&todo.id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        last_order = Some(order);
+    }
+    Ok(())
+}
+
+/// Roll incomplete todos dated `since..today` over, and record it, in one transaction
+async fn roll_over(conn: &mut SqliteConnection, mode: &str, since: &str, today: &str) -> Result<RolloverSummary, String> {
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let todos: Vec<RolloverTodo> = sqlx::query_as(
+        "SELECT id, workspace_id, status, level, parent_id FROM todos
+         WHERE page_date >= ? AND page_date < ?
+         ORDER BY workspace_id, page_date, `order`",
+    )
+    .bind(since)
+    .bind(today)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut todo_ids = Vec::new();
+    if mode == MODE_MOVE {
+        let mut workspaces: Vec<&str> = todos.iter().map(|t| t.workspace_id.as_str()).collect();
+        workspaces.dedup();
+        for workspace_id in workspaces {
+            let in_workspace: Vec<RolloverTodo> =
+                todos.iter().filter(|t| t.workspace_id == workspace_id).cloned().collect();
+            let moves = plan_moves(&in_workspace);
+            if moves.is_empty() {
+                continue;
+            }
+            move_to(&mut tx, workspace_id, today, &moves).await?;
+            todo_ids.extend(moves.into_iter().map(|m| m.id));
+        }
+    } else {
+        let now = Utc::now().timestamp();
+        for todo in todos.iter().filter(|t| t.status != "done") {
+            let tagged = sqlx::query(
+                "UPDATE todos SET tags = json_insert(tags, '$[#]', ?1), updated_at = ?2
+                 WHERE id = ?3 AND NOT EXISTS (SELECT 1 FROM json_each(todos.tags) WHERE value = ?1)",
+            )
+            .bind(OVERDUE_TAG)
+            .bind(now)
+            .bind(&todo.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .rows_affected();
+            if tagged > 0 {
+                todo_ids.push(todo.id.clone());
+            }
+        }
+    }
+
+    let summary = RolloverSummary {
+        mode: mode.to_string(),
+        to_date: today.to_string(),
+        todo_ids,
+    };
+    sqlx::query("INSERT INTO todo_rollovers (id, mode, from_date, to_date, todo_ids, created_at) VALUES (?, ?, ?, ?, ?, ?)")
+        .bind(uuid::Uuid::now_v7().to_string())
+        .bind(&summary.mode)
+        .bind(since)
+        .bind(today)
+        .bind(serde_json::to_string(&summary.todo_ids).map_err(|e| e.to_string())?)
+        .bind(Utc::now().timestamp_millis())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    settings::write_value(&mut *tx, LAST_ROLLOVER_SETTING, &serde_json::json!(today)).await?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+/// Scheduled check that rolls todos over once per local day, on the first run after midnight
+pub async fn rollover_job(app: AppHandle) -> Result<(), String> {
+    let now = Local::now().date_naive();
+    let today = now.format("%Y-%m-%d").to_string();
+    let summary = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let Some(mode) = settings::get::<String>(&pool, MODE_SETTING).await? else {
+            return Ok(());
+        };
+        if mode != MODE_MOVE && mode != MODE_OVERDUE {
+            return Err(format!("Invalid {}: {}", MODE_SETTING, mode));
+        }
+        let last = settings::get::<String>(&pool, LAST_ROLLOVER_SETTING).await?;
+        if last.as_deref().is_some_and(|last| last >= today.as_str()) {
+            return Ok(());
+        }
+        // The first rollover only looks at yesterday, not the whole history
+        let since = last.unwrap_or_else(|| (now - Duration::days(1)).format("%Y-%m-%d").to_string());
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        roll_over(&mut conn, &mode, &since, &today).await?
+    };
+
+    if !summary.todo_ids.is_empty() {
+        logger::info(&format!(
+            "Rolled over {} todos to {} ({})",
+            summary.todo_ids.len(),
+            summary.to_date,
+            summary.mode
+        ));
+        hooks::fire(
+            &app,
+            HookEvent::RolloverFinished,
+            serde_json::json!({ "mode": summary.mode, "toDate": summary.to_date, "todoIds": summary.todo_ids }),
+        );
+    }
+    changes::notify(
+        &app,
+        vec!["todos".to_string(), "pages".to_string(), "todo_rollovers".to_string()],
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: &str, status: &str, level: i64, parent_id: Option<&str>) -> RolloverTodo {
+        RolloverTodo {
+            id: id.to_string(),
+            workspace_id: "w".to_string(),
+            status: status.to_string(),
+            level,
+            parent_id: parent_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_plan_moves_keeps_subtrees_together() {
+        let todos = vec![
+            todo("a", "todo", 0, None),
+            todo("a1", "done", 1, Some("a")),
+            todo("b", "done", 0, None),
+            todo("b1", "todo", 1, Some("b")),
+            todo("b1x", "done", 2, Some("b1")),
+            todo("c", "done", 0, None),
+        ];
+        let moves = plan_moves(&todos);
+        let moved: Vec<(&str, Option<&str>, i64)> = moves
+            .iter()
+            .map(|m| (m.id.as_str(), m.parent_id.as_deref(), m.level))
+            .collect();
+        assert_eq!(
+            moved,
+            vec![
+                ("a", None, 0),
+                ("a1", Some("a"), 1),
+                ("b1", None, 0),
+                ("b1x", Some("b1"), 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use chrono::{Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex;
+
+use crate::db::{changes, DatabaseState};
+use crate::{dispatch, logger};
+
+/// Log entries kept across all rules; older ones are dropped
+const MAX_RUNS: i64 = 1000;
+const DEFAULT_RUNS_LIMIT: i64 = 100;
+
+/// What makes a rule run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Trigger {
+    #[serde(rename = "todoCreated")]
+    Created,
+    #[serde(rename = "todoCompleted")]
+    Completed,
+    #[serde(rename = "todoReopened")]
+    Reopened,
+}
+
+/// All present conditions must match the todo
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Conditions {
+    pub workspace_id: Option<String>,
+    /// The todo must carry all of these tags
+    pub tags: Vec<String>,
+    /// Substring of the todo text, case-insensitive
+    pub text: Option<String>,
+}
+
+/// `{{text}}`, `{{date}}` and `{{tags}}` in texts are filled in from the todo
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RuleAction {
+    /// Append a paragraph to today's entry
+    AppendNote { text: String },
+    /// Add a todo to today's or tomorrow's entry
+    CreateTodo {
+        text: String,
+        #[serde(default)]
+        tomorrow: bool,
+    },
+    /// Tag the todo that triggered the rule
+    AddTag { tag: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleDefinition {
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub conditions: Conditions,
+    pub actions: Vec<RuleAction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub definition: RuleDefinition,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleRun {
+    pub id: String,
+    pub rule_id: String,
+    pub todo_id: Option<String>,
+    /// "ok" or "error"
+    pub status: String,
+    pub message: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct TodoRow {
+    id: String,
+    workspace_id: String,
+    page_date: String,
+    text: String,
+    status: String,
+    #[sqlx(json)]
+    tags: Vec<String>,
+    updated_at: i64,
+}
+
+/// Todo statuses as last seen, to tell what a change did
+#[derive(Default)]
+struct Snapshot {
+    statuses: HashMap<String, String>,
+    /// Newest `updated_at` seen; rows from here on are compared on the next change
+    watermark: i64,
+}
+
+/// Loaded on start; the lock also keeps evaluations from overlapping
+static SNAPSHOT: Mutex<Option<Snapshot>> = Mutex::const_new(None);
+
+/// Triggers fired by `rows` against the statuses seen before, updating them
+fn detect(snapshot: &mut Snapshot, rows: Vec<TodoRow>) -> Vec<(Trigger, TodoRow)> {
+    let mut fired = Vec::new();
+    for row in rows {
+        snapshot.watermark = snapshot.watermark.max(row.updated_at);
+        let trigger = match snapshot.statuses.get(&row.id).map(String::as_str) {
+            None => Some(Trigger::Created),
+            Some(before) if before != "done" && row.status == "done" => Some(Trigger::Completed),
+            Some("done") if row.status != "done" => Some(Trigger::Reopened),
+            Some(_) => None,
+        };
+        snapshot.statuses.insert(row.id.clone(), row.status.clone());
+        if let Some(trigger) = trigger {
+            fired.push((trigger, row));
+        }
+    }
+    fired
+}
+
+fn matches(conditions: &Conditions, todo: &TodoRow) -> bool {
+    let workspace = conditions.workspace_id.as_ref().is_none_or(|id| *id == todo.workspace_id);
+    let tags = conditions.tags.iter().all(|tag| {
+        let tag = tag.trim_start_matches('#').to_lowercase();
+        todo.tags.iter().any(|t| t.to_lowercase() == tag)
+    });
+    let text = conditions
+        .text
+        .as_ref()
+        .is_none_or(|text| todo.text.to_lowercase().contains(&text.to_lowercase()));
+    workspace && tags && text
+}
+
+fn fill(template: &str, todo: &TodoRow) -> String {
+    let tags: Vec<String> = todo.tags.iter().map(|tag| format!("#{}", tag)).collect();
+    template
+        .replace("{{text}}", &todo.text)
+        .replace("{{date}}", &todo.page_date)
+        .replace("{{tags}}", &tags.join(" "))
+}
+
+/// Run a rule's actions for `todo` in one transaction; returns todos the actions created
+async fn execute(conn: &mut SqliteConnection, actions: &[RuleAction], todo: &TodoRow) -> Result<Vec<String>, String> {
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    let today = Local::now().date_naive();
+    let mut created = Vec::new();
+    for action in actions {
+        match action {
+            RuleAction::AppendNote { text } => {
+                let date = today.format("%Y-%m-%d").to_string();
+                dispatch::append_note(&mut tx, &todo.workspace_id, &date, &fill(text, todo)).await?;
+            }
+            RuleAction::CreateTodo { text, tomorrow } => {
+                let day = if *tomorrow { today + Duration::days(1) } else { today };
+                let date = day.format("%Y-%m-%d").to_string();
+                created.push(dispatch::insert_todo(&mut tx, &todo.workspace_id, &date, &fill(text, todo)).await?);
+            }
+            RuleAction::AddTag { tag } => {
+                let tag = tag.trim_start_matches('#').to_lowercase();
+                sqlx::query(
+                    "UPDATE todos SET tags = json_insert(tags, '$[#]', ?1), updated_at = ?2
+                     WHERE id = ?3 AND NOT EXISTS (SELECT 1 FROM json_each(todos.tags) WHERE value = ?1)",
+                )
+                .bind(&tag)
+                .bind(Utc::now().timestamp())
+                .bind(&todo.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(created)
+}
+
+async fn log_run(pool: &SqlitePool, rule_id: &str, todo_id: &str, result: &Result<Vec<String>, String>) {
+    let (status, message) = match result {
+        Ok(_) => ("ok", None),
+        Err(e) => ("error", Some(e.as_str())),
+    };
+    let logged = sqlx::query(
+        "INSERT INTO rule_runs (id, rule_id, todo_id, status, message, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(uuid::Uuid::now_v7().to_string())
+    .bind(rule_id)
+    .bind(todo_id)
+    .bind(status)
+    .bind(message)
+    .bind(Utc::now().timestamp_millis())
+    .execute(pool)
+    .await;
+    let trimmed = sqlx::query("DELETE FROM rule_runs WHERE id NOT IN (SELECT id FROM rule_runs ORDER BY created_at DESC LIMIT ?)")
+        .bind(MAX_RUNS)
+        .execute(pool)
+        .await;
+    if let Err(e) = logged.and(trimmed) {
+        logger::error(&format!("Failed to log run of rule {}: {}", rule_id, e));
+    }
+}
+
+async fn load_todos(pool: &SqlitePool, since: i64) -> Result<Vec<TodoRow>, String> {
+    sqlx::query_as(
+        "SELECT id, workspace_id, page_date, text, status, tags, updated_at FROM todos
+         WHERE updated_at >= ? ORDER BY updated_at",
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn enabled_rules(pool: &SqlitePool) -> Result<Vec<Rule>, String> {
+    Ok(load_all(pool).await?.into_iter().filter(|rule| rule.enabled).collect())
+}
+
+/// Compare todos changed since the last look and run the rules they trigger
+async fn evaluate(app: &AppHandle) -> Result<(), String> {
+    let mut guard = SNAPSHOT.lock().await;
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let Some(snapshot) = guard.as_mut() else {
+        // Everything that exists when the app starts is the baseline, not news
+        let mut snapshot = Snapshot::default();
+        detect(&mut snapshot, load_todos(&pool, 0).await?);
+        *guard = Some(snapshot);
+        return Ok(());
+    };
+
+    let fired = detect(snapshot, load_todos(&pool, snapshot.watermark).await?);
+    if fired.is_empty() {
+        return Ok(());
+    }
+    let rules = enabled_rules(&pool).await?;
+    let mut ran = false;
+    for (trigger, todo) in &fired {
+        for rule in rules
+            .iter()
+            .filter(|rule| rule.definition.trigger == *trigger && matches(&rule.definition.conditions, todo))
+        {
+            let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+            let result = execute(&mut conn, &rule.definition.actions, todo).await;
+            match &result {
+                // Todos a rule creates are known already, so they don't trigger rules in turn
+                Ok(created) => {
+                    for id in created {
+                        snapshot.statuses.insert(id.clone(), "todo".to_string());
+                    }
+                }
+                Err(e) => logger::error(&format!("Rule {} failed: {}", rule.name, e)),
+            }
+            log_run(&pool, &rule.id, &todo.id, &result).await;
+            ran = true;
+        }
+    }
+    drop(pool);
+    if ran {
+        changes::notify(
+            app,
+            vec!["todos".to_string(), "pages".to_string(), "rule_runs".to_string()],
+        );
+    }
+    Ok(())
+}
+
+/// Evaluate rules whenever todos change
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    let listener_app = app.clone();
+    changes::listen(&app, &["todos"], move |_| {
+        let app = listener_app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = evaluate(&app).await {
+                logger::error(&format!("Failed to evaluate rules: {}", e));
+            }
+        });
+    });
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = evaluate(&app).await {
+            logger::error(&format!("Failed to load todos for rules: {}", e));
+        }
+    })
+}
+
+async fn load_all(pool: &SqlitePool) -> Result<Vec<Rule>, String> {
+    let rows: Vec<(String, String, bool, String, i64, i64)> =
+        sqlx::query_as("SELECT id, name, enabled, definition, created_at, updated_at FROM rules ORDER BY name")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    rows.into_iter()
+        .map(|(id, name, enabled, definition, created_at, updated_at)| {
+            Ok(Rule {
+                definition: serde_json::from_str(&definition)
+                    .map_err(|e| format!("Invalid definition for rule {}: {}", name, e))?,
+                id,
+                name,
+                enabled,
+                created_at,
+                updated_at,
+            })
+        })
+        .collect()
+}
+
+#[crate::metrics::command]
+pub async fn list_rules(state: State<'_, DatabaseState>) -> Result<Vec<Rule>, String> {
+    let pool = state.pool.lock().await;
+    load_all(&pool).await
+}
+
+#[crate::metrics::command]
+pub async fn save_rule(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: Option<String>,
+    name: String,
+    enabled: Option<bool>,
+    definition: RuleDefinition,
+) -> Result<Rule, String> {
+    if name.trim().is_empty() {
+        return Err("Rule name cannot be empty".to_string());
+    }
+    if definition.actions.is_empty() {
+        return Err("A rule needs at least one action".to_string());
+    }
+
+    let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let now = Utc::now().timestamp_millis();
+    let saved = {
+        let pool = state.pool.lock().await;
+        sqlx::query(
+            "INSERT INTO rules (id, name, enabled, definition, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, enabled = excluded.enabled,
+                definition = excluded.definition, updated_at = excluded.updated_at",
+        )
+        .bind(&id)
+        .bind(name.trim())
+        .bind(enabled.unwrap_or(true))
+        .bind(serde_json::to_string(&definition).map_err(|e| e.to_string())?)
+        .bind(now)
+        .bind(now)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        load_all(&pool)
+            .await?
+            .into_iter()
+            .find(|rule| rule.id == id)
+            .ok_or_else(|| format!("Rule not found: {}", id))?
+    };
+    changes::notify(&app, vec!["rules".to_string()]);
+    Ok(saved)
+}
+
+#[crate::metrics::command]
+pub async fn delete_rule(app: AppHandle, state: State<'_, DatabaseState>, id: String) -> Result<(), String> {
+    {
+        let pool = state.pool.lock().await;
+        sqlx::query("DELETE FROM rules WHERE id = ?")
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("DELETE FROM rule_runs WHERE rule_id = ?")
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["rules".to_string(), "rule_runs".to_string()]);
+    Ok(())
+}
+
+/// The execution log, newest first, for one rule or all of them
+#[crate::metrics::command]
+pub async fn get_rule_runs(
+    state: State<'_, DatabaseState>,
+    rule_id: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<RuleRun>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as(
+        "SELECT id, rule_id, todo_id, status, message, created_at FROM rule_runs
+         WHERE ?1 IS NULL OR rule_id = ?1
+         ORDER BY created_at DESC LIMIT ?2",
+    )
+    .bind(rule_id)
+    .bind(limit.unwrap_or(DEFAULT_RUNS_LIMIT))
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(id: &str, status: &str, tags: &[&str]) -> TodoRow {
+        TodoRow {
+            id: id.to_string(),
+            workspace_id: "w".to_string(),
+            page_date: "2024-03-04".to_string(),
+            text: "Buy milk".to_string(),
+            status: status.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            updated_at: 1,
+        }
+    }
+
+    #[test]
+    fn test_detect_triggers() {
+        let mut snapshot = Snapshot::default();
+        snapshot.statuses.insert("a".to_string(), "todo".to_string());
+        snapshot.statuses.insert("b".to_string(), "done".to_string());
+        snapshot.statuses.insert("c".to_string(), "todo".to_string());
+        let fired = detect(
+            &mut snapshot,
+            vec![todo("a", "done", &[]), todo("b", "todo", &[]), todo("c", "todo", &[]), todo("d", "todo", &[])],
+        );
+        let fired: Vec<(Trigger, &str)> = fired.iter().map(|(t, row)| (*t, row.id.as_str())).collect();
+        assert_eq!(
+            fired,
+            vec![
+                (Trigger::Completed, "a"),
+                (Trigger::Reopened, "b"),
+                (Trigger::Created, "d")
+            ]
+        );
+        assert_eq!(snapshot.statuses["a"], "done");
+    }
+
+    #[test]
+    fn test_conditions_and_placeholders() {
+        let errand = todo("a", "done", &["errand", "home"]);
+        let conditions = Conditions {
+            tags: vec!["#Errand".to_string()],
+            text: Some("MILK".to_string()),
+            ..Default::default()
+        };
+        assert!(matches(&conditions, &errand));
+        assert!(!matches(&conditions, &todo("b", "done", &["home"])));
+        assert!(matches(&Conditions::default(), &errand));
+
+        assert_eq!(fill("Did {{text}} ({{tags}}) on {{date}}", &errand), "Did Buy milk (#errand #home) on 2024-03-04");
+    }
+}
@@ -0,0 +1,218 @@
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::DatabaseState;
+
+const DEFAULT_LIMIT: i64 = 20;
+/// Characters of context kept around a match in snippets
+const SNIPPET_CONTEXT: usize = 40;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoHit {
+    pub id: String,
+    pub workspace_id: String,
+    pub page_date: String,
+    pub text: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryHit {
+    pub workspace_id: String,
+    pub date: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TagHit {
+    pub tag: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentHit {
+    pub id: String,
+    pub workspace_id: String,
+    pub page_date: Option<String>,
+    pub file_name: String,
+    pub snippet: String,
+}
+
+/// Search results grouped by kind, each group ordered best match first
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub todos: Vec<TodoHit>,
+    pub entries: Vec<EntryHit>,
+    pub tags: Vec<TagHit>,
+    pub attachments: Vec<AttachmentHit>,
+}
+
+/// Escape `%`, `_` and `\` for a LIKE pattern using `ESCAPE '\'`
+pub fn like_pattern(query: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Turn free text into an FTS5 query matching every word as a prefix
+pub fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A short excerpt of `text` around the first case-insensitive match of `query`
+pub fn snippet(text: &str, query: &str) -> String {
+    let lower = text.to_lowercase();
+    let Some(start) = lower.find(&query.to_lowercase()).filter(|_| lower.len() == text.len()) else {
+        return text.chars().take(SNIPPET_CONTEXT * 2).collect();
+    };
+
+    let mut from = start.saturating_sub(SNIPPET_CONTEXT);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (start + query.len() + SNIPPET_CONTEXT).min(text.len());
+    while !text.is_char_boundary(to) {
+        to += 1;
+    }
+
+    let mut excerpt = text[from..to].replace('\n', " ");
+    if from > 0 {
+        excerpt.insert(0, '…');
+    }
+    if to < text.len() {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+/// Search todos, journal notes, tags and attachments in one call
+#[crate::metrics::command]
+pub async fn global_search(
+    state: State<'_, DatabaseState>,
+    query: String,
+    limit: Option<i64>,
+) -> Result<SearchResults, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(SearchResults {
+            todos: Vec::new(),
+            entries: Vec::new(),
+            tags: Vec::new(),
+            attachments: Vec::new(),
+        });
+    }
+    crate::telemetry::count("search.global");
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, 200);
+    let pattern = like_pattern(query);
+    let prefix = pattern[1..].to_string();
+
+    let pool = state.pool.lock().await;
+
+    let todos: Vec<TodoHit> = sqlx::query_as(
+        "SELECT id, workspace_id, page_date, text, status FROM todos
+         WHERE text LIKE ?1 ESCAPE '\\'
+         ORDER BY text LIKE ?2 ESCAPE '\\' DESC, status = 'done', updated_at DESC
+         LIMIT ?3",
+    )
+    .bind(&pattern)
+    .bind(&prefix)
+    .bind(limit)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let pages: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT workspace_id, date, notes FROM (
+             SELECT workspace_id, date, COALESCE(notes, zstd_decompress(notes_zstd)) AS notes FROM pages
+         )
+         WHERE notes LIKE ?1 ESCAPE '\\'
+         ORDER BY date DESC
+         LIMIT ?2",
+    )
+    .bind(&pattern)
+    .bind(limit)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let entries = pages
+        .into_iter()
+        .map(|(workspace_id, date, notes)| EntryHit {
+            workspace_id,
+            date,
+            snippet: snippet(&notes, query),
+        })
+        .collect();
+
+    let tag = query.trim_start_matches('#').to_lowercase();
+    let tags: Vec<TagHit> = sqlx::query_as(
+        "SELECT tag.value AS tag, COUNT(*) AS count
+         FROM todos, json_each(todos.tags) AS tag
+         WHERE json_valid(todos.tags) AND tag.value LIKE ?1 ESCAPE '\\'
+         GROUP BY tag.value
+         ORDER BY tag.value = ?2 DESC, count DESC
+         LIMIT ?3",
+    )
+    .bind(like_pattern(&tag))
+    .bind(&tag)
+    .bind(limit)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let attachments: Vec<AttachmentHit> = sqlx::query_as(
+        "SELECT a.id, a.workspace_id, a.page_date, a.file_name,
+                snippet(attachments_fts, -1, '', '', '…', 12) AS snippet
+         FROM attachments_fts
+         JOIN attachments a ON a.id = attachments_fts.attachment_id
+         WHERE attachments_fts MATCH ?1
+         ORDER BY bm25(attachments_fts)
+         LIMIT ?2",
+    )
+    .bind(fts_query(query))
+    .bind(limit)
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(SearchResults {
+        todos,
+        entries,
+        tags,
+        attachments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_escaping() {
+        assert_eq!(like_pattern("50%_off"), "%50\\%\\_off%");
+        assert_eq!(fts_query("buy \"milk\" now"), "\"buy\"* \"\"\"milk\"\"\"* \"now\"*");
+    }
+
+    #[test]
+    fn test_snippet() {
+        let text = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let excerpt = snippet(&text, "NEEDLE");
+        assert!(excerpt.starts_with('…') && excerpt.ends_with('…'));
+        assert!(excerpt.contains("needle"));
+        assert_eq!(snippet("short note", "missing"), "short note");
+    }
+}
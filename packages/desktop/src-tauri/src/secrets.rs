@@ -0,0 +1,51 @@
+/// Service name secrets are stored under in the OS keychain
+const SERVICE: &str = "journal-todo";
+
+fn entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, name).map_err(|e| e.to_string())
+}
+
+/// Read a secret from the OS keychain
+pub fn get(name: &str) -> Result<Option<String>, String> {
+    match entry(name)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read secret {}: {}", name, e)),
+    }
+}
+
+/// Store a secret in the OS keychain
+pub fn set(name: &str, secret: &str) -> Result<(), String> {
+    entry(name)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to store secret {}: {}", name, e))
+}
+
+/// Remove a secret from the OS keychain
+pub fn delete(name: &str) -> Result<(), String> {
+    match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to delete secret {}: {}", name, e)),
+    }
+}
+
+/// Store a secret; the webview can write secrets but never read them back
+#[crate::metrics::command]
+pub async fn set_secret(name: String, secret: String) -> Result<(), String> {
+    if secret.is_empty() {
+        return delete(&name);
+    }
+    set(&name, &secret)?;
+    crate::logger::info(&format!("Stored secret {}", name));
+    Ok(())
+}
+
+#[crate::metrics::command]
+pub async fn delete_secret(name: String) -> Result<(), String> {
+    delete(&name)
+}
+
+#[crate::metrics::command]
+pub async fn has_secret(name: String) -> Result<bool, String> {
+    Ok(get(&name)?.is_some())
+}
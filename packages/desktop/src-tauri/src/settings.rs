@@ -0,0 +1,250 @@
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::DatabaseState;
+
+/// Emitted (to the frontend and Rust listeners) whenever a setting changes
+pub const SETTINGS_CHANGED_EVENT: &str = "settings://changed";
+
+/// Version of the settings export file format
+const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Settings whose key contains one of these are never exported or imported
+const SECRET_KEY_MARKERS: &[&str] = &["secret", "password", "api_key", "token"];
+
+/// Whether a setting holds sensitive data
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEY_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// File format used by `export_settings` / `import_settings`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SettingsExport {
+    schema_version: u32,
+    app_version: String,
+    exported_at: String,
+    settings: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub value: Option<serde_json::Value>,
+}
+
+/// Read a raw setting value
+pub async fn get_value(pool: &SqlitePool, key: &str) -> Result<Option<serde_json::Value>, String> {
+    let row: Option<(String,)> = sqlx::query_as("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match row {
+        Some((value,)) => serde_json::from_str(&value)
+            .map(Some)
+            .map_err(|e| format!("Invalid value for setting {}: {}", key, e)),
+        None => Ok(None),
+    }
+}
+
+/// Read a setting as `T`
+pub async fn get<T: DeserializeOwned>(pool: &SqlitePool, key: &str) -> Result<Option<T>, String> {
+    match get_value(pool, key).await? {
+        Some(value) => serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| format!("Invalid value for setting {}: {}", key, e)),
+        None => Ok(None),
+    }
+}
+
+/// Read a setting as `T`, falling back to `default` when unset or invalid
+pub async fn get_or<T: DeserializeOwned>(pool: &SqlitePool, key: &str, default: T) -> T {
+    get(pool, key).await.ok().flatten().unwrap_or(default)
+}
+
+/// Read every setting
+pub async fn get_all(pool: &SqlitePool) -> Result<HashMap<String, serde_json::Value>, String> {
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(key, value)| {
+            serde_json::from_str(&value)
+                .map(|value| (key.clone(), value))
+                .map_err(|e| format!("Invalid value for setting {}: {}", key, e))
+        })
+        .collect()
+}
+
+/// Write a setting without emitting a change event
+pub async fn write_value<'e, E>(executor: E, key: &str, value: &serde_json::Value) -> Result<(), String>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let value = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    sqlx::query(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+    )
+    .bind(key)
+    .bind(value)
+    .bind(Utc::now().timestamp_millis())
+    .execute(executor)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Write a setting and notify listeners
+pub async fn set<T: Serialize>(app: &AppHandle, key: &str, value: T) -> Result<(), String> {
+    let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+    {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        write_value(&*pool, key, &value).await?;
+    }
+
+    app.emit(
+        SETTINGS_CHANGED_EVENT,
+        SettingChange {
+            key: key.to_string(),
+            value: Some(value),
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Remove a setting and notify listeners
+pub async fn remove(app: &AppHandle, key: &str) -> Result<(), String> {
+    {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        sqlx::query("DELETE FROM settings WHERE key = ?")
+            .bind(key)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    app.emit(
+        SETTINGS_CHANGED_EVENT,
+        SettingChange {
+            key: key.to_string(),
+            value: None,
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[crate::metrics::command]
+pub async fn get_setting(
+    state: State<'_, DatabaseState>,
+    key: String,
+) -> Result<Option<serde_json::Value>, String> {
+    let pool = state.pool.lock().await;
+    get_value(&pool, &key).await
+}
+
+#[crate::metrics::command]
+pub async fn set_setting(app: AppHandle, key: String, value: serde_json::Value) -> Result<(), String> {
+    if value.is_null() {
+        remove(&app, &key).await
+    } else {
+        set(&app, &key, value).await
+    }
+}
+
+#[crate::metrics::command]
+pub async fn get_all_settings(
+    state: State<'_, DatabaseState>,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    let pool = state.pool.lock().await;
+    get_all(&pool).await
+}
+
+/// Write all non-secret settings to a JSON file
+#[crate::metrics::command]
+pub async fn export_settings(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    path: String,
+) -> Result<usize, String> {
+    let settings: HashMap<String, serde_json::Value> = {
+        let pool = state.pool.lock().await;
+        get_all(&pool).await?
+    }
+    .into_iter()
+    .filter(|(key, _)| !is_secret_key(key))
+    .collect();
+
+    let count = settings.len();
+    let export = SettingsExport {
+        schema_version: EXPORT_SCHEMA_VERSION,
+        app_version: app.package_info().version.to_string(),
+        exported_at: Utc::now().to_rfc3339(),
+        settings,
+    };
+
+    let content = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    crate::logger::info(&format!("Exported {} settings to {}", count, path));
+    Ok(count)
+}
+
+/// Load settings from a file produced by `export_settings`, skipping secrets
+#[crate::metrics::command]
+pub async fn import_settings(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    path: String,
+) -> Result<usize, String> {
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let export: SettingsExport = serde_json::from_str(&content)
+        .map_err(|e| format!("Not a valid settings export: {}", e))?;
+
+    if export.schema_version == 0 || export.schema_version > EXPORT_SCHEMA_VERSION {
+        return Err(format!(
+            "Unsupported settings export version {} (expected {})",
+            export.schema_version, EXPORT_SCHEMA_VERSION
+        ));
+    }
+
+    let settings: Vec<(String, serde_json::Value)> = export
+        .settings
+        .into_iter()
+        .filter(|(key, value)| !is_secret_key(key) && !key.trim().is_empty() && !value.is_null())
+        .collect();
+
+    {
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        for (key, value) in &settings {
+            write_value(&mut *tx, key, value).await?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+
+    for (key, value) in &settings {
+        app.emit(
+            SETTINGS_CHANGED_EVENT,
+            SettingChange {
+                key: key.clone(),
+                value: Some(value.clone()),
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    crate::logger::info(&format!("Imported {} settings from {}", settings.len(), path));
+    Ok(settings.len())
+}
@@ -0,0 +1,428 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::attachments::{self, Attachment, ATTACHMENT_COLUMNS};
+use crate::db::DatabaseState;
+use crate::git_export::{self, ExportTodo};
+use crate::markdown::{self, RenderOptions};
+use crate::{dispatch, logger};
+
+const DEFAULT_TITLE: &str = "Journal";
+
+const BASE_CSS: &str = "\
+body { margin: 0; font: 17px/1.6 -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; background: var(--bg); color: var(--fg); }
+main { max-width: 42rem; margin: 0 auto; padding: 2rem 1.25rem 4rem; }
+header { border-bottom: 1px solid var(--line); margin-bottom: 2rem; }
+header a { color: var(--fg); text-decoration: none; font-weight: 600; }
+a { color: var(--accent); }
+article { border-bottom: 1px solid var(--line); padding-bottom: 1.5rem; margin-bottom: 1.5rem; }
+ul.contains-task-list { list-style: none; padding-left: 1.25rem; }
+figure { margin: 1rem 0; }
+figure img { max-width: 100%; border-radius: 6px; }
+.tags a { margin-right: .5rem; font-size: .9em; }
+.muted { color: var(--muted); }
+";
+const LIGHT_CSS: &str = "--bg: #ffffff; --fg: #1f2328; --muted: #6e7781; --line: #e5e7eb; --accent: #0969da;";
+const DARK_CSS: &str = "--bg: #16181d; --fg: #e6e6e6; --muted: #8b949e; --line: #2d333b; --accent: #58a6ff;";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SiteOptions {
+    /// Defaults to the current workspace
+    pub workspace_id: Option<String>,
+    /// Inclusive `YYYY-MM-DD` bounds
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Only entries with at least one of these tags; all entries when empty
+    pub tags: Vec<String>,
+    pub title: Option<String>,
+    /// "light", "dark", or follow the reader's system when unset
+    pub theme: Option<String>,
+    pub skip_images: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteReport {
+    pub entries: usize,
+    pub pages: usize,
+    pub images: usize,
+}
+
+/// An entry with everything the exports render
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    pub date: String,
+    pub notes: Option<String>,
+    pub todos: Vec<ExportTodo>,
+    pub tags: Vec<String>,
+    pub images: Vec<Attachment>,
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Markdown to HTML; raw HTML in notes is shown as text rather than passed through
+pub(crate) fn markdown_html(markdown: &str) -> String {
+    markdown::render_html(markdown, &RenderOptions::default())
+}
+
+/// The entry's text and todos, then its images from `image_dir`
+pub(crate) fn entry_html(entry: &Entry, image_dir: &str) -> String {
+    let todos: Vec<&ExportTodo> = entry.todos.iter().collect();
+    let mut out = markdown_html(&git_export::render(&entry.date, entry.notes.as_deref(), &todos));
+    for image in &entry.images {
+        out.push_str(&format!(
+            "<figure><img src=\"{}/{}\" alt=\"{}\" /></figure>\n",
+            image_dir,
+            escape(&image.stored_name),
+            escape(&image.file_name)
+        ));
+    }
+    out
+}
+
+pub(crate) fn long_date(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|day| day.format("%A, %B %-d, %Y").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+pub(crate) fn month_title(month: &str) -> String {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map(|day| day.format("%B %Y").to_string())
+        .unwrap_or_else(|_| month.to_string())
+}
+
+/// `YYYY-MM` of a `YYYY-MM-DD` date
+pub(crate) fn month_of(date: &str) -> &str {
+    date.get(..7).unwrap_or(date)
+}
+
+fn tag_slug(tag: &str) -> String {
+    tag.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// Entries of a workspace with notes or todos, oldest first; private and locked
+/// entries are never exported
+pub(crate) async fn load_entries(
+    app: &AppHandle,
+    workspace_id: Option<String>,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(Vec<Entry>, PathBuf), String> {
+    let state = app.state::<DatabaseState>();
+    let attachments_dir = attachments::attachments_dir(&state).await;
+    let mut conn = state.open_reporting_snapshot().await?;
+    let workspace_id = match workspace_id {
+        Some(id) => id,
+        None => dispatch::current_workspace_id(&mut conn).await?,
+    };
+    let from = from.unwrap_or("0000-00-00");
+    let to = to.unwrap_or("9999-99-99");
+
+    let pages: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT p.date, COALESCE(p.notes, zstd_decompress(p.notes_zstd)) FROM pages p
+         WHERE p.workspace_id = ?1 AND p.date >= ?2 AND p.date <= ?3
+           AND NOT EXISTS (SELECT 1 FROM private_pages pp WHERE pp.workspace_id = p.workspace_id AND pp.page_date = p.date)
+           AND NOT EXISTS (SELECT 1 FROM locked_pages lp WHERE lp.workspace_id = p.workspace_id AND lp.page_date = p.date)
+         ORDER BY p.date",
+    )
+    .bind(&workspace_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    let todos: Vec<(ExportTodo, String)> = sqlx::query_as::<_, (String, String, String, String, i64, String)>(
+        "SELECT workspace_id, page_date, text, status, level, tags FROM todos
+         WHERE workspace_id = ?1 AND page_date >= ?2 AND page_date <= ?3
+         ORDER BY page_date, `order`",
+    )
+    .bind(&workspace_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?
+    .into_iter()
+    .map(|(workspace_id, page_date, text, status, level, tags)| {
+        (ExportTodo { workspace_id, page_date, text, status, level }, tags)
+    })
+    .collect();
+    let images: Vec<Attachment> = sqlx::query_as(&format!(
+        "SELECT {} FROM attachments
+         WHERE workspace_id = ?1 AND page_date >= ?2 AND page_date <= ?3 AND mime_type LIKE 'image/%'
+         ORDER BY created_at",
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(&workspace_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut entries = Vec::new();
+    for (date, notes) in pages {
+        let mut tags = notes.as_deref().map(dispatch::extract_tags).unwrap_or_default();
+        let mut entry_todos = Vec::new();
+        for (todo, todo_tags) in todos.iter().filter(|(todo, _)| todo.page_date == date) {
+            for tag in serde_json::from_str::<Vec<String>>(todo_tags).unwrap_or_default() {
+                let tag = tag.to_lowercase();
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            entry_todos.push(todo.clone());
+        }
+        let has_notes = notes.as_deref().is_some_and(|notes| !notes.trim().is_empty());
+        if !has_notes && entry_todos.is_empty() {
+            continue;
+        }
+        let entry_images = images
+            .iter()
+            .filter(|image| image.page_date.as_deref() == Some(date.as_str()))
+            .cloned()
+            .collect();
+        entries.push(Entry {
+            date,
+            notes,
+            todos: entry_todos,
+            tags,
+            images: entry_images,
+        });
+    }
+    Ok((entries, attachments_dir))
+}
+
+fn stylesheet(theme: Option<&str>) -> String {
+    let colors = match theme {
+        Some("dark") => format!(":root {{ {} }}\n", DARK_CSS),
+        Some("light") => format!(":root {{ {} }}\n", LIGHT_CSS),
+        _ => format!(
+            ":root {{ {} }}\n@media (prefers-color-scheme: dark) {{ :root {{ {} }} }}\n",
+            LIGHT_CSS, DARK_CSS
+        ),
+    };
+    format!("{}{}", colors, BASE_CSS)
+}
+
+/// A full page; `root` is the relative path back to the site root
+fn layout(site_title: &str, title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title} · {site}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n<body>\n<main>\n\
+         <header><p><a href=\"{root}index.html\">{site}</a></p></header>\n{body}</main>\n</body>\n</html>\n",
+        title = escape(title),
+        site = escape(site_title),
+        root = root,
+        body = body
+    )
+}
+
+fn tag_links(tags: &[String], root: &str) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let links: Vec<String> = tags
+        .iter()
+        .map(|tag| format!("<a href=\"{}tags/{}.html\">#{}</a>", root, tag_slug(tag), escape(tag)))
+        .collect();
+    format!("<p class=\"tags\">{}</p>\n", links.join(""))
+}
+
+/// Every page of the site by path relative to its root
+fn render_site(entries: &[Entry], site_title: &str) -> BTreeMap<String, String> {
+    let mut files = BTreeMap::new();
+    let mut months: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    let mut tags: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        months.entry(month_of(&entry.date)).or_default().push(entry);
+        for tag in &entry.tags {
+            tags.entry(tag).or_default().push(entry);
+        }
+
+        let body = format!(
+            "<article>\n{}{}</article>\n<p><a href=\"../months/{}.html\">{}</a></p>\n",
+            entry_html(entry, "../images"),
+            tag_links(&entry.tags, "../"),
+            month_of(&entry.date),
+            escape(&month_title(month_of(&entry.date)))
+        );
+        files.insert(
+            format!("entries/{}.html", entry.date),
+            layout(site_title, &long_date(&entry.date), "../", &body),
+        );
+    }
+
+    for (month, month_entries) in &months {
+        let mut body = format!("<h1>{}</h1>\n", escape(&month_title(month)));
+        for entry in month_entries {
+            body.push_str(&format!(
+                "<article id=\"{date}\">\n{}{}<p><a href=\"../entries/{date}.html\">Permalink</a></p>\n</article>\n",
+                entry_html(entry, "../images"),
+                tag_links(&entry.tags, "../"),
+                date = entry.date
+            ));
+        }
+        files.insert(format!("months/{}.html", month), layout(site_title, &month_title(month), "../", &body));
+    }
+
+    for (tag, tag_entries) in &tags {
+        let mut body = format!("<h1>#{}</h1>\n<ul>\n", escape(tag));
+        for entry in tag_entries.iter().rev() {
+            body.push_str(&format!(
+                "<li><a href=\"../entries/{}.html\">{}</a></li>\n",
+                entry.date,
+                escape(&long_date(&entry.date))
+            ));
+        }
+        body.push_str("</ul>\n");
+        files.insert(format!("tags/{}.html", tag_slug(tag)), layout(site_title, &format!("#{}", tag), "../", &body));
+    }
+
+    let mut body = format!("<h1>{}</h1>\n<h2>By month</h2>\n<ul>\n", escape(site_title));
+    for (month, month_entries) in months.iter().rev() {
+        body.push_str(&format!(
+            "<li><a href=\"months/{}.html\">{}</a> <span class=\"muted\">{}</span></li>\n",
+            month,
+            escape(&month_title(month)),
+            month_entries.len()
+        ));
+    }
+    body.push_str("</ul>\n");
+    if !tags.is_empty() {
+        body.push_str("<h2>By tag</h2>\n<ul>\n");
+        for (tag, tag_entries) in &tags {
+            body.push_str(&format!(
+                "<li><a href=\"tags/{}.html\">#{}</a> <span class=\"muted\">{}</span></li>\n",
+                tag_slug(tag),
+                escape(tag),
+                tag_entries.len()
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+    files.insert("index.html".to_string(), layout(site_title, "Index", "", &body));
+    files
+}
+
+fn write_site(dest: &Path, files: &BTreeMap<String, String>, images: &[(PathBuf, String)]) -> Result<(), String> {
+    for (relative, contents) in files {
+        let path = dest.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    if !images.is_empty() {
+        std::fs::create_dir_all(dest.join("images")).map_err(|e| e.to_string())?;
+    }
+    for (source, stored_name) in images {
+        std::fs::copy(source, dest.join("images").join(stored_name))
+            .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Render the selected entries into a static HTML site in `dest_dir`, indexed by month and tag.
+/// Existing files in `dest_dir` are overwritten but never removed.
+#[crate::metrics::command]
+pub async fn export_site(app: AppHandle, dest_dir: String, options: Option<SiteOptions>) -> Result<SiteReport, String> {
+    let options = options.unwrap_or_default();
+    let (mut entries, attachments_dir) =
+        load_entries(&app, options.workspace_id.clone(), options.from.as_deref(), options.to.as_deref()).await?;
+    if !options.tags.is_empty() {
+        let wanted: Vec<String> = options
+            .tags
+            .iter()
+            .map(|tag| tag.trim_start_matches('#').to_lowercase())
+            .collect();
+        entries.retain(|entry| entry.tags.iter().any(|tag| wanted.contains(tag)));
+    }
+    if options.skip_images {
+        entries.iter_mut().for_each(|entry| entry.images.clear());
+    }
+    // Images whose file is gone are left out rather than linked broken
+    for entry in &mut entries {
+        entry.images.retain(|image| attachments_dir.join(&image.stored_name).is_file());
+    }
+
+    let site_title = options.title.clone().unwrap_or_else(|| DEFAULT_TITLE.to_string());
+    let mut files = render_site(&entries, &site_title);
+    files.insert("style.css".to_string(), stylesheet(options.theme.as_deref()));
+    let images: Vec<(PathBuf, String)> = entries
+        .iter()
+        .flat_map(|entry| &entry.images)
+        .map(|image| (attachments_dir.join(&image.stored_name), image.stored_name.clone()))
+        .collect();
+    let report = SiteReport {
+        entries: entries.len(),
+        pages: files.len() - 1,
+        images: images.len(),
+    };
+
+    let dest = PathBuf::from(dest_dir);
+    tauri::async_runtime::spawn_blocking(move || write_site(&dest, &files, &images))
+        .await
+        .map_err(|e| e.to_string())??;
+    logger::info(&format!("Exported {} entries as a site", report.entries));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, notes: &str, tags: &[&str]) -> Entry {
+        Entry {
+            date: date.to_string(),
+            notes: Some(notes.to_string()),
+            todos: Vec::new(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_markdown_html_escapes_raw_html() {
+        assert_eq!(markdown_html("**hi** <script>x</script>"), "<p><strong>hi</strong> &lt;script&gt;x&lt;/script&gt;</p>\n");
+    }
+
+    #[test]
+    fn test_render_site_indexes_months_and_tags() {
+        let entries = vec![
+            entry("2024-02-28", "Feb #work", &["work"]),
+            entry("2024-03-04", "Mar", &["work", "Home Life"]),
+        ];
+        let files = render_site(&entries, "My <Journal>");
+        let names: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            vec![
+                "entries/2024-02-28.html",
+                "entries/2024-03-04.html",
+                "index.html",
+                "months/2024-02.html",
+                "months/2024-03.html",
+                "tags/home-life.html",
+                "tags/work.html",
+            ]
+        );
+        let index = &files["index.html"];
+        assert!(index.contains("My &lt;Journal&gt;"));
+        assert!(index.find("March 2024").unwrap() < index.find("February 2024").unwrap());
+        assert!(files["tags/work.html"].contains("../entries/2024-02-28.html"));
+    }
+}
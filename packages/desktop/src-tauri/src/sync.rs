@@ -0,0 +1,111 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::logger;
+
+/// Emitted with the new `SyncStatus` whenever a provider reports
+pub const SYNC_STATUS_EVENT: &str = "sync://status";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum SyncState {
+    Idle,
+    Syncing,
+    Progress {
+        done: u64,
+        total: Option<u64>,
+    },
+    Error {
+        message: String,
+    },
+    /// Finished, with entries that need resolving
+    #[allow(dead_code)] // no provider reports conflicts yet
+    Conflicts {
+        count: u64,
+    },
+}
+
+/// What the sync indicator shows, whichever provider is doing the syncing
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    /// The provider that reported last, e.g. "git"
+    pub provider: Option<String>,
+    #[serde(flatten)]
+    pub state: SyncState,
+    /// When a sync last ran to the end
+    pub last_synced_at: Option<i64>,
+    pub updated_at: i64,
+}
+
+/// The latest sync status, shared by every provider
+pub struct SyncTracker(Mutex<SyncStatus>);
+
+impl Default for SyncTracker {
+    fn default() -> Self {
+        Self(Mutex::new(SyncStatus {
+            provider: None,
+            state: SyncState::Idle,
+            last_synced_at: None,
+            updated_at: Utc::now().timestamp_millis(),
+        }))
+    }
+}
+
+impl SyncTracker {
+    fn update(&self, provider: &str, state: SyncState) -> SyncStatus {
+        let now = Utc::now().timestamp_millis();
+        let mut status = self.0.lock().unwrap();
+        let was_running = matches!(status.state, SyncState::Syncing | SyncState::Progress { .. });
+        if was_running && matches!(state, SyncState::Idle | SyncState::Conflicts { .. }) {
+            status.last_synced_at = Some(now);
+        }
+        status.provider = Some(provider.to_string());
+        status.state = state;
+        status.updated_at = now;
+        status.clone()
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Record a provider's sync state and tell the frontend
+pub fn report(app: &AppHandle, provider: &str, state: SyncState) {
+    let status = app.state::<SyncTracker>().update(provider, state);
+    if let Err(e) = app.emit(SYNC_STATUS_EVENT, &status) {
+        logger::error(&format!("Failed to emit sync status: {}", e));
+    }
+}
+
+/// The sync status as last reported, for rendering the indicator before any event arrives
+#[crate::metrics::command]
+pub fn get_sync_status(tracker: State<'_, SyncTracker>) -> SyncStatus {
+    tracker.status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_synced_at_follows_finished_syncs() {
+        let tracker = SyncTracker::default();
+        assert_eq!(tracker.update("git", SyncState::Idle).last_synced_at, None);
+        tracker.update("git", SyncState::Syncing);
+        tracker.update("git", SyncState::Progress { done: 1, total: Some(2) });
+        let finished = tracker.update("git", SyncState::Idle);
+        assert!(finished.last_synced_at.is_some());
+
+        tracker.update("git", SyncState::Syncing);
+        let failed = tracker.update("git", SyncState::Error { message: "offline".to_string() });
+        assert_eq!(failed.last_synced_at, finished.last_synced_at);
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap()["state"],
+            serde_json::json!("error")
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{changes, DatabaseState};
+
+/// JSON tag arrays outside of todos: a table, its JSON column and the array's path in it
+const TAG_ARRAYS: &[(&str, &str, &str)] = &[
+    ("saved_filters", "definition", "$.tags"),
+    ("rules", "definition", "$.conditions.tags"),
+];
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TagUsage {
+    pub tag: String,
+    pub todos: i64,
+    pub open_todos: i64,
+    /// Latest page date a todo with the tag is on
+    pub last_used: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagMergeReport {
+    pub todos: u64,
+    pub filters: u64,
+    pub rules: u64,
+}
+
+/// Tags are stored lowercased without the `#`
+fn normalize(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+/// `text` with every `#tag` in `from` written as `#into`. Tags run up to whitespace or the
+/// next `#`, as in `dispatch::extract_tags`.
+fn rewrite_text(text: &str, from: &[String], into: &str) -> String {
+    let mut rewritten = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(hash) = rest.find('#') {
+        rewritten.push_str(&rest[..=hash]);
+        rest = &rest[hash + 1..];
+        let end = rest.find(|c: char| c.is_whitespace() || c == '#').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        if from.contains(&tag.to_lowercase()) {
+            rewritten.push_str(into);
+        } else {
+            rewritten.push_str(tag);
+        }
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+    rewritten
+}
+
+/// Replace the tags in `from` with `into` in the JSON array at `path` of `table.column`, keeping
+/// each array's order and dropping the duplicates a merge leaves. `?1` is `from` as a JSON
+/// array, `?2` is `into` and `?3` the update time.
+fn merge_sql(table: &str, column: &str, path: &str) -> String {
+    format!(
+        "UPDATE {table} SET {column} = json_set({column}, '{path}', (
+             SELECT json_group_array(value) FROM (
+                 SELECT CASE WHEN lower(value) IN (SELECT value FROM json_each(?1)) THEN ?2 ELSE value END AS value,
+                        MIN(key) AS position
+                 FROM json_each({table}.{column}, '{path}')
+                 GROUP BY 1
+                 ORDER BY position
+             )
+         )), updated_at = ?3
+         WHERE EXISTS (
+             SELECT 1 FROM json_each({table}.{column}, '{path}') WHERE lower(value) IN (SELECT value FROM json_each(?1))
+         )"
+    )
+}
+
+async fn merge(conn: &mut SqliteConnection, from: &[String], into: &str) -> Result<TagMergeReport, String> {
+    let into = normalize(into);
+    if into.is_empty() || into.contains(|c: char| c.is_whitespace() || c == '#') {
+        return Err(format!("Invalid tag name: {}", into));
+    }
+    let mut from: Vec<String> = from.iter().map(|tag| normalize(tag)).filter(|tag| *tag != into).collect();
+    from.sort();
+    from.dedup();
+    if from.is_empty() {
+        return Ok(TagMergeReport::default());
+    }
+    let from_json = serde_json::to_string(&from).map_err(|e| e.to_string())?;
+    let now = Utc::now().timestamp_millis();
+
+    let mut tx = conn.begin().await.map_err(|e| e.to_string())?;
+    // Tags are read back out of todo text when it's edited, so the text has to change too
+    let texts: Vec<(String, String)> = sqlx::query_as(
+        "SELECT id, text FROM todos
+         WHERE EXISTS (SELECT 1 FROM json_each(todos.tags) WHERE lower(value) IN (SELECT value FROM json_each(?)))",
+    )
+    .bind(&from_json)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let todos = sqlx::query(&merge_sql("todos", "tags", "$"))
+        .bind(&from_json)
+        .bind(&into)
+        .bind(Utc::now().timestamp())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+    for (id, text) in texts {
+        let rewritten = rewrite_text(&text, &from, &into);
+        if rewritten != text {
+            sqlx::query("UPDATE todos SET text = ? WHERE id = ?")
+                .bind(rewritten)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let mut report = TagMergeReport {
+        todos,
+        ..Default::default()
+    };
+    for (table, column, path) in TAG_ARRAYS {
+        let updated = sqlx::query(&merge_sql(table, column, path))
+            .bind(&from_json)
+            .bind(&into)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .rows_affected();
+        match *table {
+            "saved_filters" => report.filters = updated,
+            _ => report.rules = updated,
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+fn notify_merged(app: &AppHandle, report: &TagMergeReport) {
+    let mut tables = Vec::new();
+    if report.todos > 0 {
+        tables.push("todos".to_string());
+    }
+    if report.filters > 0 {
+        tables.push("saved_filters".to_string());
+    }
+    if report.rules > 0 {
+        tables.push("rules".to_string());
+    }
+    if !tables.is_empty() {
+        changes::notify(app, tables);
+    }
+}
+
+/// How many todos carry each tag, most used first
+#[crate::metrics::command]
+pub async fn get_tag_usage(state: State<'_, DatabaseState>) -> Result<Vec<TagUsage>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as(
+        "SELECT lower(tag.value) AS tag, COUNT(DISTINCT t.id) AS todos,
+                COUNT(DISTINCT CASE WHEN t.status != 'done' THEN t.id END) AS open_todos,
+                MAX(t.page_date) AS last_used
+         FROM todos t, json_each(t.tags) tag
+         GROUP BY 1
+         ORDER BY todos DESC, tag",
+    )
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Rename a tag on every todo, in todo text, and in saved filters and rules
+#[crate::metrics::command]
+pub async fn rename_tag(app: AppHandle, old: String, new: String) -> Result<TagMergeReport, String> {
+    merge_tags(app, vec![old], new).await
+}
+
+/// Fold the `from` tags into `into` everywhere they're used
+#[crate::metrics::command]
+pub async fn merge_tags(app: AppHandle, from: Vec<String>, into: String) -> Result<TagMergeReport, String> {
+    let report = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+        merge(&mut conn, &from, &into).await?
+    };
+    notify_merged(&app, &report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_text() {
+        let from = vec!["work".to_string(), "job".to_string()];
+        assert_eq!(
+            rewrite_text("Call Bob #Work #home#job", &from, "office"),
+            "Call Bob #office #home#office"
+        );
+        assert_eq!(rewrite_text("#workshop and # alone", &from, "office"), "#workshop and # alone");
+    }
+
+    #[tokio::test]
+    async fn test_merge_tags() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for statement in [
+            "CREATE TABLE todos (id TEXT, text TEXT, tags TEXT, updated_at INTEGER)",
+            "CREATE TABLE saved_filters (definition TEXT, updated_at INTEGER)",
+            "CREATE TABLE rules (definition TEXT, updated_at INTEGER)",
+            r##"INSERT INTO todos VALUES ('a', 'Plan #work #job', '["work","job"]', 0),
+                ('b', 'Read #home', '["home"]', 0), ('c', 'Fix #job #later', '["job","later"]', 0)"##,
+            r#"INSERT INTO saved_filters VALUES ('{"tags":["job"],"overdue":false}', 0)"#,
+            r#"INSERT INTO rules VALUES ('{"trigger":"todoCreated","conditions":{"tags":["home"]},"actions":[]}', 0)"#,
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+
+        let from = vec!["#Job".to_string(), "work".to_string()];
+        let report = merge(&mut conn, &from, "office").await.unwrap();
+        assert_eq!((report.todos, report.filters, report.rules), (2, 1, 0));
+
+        let todos: Vec<(String, String)> = sqlx::query_as("SELECT text, tags FROM todos ORDER BY id")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(todos[0], ("Plan #office #office".to_string(), r#"["office"]"#.to_string()));
+        assert_eq!(todos[1].1, r#"["home"]"#);
+        assert_eq!(todos[2].1, r#"["office","later"]"#);
+        let (filter,): (String,) = sqlx::query_as("SELECT definition FROM saved_filters")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(filter, r#"{"tags":["office"],"overdue":false}"#);
+        assert!(merge(&mut conn, &from, "two words").await.is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::{Local, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Listener, Manager, State};
+
+use crate::db::DatabaseState;
+use crate::logger;
+use crate::settings::{self, SettingChange, SETTINGS_CHANGED_EVENT};
+
+/// Telemetry is off unless the user turns it on
+const ENABLED_SETTING: &str = "telemetry.enabled";
+/// Where batches are POSTed; nothing is uploaded while unset
+const ENDPOINT_SETTING: &str = "telemetry.endpoint";
+/// Random id generated when telemetry is enabled, dropped when it's disabled
+const INSTALL_ID_SETTING: &str = "telemetry.install_id";
+const LAST_UPLOAD_SETTING: &str = "telemetry.last_upload_at";
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+const UPLOAD_INTERVAL_MS: i64 = 24 * 60 * 60 * 1000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Events kept in memory between flushes; more are dropped
+const MAX_PENDING: usize = 10_000;
+const MAX_SIGNATURE_LEN: usize = 120;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PENDING: Mutex<Vec<(&'static str, String)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryEvent {
+    /// "counter" or "error"
+    pub kind: String,
+    pub name: String,
+    pub day: String,
+    pub count: i64,
+}
+
+/// Exactly what an upload sends
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryBatch {
+    pub install_id: String,
+    pub app_version: String,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub events: Vec<TelemetryEvent>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    pub endpoint_configured: bool,
+    pub pending_events: i64,
+    pub last_upload_at: Option<i64>,
+}
+
+fn record(kind: &'static str, name: String) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut pending) = PENDING.lock() {
+        if pending.len() < MAX_PENDING {
+            pending.push((kind, name));
+        }
+    }
+}
+
+/// Count one use of a feature
+pub fn count(name: &str) {
+    record("counter", name.to_string());
+}
+
+/// Count an error by its anonymized signature
+pub fn record_error(message: &str) {
+    record("error", signature(message));
+}
+
+/// Reduce an error message to a shape without user data: quoted text and paths
+/// are dropped and numbers masked
+fn signature(message: &str) -> String {
+    let mut unquoted = String::with_capacity(message.len());
+    let mut quote = None;
+    for c in message.chars() {
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+                unquoted.push_str("<str>");
+            }
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => unquoted.push(if c.is_ascii_digit() { '#' } else { c }),
+        }
+    }
+
+    let signature = unquoted
+        .split_whitespace()
+        .map(|word| {
+            if word.contains('/') || word.contains('\\') {
+                "<path>"
+            } else if word.contains('@') {
+                "<email>"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    signature.chars().take(MAX_SIGNATURE_LEN).collect()
+}
+
+/// Move in-memory events into the local table
+async fn flush(pool: &SqlitePool) -> Result<(), String> {
+    let events = match PENDING.lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(_) => return Ok(()),
+    };
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut counts: HashMap<(&str, String), i64> = HashMap::new();
+    for event in events {
+        *counts.entry(event).or_default() += 1;
+    }
+    let day = Local::now().format("%Y-%m-%d").to_string();
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    for ((kind, name), count) in counts {
+        sqlx::query(
+            "INSERT INTO telemetry_events (kind, name, day, count) VALUES (?, ?, ?, ?)
+             ON CONFLICT(kind, name, day) DO UPDATE SET count = count + excluded.count",
+        )
+        .bind(kind)
+        .bind(name)
+        .bind(&day)
+        .bind(count)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+async fn build_batch(app: &AppHandle, pool: &SqlitePool) -> Result<Option<TelemetryBatch>, String> {
+    let Some(install_id) = settings::get::<String>(pool, INSTALL_ID_SETTING).await? else {
+        return Ok(None);
+    };
+    let events: Vec<TelemetryEvent> =
+        sqlx::query_as("SELECT kind, name, day, count FROM telemetry_events ORDER BY day, kind, name")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    Ok(Some(TelemetryBatch {
+        install_id,
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        events,
+    }))
+}
+
+/// Send stored events if an upload is due, subtracting what was sent afterwards
+async fn upload_if_due(app: &AppHandle) -> Result<(), String> {
+    let now = Utc::now().timestamp_millis();
+    let (endpoint, batch) = {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let last_upload = settings::get_or(&pool, LAST_UPLOAD_SETTING, 0i64).await;
+        if now - last_upload < UPLOAD_INTERVAL_MS {
+            return Ok(());
+        }
+        let Some(endpoint) = settings::get::<String>(&pool, ENDPOINT_SETTING).await? else {
+            return Ok(());
+        };
+        match build_batch(app, &pool).await? {
+            Some(batch) if !batch.events.is_empty() => (endpoint, batch),
+            _ => return Ok(()),
+        }
+    };
+
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?
+        .post(&endpoint)
+        .json(&batch)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Telemetry upload failed: {}", e))?;
+
+    {
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        // Events recorded while uploading keep their remaining count
+        for event in &batch.events {
+            sqlx::query("UPDATE telemetry_events SET count = count - ? WHERE kind = ? AND name = ? AND day = ?")
+                .bind(event.count)
+                .bind(&event.kind)
+                .bind(&event.name)
+                .bind(&event.day)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        sqlx::query("DELETE FROM telemetry_events WHERE count <= 0")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        settings::write_value(&mut *tx, LAST_UPLOAD_SETTING, &now.into()).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+    }
+    logger::info(&format!("Uploaded {} telemetry event(s)", batch.events.len()));
+    Ok(())
+}
+
+/// Start batching telemetry if the user opted in, and follow the setting afterwards
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    app.listen(SETTINGS_CHANGED_EVENT, |event| {
+        if let Ok(change) = serde_json::from_str::<SettingChange>(event.payload()) {
+            if change.key == ENABLED_SETTING {
+                let enabled = change.value.and_then(|v| v.as_bool()).unwrap_or(false);
+                ENABLED.store(enabled, Ordering::Relaxed);
+            }
+        }
+    });
+
+    tauri::async_runtime::spawn(async move {
+        let enabled = {
+            let state = app.state::<DatabaseState>();
+            let pool = state.pool.lock().await;
+            settings::get_or(&pool, ENABLED_SETTING, false).await
+        };
+        ENABLED.store(enabled, Ordering::Relaxed);
+        count("app.launch");
+
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !ENABLED.load(Ordering::Relaxed) {
+                continue;
+            }
+            let flushed = {
+                let state = app.state::<DatabaseState>();
+                let pool = state.pool.lock().await;
+                flush(&pool).await
+            };
+            let result = match flushed {
+                Ok(()) => upload_if_due(&app).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                // Not logger::error: that would record the failure as telemetry again
+                logger::info(&format!("Telemetry: {}", e));
+            }
+        }
+    })
+}
+
+#[crate::metrics::command]
+pub async fn get_telemetry_status(state: State<'_, DatabaseState>) -> Result<TelemetryStatus, String> {
+    let pool = state.pool.lock().await;
+    let (pending_events,): (i64,) = sqlx::query_as("SELECT COALESCE(SUM(count), 0) FROM telemetry_events")
+        .fetch_one(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(TelemetryStatus {
+        enabled: settings::get_or(&pool, ENABLED_SETTING, false).await,
+        endpoint_configured: settings::get::<String>(&pool, ENDPOINT_SETTING).await?.is_some(),
+        pending_events,
+        last_upload_at: settings::get(&pool, LAST_UPLOAD_SETTING).await?,
+    })
+}
+
+/// The batch the next upload would send, so users can see what is collected
+#[crate::metrics::command]
+pub async fn get_telemetry_data(app: AppHandle) -> Result<Option<TelemetryBatch>, String> {
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    flush(&pool).await?;
+    build_batch(&app, &pool).await
+}
+
+/// Opt in or out; opting out deletes everything collected so far
+#[crate::metrics::command]
+pub async fn set_telemetry_enabled(app: AppHandle, enabled: bool) -> Result<(), String> {
+    if enabled {
+        let has_id = {
+            let state = app.state::<DatabaseState>();
+            let pool = state.pool.lock().await;
+            settings::get::<String>(&pool, INSTALL_ID_SETTING).await?.is_some()
+        };
+        if !has_id {
+            settings::set(&app, INSTALL_ID_SETTING, uuid::Uuid::new_v4().to_string()).await?;
+        }
+        settings::set(&app, ENABLED_SETTING, true).await?;
+    } else {
+        ENABLED.store(false, Ordering::Relaxed);
+        if let Ok(mut pending) = PENDING.lock() {
+            pending.clear();
+        }
+        settings::set(&app, ENABLED_SETTING, false).await?;
+        settings::remove(&app, INSTALL_ID_SETTING).await?;
+        let state = app.state::<DatabaseState>();
+        let pool = state.pool.lock().await;
+        sqlx::query("DELETE FROM telemetry_events")
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    logger::info(&format!("Telemetry {}", if enabled { "enabled" } else { "disabled" }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_strips_user_data() {
+        assert_eq!(
+            signature("Failed to read /home/ada/notes.md: file 'ideas' not found (os error 2)"),
+            "Failed to read <path> file <str> not found (os error #)"
+        );
+        assert_eq!(signature("Invalid token for ada@example.com"), "Invalid token for <email>");
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, Theme, WebviewWindow, WindowEvent};
+
+use crate::logger;
+
+/// Emitted to every window when the OS switches between light and dark mode
+pub const THEME_CHANGED_EVENT: &str = "system://theme-changed";
+
+/// Last theme announced, so a switch seen by several windows is emitted once
+static CURRENT: Mutex<Option<Theme>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThemeChanged {
+    theme: String,
+}
+
+fn name(theme: Theme) -> String {
+    match theme {
+        Theme::Dark => "dark",
+        _ => "light",
+    }
+    .to_string()
+}
+
+/// Announce OS theme switches seen by `window`; hidden windows still get them,
+/// so the UI is right when it comes back from the tray
+pub fn track<R: Runtime>(window: &WebviewWindow<R>) {
+    if let (Ok(theme), Ok(mut current)) = (window.theme(), CURRENT.lock()) {
+        current.get_or_insert(theme);
+    }
+    let app = window.app_handle().clone();
+    window.on_window_event(move |event| {
+        let WindowEvent::ThemeChanged(theme) = event else {
+            return;
+        };
+        match CURRENT.lock() {
+            Ok(mut current) if *current != Some(*theme) => *current = Some(*theme),
+            _ => return,
+        }
+        logger::info(&format!("System theme changed to {}", name(*theme)));
+        if let Err(e) = app.emit(THEME_CHANGED_EVENT, ThemeChanged { theme: name(*theme) }) {
+            logger::error(&format!("Failed to emit {}: {}", THEME_CHANGED_EVENT, e));
+        }
+    });
+}
+
+/// "light" or "dark"
+#[crate::metrics::command]
+pub fn get_system_theme(app: AppHandle) -> Result<String, String> {
+    if let Some(theme) = CURRENT.lock().ok().and_then(|current| *current) {
+        return Ok(name(theme));
+    }
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "Main window is not open".to_string())?;
+    window.theme().map(name).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_names() {
+        assert_eq!(name(Theme::Dark), "dark");
+        assert_eq!(name(Theme::Light), "light");
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::{AppHandle, State};
+
+use crate::db::{changes, DatabaseState};
+
+/// Length of a pomodoro, for showing the time left
+pub const POMODORO_MINUTES_SETTING: &str = "timers.pomodoro_minutes";
+pub const DEFAULT_POMODORO_MINUTES: i64 = 25;
+
+const SESSION_COLUMNS: &str = "id, todo_id, kind, started_at, ended_at, paused_at, paused_ms";
+
+/// A pomodoro or time-tracking session
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSession {
+    pub id: String,
+    pub todo_id: Option<String>,
+    /// "pomodoro" or "tracking"
+    pub kind: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    /// Set while the session is paused
+    pub paused_at: Option<i64>,
+    /// Total time spent paused, excluding the current pause
+    pub paused_ms: i64,
+}
+
+impl TimeSession {
+    /// Time counted so far as of `now`, excluding pauses
+    pub fn elapsed_ms(&self, now: i64) -> i64 {
+        let until = self.ended_at.or(self.paused_at).unwrap_or(now);
+        (until - self.started_at - self.paused_ms).max(0)
+    }
+}
+
+pub async fn get(pool: &SqlitePool, id: &str) -> Result<TimeSession, String> {
+    sqlx::query_as(&format!("SELECT {} FROM time_sessions WHERE id = ?", SESSION_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Timer not found: {}", id))
+}
+
+/// Sessions that haven't been stopped
+pub async fn active(pool: &SqlitePool) -> Result<Vec<TimeSession>, String> {
+    sqlx::query_as(&format!(
+        "SELECT {} FROM time_sessions WHERE ended_at IS NULL ORDER BY started_at",
+        SESSION_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Pause every running session as of `at`, returning the ids that were paused
+pub async fn pause_running(pool: &SqlitePool, at: i64) -> Result<Vec<String>, String> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "UPDATE time_sessions SET paused_at = MAX(started_at, ?)
+         WHERE ended_at IS NULL AND paused_at IS NULL
+         RETURNING id",
+    )
+    .bind(at)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Resume a paused session; with `count_pause` the paused time is counted as worked
+pub async fn resume(pool: &SqlitePool, id: &str, count_pause: bool) -> Result<TimeSession, String> {
+    let now = Utc::now().timestamp_millis();
+    sqlx::query(
+        "UPDATE time_sessions
+         SET paused_ms = paused_ms + CASE WHEN ? THEN 0 ELSE ? - paused_at END, paused_at = NULL
+         WHERE id = ? AND paused_at IS NOT NULL AND ended_at IS NULL",
+    )
+    .bind(count_pause)
+    .bind(now)
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    get(pool, id).await
+}
+
+#[crate::metrics::command]
+pub async fn start_timer(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    todo_id: Option<String>,
+    kind: String,
+) -> Result<TimeSession, String> {
+    if kind != "pomodoro" && kind != "tracking" {
+        return Err(format!("Unknown timer kind: {}", kind));
+    }
+    let session = TimeSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        todo_id,
+        kind,
+        started_at: Utc::now().timestamp_millis(),
+        ended_at: None,
+        paused_at: None,
+        paused_ms: 0,
+    };
+    {
+        let pool = state.pool.lock().await;
+        sqlx::query(&format!(
+            "INSERT INTO time_sessions ({}) VALUES (?, ?, ?, ?, NULL, NULL, 0)",
+            SESSION_COLUMNS
+        ))
+        .bind(&session.id)
+        .bind(&session.todo_id)
+        .bind(&session.kind)
+        .bind(session.started_at)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    changes::notify(&app, vec!["time_sessions".to_string()]);
+    crate::telemetry::count(&format!("timers.start.{}", session.kind));
+    Ok(session)
+}
+
+#[crate::metrics::command]
+pub async fn pause_timer(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: String,
+) -> Result<TimeSession, String> {
+    let session = {
+        let pool = state.pool.lock().await;
+        sqlx::query("UPDATE time_sessions SET paused_at = ? WHERE id = ? AND paused_at IS NULL AND ended_at IS NULL")
+            .bind(Utc::now().timestamp_millis())
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        get(&pool, &id).await?
+    };
+    changes::notify(&app, vec!["time_sessions".to_string()]);
+    Ok(session)
+}
+
+/// Resume a paused timer; `count_pause` keeps the paused time (e.g. "I was still working")
+#[crate::metrics::command]
+pub async fn resume_timer(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: String,
+    count_pause: Option<bool>,
+) -> Result<TimeSession, String> {
+    let session = {
+        let pool = state.pool.lock().await;
+        resume(&pool, &id, count_pause.unwrap_or(false)).await?
+    };
+    changes::notify(&app, vec!["time_sessions".to_string()]);
+    Ok(session)
+}
+
+#[crate::metrics::command]
+pub async fn stop_timer(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    id: String,
+) -> Result<TimeSession, String> {
+    let session = {
+        let pool = state.pool.lock().await;
+        // A paused session ends when it was paused
+        sqlx::query(
+            "UPDATE time_sessions SET ended_at = COALESCE(paused_at, ?), paused_at = NULL
+             WHERE id = ? AND ended_at IS NULL",
+        )
+        .bind(Utc::now().timestamp_millis())
+        .bind(&id)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        get(&pool, &id).await?
+    };
+    changes::notify(&app, vec!["time_sessions".to_string()]);
+    Ok(session)
+}
+
+#[crate::metrics::command]
+pub async fn get_active_timers(state: State<'_, DatabaseState>) -> Result<Vec<TimeSession>, String> {
+    let pool = state.pool.lock().await;
+    active(&pool).await
+}
@@ -0,0 +1,200 @@
+use serde::Serialize;
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+use tauri::State;
+
+use crate::bootstrap::TodoRow;
+use crate::db::DatabaseState;
+
+/// Progress of a todo's sub-tasks at every depth below it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Rollup {
+    pub total: i64,
+    pub done: i64,
+    /// Whole percent of `total` that is done, 0 without sub-tasks
+    pub percent: i64,
+    /// Earliest reminder among the sub-tasks still open
+    pub earliest_due_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoNode {
+    #[serde(flatten)]
+    pub todo: TodoRow,
+    /// The todo's own reminder, if it has one
+    pub due_at: Option<i64>,
+    pub rollup: Rollup,
+    pub children: Vec<TodoNode>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TreeRow {
+    #[sqlx(flatten)]
+    todo: TodoRow,
+    due_at: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RollupRow {
+    id: String,
+    #[sqlx(flatten)]
+    rollup: Rollup,
+}
+
+/// Rollups of the given todos, walking each one's descendants with a recursive CTE.
+/// `UNION` rather than `UNION ALL` keeps a parent cycle in bad data from looping forever.
+async fn load_rollups(conn: &mut SqliteConnection, ids: &[String]) -> Result<HashMap<String, Rollup>, String> {
+    let ids_json = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+    let rows: Vec<RollupRow> = sqlx::query_as(
+        "WITH RECURSIVE descendants(ancestor, id) AS (
+             SELECT t.parent_id, t.id FROM todos t WHERE t.parent_id IN (SELECT value FROM json_each(?1))
+             UNION
+             SELECT d.ancestor, t.id FROM descendants d JOIN todos t ON t.parent_id = d.id
+         )
+         SELECT d.ancestor AS id,
+                COUNT(*) AS total,
+                SUM(t.status = 'done') AS done,
+                SUM(t.status = 'done') * 100 / COUNT(*) AS percent,
+                MIN(CASE WHEN t.status != 'done' THEN r.due_at END) AS earliest_due_at
+         FROM descendants d
+         JOIN todos t ON t.id = d.id
+         LEFT JOIN todo_reminders r ON r.todo_id = t.id
+         WHERE d.id != d.ancestor
+         GROUP BY d.ancestor",
+    )
+    .bind(ids_json)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().map(|row| (row.id, row.rollup)).collect())
+}
+
+/// The todo and everything under it, each with its own reminder
+async fn load_subtree(conn: &mut SqliteConnection, root_id: &str) -> Result<Vec<TreeRow>, String> {
+    sqlx::query_as(
+        r#"WITH RECURSIVE subtree(id) AS (
+               SELECT id FROM todos WHERE id = ?1
+               UNION
+               SELECT t.id FROM subtree s JOIN todos t ON t.parent_id = s.id
+           )
+           SELECT t.id, t.workspace_id, t.page_date, t.text, t.status, t.tags, t."order", t.level, t.parent_id,
+                  t.created_at, t.updated_at, r.due_at
+           FROM subtree s
+           JOIN todos t ON t.id = s.id
+           LEFT JOIN todo_reminders r ON r.todo_id = t.id
+           ORDER BY t."order""#,
+    )
+    .bind(root_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Nest the rows under `root_id`, children in the rows' order
+fn assemble(root_id: &str, rows: Vec<TreeRow>, rollups: &mut HashMap<String, Rollup>) -> Option<TodoNode> {
+    let mut root = None;
+    let mut children: HashMap<String, Vec<TreeRow>> = HashMap::new();
+    for row in rows {
+        if row.todo.id == root_id {
+            root = Some(row);
+        } else if let Some(parent_id) = row.todo.parent_id.clone() {
+            children.entry(parent_id).or_default().push(row);
+        }
+    }
+
+    // Each row has one parent and is taken out of `children` once placed, so a parent
+    // cycle in bad data can't recurse forever
+    fn build(
+        row: TreeRow,
+        children: &mut HashMap<String, Vec<TreeRow>>,
+        rollups: &mut HashMap<String, Rollup>,
+    ) -> TodoNode {
+        let nodes = children
+            .remove(&row.todo.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| build(child, children, rollups))
+            .collect();
+        TodoNode {
+            rollup: rollups.remove(&row.todo.id).unwrap_or_default(),
+            due_at: row.due_at,
+            todo: row.todo,
+            children: nodes,
+        }
+    }
+    Some(build(root?, &mut children, rollups))
+}
+
+async fn load_tree(conn: &mut SqliteConnection, root_id: &str) -> Result<TodoNode, String> {
+    let rows = load_subtree(conn, root_id).await?;
+    let ids: Vec<String> = rows.iter().map(|row| row.todo.id.clone()).collect();
+    let mut rollups = load_rollups(conn, &ids).await?;
+    assemble(root_id, rows, &mut rollups).ok_or_else(|| format!("Todo not found: {}", root_id))
+}
+
+/// A todo with its sub-tasks nested under it, every level carrying its rollup
+#[crate::metrics::command]
+pub async fn get_todo_tree(state: State<'_, DatabaseState>, root_id: String) -> Result<TodoNode, String> {
+    let pool = state.pool.lock().await;
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    load_tree(&mut conn, &root_id).await
+}
+
+/// Rollups of many todos at once, e.g. every parent on a page; todos without sub-tasks are left out
+#[crate::metrics::command]
+pub async fn get_todo_rollups(
+    state: State<'_, DatabaseState>,
+    todo_ids: Vec<String>,
+) -> Result<HashMap<String, Rollup>, String> {
+    let pool = state.pool.lock().await;
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    load_rollups(&mut conn, &todo_ids).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Connection;
+
+    #[tokio::test]
+    async fn test_tree_rollups() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        for statement in [
+            "CREATE TABLE todos (id TEXT, workspace_id TEXT, page_date TEXT, text TEXT, status TEXT, tags TEXT,
+                `order` TEXT, level INTEGER, parent_id TEXT, created_at INTEGER, updated_at INTEGER)",
+            "CREATE TABLE todo_reminders (todo_id TEXT, due_at INTEGER)",
+            "INSERT INTO todos VALUES ('root', 'w', '2024-03-04', 'Root', 'todo', '[]', 'a0', 0, NULL, 0, 0),
+                ('a', 'w', '2024-03-04', 'A', 'done', '[]', 'a1', 1, 'root', 0, 0),
+                ('b', 'w', '2024-03-04', 'B', 'todo', '[]', 'a2', 1, 'root', 0, 0),
+                ('b1', 'w', '2024-03-04', 'B1', 'done', '[]', 'a3', 2, 'b', 0, 0),
+                ('b2', 'w', '2024-03-04', 'B2', 'todo', '[]', 'a4', 2, 'b', 0, 0)",
+            "INSERT INTO todo_reminders VALUES ('a', 100), ('b2', 300), ('b', 500)",
+        ] {
+            sqlx::query(statement).execute(&mut conn).await.unwrap();
+        }
+
+        let tree = load_tree(&mut conn, "root").await.unwrap();
+        assert_eq!(
+            tree.rollup,
+            Rollup {
+                total: 4,
+                done: 2,
+                percent: 50,
+                earliest_due_at: Some(300),
+            }
+        );
+        let children: Vec<&str> = tree.children.iter().map(|child| child.todo.id.as_str()).collect();
+        assert_eq!(children, vec!["a", "b"]);
+        assert_eq!(tree.children[1].due_at, Some(500));
+        assert_eq!(tree.children[1].rollup.percent, 50);
+        assert_eq!(tree.children[1].children.len(), 2);
+        assert_eq!(tree.children[0].rollup, Rollup::default());
+
+        let rollups = load_rollups(&mut conn, &["b".to_string(), "a".to_string()]).await.unwrap();
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups["b"].total, 2);
+        assert!(load_tree(&mut conn, "missing").await.is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use chrono::Utc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::image::Image;
+use tauri::menu::{CheckMenuItem, CheckMenuItemBuilder, MenuBuilder, MenuItemBuilder, PredefinedMenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{App, AppHandle, Manager, Wry};
+
+use crate::db::{changes, DatabaseState};
+use crate::timers::{self, TimeSession};
+use crate::widget::{self, WidgetState};
+use crate::{badge, logger, settings};
+
+const TRAY_ID: &str = "main";
+const APP_NAME: &str = "Journal Todo";
+
+const SHOW_ID: &str = "tray.show";
+const WIDGET_ID: &str = "tray.widget";
+const PIN_ID: &str = "tray.widget_pin";
+const CLICK_THROUGH_ID: &str = "tray.widget_click_through";
+const QUIT_ID: &str = "tray.quit";
+
+/// How often the tooltip's countdown is redrawn
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// Reload even without changes, so the date rolling over is picked up
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const OVERDUE_COLOR: [u8; 3] = [220, 38, 38];
+const FOCUS_COLOR: [u8; 3] = [22, 163, 74];
+
+/// Check items mirroring the widget state
+struct WidgetItems {
+    visible: CheckMenuItem<Wry>,
+    pinned: CheckMenuItem<Wry>,
+    click_through: CheckMenuItem<Wry>,
+}
+
+/// Keep the tray's check marks in line with the widget
+pub fn sync_widget_items(app: &AppHandle, state: WidgetState) {
+    if let Some(items) = app.try_state::<WidgetItems>() {
+        let _ = items.visible.set_checked(state.visible);
+        let _ = items.pinned.set_checked(state.pinned);
+        let _ = items.click_through.set_checked(state.click_through);
+    }
+}
+
+fn on_menu_event(app: &AppHandle, id: &str) {
+    let change: fn(&mut WidgetState) = match id {
+        SHOW_ID => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            return;
+        }
+        QUIT_ID => return app.exit(0),
+        WIDGET_ID => |state| state.visible = !state.visible,
+        PIN_ID => |state| state.pinned = !state.pinned,
+        CLICK_THROUGH_ID => |state| state.click_through = !state.click_through,
+        _ => return,
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = widget::update(&app, change).await {
+            logger::error(&format!("Failed to update widget: {}", e));
+        }
+    });
+}
+
+/// Create the tray icon with the widget toggles
+pub fn init(app: &App) -> tauri::Result<()> {
+    let visible = CheckMenuItemBuilder::with_id(WIDGET_ID, "Today Widget").build(app)?;
+    let pinned = CheckMenuItemBuilder::with_id(PIN_ID, "Keep Widget on Top").build(app)?;
+    let click_through = CheckMenuItemBuilder::with_id(CLICK_THROUGH_ID, "Click Through Widget").build(app)?;
+    let menu = MenuBuilder::new(app)
+        .item(&MenuItemBuilder::with_id(SHOW_ID, "Show Journal Todo").build(app)?)
+        .item(&PredefinedMenuItem::separator(app)?)
+        .items(&[&visible, &pinned, &click_through])
+        .item(&PredefinedMenuItem::separator(app)?)
+        .item(&MenuItemBuilder::with_id(QUIT_ID, "Quit").build(app)?)
+        .build()?;
+
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(APP_NAME)
+        .menu(&menu)
+        .on_menu_event(|app, event| on_menu_event(app, event.id().as_ref()));
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+
+    app.manage(WidgetItems {
+        visible,
+        pinned,
+        click_through,
+    });
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IconState {
+    Normal,
+    Overdue,
+    Focus,
+}
+
+/// What the tray shows, reloaded when todos, reminders or timers change
+#[derive(Debug, Clone, Default)]
+struct TrayStatus {
+    remaining_today: i64,
+    /// When reminders of incomplete todos are (or were) due
+    due_at: Vec<i64>,
+    timer: Option<TimeSession>,
+    pomodoro_ms: i64,
+}
+
+impl TrayStatus {
+    fn icon_state(&self, now: i64) -> IconState {
+        if self.timer.as_ref().is_some_and(|t| t.kind == "pomodoro" && t.paused_at.is_none()) {
+            IconState::Focus
+        } else if self.due_at.iter().any(|due| *due <= now) {
+            IconState::Overdue
+        } else {
+            IconState::Normal
+        }
+    }
+
+    fn tooltip(&self, now: i64) -> String {
+        let mut parts = vec![match self.remaining_today {
+            0 => "Nothing left today".to_string(),
+            1 => "1 todo left today".to_string(),
+            n => format!("{} todos left today", n),
+        }];
+        if let Some(timer) = &self.timer {
+            let elapsed = timer.elapsed_ms(now);
+            let paused = if timer.paused_at.is_some() { " (paused)" } else { "" };
+            parts.push(if timer.kind == "pomodoro" {
+                let left = self.pomodoro_ms - elapsed;
+                if left > 0 {
+                    format!("focus {} remaining{}", clock(left), paused)
+                } else {
+                    "focus finished".to_string()
+                }
+            } else {
+                format!("tracking {}{}", clock(elapsed), paused)
+            });
+        }
+        parts.join(" · ")
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour
+fn clock(ms: i64) -> String {
+    let secs = (ms.max(0) + 999) / 1000;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+async fn load_status(app: &AppHandle) -> Result<TrayStatus, String> {
+    let remaining_today = badge::remaining_today(app).await?;
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    let due_at: Vec<(i64,)> = sqlx::query_as(
+        "SELECT COALESCE(r.snoozed_until, r.due_at) FROM todo_reminders r
+         JOIN todos t ON t.id = r.todo_id
+         WHERE t.status != 'done'",
+    )
+    .fetch_all(&*pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    // A running pomodoro wins over a tracking timer, running over paused
+    let mut sessions = timers::active(&pool).await?;
+    sessions.sort_by_key(|s| (s.kind != "pomodoro", s.paused_at.is_some()));
+    let minutes = settings::get_or(&pool, timers::POMODORO_MINUTES_SETTING, timers::DEFAULT_POMODORO_MINUTES).await;
+    Ok(TrayStatus {
+        remaining_today,
+        due_at: due_at.into_iter().map(|(due,)| due).collect(),
+        timer: sessions.into_iter().next(),
+        pomodoro_ms: minutes * 60_000,
+    })
+}
+
+/// `icon` with a colored dot in its bottom-right corner
+fn with_dot(icon: &Image<'_>, color: [u8; 3]) -> Image<'static> {
+    let (width, height) = (icon.width(), icon.height());
+    let mut rgba = icon.rgba().to_vec();
+    let radius = width.min(height) as f32 / 4.0;
+    let (cx, cy) = (width as f32 - radius - 1.0, height as f32 - radius - 1.0);
+    for y in 0..height {
+        for x in 0..width {
+            if (x as f32 - cx).powi(2) + (y as f32 - cy).powi(2) <= radius * radius {
+                let i = ((y * width + x) * 4) as usize;
+                rgba[i..i + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+    }
+    Image::new_owned(rgba, width, height)
+}
+
+fn apply_status(app: &AppHandle, icon_state: IconState, tooltip: &str) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    if let Err(e) = tray.set_tooltip(Some(format!("{} — {}", APP_NAME, tooltip))) {
+        logger::error(&format!("Failed to update tray tooltip: {}", e));
+    }
+    let Some(icon) = app.default_window_icon() else {
+        return;
+    };
+    let icon = match icon_state {
+        IconState::Normal => icon.clone().to_owned(),
+        IconState::Overdue => with_dot(icon, OVERDUE_COLOR),
+        IconState::Focus => with_dot(icon, FOCUS_COLOR),
+    };
+    if let Err(e) = tray.set_icon(Some(icon)) {
+        logger::error(&format!("Failed to update tray icon: {}", e));
+    }
+}
+
+/// Keep the tray icon and tooltip in line with today's todos, overdue reminders and timers
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    let dirty = Arc::new(AtomicBool::new(true));
+    let listener_dirty = dirty.clone();
+    changes::listen(&app, &["todos", "todo_reminders", "time_sessions", "settings"], move |_| {
+        listener_dirty.store(true, Ordering::Relaxed);
+    });
+
+    tauri::async_runtime::spawn(async move {
+        let mut status = TrayStatus::default();
+        let mut loaded_at = Instant::now();
+        let mut shown: Option<(IconState, String)> = None;
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if dirty.swap(false, Ordering::Relaxed) || loaded_at.elapsed() >= RELOAD_INTERVAL {
+                match load_status(&app).await {
+                    Ok(loaded) => status = loaded,
+                    Err(e) => logger::error(&format!("Failed to load tray status: {}", e)),
+                }
+                loaded_at = Instant::now();
+            }
+
+            let now = Utc::now().timestamp_millis();
+            let next = (status.icon_state(now), status.tooltip(now));
+            if shown.as_ref() != Some(&next) {
+                // The icon is only redrawn when its state changes, the tooltip every tick it differs
+                if shown.as_ref().is_none_or(|(icon, _)| *icon != next.0) {
+                    apply_status(&app, next.0, &next.1);
+                } else if let Some(tray) = app.tray_by_id(TRAY_ID) {
+                    let _ = tray.set_tooltip(Some(format!("{} — {}", APP_NAME, next.1)));
+                }
+                shown = Some(next);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tooltip_and_icon_state() {
+        let now = 10_000_000;
+        let mut status = TrayStatus {
+            remaining_today: 3,
+            due_at: vec![now + 60_000],
+            timer: None,
+            pomodoro_ms: 25 * 60_000,
+        };
+        assert_eq!(status.tooltip(now), "3 todos left today");
+        assert_eq!(status.icon_state(now), IconState::Normal);
+        status.due_at.push(now - 1);
+        assert_eq!(status.icon_state(now), IconState::Overdue);
+
+        status.timer = Some(TimeSession {
+            id: "t".to_string(),
+            todo_id: None,
+            kind: "pomodoro".to_string(),
+            started_at: now - (12 * 60_000 + 26_000),
+            ended_at: None,
+            paused_at: None,
+            paused_ms: 0,
+        });
+        assert_eq!(status.tooltip(now), "3 todos left today · focus 12:34 remaining");
+        assert_eq!(status.icon_state(now), IconState::Focus);
+
+        assert_eq!(clock(3_723_000), "1:02:03");
+    }
+}
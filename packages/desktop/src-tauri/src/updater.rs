@@ -0,0 +1,133 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tokio::sync::Mutex;
+
+use crate::db::DatabaseState;
+use crate::{logger, settings};
+
+/// "stable" or "beta"
+const CHANNEL_SETTING: &str = "updater.channel";
+const DEFAULT_CHANNEL: &str = "stable";
+
+const STABLE_ENDPOINT: &str = "https://github.com/BarrySong97/journal_todo/releases/latest/download/latest.json";
+/// Beta builds are published under a moving `beta` release tag
+const BETA_ENDPOINT: &str = "https://github.com/BarrySong97/journal_todo/releases/download/beta/latest.json";
+
+/// Emitted while an update downloads
+pub const UPDATE_PROGRESS_EVENT: &str = "updater://progress";
+/// Emitted once the update is installed and the app can be restarted
+pub const UPDATE_READY_EVENT: &str = "updater://ready";
+
+/// The update found by the last `check_for_updates`, waiting to be installed
+#[derive(Default)]
+pub struct PendingUpdate(Mutex<Option<Update>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub channel: String,
+    pub notes: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateProgress {
+    downloaded: u64,
+    total: Option<u64>,
+    percent: Option<f64>,
+}
+
+fn endpoint(channel: &str) -> Result<url::Url, String> {
+    let endpoint = match channel {
+        "stable" => STABLE_ENDPOINT,
+        "beta" => BETA_ENDPOINT,
+        other => return Err(format!("Unknown update channel: {}", other)),
+    };
+    url::Url::parse(endpoint).map_err(|e| e.to_string())
+}
+
+/// Look for a newer version on the configured channel
+#[crate::metrics::command]
+pub async fn check_for_updates(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    pending: State<'_, PendingUpdate>,
+) -> Result<Option<UpdateInfo>, String> {
+    let channel = {
+        let pool = state.pool.lock().await;
+        settings::get_or(&pool, CHANNEL_SETTING, DEFAULT_CHANNEL.to_string()).await
+    };
+
+    let update = app
+        .updater_builder()
+        .endpoints(vec![endpoint(&channel)?])
+        .map_err(|e| e.to_string())?
+        .build()
+        .map_err(|e| e.to_string())?
+        .check()
+        .await
+        .map_err(|e| format!("Failed to check for updates: {}", e))?;
+
+    let info = update.as_ref().map(|update| UpdateInfo {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        channel: channel.clone(),
+        notes: update.body.clone(),
+        date: update.date.map(|date| date.to_string()),
+    });
+    match &info {
+        Some(info) => logger::info(&format!("Update {} available on {} channel", info.version, channel)),
+        None => logger::info(&format!("No update available on {} channel", channel)),
+    }
+    *pending.0.lock().await = update;
+    Ok(info)
+}
+
+/// Download and install the update found by `check_for_updates`, reporting progress
+#[crate::metrics::command]
+pub async fn install_update(app: AppHandle, pending: State<'_, PendingUpdate>) -> Result<(), String> {
+    let update = pending
+        .0
+        .lock()
+        .await
+        .take()
+        .ok_or_else(|| "No update to install; check for updates first".to_string())?;
+
+    let mut downloaded = 0u64;
+    let progress_app = app.clone();
+    update
+        .download_and_install(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = progress_app.emit(
+                    UPDATE_PROGRESS_EVENT,
+                    UpdateProgress {
+                        downloaded,
+                        total,
+                        percent: total.filter(|t| *t > 0).map(|t| downloaded as f64 * 100.0 / t as f64),
+                    },
+                );
+            },
+            || logger::info("Update downloaded"),
+        )
+        .await
+        .map_err(|e| format!("Failed to install update: {}", e))?;
+
+    logger::info(&format!("Update {} installed", update.version));
+    app.emit(UPDATE_READY_EVENT, &update.version)
+        .map_err(|e| e.to_string())
+}
+
+/// Switch between the stable and beta channels
+#[crate::metrics::command]
+pub async fn set_update_channel(app: AppHandle, channel: String) -> Result<(), String> {
+    endpoint(&channel)?;
+    if let Some(pending) = app.try_state::<PendingUpdate>() {
+        pending.0.lock().await.take();
+    }
+    settings::set(&app, CHANNEL_SETTING, channel).await
+}
@@ -0,0 +1,290 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, State};
+
+use crate::attachments::{self, Attachment};
+use crate::db::{changes, DatabaseState};
+use crate::logger;
+
+/// Recording stops collecting samples after this long
+const MAX_DURATION_SECS: u32 = 30 * 60;
+/// Number of peaks in the stored waveform
+const WAVEFORM_POINTS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceMemoInfo {
+    pub attachment_id: String,
+    pub duration_ms: i64,
+    pub sample_rate: i64,
+    /// Peak amplitudes in 0..1, `WAVEFORM_POINTS` long
+    #[sqlx(json)]
+    pub waveform: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceMemo {
+    pub attachment: Attachment,
+    pub info: VoiceMemoInfo,
+}
+
+/// Mono samples captured by a finished recording
+struct Captured {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+/// A recording in progress; the cpal stream lives on its own thread since it isn't `Send`
+struct Recording {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Result<Captured, String>>,
+    workspace_id: String,
+    page_date: Option<String>,
+    todo_id: Option<String>,
+}
+
+/// Managed state holding the active recording, if any
+#[derive(Default)]
+pub struct VoiceRecorder {
+    active: Mutex<Option<Recording>>,
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Arc<Mutex<Vec<f32>>>,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    let max_samples = (config.sample_rate.0 * MAX_DURATION_SECS) as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let Ok(mut buffer) = samples.lock() else {
+                    return;
+                };
+                for frame in data.chunks(channels) {
+                    if buffer.len() >= max_samples {
+                        return;
+                    }
+                    let sum: f32 = frame.iter().map(|&s| <f32 as FromSample<T>>::from_sample_(s)).sum();
+                    buffer.push(sum / frame.len() as f32);
+                }
+            },
+            |e| logger::error(&format!("Audio input error: {}", e)),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
+
+/// A playing input stream and the buffer it fills
+struct LiveStream {
+    stream: cpal::Stream,
+    samples: Arc<Mutex<Vec<f32>>>,
+    sample_rate: u32,
+}
+
+/// Open the default input device and record until `stop` fires
+fn record(stop: mpsc::Receiver<()>, ready: mpsc::Sender<Result<(), String>>) -> Result<Captured, String> {
+    let setup = || -> Result<LiveStream, String> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or("No microphone found")?;
+        let supported = device.default_input_config().map_err(|e| e.to_string())?;
+        let config: cpal::StreamConfig = supported.config();
+        let samples = Arc::new(Mutex::new(Vec::new()));
+
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, samples.clone()),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, samples.clone()),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, samples.clone()),
+            cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, samples.clone()),
+            other => Err(format!("Unsupported sample format: {}", other)),
+        }?;
+        stream.play().map_err(|e| e.to_string())?;
+        Ok(LiveStream {
+            stream,
+            samples,
+            sample_rate: config.sample_rate.0,
+        })
+    };
+
+    let LiveStream {
+        stream,
+        samples,
+        sample_rate,
+    } = match setup() {
+        Ok(recording) => {
+            let _ = ready.send(Ok(()));
+            recording
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e.clone()));
+            return Err(e);
+        }
+    };
+
+    // Either an explicit stop or the recorder being dropped ends the recording
+    let _ = stop.recv();
+    drop(stream);
+
+    let samples = std::mem::take(&mut *samples.lock().map_err(|e| e.to_string())?);
+    Ok(Captured { samples, sample_rate })
+}
+
+/// Peak amplitude per bucket, normalized so the loudest bucket is 1
+fn waveform(samples: &[f32]) -> Vec<f32> {
+    if samples.is_empty() {
+        return vec![0.0; WAVEFORM_POINTS];
+    }
+    let bucket = samples.len().div_ceil(WAVEFORM_POINTS);
+    let mut peaks: Vec<f32> = samples
+        .chunks(bucket)
+        .map(|chunk| chunk.iter().fold(0.0f32, |max, s| max.max(s.abs())))
+        .collect();
+    peaks.resize(WAVEFORM_POINTS, 0.0);
+
+    let loudest = peaks.iter().cloned().fold(0.0f32, f32::max);
+    if loudest > 0.0 {
+        peaks.iter_mut().for_each(|p| *p /= loudest);
+    }
+    peaks
+}
+
+fn write_wav(path: &Path, captured: &Captured) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: captured.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(|e| e.to_string())?;
+    for &sample in &captured.samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(value).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+impl VoiceRecorder {
+    fn take(&self) -> Result<Recording, String> {
+        self.active
+            .lock()
+            .map_err(|e| e.to_string())?
+            .take()
+            .ok_or_else(|| "No recording in progress".to_string())
+    }
+}
+
+/// Start recording from the default microphone
+#[crate::metrics::command]
+pub async fn start_voice_memo(
+    recorder: State<'_, VoiceRecorder>,
+    workspace_id: String,
+    page_date: Option<String>,
+    todo_id: Option<String>,
+) -> Result<(), String> {
+    let mut active = recorder.active.lock().map_err(|e| e.to_string())?;
+    if active.is_some() {
+        return Err("A recording is already in progress".to_string());
+    }
+
+    let (stop, stop_rx) = mpsc::channel();
+    let (ready_tx, ready) = mpsc::channel();
+    let thread = std::thread::spawn(move || record(stop_rx, ready_tx));
+    ready
+        .recv()
+        .map_err(|_| "Recording thread exited unexpectedly".to_string())??;
+
+    logger::info("Voice memo recording started");
+    *active = Some(Recording {
+        stop,
+        thread,
+        workspace_id,
+        page_date,
+        todo_id,
+    });
+    Ok(())
+}
+
+/// Stop recording and save the memo as a WAV attachment
+#[crate::metrics::command]
+pub async fn stop_voice_memo(
+    app: AppHandle,
+    recorder: State<'_, VoiceRecorder>,
+    state: State<'_, DatabaseState>,
+) -> Result<VoiceMemo, String> {
+    let recording = recorder.take()?;
+    let _ = recording.stop.send(());
+    let captured = tauri::async_runtime::spawn_blocking(move || recording.thread.join())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|_| "Recording thread panicked".to_string())??;
+
+    let file_name = format!("Voice memo {}.wav", chrono::Local::now().format("%Y-%m-%d %H-%M-%S"));
+    let temp_path = std::env::temp_dir().join(&file_name);
+    write_wav(&temp_path, &captured)?;
+    let stored = attachments::store_file(
+        &state,
+        &temp_path,
+        &recording.workspace_id,
+        recording.page_date.as_deref(),
+        recording.todo_id.as_deref(),
+    )
+    .await;
+    let _ = std::fs::remove_file(&temp_path);
+    let attachment = stored?;
+
+    let info = VoiceMemoInfo {
+        attachment_id: attachment.id.clone(),
+        duration_ms: captured.samples.len() as i64 * 1000 / captured.sample_rate.max(1) as i64,
+        sample_rate: captured.sample_rate as i64,
+        waveform: waveform(&captured.samples),
+    };
+    {
+        let pool = state.pool.lock().await;
+        sqlx::query("INSERT INTO voice_memos (attachment_id, duration_ms, sample_rate, waveform) VALUES (?, ?, ?, ?)")
+            .bind(&info.attachment_id)
+            .bind(info.duration_ms)
+            .bind(info.sample_rate)
+            .bind(serde_json::to_string(&info.waveform).map_err(|e| e.to_string())?)
+            .execute(&*pool)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    logger::info(&format!("Voice memo saved ({} ms)", info.duration_ms));
+    changes::notify(&app, vec!["attachments".to_string(), "voice_memos".to_string()]);
+    Ok(VoiceMemo { attachment, info })
+}
+
+/// Stop recording and throw the audio away
+#[crate::metrics::command]
+pub async fn cancel_voice_memo(recorder: State<'_, VoiceRecorder>) -> Result<(), String> {
+    let recording = recorder.take()?;
+    let _ = recording.stop.send(());
+    Ok(())
+}
+
+/// Duration and waveform of a recorded memo
+#[crate::metrics::command]
+pub async fn get_voice_memo(
+    state: State<'_, DatabaseState>,
+    attachment_id: String,
+) -> Result<Option<VoiceMemoInfo>, String> {
+    let pool = state.pool.lock().await;
+    sqlx::query_as("SELECT attachment_id, duration_ms, sample_rate, waveform FROM voice_memos WHERE attachment_id = ?")
+        .bind(attachment_id)
+        .fetch_optional(&*pool)
+        .await
+        .map_err(|e| e.to_string())
+}
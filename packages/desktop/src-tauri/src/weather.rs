@@ -0,0 +1,255 @@
+use chrono::{Local, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{changes, DatabaseState};
+use crate::{logger, secrets, settings};
+
+const ENABLED_SETTING: &str = "weather.enabled";
+/// "open-meteo" (default, no key needed) or "openweathermap"
+const PROVIDER_SETTING: &str = "weather.provider";
+/// Fixed coordinates; otherwise the location tagged on journal days is used
+const LATITUDE_SETTING: &str = "weather.latitude";
+const LONGITUDE_SETTING: &str = "weather.longitude";
+/// Keychain entry for providers that need an API key
+const API_KEY_SECRET: &str = "weather.api_key";
+
+const PROVIDER_OPEN_METEO: &str = "open-meteo";
+const PROVIDER_OPENWEATHERMAP: &str = "openweathermap";
+
+/// How often the background task checks whether today's weather is stored
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyWeather {
+    pub date: String,
+    pub provider: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub summary: Option<String>,
+    pub weather_code: Option<i64>,
+    pub temp_min: Option<f64>,
+    pub temp_max: Option<f64>,
+    pub precipitation: Option<f64>,
+    pub fetched_at: i64,
+}
+
+/// Start the background task that records the weather once per day
+pub fn start(app: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let today = Local::now().format("%Y-%m-%d").to_string();
+            if let Err(e) = capture(&app, &today).await {
+                logger::error(&format!("Weather capture failed: {}", e));
+            }
+        }
+    })
+}
+
+async fn cached(pool: &SqlitePool, date: &str) -> Result<Option<DailyWeather>, String> {
+    sqlx::query_as(
+        "SELECT date, provider, latitude, longitude, summary, weather_code, temp_min, temp_max, precipitation, fetched_at
+         FROM daily_weather WHERE date = ?",
+    )
+    .bind(date)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Coordinates to fetch the weather for: configured, else the most recent tagged location
+async fn coordinates(pool: &SqlitePool, date: &str) -> Result<Option<(f64, f64)>, String> {
+    let configured = (
+        settings::get::<f64>(pool, LATITUDE_SETTING).await?,
+        settings::get::<f64>(pool, LONGITUDE_SETTING).await?,
+    );
+    if let (Some(lat), Some(lon)) = configured {
+        return Ok(Some((lat, lon)));
+    }
+
+    sqlx::query_as(
+        "SELECT latitude, longitude FROM page_locations
+         ORDER BY page_date = ? DESC, recorded_at DESC LIMIT 1",
+    )
+    .bind(date)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Short description of a WMO weather code as used by Open-Meteo
+fn describe_wmo_code(code: i64) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 | 2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51..=57 => "Drizzle",
+        61..=67 => "Rain",
+        71..=77 => "Snow",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95..=99 => "Thunderstorm",
+        _ => "Unknown",
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+async fn fetch_json(request: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+    request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Weather request failed: {}", e.without_url()))?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn fetch_open_meteo(date: &str, lat: f64, lon: f64) -> Result<DailyWeather, String> {
+    let response = fetch_json(
+        http_client()?
+            .get("https://api.open-meteo.com/v1/forecast")
+            .query(&[
+                ("latitude", lat.to_string()),
+                ("longitude", lon.to_string()),
+                (
+                    "daily",
+                    "weather_code,temperature_2m_min,temperature_2m_max,precipitation_sum"
+                        .to_string(),
+                ),
+                ("timezone", "auto".to_string()),
+                ("start_date", date.to_string()),
+                ("end_date", date.to_string()),
+            ]),
+    )
+    .await?;
+
+    let daily = &response["daily"];
+    let weather_code = daily["weather_code"][0].as_i64();
+    Ok(DailyWeather {
+        date: date.to_string(),
+        provider: PROVIDER_OPEN_METEO.to_string(),
+        latitude: lat,
+        longitude: lon,
+        summary: weather_code.map(|code| describe_wmo_code(code).to_string()),
+        weather_code,
+        temp_min: daily["temperature_2m_min"][0].as_f64(),
+        temp_max: daily["temperature_2m_max"][0].as_f64(),
+        precipitation: daily["precipitation_sum"][0].as_f64(),
+        fetched_at: Utc::now().timestamp_millis(),
+    })
+}
+
+/// OpenWeatherMap only reports current conditions, so this is only used for today
+async fn fetch_openweathermap(date: &str, lat: f64, lon: f64) -> Result<DailyWeather, String> {
+    let api_key = secrets::get(API_KEY_SECRET)?.ok_or("No weather API key configured")?;
+    let response = fetch_json(
+        http_client()?
+            .get("https://api.openweathermap.org/data/2.5/weather")
+            .query(&[
+                ("lat", lat.to_string()),
+                ("lon", lon.to_string()),
+                ("units", "metric".to_string()),
+                ("appid", api_key),
+            ]),
+    )
+    .await?;
+
+    let condition = &response["weather"][0];
+    Ok(DailyWeather {
+        date: date.to_string(),
+        provider: PROVIDER_OPENWEATHERMAP.to_string(),
+        latitude: lat,
+        longitude: lon,
+        summary: condition["description"].as_str().map(str::to_string),
+        weather_code: condition["id"].as_i64(),
+        temp_min: response["main"]["temp_min"].as_f64(),
+        temp_max: response["main"]["temp_max"].as_f64(),
+        precipitation: response["rain"]["1h"].as_f64(),
+        fetched_at: Utc::now().timestamp_millis(),
+    })
+}
+
+/// Fetch and store the weather for `date` unless it is already cached
+async fn capture(app: &AppHandle, date: &str) -> Result<Option<DailyWeather>, String> {
+    let state = app.state::<DatabaseState>();
+    let (provider, coords) = {
+        let pool = state.pool.lock().await;
+        if !settings::get_or(&pool, ENABLED_SETTING, true).await {
+            return Ok(None);
+        }
+        if let Some(weather) = cached(&pool, date).await? {
+            return Ok(Some(weather));
+        }
+        (
+            settings::get_or(&pool, PROVIDER_SETTING, PROVIDER_OPEN_METEO.to_string()).await,
+            coordinates(&pool, date).await?,
+        )
+    };
+    let Some((lat, lon)) = coords else {
+        return Ok(None);
+    };
+
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let weather = match provider.as_str() {
+        PROVIDER_OPENWEATHERMAP if date == today => fetch_openweathermap(date, lat, lon).await?,
+        PROVIDER_OPENWEATHERMAP | PROVIDER_OPEN_METEO => fetch_open_meteo(date, lat, lon).await?,
+        other => return Err(format!("Unknown weather provider: {}", other)),
+    };
+
+    {
+        let pool = state.pool.lock().await;
+        sqlx::query(
+            "INSERT OR REPLACE INTO daily_weather
+             (date, provider, latitude, longitude, summary, weather_code, temp_min, temp_max, precipitation, fetched_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&weather.date)
+        .bind(&weather.provider)
+        .bind(weather.latitude)
+        .bind(weather.longitude)
+        .bind(&weather.summary)
+        .bind(weather.weather_code)
+        .bind(weather.temp_min)
+        .bind(weather.temp_max)
+        .bind(weather.precipitation)
+        .bind(weather.fetched_at)
+        .execute(&*pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    logger::info(&format!("Recorded weather for {}", date));
+    changes::notify(app, vec!["daily_weather".to_string()]);
+    Ok(Some(weather))
+}
+
+/// Weather recorded for a day, fetched on demand if it isn't cached yet
+#[crate::metrics::command]
+pub async fn get_weather(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    date: String,
+) -> Result<Option<DailyWeather>, String> {
+    {
+        let pool = state.pool.lock().await;
+        if let Some(weather) = cached(&pool, &date).await? {
+            return Ok(Some(weather));
+        }
+    }
+    capture(&app, &date).await
+}
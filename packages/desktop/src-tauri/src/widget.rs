@@ -0,0 +1,122 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+use crate::db::DatabaseState;
+use crate::{logger, settings, window_state};
+
+pub const WIDGET_LABEL: &str = "widget";
+
+const VISIBLE_SETTING: &str = "widget.visible";
+/// Keep the widget above other windows
+const PINNED_SETTING: &str = "widget.pinned";
+/// Let clicks pass through the widget to the windows below
+const CLICK_THROUGH_SETTING: &str = "widget.click_through";
+
+/// Emitted whenever the widget is shown, hidden, pinned or made click-through
+pub const WIDGET_STATE_EVENT: &str = "widget://state";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetState {
+    pub visible: bool,
+    pub pinned: bool,
+    pub click_through: bool,
+}
+
+async fn load_state(app: &AppHandle) -> WidgetState {
+    let state = app.state::<DatabaseState>();
+    let pool = state.pool.lock().await;
+    WidgetState {
+        visible: settings::get_or(&pool, VISIBLE_SETTING, false).await,
+        pinned: settings::get_or(&pool, PINNED_SETTING, true).await,
+        click_through: settings::get_or(&pool, CLICK_THROUGH_SETTING, false).await,
+    }
+}
+
+/// Create the widget window hidden, at its saved position
+async fn create(app: &AppHandle, state: WidgetState) -> Result<WebviewWindow, String> {
+    let url = WebviewUrl::App("index.html?window=widget".into());
+    let window = WebviewWindowBuilder::new(app, WIDGET_LABEL, url)
+        .title("Today")
+        .inner_size(300.0, 420.0)
+        .min_inner_size(220.0, 160.0)
+        .decorations(false)
+        .skip_taskbar(true)
+        .always_on_top(state.pinned)
+        .visible(false)
+        .build()
+        .map_err(|e| format!("Failed to create widget window: {}", e))?;
+
+    if let Err(e) = window_state::restore(&window).await {
+        logger::error(&format!("Failed to restore widget position: {}", e));
+    }
+    window_state::track(&window);
+    window
+        .set_ignore_cursor_events(state.click_through)
+        .map_err(|e| e.to_string())?;
+    Ok(window)
+}
+
+async fn apply(app: &AppHandle, state: WidgetState) -> Result<(), String> {
+    let window = match app.get_webview_window(WIDGET_LABEL) {
+        Some(window) => window,
+        None if state.visible => create(app, state).await?,
+        None => return Ok(()),
+    };
+    window.set_always_on_top(state.pinned).map_err(|e| e.to_string())?;
+    window
+        .set_ignore_cursor_events(state.click_through)
+        .map_err(|e| e.to_string())?;
+    if state.visible {
+        window.show().map_err(|e| e.to_string())?;
+    } else {
+        window.hide().map_err(|e| e.to_string())?;
+    }
+
+    crate::tray::sync_widget_items(app, state);
+    let _ = app.emit(WIDGET_STATE_EVENT, state);
+    Ok(())
+}
+
+/// Change the widget state, persist it and apply it to the window
+pub async fn update(app: &AppHandle, change: impl FnOnce(&mut WidgetState)) -> Result<WidgetState, String> {
+    let mut state = load_state(app).await;
+    change(&mut state);
+    {
+        let db = app.state::<DatabaseState>();
+        let pool = db.pool.lock().await;
+        settings::write_value(&*pool, VISIBLE_SETTING, &state.visible.into()).await?;
+        settings::write_value(&*pool, PINNED_SETTING, &state.pinned.into()).await?;
+        settings::write_value(&*pool, CLICK_THROUGH_SETTING, &state.click_through.into()).await?;
+    }
+    apply(app, state).await?;
+    Ok(state)
+}
+
+/// Bring the widget back if it was open when the app last quit
+pub async fn restore(app: &AppHandle) -> Result<(), String> {
+    let state = load_state(app).await;
+    crate::tray::sync_widget_items(app, state);
+    apply(app, state).await
+}
+
+#[crate::metrics::command]
+pub async fn get_widget_state(app: AppHandle) -> Result<WidgetState, String> {
+    Ok(load_state(&app).await)
+}
+
+#[crate::metrics::command]
+pub async fn toggle_today_widget(app: AppHandle) -> Result<WidgetState, String> {
+    update(&app, |state| state.visible = !state.visible).await
+}
+
+#[crate::metrics::command]
+pub async fn set_widget_pinned(app: AppHandle, pinned: bool) -> Result<WidgetState, String> {
+    update(&app, |state| state.pinned = pinned).await
+}
+
+/// While click-through is on the widget can't be clicked; it is turned off from the tray
+#[crate::metrics::command]
+pub async fn set_widget_click_through(app: AppHandle, enabled: bool) -> Result<WidgetState, String> {
+    update(&app, |state| state.click_through = enabled).await
+}
@@ -0,0 +1,116 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::menu::{Menu, MenuBuilder, SubmenuBuilder};
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+
+/// Emitted to a window that was already open when `open_window` asked for it again
+pub const WINDOW_PAYLOAD_EVENT: &str = "window://payload";
+
+/// Payloads of the extra windows, read by each window once it has loaded
+#[derive(Default)]
+pub struct WindowPayloads(Mutex<HashMap<String, serde_json::Value>>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenedWindow {
+    pub label: String,
+    /// False when an existing window was focused instead
+    pub created: bool,
+}
+
+/// Label, title and size for a window kind; journal windows are one per date
+fn window_spec(kind: &str, payload: &serde_json::Value) -> Result<(String, String, f64, f64), String> {
+    match kind {
+        "journal" => {
+            let date = payload
+                .get("date")
+                .and_then(|d| d.as_str())
+                .ok_or_else(|| "Journal windows need a date".to_string())?;
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| format!("Invalid date: {}", date))?;
+            Ok((format!("journal-{}", date), format!("Journal - {}", date), 520.0, 720.0))
+        }
+        "board" => Ok(("board".to_string(), "Board".to_string(), 1100.0, 760.0)),
+        other => Err(format!("Unknown window kind: {}", other)),
+    }
+}
+
+fn window_menu<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<Menu<R>> {
+    let file = SubmenuBuilder::new(app, "File").close_window().build()?;
+    let edit = SubmenuBuilder::new(app, "Edit")
+        .undo()
+        .redo()
+        .separator()
+        .cut()
+        .copy()
+        .paste()
+        .select_all()
+        .build()?;
+    MenuBuilder::new(app).items(&[&file, &edit]).build()
+}
+
+fn focus<R: Runtime>(window: &WebviewWindow<R>) {
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+}
+
+/// Open a standalone journal or board window, or focus it if it is already open.
+/// The window reads its payload with `get_window_payload`.
+#[crate::metrics::command]
+pub async fn open_window(
+    app: AppHandle,
+    kind: String,
+    payload: Option<serde_json::Value>,
+) -> Result<OpenedWindow, String> {
+    let payload = payload.unwrap_or(serde_json::Value::Null);
+    let (label, title, width, height) = window_spec(&kind, &payload)?;
+
+    if let Ok(mut payloads) = app.state::<WindowPayloads>().0.lock() {
+        payloads.insert(label.clone(), payload.clone());
+    }
+
+    if let Some(window) = app.get_webview_window(&label) {
+        focus(&window);
+        window
+            .emit(WINDOW_PAYLOAD_EVENT, payload)
+            .map_err(|e| e.to_string())?;
+        return Ok(OpenedWindow { label, created: false });
+    }
+
+    let url = WebviewUrl::App(format!("index.html?window={}", kind).into());
+    let window = WebviewWindowBuilder::new(&app, &label, url)
+        .title(title)
+        .inner_size(width, height)
+        .min_inner_size(362.0, 400.0)
+        .menu(window_menu(&app).map_err(|e| e.to_string())?)
+        .build()
+        .map_err(|e| format!("Failed to open {} window: {}", kind, e))?;
+
+    let closed_app = app.clone();
+    let closed_label = label.clone();
+    window.on_window_event(move |event| {
+        if let tauri::WindowEvent::Destroyed = event {
+            if let Ok(mut payloads) = closed_app.state::<WindowPayloads>().0.lock() {
+                payloads.remove(&closed_label);
+            }
+        }
+    });
+
+    crate::logger::info(&format!("Opened window {}", label));
+    Ok(OpenedWindow { label, created: true })
+}
+
+/// The payload `open_window` was called with for the calling window
+#[crate::metrics::command]
+pub fn get_window_payload(
+    window: WebviewWindow,
+    payloads: tauri::State<'_, WindowPayloads>,
+) -> Option<serde_json::Value> {
+    payloads
+        .0
+        .lock()
+        .ok()
+        .and_then(|payloads| payloads.get(window.label()).cloned())
+}
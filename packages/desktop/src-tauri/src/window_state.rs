@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Manager, PhysicalPosition, PhysicalSize, Runtime, WebviewWindow, WindowEvent};
+
+use crate::db::DatabaseState;
+use crate::{logger, settings};
+
+/// Moves and resizes are saved once the window has been still this long
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Window geometry in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedWindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+/// A monitor's work area as (x, y, width, height)
+type Area = (i32, i32, u32, u32);
+
+fn setting_key(label: &str) -> String {
+    format!("window.{}", label)
+}
+
+/// Whether enough of the window's title bar lands on a monitor to grab it
+fn is_reachable(state: &SavedWindowState, monitors: &[Area]) -> bool {
+    const MIN_VISIBLE: i32 = 50;
+    monitors.iter().any(|&(x, y, width, height)| {
+        let right = x + width as i32;
+        let bottom = y + height as i32;
+        state.x + state.width as i32 - MIN_VISIBLE > x
+            && state.x + MIN_VISIBLE < right
+            && state.y >= y
+            && state.y + MIN_VISIBLE < bottom
+    })
+}
+
+fn current_state<R: Runtime>(window: &WebviewWindow<R>) -> Option<SavedWindowState> {
+    let position = window.outer_position().ok()?;
+    let size = window.inner_size().ok()?;
+    Some(SavedWindowState {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized: window.is_maximized().unwrap_or(false),
+    })
+}
+
+async fn save<R: Runtime>(window: &WebviewWindow<R>) -> Result<(), String> {
+    if window.is_minimized().unwrap_or(false) {
+        return Ok(());
+    }
+    let Some(mut state) = current_state(window) else {
+        return Ok(());
+    };
+    let key = setting_key(window.label());
+    let db = window.state::<DatabaseState>();
+    let pool = db.pool.lock().await;
+    if state.maximized {
+        // Keep the size to restore to when the window is unmaximized
+        if let Some(previous) = settings::get::<SavedWindowState>(&pool, &key).await.ok().flatten() {
+            state = SavedWindowState {
+                maximized: true,
+                ..previous
+            };
+        }
+    }
+    let value = serde_json::to_value(state).map_err(|e| e.to_string())?;
+    settings::write_value(&*pool, &key, &value).await
+}
+
+/// Apply the saved geometry, falling back to a centered window when it would be off-screen
+pub async fn restore<R: Runtime>(window: &WebviewWindow<R>) -> Result<(), String> {
+    let saved: Option<SavedWindowState> = {
+        let db = window.state::<DatabaseState>();
+        let pool = db.pool.lock().await;
+        settings::get(&pool, &setting_key(window.label())).await.ok().flatten()
+    };
+    let Some(saved) = saved else {
+        return Ok(());
+    };
+
+    let monitors: Vec<Area> = window
+        .available_monitors()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|m| {
+            let area = m.work_area();
+            (area.position.x, area.position.y, area.size.width, area.size.height)
+        })
+        .collect();
+
+    window
+        .set_size(PhysicalSize::new(saved.width, saved.height))
+        .map_err(|e| e.to_string())?;
+    if is_reachable(&saved, &monitors) {
+        window
+            .set_position(PhysicalPosition::new(saved.x, saved.y))
+            .map_err(|e| e.to_string())?;
+    } else {
+        logger::info(&format!("Saved position of {} window is off-screen, centering", window.label()));
+        window.center().map_err(|e| e.to_string())?;
+    }
+    if saved.maximized {
+        window.maximize().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Save the window's geometry whenever it settles after moving or resizing
+pub fn track<R: Runtime>(window: &WebviewWindow<R>) {
+    let generation = Arc::new(AtomicU64::new(0));
+    let tracked = window.clone();
+    window.on_window_event(move |event| {
+        if !matches!(event, WindowEvent::Moved(_) | WindowEvent::Resized(_)) {
+            return;
+        }
+        let current = generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let generation = generation.clone();
+        let window = tracked.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            if generation.load(Ordering::SeqCst) != current {
+                return;
+            }
+            if let Err(e) = save(&window).await {
+                logger::error(&format!("Failed to save window state: {}", e));
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_reachable() {
+        let monitors = [(0, 0, 1920, 1080), (1920, 0, 1280, 1024)];
+        let window = |x, y| SavedWindowState {
+            x,
+            y,
+            width: 400,
+            height: 800,
+            maximized: false,
+        };
+        assert!(is_reachable(&window(100, 100), &monitors));
+        assert!(is_reachable(&window(2500, 200), &monitors));
+        // A monitor that used to sit on the right has been unplugged
+        assert!(!is_reachable(&window(3400, 200), &monitors));
+        assert!(!is_reachable(&window(100, -300), &monitors));
+    }
+}
@@ -0,0 +1,347 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::DatabaseState;
+use crate::logger;
+
+const REGISTRY_FILE_NAME: &str = "workspaces.json";
+const DEFAULT_WORKSPACE_ID: &str = "default";
+const DATABASE_FILE_NAME: &str = "journal.db";
+
+/// A known vault: one database file plus its own settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceInfo {
+    pub id: String,
+    pub name: String,
+    pub db_path: PathBuf,
+    pub created_at: i64,
+    #[serde(default)]
+    pub settings: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceRegistry {
+    active_id: String,
+    workspaces: Vec<WorkspaceInfo>,
+}
+
+/// Tracks known workspaces, persisted as `workspaces.json` in the active profile's directory
+pub struct WorkspaceManager {
+    registry_path: Mutex<PathBuf>,
+    registry: Mutex<WorkspaceRegistry>,
+}
+
+impl WorkspaceManager {
+    /// Load the registry, registering `default_db_path` as the default workspace on first run
+    pub fn load(app_data_dir: &Path, default_db_path: &Path) -> Self {
+        let registry_path = app_data_dir.join(REGISTRY_FILE_NAME);
+
+        let mut writable = true;
+        let mut registry = match std::fs::read_to_string(&registry_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                logger::error(&format!("Failed to parse workspace registry: {}", e));
+                writable = set_aside_corrupt(&registry_path);
+                WorkspaceRegistry::default()
+            }),
+            Err(_) => WorkspaceRegistry::default(),
+        };
+
+        if !registry.workspaces.iter().any(|w| w.id == DEFAULT_WORKSPACE_ID) {
+            registry.workspaces.insert(
+                0,
+                WorkspaceInfo {
+                    id: DEFAULT_WORKSPACE_ID.to_string(),
+                    name: "Default".to_string(),
+                    db_path: default_db_path.to_path_buf(),
+                    created_at: Utc::now().timestamp_millis(),
+                    settings: HashMap::new(),
+                },
+            );
+        }
+
+        if !registry.workspaces.iter().any(|w| w.id == registry.active_id) {
+            registry.active_id = DEFAULT_WORKSPACE_ID.to_string();
+        }
+
+        let manager = Self {
+            registry_path: Mutex::new(registry_path),
+            registry: Mutex::new(registry),
+        };
+        if !writable {
+            return manager;
+        }
+        if let Err(e) = manager.save() {
+            logger::error(&format!("Failed to save workspace registry: {}", e));
+        }
+        manager
+    }
+
+    /// Switch to the registry in another profile's directory, loading it as `load` does
+    pub fn reload(&self, app_data_dir: &Path, default_db_path: &Path) {
+        let loaded = Self::load(app_data_dir, default_db_path);
+        *self.registry_path.lock().unwrap() = loaded.registry_path.into_inner().unwrap();
+        *self.registry.lock().unwrap() = loaded.registry.into_inner().unwrap();
+    }
+
+    /// The workspace that should be opened
+    pub fn active(&self) -> WorkspaceInfo {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .workspaces
+            .iter()
+            .find(|w| w.id == registry.active_id)
+            .cloned()
+            .expect("active workspace is always registered")
+    }
+
+    fn get(&self, id: &str) -> Result<WorkspaceInfo, String> {
+        self.registry
+            .lock()
+            .unwrap()
+            .workspaces
+            .iter()
+            .find(|w| w.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Workspace not found: {}", id))
+    }
+
+    pub fn list(&self) -> Vec<WorkspaceInfo> {
+        self.registry.lock().unwrap().workspaces.clone()
+    }
+
+    fn add(&self, workspace: WorkspaceInfo) -> Result<(), String> {
+        {
+            let mut registry = self.registry.lock().unwrap();
+            if registry.workspaces.iter().any(|w| w.db_path == workspace.db_path) {
+                return Err(format!(
+                    "A workspace already uses {}",
+                    workspace.db_path.display()
+                ));
+            }
+            registry.workspaces.push(workspace);
+        }
+        self.save()
+    }
+
+    fn set_active(&self, id: &str) -> Result<(), String> {
+        self.registry.lock().unwrap().active_id = id.to_string();
+        self.save()
+    }
+
+    fn set_db_path(&self, id: &str, db_path: PathBuf) -> Result<WorkspaceInfo, String> {
+        let updated = {
+            let mut registry = self.registry.lock().unwrap();
+            let workspace = registry
+                .workspaces
+                .iter_mut()
+                .find(|w| w.id == id)
+                .ok_or_else(|| format!("Workspace not found: {}", id))?;
+            workspace.db_path = db_path;
+            workspace.clone()
+        };
+        self.save()?;
+        Ok(updated)
+    }
+
+    fn update_settings(
+        &self,
+        id: &str,
+        settings: HashMap<String, serde_json::Value>,
+    ) -> Result<WorkspaceInfo, String> {
+        let updated = {
+            let mut registry = self.registry.lock().unwrap();
+            let workspace = registry
+                .workspaces
+                .iter_mut()
+                .find(|w| w.id == id)
+                .ok_or_else(|| format!("Workspace not found: {}", id))?;
+            workspace.settings.extend(settings);
+            workspace.clone()
+        };
+        self.save()?;
+        Ok(updated)
+    }
+
+    /// Write the registry atomically
+    fn save(&self) -> Result<(), String> {
+        let content = {
+            let registry = self.registry.lock().unwrap();
+            serde_json::to_string_pretty(&*registry).map_err(|e| e.to_string())?
+        };
+
+        let registry_path = self.registry_path.lock().unwrap().clone();
+        if let Some(parent) = registry_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let tmp_path = registry_path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, &registry_path).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// Rename an unparsable JSON file to `*.json.corrupt` (`*.json.corrupt.<ms>` beside an earlier
+/// one) so a fresh one can be written without losing it. False when it couldn't be moved, in
+/// which case it mustn't be overwritten either.
+pub(crate) fn set_aside_corrupt(path: &Path) -> bool {
+    let mut backup = path.with_extension("json.corrupt");
+    if backup.exists() {
+        backup = path.with_extension(format!("json.corrupt.{}", Utc::now().timestamp_millis()));
+    }
+    match std::fs::rename(path, &backup) {
+        Ok(()) => {
+            logger::error(&format!("Kept the unreadable {} as {}", path.display(), backup.display()));
+            true
+        }
+        Err(e) => {
+            logger::error(&format!("Failed to set aside {}: {}", path.display(), e));
+            false
+        }
+    }
+}
+
+/// Resolve a user-supplied vault path: directories get a `journal.db` inside them
+fn resolve_db_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.extension().is_some_and(|ext| ext == "db") {
+        path
+    } else {
+        path.join(DATABASE_FILE_NAME)
+    }
+}
+
+#[crate::metrics::command]
+pub fn list_workspaces(manager: State<'_, WorkspaceManager>) -> Vec<WorkspaceInfo> {
+    manager.list()
+}
+
+#[crate::metrics::command]
+pub fn get_active_workspace(manager: State<'_, WorkspaceManager>) -> WorkspaceInfo {
+    manager.active()
+}
+
+#[crate::metrics::command]
+pub fn create_workspace(
+    manager: State<'_, WorkspaceManager>,
+    name: String,
+    path: String,
+) -> Result<WorkspaceInfo, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Workspace name cannot be empty".to_string());
+    }
+
+    let workspace = WorkspaceInfo {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        db_path: resolve_db_path(&path),
+        created_at: Utc::now().timestamp_millis(),
+        settings: HashMap::new(),
+    };
+    manager.add(workspace.clone())?;
+    logger::info(&format!(
+        "Created workspace {} at {}",
+        workspace.name,
+        workspace.db_path.display()
+    ));
+    Ok(workspace)
+}
+
+/// Close the current database and open the given workspace's database instead
+#[crate::metrics::command]
+pub async fn switch_workspace(
+    app: AppHandle,
+    manager: State<'_, WorkspaceManager>,
+    db_state: State<'_, DatabaseState>,
+    id: String,
+) -> Result<WorkspaceInfo, String> {
+    let workspace = manager.get(&id)?;
+    logger::info(&format!(
+        "Switching to workspace {} ({})",
+        workspace.name,
+        workspace.db_path.display()
+    ));
+
+    db_state.reopen(workspace.db_path.clone()).await.map_err(|e| {
+        logger::error(&format!("Failed to switch workspace: {}", e));
+        e
+    })?;
+    manager.set_active(&workspace.id)?;
+
+    app.emit("db://reload", ()).map_err(|e| e.to_string())?;
+    Ok(workspace)
+}
+
+/// Move the active workspace's database file to a new location
+#[crate::metrics::command]
+pub async fn set_database_location(
+    manager: State<'_, WorkspaceManager>,
+    db_state: State<'_, DatabaseState>,
+    path: String,
+) -> Result<WorkspaceInfo, String> {
+    let workspace = manager.active();
+    let new_path = resolve_db_path(&path);
+    logger::info(&format!(
+        "Moving database for {} from {} to {}",
+        workspace.name,
+        workspace.db_path.display(),
+        new_path.display()
+    ));
+
+    db_state.relocate(new_path.clone()).await.map_err(|e| {
+        logger::error(&format!("Failed to move database: {}", e));
+        e
+    })?;
+    manager.set_db_path(&workspace.id, new_path)
+}
+
+#[crate::metrics::command]
+pub fn get_workspace_settings(
+    manager: State<'_, WorkspaceManager>,
+    id: String,
+) -> Result<HashMap<String, serde_json::Value>, String> {
+    Ok(manager.get(&id)?.settings)
+}
+
+/// Merge the given keys into a workspace's settings
+#[crate::metrics::command]
+pub fn update_workspace_settings(
+    manager: State<'_, WorkspaceManager>,
+    id: String,
+    settings: HashMap<String, serde_json::Value>,
+) -> Result<WorkspaceInfo, String> {
+    manager.update_settings(&id, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unparsable_registry_is_kept_aside() {
+        let dir = std::env::temp_dir().join(format!("journal-todo-workspaces-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let registry_path = dir.join(REGISTRY_FILE_NAME);
+        std::fs::write(&registry_path, "{\"activeId\": \"work\", \"workspaces\": [").unwrap();
+
+        let manager = WorkspaceManager::load(&dir, &dir.join(DATABASE_FILE_NAME));
+        assert_eq!(manager.active().id, DEFAULT_WORKSPACE_ID);
+        let kept = std::fs::read_to_string(dir.join("workspaces.json.corrupt")).unwrap();
+        assert!(kept.starts_with("{\"activeId\": \"work\""));
+        assert!(registry_path.exists());
+
+        std::fs::write(&registry_path, "not json").unwrap();
+        WorkspaceManager::load(&dir, &dir.join(DATABASE_FILE_NAME));
+        let backups = std::fs::read_dir(&dir).unwrap().filter(|entry| {
+            entry.as_ref().is_ok_and(|entry| entry.file_name().to_string_lossy().contains(".corrupt"))
+        });
+        assert_eq!(backups.count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}